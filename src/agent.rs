@@ -0,0 +1,700 @@
+use std::collections::HashMap;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, instrument, warn};
+
+use super::{
+    model::{LanguageModel, LanguageModelPrompt},
+    tool::ToolRegistry,
+    Error,
+};
+
+/// Extracts the outermost JSON object from a model response, tolerating surrounding prose or code fences.
+fn parse_json<T>(text: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let start = text.find('{').ok_or_else(|| Error::Unexpected(anyhow!("agent-response-not-json")))?;
+    let end = text.rfind('}').ok_or_else(|| Error::Unexpected(anyhow!("agent-response-not-json")))?;
+
+    serde_json::from_str(&text[start..=end]).map_err(|err| Error::Unexpected(anyhow!(err)))
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlanStep {
+    description: String,
+
+    /// Tools the step expects to use; empty when the step needs none.
+    #[serde(default)]
+    tools: Vec<String>,
+
+    /// Filled in from the agent's `CostModel` once the plan is validated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    estimated_cost: Option<f64>,
+}
+
+impl PlanStep {
+    pub fn new(description: impl Into<String>) -> Self {
+        Self { description: description.into(), tools: Vec::new(), estimated_cost: None }
+    }
+
+    pub fn tool(self, tool: impl Into<String>) -> Self {
+        let mut tools = self.tools;
+        tools.push(tool.into());
+
+        Self {
+            tools,
+            ..self
+        }
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn tools(&self) -> &[String] {
+        &self.tools
+    }
+
+    pub fn estimated_cost(&self) -> Option<f64> {
+        self.estimated_cost
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Plan {
+    steps: Vec<PlanStep>,
+
+    #[serde(default)]
+    success_criteria: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    selection: Option<PlanSelection>,
+}
+
+impl Plan {
+    pub fn new(steps: Vec<PlanStep>, success_criteria: Vec<String>) -> Self {
+        Self { steps, success_criteria, selection: None }
+    }
+
+    /// The sum of the steps' estimated costs, when they have been estimated.
+    pub fn estimated_cost(&self) -> Option<f64> {
+        self.steps.iter().map(PlanStep::estimated_cost).sum()
+    }
+
+    /// How this plan was chosen among the alternatives the model proposed, under a `CostModel`.
+    pub fn selection(&self) -> Option<&PlanSelection> {
+        self.selection.as_ref()
+    }
+
+    pub fn steps(&self) -> &[PlanStep] {
+        &self.steps
+    }
+
+    pub fn success_criteria(&self) -> &[String] {
+        &self.success_criteria
+    }
+}
+
+/// The trade-off made when choosing between alternative plans.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PlanSelection {
+    /// Index of the chosen plan among the model's proposals.
+    chosen: usize,
+
+    /// Estimated cost of each proposal, `None` for proposals rejected as invalid.
+    costs: Vec<Option<f64>>,
+
+    /// Whether every valid proposal exceeded the budget, in which case the cheapest was chosen anyway.
+    over_budget: bool,
+}
+
+impl PlanSelection {
+    pub fn chosen(&self) -> usize {
+        self.chosen
+    }
+
+    pub fn costs(&self) -> &[Option<f64>] {
+        &self.costs
+    }
+
+    pub fn over_budget(&self) -> bool {
+        self.over_budget
+    }
+
+    /// The estimated amount saved over the most expensive valid proposal.
+    pub fn savings(&self) -> f64 {
+        let costs = self.costs.iter().flatten();
+        let chosen = self.costs.get(self.chosen).copied().flatten().unwrap_or_default();
+        costs.fold(chosen, |max, cost| max.max(*cost)) - chosen
+    }
+}
+
+/// Estimated prices used to compare plans: a fixed cost per tool call plus model tokens per turn.
+///
+/// Costs are in whatever unit the prices are given in. Each step is assumed to take one model turn per tool
+/// it names plus one to answer.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct CostModel {
+    #[serde(default)]
+    tools: HashMap<String, f64>,
+
+    #[serde(default)]
+    input_price_per_million: f64,
+
+    #[serde(default)]
+    output_price_per_million: f64,
+
+    #[serde(default = "CostModel::default_turn_tokens")]
+    turn_tokens: (usize, usize),
+
+    #[serde(default)]
+    budget: Option<f64>,
+
+    #[serde(default = "CostModel::default_alternatives")]
+    alternatives: usize,
+}
+
+impl CostModel {
+    fn default_turn_tokens() -> (usize, usize) {
+        (2_000, 300)
+    }
+
+    fn default_alternatives() -> usize {
+        3
+    }
+
+    pub fn new() -> Self {
+        Self {
+            turn_tokens: Self::default_turn_tokens(),
+            alternatives: Self::default_alternatives(),
+            ..Self::default()
+        }
+    }
+
+    pub fn tool(self, name: impl Into<String>, cost: f64) -> Self {
+        let mut tools = self.tools;
+        tools.insert(name.into(), cost);
+
+        Self {
+            tools,
+            ..self
+        }
+    }
+
+    pub fn token_prices(self, input_price_per_million: f64, output_price_per_million: f64) -> Self {
+        Self {
+            input_price_per_million,
+            output_price_per_million,
+            ..self
+        }
+    }
+
+    /// Estimated input and output tokens of a single model turn.
+    pub fn turn_tokens(self, input_tokens: usize, output_tokens: usize) -> Self {
+        Self {
+            turn_tokens: (input_tokens, output_tokens),
+            ..self
+        }
+    }
+
+    /// Plans estimated above `budget` are only chosen when no proposal fits within it.
+    pub fn budget(self, budget: f64) -> Self {
+        Self {
+            budget: Some(budget),
+            ..self
+        }
+    }
+
+    /// How many alternative plans the model is asked to propose.
+    pub fn alternatives(self, alternatives: usize) -> Self {
+        Self {
+            alternatives: alternatives.max(1),
+            ..self
+        }
+    }
+
+    pub fn step_cost(&self, step: &PlanStep) -> f64 {
+        let turns = (step.tools.len() + 1) as f64;
+        let (input_tokens, output_tokens) = self.turn_tokens;
+        let turn = (input_tokens as f64 * self.input_price_per_million + output_tokens as f64 * self.output_price_per_million) / 1_000_000.0;
+
+        turns * turn + step.tools.iter().map(|tool| self.tools.get(tool).copied().unwrap_or_default()).sum::<f64>()
+    }
+}
+
+#[derive(Deserialize)]
+struct PlanAlternatives {
+    plans: Vec<Plan>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ToolCall {
+    tool: String,
+    input: Value,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output: Option<Value>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ToolCall {
+    pub fn tool(&self) -> &str {
+        &self.tool
+    }
+
+    pub fn input(&self) -> &Value {
+        &self.input
+    }
+
+    pub fn output(&self) -> Option<&Value> {
+        self.output.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct StepResult {
+    description: String,
+    tool_calls: Vec<ToolCall>,
+    output: String,
+}
+
+impl StepResult {
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    pub fn tool_calls(&self) -> &[ToolCall] {
+        &self.tool_calls
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+}
+
+/// The self-critique pass over a run's draft answer.
+#[derive(Clone, Debug, Serialize)]
+pub struct Reflection {
+    draft: String,
+    critique: String,
+    revised: bool,
+}
+
+impl Reflection {
+    pub fn draft(&self) -> &str {
+        &self.draft
+    }
+
+    pub fn critique(&self) -> &str {
+        &self.critique
+    }
+
+    /// Whether the critic asked for changes and the answer was revised.
+    pub fn revised(&self) -> bool {
+        self.revised
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct AgentRun {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    plan: Option<Plan>,
+
+    steps: Vec<StepResult>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reflection: Option<Reflection>,
+}
+
+impl AgentRun {
+    /// The plan that was executed, in planning mode.
+    pub fn plan(&self) -> Option<&Plan> {
+        self.plan.as_ref()
+    }
+
+    pub fn steps(&self) -> &[StepResult] {
+        &self.steps
+    }
+
+    pub fn reflection(&self) -> Option<&Reflection> {
+        self.reflection.as_ref()
+    }
+
+    /// The output of the final step, i.e. the revised answer when reflection revised it.
+    pub fn answer(&self) -> &str {
+        self.steps.last().map(|step| step.output.as_str()).unwrap_or_default()
+    }
+}
+
+#[derive(Deserialize)]
+struct Critique {
+    critique: String,
+
+    #[serde(default)]
+    revise: bool,
+}
+
+/// What the model chose to do next: call a tool or finish with an answer.
+#[derive(Deserialize)]
+struct Action {
+    #[serde(default)]
+    tool: Option<String>,
+
+    #[serde(default)]
+    input: Value,
+
+    #[serde(default)]
+    answer: Option<String>,
+}
+
+const PLAN_SYSTEM: &str = "You are planning how to accomplish a task before doing it. \
+Break the task into a short sequence of concrete steps, naming for each step only tools from the provided list that it needs, \
+and state how to tell the task has been accomplished. \
+Reply with only a JSON object of the form {\"steps\": [{\"description\": string, \"tools\": [string]}], \"success_criteria\": [string]}.";
+
+const PLAN_ALTERNATIVES_SYSTEM: &str = "You are planning how to accomplish a task before doing it. \
+Propose up to {count} alternative plans that would each accomplish the task, for example using different tools or fewer steps. \
+Break each plan into a short sequence of concrete steps, naming for each step only tools from the provided list that it needs, \
+and state how to tell the task has been accomplished. \
+Reply with only a JSON object of the form {\"plans\": [{\"steps\": [{\"description\": string, \"tools\": [string]}], \"success_criteria\": [string]}]}.";
+
+const ACT_SYSTEM: &str = "You are carrying out a task using the provided tools. \
+To call a tool reply with only a JSON object of the form {\"tool\": string, \"input\": object}; the result will be sent back to you. \
+When you are done reply with only a JSON object of the form {\"answer\": string}.";
+
+const CRITIQUE_SYSTEM: &str = "You are reviewing a draft answer against the task and instructions it was written for. \
+Point out anything missing, incorrect or not following the instructions, and decide whether the draft should be revised. \
+Reply with only a JSON object of the form {\"critique\": string, \"revise\": boolean}.";
+
+const REVISE_SYSTEM: &str = "You are revising a draft answer to address a reviewer's critique. \
+Reply with only the revised answer.";
+
+/// Runs a task against a model with access to a set of tools.
+///
+/// The model drives the loop by replying with JSON actions; tool errors are reported back to it rather than
+/// aborting the run. In planning mode the model first writes a [`Plan`], which is validated against the
+/// registered tools before each step is carried out in turn. With reflection enabled, a critic (the agent's own
+/// model unless one is given) reviews the final answer and the agent revises it once if asked to.
+#[derive(Debug)]
+pub struct Agent<M, C = M> {
+    model: M,
+    critic: Option<C>,
+    tools: ToolRegistry,
+    cost_model: Option<CostModel>,
+    system: Option<String>,
+    planning: bool,
+    reflection: bool,
+    max_steps: usize,
+    max_iterations: usize,
+}
+
+impl<M> Agent<M>
+where
+    M: LanguageModel,
+{
+    pub fn new(model: M, tools: ToolRegistry) -> Self {
+        Self {
+            model,
+            critic: None,
+            tools,
+            cost_model: None,
+            system: None,
+            planning: false,
+            reflection: false,
+            max_steps: 10,
+            max_iterations: 8,
+        }
+    }
+}
+
+impl<M, C> Agent<M, C>
+where
+    M: LanguageModel,
+    C: LanguageModel,
+{
+    /// Enables reflection with `critic` reviewing drafts instead of the agent's own model.
+    pub fn critic<D>(self, critic: D) -> Agent<M, D>
+    where
+        D: LanguageModel,
+    {
+        Agent {
+            model: self.model,
+            critic: Some(critic),
+            tools: self.tools,
+            cost_model: self.cost_model,
+            system: self.system,
+            planning: self.planning,
+            reflection: true,
+            max_steps: self.max_steps,
+            max_iterations: self.max_iterations,
+        }
+    }
+
+    pub fn reflection(self, reflection: bool) -> Self {
+        Self {
+            reflection,
+            ..self
+        }
+    }
+
+    /// Instructions prepended to the agent's own system prompts.
+    pub fn system(self, system: impl Into<String>) -> Self {
+        Self {
+            system: Some(system.into()),
+            ..self
+        }
+    }
+
+    /// Estimates step costs and, in planning mode, picks the cheapest of several proposed plans.
+    pub fn cost_model(self, cost_model: CostModel) -> Self {
+        Self {
+            cost_model: Some(cost_model),
+            ..self
+        }
+    }
+
+    pub fn planning(self, planning: bool) -> Self {
+        Self {
+            planning,
+            ..self
+        }
+    }
+
+    /// The largest plan accepted in planning mode.
+    pub fn max_steps(self, max_steps: usize) -> Self {
+        Self {
+            max_steps: max_steps.max(1),
+            ..self
+        }
+    }
+
+    /// The number of model turns allowed for each step before it is abandoned.
+    pub fn max_iterations(self, max_iterations: usize) -> Self {
+        Self {
+            max_iterations: max_iterations.max(1),
+            ..self
+        }
+    }
+
+    pub fn tools(&self) -> &ToolRegistry {
+        &self.tools
+    }
+
+    fn system_prompt(&self, instructions: &str) -> String {
+        match &self.system {
+            Some(system) => format!("{}\n\n{}", system, instructions),
+            None => instructions.to_string(),
+        }
+    }
+
+    fn tool_list(&self, names: &[String]) -> String {
+        let definitions = self.tools.definitions().into_iter()
+            .filter(|definition| names.is_empty() || definition["name"].as_str().is_some_and(|name| names.iter().any(|allowed| allowed == name)))
+            .collect::<Vec<Value>>();
+
+        serde_json::to_string_pretty(&definitions).unwrap_or_default()
+    }
+
+    /// Asks the model for a plan and checks it is non-empty, within `max_steps` and only names registered tools.
+    ///
+    /// With a `CostModel` the model proposes several alternatives instead; each valid one is costed and the
+    /// cheapest within budget is returned, with the comparison recorded in `Plan::selection`.
+    #[instrument(name = "Agent::plan", level = "trace", skip(self))]
+    pub async fn plan(&self, task: &str) -> Result<Plan, Error> {
+        let system = match &self.cost_model {
+            Some(cost_model) => PLAN_ALTERNATIVES_SYSTEM.replace("{count}", &cost_model.alternatives.to_string()),
+            None => PLAN_SYSTEM.to_string(),
+        };
+        let prompt = LanguageModelPrompt::from(format!("<tools>\n{}\n</tools>\n\n<task>\n{}\n</task>", self.tool_list(&[]), task))
+            .system(self.system_prompt(&system))
+            .temperature(0.0);
+        let response = self.model.inference(prompt).await?.to_string();
+
+        let Some(cost_model) = &self.cost_model else {
+            let plan = parse_json::<Plan>(&response)?;
+            debug! { ?plan };
+
+            self.validate(&plan)?;
+            return Ok(plan);
+        };
+
+        let plans = parse_json::<PlanAlternatives>(&response)?.plans.into_iter()
+            .map(|mut plan| {
+                self.validate(&plan)?;
+                for step in plan.steps.iter_mut() {
+                    step.estimated_cost = Some(cost_model.step_cost(step));
+                }
+                Ok(plan)
+            })
+            .collect::<Vec<Result<Plan, Error>>>();
+        let costs = plans.iter().map(|plan| plan.as_ref().ok().and_then(Plan::estimated_cost)).collect::<Vec<Option<f64>>>();
+
+        let cheapest = costs.iter().enumerate()
+            .filter_map(|(index, cost)| cost.map(|cost| (index, cost)))
+            .min_by(|(_, a), (_, b)| a.total_cmp(b));
+        let Some((chosen, cost)) = cheapest else {
+            return plans.into_iter().next().unwrap_or_else(|| Err(Error::Unexpected(anyhow!("invalid-plan: no plans"))));
+        };
+        let over_budget = cost_model.budget.is_some_and(|budget| cost > budget);
+        debug! { chosen, ?costs, over_budget };
+        if over_budget {
+            warn! { cost, budget = cost_model.budget, "every proposed plan exceeds the budget" };
+        }
+
+        let mut plan = plans.into_iter().nth(chosen).and_then(Result::ok).ok_or_else(|| Error::Unexpected(anyhow!("invalid-plan")))?;
+        plan.selection = Some(PlanSelection { chosen, costs, over_budget });
+        Ok(plan)
+    }
+
+    pub fn validate(&self, plan: &Plan) -> Result<(), Error> {
+        if plan.steps.is_empty() {
+            return Err(Error::Unexpected(anyhow!("invalid-plan: no steps")));
+        }
+        if plan.steps.len() > self.max_steps {
+            return Err(Error::Unexpected(anyhow!("invalid-plan: {} steps exceeds {}", plan.steps.len(), self.max_steps)));
+        }
+        if let Some(tool) = plan.steps.iter().flat_map(|step| step.tools.iter()).find(|tool| self.tools.get(tool).is_none()) {
+            return Err(Error::Unexpected(anyhow!("invalid-plan: unknown tool {}", tool)));
+        }
+
+        Ok(())
+    }
+
+    /// Carries out `plan` step by step, giving each step the results of the steps before it.
+    #[instrument(name = "Agent::execute", level = "trace", skip(self, plan))]
+    pub async fn execute(&self, task: &str, mut plan: Plan) -> Result<AgentRun, Error> {
+        self.validate(&plan)?;
+        if let Some(cost_model) = &self.cost_model {
+            for step in plan.steps.iter_mut().filter(|step| step.estimated_cost.is_none()) {
+                step.estimated_cost = Some(cost_model.step_cost(step));
+            }
+        }
+
+        let mut steps = Vec::<StepResult>::with_capacity(plan.steps.len());
+        for (index, step) in plan.steps.iter().enumerate() {
+            let previous = steps.iter().enumerate()
+                .map(|(index, result)| format!("{}. {}\n{}", index + 1, result.description, result.output))
+                .collect::<Vec<String>>()
+                .join("\n\n");
+            let instruction = format!(
+                "Carry out step {} of {}: {}\n\nThe plan is accomplished when: {}",
+                index + 1,
+                plan.steps.len(),
+                step.description,
+                plan.success_criteria.join("; "),
+            );
+
+            steps.push(self.step(task, &instruction, &previous, &step.tools, &step.description).await?);
+        }
+
+        Ok(AgentRun { plan: Some(plan), steps, reflection: None })
+    }
+
+    /// Runs `task`, planning first when planning mode is enabled and reflecting on the answer when enabled.
+    pub async fn run(&self, task: &str) -> Result<AgentRun, Error> {
+        let run = match self.planning {
+            true => self.execute(task, self.plan(task).await?).await?,
+            false => AgentRun { plan: None, steps: vec![self.step(task, "Complete the task.", "", &[], task).await?], reflection: None },
+        };
+
+        match self.reflection {
+            true => self.reflect(task, run).await,
+            false => Ok(run),
+        }
+    }
+
+    /// Has the critic review the run's answer and, if it asks for changes, revises the answer once.
+    #[instrument(name = "Agent::reflect", level = "trace", skip(self, run))]
+    pub async fn reflect(&self, task: &str, run: AgentRun) -> Result<AgentRun, Error> {
+        let draft = run.answer().to_string();
+        let instructions = self.system.as_deref().unwrap_or_default();
+
+        let prompt = LanguageModelPrompt::from(format!(
+            "<instructions>\n{}\n</instructions>\n\n<task>\n{}\n</task>\n\n<draft>\n{}\n</draft>",
+            instructions, task, draft
+        ))
+            .system(CRITIQUE_SYSTEM)
+            .temperature(0.0);
+        let response = match &self.critic {
+            Some(critic) => critic.inference(prompt).await?,
+            None => self.model.inference(prompt).await?,
+        };
+        let critique = parse_json::<Critique>(&response.to_string())?;
+        debug! { revise = critique.revise, critique = critique.critique };
+
+        let mut run = run;
+        if critique.revise {
+            let prompt = LanguageModelPrompt::from(format!(
+                "<instructions>\n{}\n</instructions>\n\n<task>\n{}\n</task>\n\n<draft>\n{}\n</draft>\n\n<critique>\n{}\n</critique>",
+                instructions, task, draft, critique.critique
+            ))
+                .system(self.system_prompt(REVISE_SYSTEM));
+
+            run.steps.push(StepResult {
+                description: "Revise the answer to address the critique".to_string(),
+                tool_calls: Vec::new(),
+                output: self.model.inference(prompt).await?.to_string(),
+            });
+        }
+
+        run.reflection = Some(Reflection { draft, critique: critique.critique, revised: critique.revise });
+        Ok(run)
+    }
+
+    async fn step(&self, task: &str, instruction: &str, previous: &str, tools: &[String], description: &str) -> Result<StepResult, Error> {
+        let mut tool_calls = Vec::<ToolCall>::new();
+
+        for _ in 0..self.max_iterations {
+            let transcript = tool_calls.iter()
+                .map(|call| serde_json::to_string(call).unwrap_or_default())
+                .collect::<Vec<String>>()
+                .join("\n");
+
+            let mut context = format!("<tools>\n{}\n</tools>\n\n<task>\n{}\n</task>\n\n", self.tool_list(tools), task);
+            if !previous.is_empty() {
+                context.push_str(&format!("<completed_steps>\n{}\n</completed_steps>\n\n", previous));
+            }
+            if !transcript.is_empty() {
+                context.push_str(&format!("<tool_calls>\n{}\n</tool_calls>\n\n", transcript));
+            }
+            context.push_str(instruction);
+
+            let prompt = LanguageModelPrompt::from(context)
+                .system(self.system_prompt(ACT_SYSTEM))
+                .temperature(0.0);
+            let action = parse_json::<Action>(&self.model.inference(prompt).await?.to_string())?;
+
+            match (action.answer, action.tool) {
+                (Some(answer), _) => return Ok(StepResult { description: description.to_string(), tool_calls, output: answer }),
+                (None, Some(tool)) => {
+                    let result = match tools.is_empty() || tools.contains(&tool) {
+                        true => self.tools.call(&tool, action.input.clone()).await,
+                        false => Err(Error::Unexpected(anyhow!("tool-not-in-plan-step: {}", tool))),
+                    };
+                    if let Err(err) = &result {
+                        warn! { tool, ?err };
+                    }
+
+                    tool_calls.push(ToolCall {
+                        tool,
+                        input: action.input,
+                        error: result.as_ref().err().map(|err| err.to_string()),
+                        output: result.ok(),
+                    });
+                },
+                (None, None) => return Err(Error::Unexpected(anyhow!("agent-action-missing-tool-or-answer"))),
+            }
+        }
+
+        Err(Error::Unexpected(anyhow!("max-iterations-exceeded: {}", description)))
+    }
+}
@@ -0,0 +1,183 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::anyhow;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    tokio::{fs, io::AsyncWriteExt},
+    tracing::instrument,
+};
+
+use super::{Error, Message};
+
+/// Placeholder-to-original mapping produced by an [`Anonymizer`].
+///
+/// Keep this separate from the exported data; it is the only way to reverse the anonymization.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct AnonymizationMap {
+    originals: HashMap<String, String>,
+
+    #[serde(skip)]
+    placeholders: HashMap<String, String>,
+
+    #[serde(skip)]
+    counters: HashMap<String, usize>,
+}
+
+impl AnonymizationMap {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        let mut map = serde_json::from_str::<Self>(&contents).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        map.placeholders = map.originals.iter().map(|(placeholder, original)| (original.clone(), placeholder.clone())).collect();
+        for placeholder in map.originals.keys() {
+            if let Some((kind, index)) = placeholder.trim_matches(|c| c == '<' || c == '>').rsplit_once('_') {
+                let counter = map.counters.entry(kind.to_string()).or_default();
+                *counter = (*counter).max(index.parse().unwrap_or_default());
+            }
+        }
+
+        Ok(map)
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let contents = serde_json::to_string_pretty(self).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        std::fs::write(path, contents).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn original(&self, placeholder: &str) -> Option<&str> {
+        self.originals.get(placeholder).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.originals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.originals.is_empty()
+    }
+
+    fn placeholder(&mut self, kind: &str, original: &str) -> String {
+        if let Some(placeholder) = self.placeholders.get(original) {
+            return placeholder.clone();
+        }
+
+        let counter = self.counters.entry(kind.to_string()).or_default();
+        *counter += 1;
+        let placeholder = format!("<{}_{}>", kind, counter);
+
+        self.placeholders.insert(original.to_string(), placeholder.clone());
+        self.originals.insert(placeholder.clone(), original.to_string());
+        placeholder
+    }
+
+    /// Restores the original values in `text`.
+    pub fn deanonymize(&self, text: &str) -> String {
+        let mut placeholders = self.originals.keys().collect::<Vec<&String>>();
+        // Longest first so `<NAME_12>` is not clobbered by `<NAME_1>`.
+        placeholders.sort_by_key(|placeholder| std::cmp::Reverse(placeholder.len()));
+
+        placeholders.into_iter().fold(text.to_string(), |text, placeholder| text.replace(placeholder.as_str(), &self.originals[placeholder]))
+    }
+}
+
+/// Replaces personal data with stable placeholders such as `<EMAIL_1>`, so that the same value maps to the
+/// same placeholder across every message and conversation passed through one [`AnonymizationMap`].
+#[derive(Clone, Debug)]
+pub struct Anonymizer {
+    patterns: Vec<(String, Regex)>,
+    terms: Vec<(String, String)>,
+}
+
+impl Default for Anonymizer {
+    fn default() -> Self {
+        let pattern = |kind: &str, pattern: &str| (kind.to_string(), Regex::new(pattern).expect("built-in anonymizer pattern"));
+
+        Self {
+            patterns: vec![
+                pattern("EMAIL", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+                pattern("UUID", r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b"),
+                pattern("PHONE", r"\+?\d[\d ().-]{7,}\d"),
+                pattern("ID", r"\b\d{6,}\b"),
+            ],
+            terms: Vec::new(),
+        }
+    }
+}
+
+impl Anonymizer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a custom pattern; matches are replaced with `<{kind}_{n}>`.
+    pub fn pattern(self, kind: impl Into<String>, pattern: &str) -> Result<Self, Error> {
+        let mut patterns = self.patterns;
+        patterns.push((kind.into(), Regex::new(pattern).map_err(|err| Error::Unexpected(anyhow!(err)))?));
+
+        Ok(Self {
+            patterns,
+            ..self
+        })
+    }
+
+    /// Adds known names (users, customers, staff) to be replaced with `<NAME_n>`.
+    pub fn names(self, names: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let mut terms = self.terms;
+        terms.extend(names.into_iter().map(|name| ("NAME".to_string(), name.into())));
+        terms.sort_by_key(|(_, term)| std::cmp::Reverse(term.len()));
+
+        Self {
+            terms,
+            ..self
+        }
+    }
+
+    pub fn anonymize(&self, text: &str, map: &mut AnonymizationMap) -> String {
+        let mut text = text.to_string();
+
+        for (kind, term) in &self.terms {
+            if !term.is_empty() && text.contains(term.as_str()) {
+                let placeholder = map.placeholder(kind, term);
+                text = text.replace(term.as_str(), &placeholder);
+            }
+        }
+
+        for (kind, pattern) in &self.patterns {
+            text = pattern.replace_all(&text, |captures: &regex::Captures| map.placeholder(kind, &captures[0])).into_owned();
+        }
+
+        text
+    }
+
+    pub fn anonymize_message(&self, message: &Message, map: &mut AnonymizationMap) -> Message {
+        match message {
+            Message::Text { text } => Message::Text { text: self.anonymize(text, map) },
+            message => message.clone(),
+        }
+    }
+
+    /// Writes each conversation as one anonymized JSONL line and returns the mapping needed to reverse it.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[instrument(name = "Anonymizer::export", level = "trace", skip(self, conversations, path))]
+    pub async fn export<C>(&self, conversations: impl IntoIterator<Item = C>, path: impl AsRef<Path>) -> Result<AnonymizationMap, Error>
+    where
+        C: AsRef<[Message]>,
+    {
+        let mut map = AnonymizationMap::default();
+        let mut file = fs::File::create(path).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        for conversation in conversations {
+            let messages = conversation.as_ref().iter().map(|message| self.anonymize_message(message, &mut map)).collect::<Vec<Message>>();
+
+            let mut line = serde_json::to_vec(&messages).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+            line.push(b'\n');
+            file.write_all(&line).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        }
+
+        file.flush().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        Ok(map)
+    }
+}
@@ -0,0 +1,111 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::anyhow;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::Error;
+
+fn sha256(content: &str) -> String {
+    Sha256::digest(content.as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn reference() -> &'static Regex {
+    static REFERENCE: OnceLock<Regex> = OnceLock::new();
+    REFERENCE.get_or_init(|| Regex::new(r"\{\{asset:(sha256:[0-9a-f]{64})\}\}").expect("valid asset reference pattern"))
+}
+
+/// Content address of an asset, `sha256:<hex digest>`.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct AssetId(String);
+
+impl AssetId {
+    pub fn of(content: &str) -> Self {
+        Self(format!("sha256:{}", sha256(content)))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The placeholder that `AssetStore::render` replaces with this asset's content.
+    pub fn reference(&self) -> String {
+        format!("{{{{asset:{}}}}}", self.0)
+    }
+}
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Large reusable prompt components (few-shot banks, long instructions, schemas) stored once by hash.
+///
+/// Templates embed `{{asset:sha256:...}}` references instead of the content itself, so identical assets
+/// share one allocation and a template's cache key stays small and stable however large its assets are.
+#[derive(Debug, Default)]
+pub struct AssetStore {
+    assets: Mutex<HashMap<AssetId, Arc<str>>>,
+}
+
+impl AssetStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stores `content` unless an identical asset is already present, returning its id either way.
+    pub fn put(&self, content: impl AsRef<str>) -> Result<AssetId, Error> {
+        let content = content.as_ref();
+        let id = AssetId::of(content);
+
+        let mut assets = self.assets.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        assets.entry(id.clone()).or_insert_with(|| Arc::from(content));
+
+        Ok(id)
+    }
+
+    pub fn get(&self, id: &AssetId) -> Result<Option<Arc<str>>, Error> {
+        let assets = self.assets.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        Ok(assets.get(id).cloned())
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.lock().map(|assets| assets.len()).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Expands every asset reference in `template`, failing on references to assets not in the store.
+    pub fn render(&self, template: &str) -> Result<String, Error> {
+        let assets = self.assets.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+
+        let mut missing = None;
+        let rendered = reference().replace_all(template, |captures: &Captures| {
+            match assets.get(&AssetId(captures[1].to_string())) {
+                Some(content) => content.to_string(),
+                None => {
+                    missing.get_or_insert_with(|| captures[1].to_string());
+                    String::new()
+                },
+            }
+        });
+
+        match missing {
+            Some(id) => Err(Error::Unexpected(anyhow!("unknown-asset: {}", id))),
+            None => Ok(rendered.into_owned()),
+        }
+    }
+
+    /// Cache key for a template, hashed over its unexpanded text so asset content is never rehashed.
+    pub fn cache_key(template: &str) -> String {
+        sha256(template)
+    }
+}
\ No newline at end of file
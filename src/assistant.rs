@@ -1,21 +1,215 @@
 use async_trait::async_trait;
+use reqwest::StatusCode;
 use serde::Serialize;
 use serde_json::Value;
-use tokio::sync::broadcast;
+use tokio::sync::broadcast::{self, error::RecvError};
+use tracing::warn;
 
-use super::Message;
+use super::{Context, Conversation, Error, Message, Problem};
 
-#[derive(Serialize)]
+/// A required field that could not be filled, with enough detail for a frontend to ask for it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MissingField {
+    field: String,
+    reason: String,
+
+    /// The field's JSON schema, e.g. to pick an input widget from its `type` and label it from `description`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<Value>,
+}
+
+impl MissingField {
+    pub fn new(field: impl Into<String>, reason: impl Into<String>) -> Self {
+        Self { field: field.into(), reason: reason.into(), schema: None }
+    }
+
+    pub fn schema(self, schema: Value) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    pub fn field(&self) -> &str {
+        &self.field
+    }
+
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.schema.as_ref().and_then(|schema| schema.get("description")).and_then(Value::as_str)
+    }
+}
+
+/// What a `Query` is asking for, serialized as its `kind` alongside any kind-specific fields.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryKind {
+    #[default]
+    FreeForm,
+    MissingFields { fields: Vec<MissingField> },
+}
+
+#[derive(Clone, Debug, Serialize)]
 #[serde(untagged)]
 pub enum AssistantResponse {
-    Final { response: Message, #[serde(skip_serializing_if = "Option::is_none")] context: Option<Value> },
-    Query { ask: String, #[serde(skip_serializing_if = "Option::is_none")] context: Option<Value> },
+    Final { response: Message, #[serde(skip_serializing_if = "Option::is_none")] context: Option<Context> },
+    Query {
+        ask: String,
+        #[serde(flatten)] kind: QueryKind,
+        #[serde(skip_serializing_if = "Option::is_none")] context: Option<Context>,
+
+        /// Set by a `QueryBroker`; the client sends it back with the human's answer to resume the solve.
+        #[serde(skip_serializing_if = "Option::is_none")] token: Option<String>,
+    },
+    Error { error: Problem },
+}
+
+impl AssistantResponse {
+    pub fn error(err: &Error) -> Self {
+        Self::Error { error: err.problem() }
+    }
+
+    pub fn query(ask: impl Into<String>, context: Option<Context>) -> Self {
+        Self::Query { ask: ask.into(), kind: QueryKind::FreeForm, context, token: None }
+    }
+
+    /// A follow-up asking for `fields`, with a plain-language `ask` generated from their names and reasons.
+    pub fn missing_fields(fields: Vec<MissingField>, context: Option<Context>) -> Self {
+        let ask = fields.iter()
+            .map(|field| match field.description() {
+                Some(description) => format!("- {} ({}): {}", field.field, description, field.reason),
+                None => format!("- {}: {}", field.field, field.reason),
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+
+        Self::Query {
+            ask: format!("Some required information is missing. Could you provide:\n{}", ask),
+            kind: QueryKind::MissingFields { fields },
+            context,
+            token: None,
+        }
+    }
+
+    /// The HTTP status to serve this response with: 200 unless it is an error.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Final { .. } | Self::Query { .. } => StatusCode::OK,
+            Self::Error { error } => StatusCode::from_u16(error.status()).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+        }
+    }
+}
+
+/// Progress of a `solve` call, published so UIs can render it as it happens.
+///
+/// Serialized with an `event` tag, e.g. `{"event": "tool_called", "session_id": "...", "tool": "...", ...}`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AssistantEvent {
+    ThinkingStarted { session_id: String },
+    ToolCalled { session_id: String, tool: String, input: Value },
+    PartialToken { session_id: String, text: String },
+    FinalResponse { session_id: String, response: Message },
+    Error { session_id: String, error: Problem },
+}
+
+impl AssistantEvent {
+    pub fn error(session_id: impl Into<String>, err: &Error) -> Self {
+        Self::Error { session_id: session_id.into(), error: err.problem() }
+    }
+
+    pub fn session_id(&self) -> &str {
+        match self {
+            Self::ThinkingStarted { session_id }
+            | Self::ToolCalled { session_id, .. }
+            | Self::PartialToken { session_id, .. }
+            | Self::FinalResponse { session_id, .. }
+            | Self::Error { session_id, .. } => session_id,
+        }
+    }
+
+    /// Whether this is the last event of a `solve` call.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::FinalResponse { .. } | Self::Error { .. })
+    }
+}
+
+/// The channel an assistant publishes `AssistantEvent`s on. Clones publish to the same subscribers.
+#[derive(Clone, Debug)]
+pub struct AssistantEvents {
+    sender: broadcast::Sender<AssistantEvent>,
+}
+
+impl Default for AssistantEvents {
+    fn default() -> Self {
+        Self::new(256)
+    }
+}
+
+impl AssistantEvents {
+    /// `capacity` events are buffered per subscriber; a subscriber that falls further behind skips the oldest.
+    pub fn new(capacity: usize) -> Self {
+        Self { sender: broadcast::channel(capacity).0 }
+    }
+
+    /// Publishes `event`; events with no subscriber are dropped.
+    pub fn emit(&self, event: AssistantEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Every event published from now on.
+    pub fn subscribe(&self) -> EventSubscription {
+        EventSubscription { receiver: self.sender.subscribe(), session_id: None }
+    }
+
+    /// Events published from now on for `session_id` only.
+    pub fn subscribe_session(&self, session_id: impl Into<String>) -> EventSubscription {
+        EventSubscription { receiver: self.sender.subscribe(), session_id: Some(session_id.into()) }
+    }
+
+    pub fn subscribers(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+#[derive(Debug)]
+pub struct EventSubscription {
+    receiver: broadcast::Receiver<AssistantEvent>,
+    session_id: Option<String>,
+}
+
+impl EventSubscription {
+    /// The next event, or `None` once every `AssistantEvents` handle is dropped. Events missed because this
+    /// subscriber lagged behind are skipped with a warning.
+    pub async fn recv(&mut self) -> Option<AssistantEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.session_id.as_deref().is_none_or(|session_id| session_id == event.session_id()) => return Some(event),
+                Ok(_) => continue,
+                Err(RecvError::Lagged(skipped)) => warn! { skipped, "assistant event subscriber lagged" },
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
 }
 
 #[async_trait]
 #[typetag::serde(tag = "type")]
 pub trait Assistant: std::fmt::Debug + Send + Sync {
-    fn communicate(&mut self, #[allow(unused)] bx: broadcast::Sender<(String, Message)>) {}
+    /// Hands the assistant the channel to publish its progress on; assistants that report none ignore it.
+    fn communicate(&mut self, #[allow(unused)] events: AssistantEvents) {}
+
+    async fn solve(&self, query: &str, context: Option<Context>, session_id: &str) -> AssistantResponse;
 
-    async fn solve(&self, query: &str, context: Option<Value>, session_id: &str) -> AssistantResponse;
+    /// Stateless mode: answers `query` from the `history` the client sent instead of a server-side session.
+    ///
+    /// Implementations should check `history` with `HistoryLimits::validate` before using it. Assistants that
+    /// rely on a session store answer with an invalid-request error.
+    #[allow(unused_variables)]
+    async fn solve_stateless(&self, query: &str, context: Option<Context>, history: &Conversation) -> AssistantResponse {
+        AssistantResponse::error(&Error::InvalidRequest("this assistant does not support stateless mode".to_string()))
+    }
 }
\ No newline at end of file
@@ -0,0 +1,205 @@
+use std::{
+    collections::VecDeque,
+    time::Duration,
+};
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, info, instrument, warn};
+
+use super::{
+    model::{retry_delay, LanguageModel, LanguageModelPrompt},
+    CheckpointStore,
+    Error,
+};
+
+#[derive(Debug, Default)]
+pub struct BatchReport {
+    succeeded: usize,
+    skipped: usize,
+    failed: Vec<(String, String)>,
+}
+
+impl BatchReport {
+    pub fn succeeded(&self) -> usize {
+        self.succeeded
+    }
+
+    /// Jobs already present in the checkpoint store and therefore not re-run.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    pub fn failed(&self) -> &[(String, String)] {
+        &self.failed
+    }
+}
+
+struct BatchJob {
+    id: String,
+    prompt: LanguageModelPrompt,
+    attempts: usize,
+}
+
+/// Runs bulk inference jobs with adaptive concurrency, request pacing and resumable progress.
+///
+/// Concurrency grows by one after each success and halves whenever the provider reports throttling or an
+/// exhausted rate-limit budget. Errors are retried like `BatchInference::batch_inference` retries them: only
+/// retriable ones, after the provider's `retry_after` or a linear backoff. Completed responses are written to
+/// the checkpoint store under `namespace`, so re-running the same job list after an interruption only issues
+/// the outstanding requests.
+#[derive(Debug)]
+pub struct BatchScheduler<M, S> {
+    model: M,
+    store: S,
+    namespace: String,
+    min_concurrency: usize,
+    max_concurrency: usize,
+    window: Option<(usize, Duration)>,
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl<M, S> BatchScheduler<M, S>
+where
+    M: LanguageModel,
+    S: CheckpointStore,
+{
+    pub fn new(model: M, store: S, namespace: impl Into<String>) -> Self {
+        Self {
+            model,
+            store,
+            namespace: namespace.into(),
+            min_concurrency: 1,
+            max_concurrency: 8,
+            window: None,
+            max_retries: 3,
+            backoff: Duration::from_secs(1),
+        }
+    }
+
+    pub fn concurrency(self, min_concurrency: usize, max_concurrency: usize) -> Self {
+        let min_concurrency = min_concurrency.max(1);
+
+        Self {
+            min_concurrency,
+            max_concurrency: max_concurrency.max(min_concurrency),
+            ..self
+        }
+    }
+
+    /// Spreads request starts evenly so that at most `requests` begin in any `window`.
+    pub fn requests_per_window(self, requests: usize, window: Duration) -> Self {
+        Self {
+            window: Some((requests.max(1), window)),
+            ..self
+        }
+    }
+
+    pub fn max_retries(self, max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    pub fn backoff(self, backoff: Duration) -> Self {
+        Self {
+            backoff,
+            ..self
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[instrument(name = "BatchScheduler::run", level = "trace", skip(self, jobs), fields(namespace = %self.namespace))]
+    pub async fn run(&self, jobs: impl IntoIterator<Item = (String, LanguageModelPrompt)>) -> Result<BatchReport, Error> {
+        let completed = self.store.entries(&self.namespace).await?;
+        let mut report = BatchReport::default();
+
+        let mut pending = jobs.into_iter()
+            .filter(|(id, _)| {
+                let done = completed.contains_key(id);
+                if done {
+                    report.skipped += 1;
+                }
+                !done
+            })
+            .map(|(id, prompt)| BatchJob { id, prompt, attempts: 0 })
+            .collect::<VecDeque<BatchJob>>();
+        info! { pending = pending.len(), skipped = report.skipped };
+
+        let interval = self.window.map(|(requests, window)| window / requests as u32).unwrap_or_default();
+        let mut concurrency = self.min_concurrency;
+        let mut next_start = Instant::now();
+        let mut in_flight = FuturesUnordered::new();
+
+        loop {
+            while in_flight.len() < concurrency {
+                let Some(job) = pending.pop_front() else { break };
+
+                let start_at = next_start.max(Instant::now());
+                next_start = start_at + interval;
+
+                in_flight.push(async move {
+                    sleep_until(start_at).await;
+                    let result = self.model.inference(job.prompt.clone()).await;
+                    (job, result)
+                });
+            }
+
+            let Some((mut job, result)) = in_flight.next().await else { break };
+            job.attempts += 1;
+
+            let rate_limit = self.model.rate_limit();
+            let exhausted = rate_limit.as_ref().is_some_and(|rate_limit| rate_limit.exhausted());
+
+            match result {
+                Ok(message) => {
+                    let value = serde_json::to_value(&message).map_err(|err| Error::Unexpected(err.into()))?;
+                    self.store.put(&self.namespace, &job.id, value).await?;
+                    report.succeeded += 1;
+
+                    if !exhausted && concurrency < self.max_concurrency {
+                        concurrency += 1;
+                    }
+                },
+                Err(err) => {
+                    let throttled = matches!(err, Error::RateLimited { .. });
+                    if throttled {
+                        concurrency = (concurrency / 2).max(self.min_concurrency);
+                    }
+                    warn! { id = job.id, attempts = job.attempts, concurrency, ?err };
+
+                    let delay = retry_delay(&err, job.attempts, self.backoff)
+                        .filter(|delay| job.attempts <= self.max_retries && job.prompt.can_wait(*delay));
+                    match delay {
+                        Some(delay) => {
+                            next_start = next_start.max(Instant::now() + delay);
+                            match throttled {
+                                true => pending.push_front(job),
+                                false => pending.push_back(job),
+                            }
+                        },
+                        None => report.failed.push((job.id, err.to_string())),
+                    }
+                    if throttled {
+                        continue;
+                    }
+                },
+            }
+
+            if exhausted {
+                concurrency = (concurrency / 2).max(self.min_concurrency);
+                let pause = rate_limit.and_then(|rate_limit| rate_limit.retry_after()).unwrap_or(self.backoff);
+                next_start = next_start.max(Instant::now() + pause);
+                debug! { concurrency, ?pause };
+            }
+        }
+
+        info! { succeeded = report.succeeded, failed = report.failed.len(), skipped = report.skipped };
+        Ok(report)
+    }
+}
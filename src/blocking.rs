@@ -0,0 +1,40 @@
+use std::{future::Future, sync::OnceLock};
+
+use anyhow::anyhow;
+use tokio::runtime::{Builder, Handle, Runtime};
+
+use super::Error;
+
+/// One worker is enough for the HTTP clients' background connection tasks; callers' futures run on their own
+/// thread inside `block_on`.
+fn runtime() -> Result<&'static Runtime, Error> {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+    if let Some(runtime) = RUNTIME.get() {
+        return Ok(runtime);
+    }
+
+    let runtime = Builder::new_multi_thread()
+        .worker_threads(1)
+        .thread_name("april-core-blocking")
+        .enable_all()
+        .build()
+        .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+    Ok(RUNTIME.get_or_init(|| runtime))
+}
+
+/// Runs any of the crate's futures to completion on a runtime shared by all blocking calls, e.g.
+/// `block_on(config::load_model("model.toml"))` in a synchronous CLI.
+///
+/// Fails instead of panicking when called from within an async runtime, where the caller should `.await`.
+pub fn block_on<F>(future: F) -> Result<F::Output, Error>
+where
+    F: Future,
+{
+    if Handle::try_current().is_ok() {
+        return Err(Error::Unexpected(anyhow!("blocking-call-in-async-context")));
+    }
+
+    Ok(runtime()?.block_on(future))
+}
@@ -0,0 +1,190 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::anyhow;
+use serde_json::{json, Value};
+use tokio::{sync::Notify, time::timeout};
+use tracing::{debug, instrument};
+
+use super::{Assistant, AssistantResponse, Context, Error};
+
+/// 256 bits from the operating system's CSPRNG, hex-encoded: the tokens are bearer capabilities for resuming
+/// someone's solve.
+fn token() -> Result<String, Error> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|err| Error::Unexpected(anyhow!("token-generation-failed: {}", err)))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// `context` with the answers so far under `answers`, keeping the caller's other context.
+fn with_answers(context: Option<Context>, answers: &[Value]) -> Option<Context> {
+    if answers.is_empty() {
+        return context;
+    }
+
+    let mut context = context.unwrap_or_default();
+    context.set("answers", Value::Array(answers.to_vec()));
+    Some(context)
+}
+
+#[derive(Debug)]
+struct PendingQuery {
+    query: String,
+    context: Option<Context>,
+    session_id: String,
+    ask: String,
+
+    /// Asks answered in earlier rounds, as `{"ask", "answer"}` objects.
+    answers: Vec<Value>,
+    answer: Option<Value>,
+    notify: Arc<Notify>,
+    created: Instant,
+}
+
+/// Turns `AssistantResponse::Query` into a resumable pause.
+///
+/// `solve` registers every query an assistant returns under a fresh token, set on the response. The host
+/// application gets the human's answer however it likes and hands it back with `answer`; `resume` then solves
+/// the original query again with each round's ask and answer under `answers` in the context. A resumed solve
+/// may ask again, which gets a new token and carries the earlier answers forward. Tokens are single-use and
+/// expire after `ttl`.
+#[derive(Debug)]
+pub struct QueryBroker {
+    pending: Mutex<HashMap<String, PendingQuery>>,
+    ttl: Duration,
+}
+
+impl Default for QueryBroker {
+    fn default() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+            ttl: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl QueryBroker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ttl(self, ttl: Duration) -> Self {
+        Self {
+            ttl,
+            ..self
+        }
+    }
+
+    /// Queries waiting for an answer.
+    pub fn pending(&self) -> usize {
+        self.pending.lock().map(|pending| pending.values().filter(|query| query.created.elapsed() < self.ttl).count()).unwrap_or_default()
+    }
+
+    /// Calls `assistant.solve`, registering the query if it asks for something.
+    pub async fn solve<A>(&self, assistant: &A, query: &str, context: Option<Context>, session_id: &str) -> AssistantResponse
+    where
+        A: Assistant + ?Sized,
+    {
+        self.solve_with_answers(assistant, query, context, session_id, Vec::new()).await
+    }
+
+    async fn solve_with_answers<A>(&self, assistant: &A, query: &str, context: Option<Context>, session_id: &str, answers: Vec<Value>) -> AssistantResponse
+    where
+        A: Assistant + ?Sized,
+    {
+        match assistant.solve(query, with_answers(context.clone(), &answers), session_id).await {
+            AssistantResponse::Query { ask, kind, context: query_context, .. } => {
+                let token = match token() {
+                    Ok(token) => token,
+                    Err(err) => return AssistantResponse::error(&err),
+                };
+                let pending = PendingQuery {
+                    query: query.to_string(),
+                    context,
+                    session_id: session_id.to_string(),
+                    ask: ask.clone(),
+                    answers,
+                    answer: None,
+                    notify: Arc::new(Notify::new()),
+                    created: Instant::now(),
+                };
+
+                match self.pending.lock() {
+                    Ok(mut queries) => {
+                        queries.retain(|_, query| query.created.elapsed() < self.ttl);
+                        queries.insert(token.clone(), pending);
+                    },
+                    Err(err) => return AssistantResponse::error(&Error::Unexpected(anyhow!("{}", err))),
+                }
+                debug! { session_id, "query registered" };
+
+                AssistantResponse::Query { ask, kind, context: query_context, token: Some(token) }
+            },
+            response => response,
+        }
+    }
+
+    /// Records the human's answer to the query registered under `token`, waking any `resume` waiting for it.
+    #[instrument(name = "QueryBroker::answer", level = "trace", skip(self, token, answer))]
+    pub fn answer(&self, token: &str, answer: impl Into<Value>) -> Result<(), Error> {
+        let mut pending = self.pending.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        let query = pending.get_mut(token)
+            .filter(|query| query.created.elapsed() < self.ttl)
+            .ok_or_else(|| Error::InvalidRequest("unknown or expired query token".to_string()))?;
+
+        query.answer = Some(answer.into());
+        query.notify.notify_one();
+        Ok(())
+    }
+
+    /// Waits for the answer to the query under `token`, then solves the original query again with it.
+    ///
+    /// Fails with `Error::InvalidRequest` if the token is unknown, already resumed, or expires before it is
+    /// answered.
+    #[instrument(name = "QueryBroker::resume", level = "trace", skip(self, assistant, token))]
+    pub async fn resume<A>(&self, assistant: &A, token: &str) -> Result<AssistantResponse, Error>
+    where
+        A: Assistant + ?Sized,
+    {
+        let expired = || Error::InvalidRequest("unknown or expired query token".to_string());
+
+        let pending = loop {
+            let (notify, remaining) = {
+                let mut pending = self.pending.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+                let query = pending.get(token).ok_or_else(expired)?;
+
+                let Some(remaining) = self.ttl.checked_sub(query.created.elapsed()) else {
+                    pending.remove(token);
+                    return Err(expired());
+                };
+                if query.answer.is_some() {
+                    break pending.remove(token).ok_or_else(expired)?;
+                }
+                (query.notify.clone(), remaining)
+            };
+
+            if timeout(remaining, notify.notified()).await.is_err() {
+                self.pending.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?.remove(token);
+                return Err(expired());
+            }
+        };
+
+        let mut answers = pending.answers;
+        answers.push(json!({ "ask": pending.ask, "answer": pending.answer }));
+        debug! { session_id = %pending.session_id, rounds = answers.len() };
+
+        Ok(self.solve_with_answers(assistant, &pending.query, pending.context, &pending.session_id, answers).await)
+    }
+
+    /// `answer` and `resume` in one call, for hosts that already have the answer in hand.
+    pub async fn resume_with<A>(&self, assistant: &A, token: &str, answer: impl Into<Value>) -> Result<AssistantResponse, Error>
+    where
+        A: Assistant + ?Sized,
+    {
+        self.answer(token, answer)?;
+        self.resume(assistant, token).await
+    }
+}
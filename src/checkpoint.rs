@@ -0,0 +1,112 @@
+use std::{collections::HashMap, future::Future, sync::Mutex};
+
+use anyhow::anyhow;
+use serde_json::Value;
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    serde::{Deserialize, Serialize},
+    std::path::PathBuf,
+    tokio::{fs, io::AsyncWriteExt},
+};
+
+use super::Error;
+
+pub trait CheckpointStore {
+    /// Returns every entry recorded under `namespace`, with later writes to a key replacing earlier ones.
+    fn entries(&self, namespace: &str) -> impl Future<Output = Result<HashMap<String, Value>, Error>>;
+
+    fn put(&self, namespace: &str, key: &str, value: Value) -> impl Future<Output = Result<(), Error>>;
+
+    fn get(&self, namespace: &str, key: &str) -> impl Future<Output = Result<Option<Value>, Error>> {
+        async move {
+            Ok(self.entries(namespace).await?.remove(key))
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryCheckpointStore {
+    entries: Mutex<HashMap<String, HashMap<String, Value>>>,
+}
+
+impl MemoryCheckpointStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl CheckpointStore for MemoryCheckpointStore {
+    async fn entries(&self, namespace: &str) -> Result<HashMap<String, Value>, Error> {
+        let entries = self.entries.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        Ok(entries.get(namespace).cloned().unwrap_or_default())
+    }
+
+    async fn put(&self, namespace: &str, key: &str, value: Value) -> Result<(), Error> {
+        let mut entries = self.entries.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        entries.entry(namespace.to_string()).or_default().insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize, Serialize)]
+struct FileCheckpointEntry {
+    key: String,
+    value: Value,
+}
+
+/// Append-only store keeping one JSONL file per namespace under `root`. Not available on wasm32.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Debug)]
+pub struct FileCheckpointStore {
+    root: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FileCheckpointStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, namespace: &str) -> PathBuf {
+        let name = namespace.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+            .collect::<String>();
+        self.root.join(format!("{}.jsonl", name))
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl CheckpointStore for FileCheckpointStore {
+    async fn entries(&self, namespace: &str) -> Result<HashMap<String, Value>, Error> {
+        let contents = match fs::read_to_string(self.path(namespace)).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(HashMap::new()),
+            Err(err) => return Err(Error::Unexpected(anyhow!(err))),
+        };
+
+        // A trailing partial line is expected if the process died mid-write, so it is skipped rather than rejected.
+        Ok(contents.lines()
+            .filter_map(|line| serde_json::from_str::<FileCheckpointEntry>(line).ok())
+            .map(|entry| (entry.key, entry.value))
+            .collect())
+    }
+
+    async fn put(&self, namespace: &str, key: &str, value: Value) -> Result<(), Error> {
+        fs::create_dir_all(&self.root).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        let mut line = serde_json::to_vec(&FileCheckpointEntry { key: key.to_string(), value })
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        line.push(b'\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.path(namespace))
+            .await
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.write_all(&line).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.flush().await.map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+}
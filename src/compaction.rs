@@ -0,0 +1,245 @@
+use std::future::Future;
+
+use tracing::{debug, instrument};
+
+use super::{
+    model::{estimate_tokens, LanguageModel, LanguageModelPrompt},
+    Error,
+    Message,
+    Role,
+    Turn,
+};
+
+/// Marks the system turn holding a `RollingSummary`, so the next compaction folds it into the new summary.
+const SUMMARY_PREFIX: &str = "Summary of the earlier conversation:\n";
+
+const SUMMARIZE_SYSTEM: &str = "You condense conversations. Summarize the transcript you are given, including any earlier \
+summary it starts with, into a short paragraph that keeps every fact, decision, name, number and open question a \
+participant might refer back to. Reply with only the summary.";
+
+/// Estimated tokens a turn adds to a prompt, counted the way `Capabilities::check` counts them.
+pub fn turn_tokens(turn: &Turn) -> usize {
+    let message = match turn.message() {
+        Message::Text { text } => estimate_tokens(text),
+        Message::Unknown(value) => estimate_tokens(&value.to_string()),
+        Message::Audio(_) | Message::Image(_) => 0,
+    };
+
+    message + turn.tool_uses().iter().map(|tool_use| estimate_tokens(&tool_use.input().to_string())).sum::<usize>()
+}
+
+fn tokens(turns: &[Turn]) -> usize {
+    turns.iter().map(turn_tokens).sum()
+}
+
+/// Shrinks a history to fit a token budget.
+///
+/// Implementations keep system turns and the order of the turns they keep, and return the history unchanged
+/// when it already fits. A history that cannot be brought under budget is returned as small as the strategy
+/// can make it, leaving the provider to reject it.
+pub trait Compaction {
+    fn compact(&self, turns: Vec<Turn>, budget: usize) -> impl Future<Output = Result<Vec<Turn>, Error>>;
+}
+
+/// Keeps the most recent turns that fit the budget, dropping everything before the first turn that does not.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SlidingWindow {
+    max_turns: Option<usize>,
+}
+
+impl SlidingWindow {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also caps the window at `max_turns` non-system turns, even when more would fit.
+    pub fn max_turns(self, max_turns: usize) -> Self {
+        Self {
+            max_turns: Some(max_turns),
+        }
+    }
+
+    fn window(&self, turns: Vec<Turn>, budget: usize) -> Vec<Turn> {
+        let system = turns.iter().filter(|turn| turn.role() == Role::System).map(turn_tokens).sum::<usize>();
+        let mut remaining = budget.saturating_sub(system);
+        let mut start = turns.len();
+
+        // The newest turn is always kept: without it there is nothing to respond to.
+        let recent = turns.iter().enumerate().rev().filter(|(_, turn)| turn.role() != Role::System);
+        for (kept, (index, turn)) in recent.enumerate() {
+            let cost = turn_tokens(turn);
+            if kept > 0 && (cost > remaining || self.max_turns.is_some_and(|max_turns| kept >= max_turns)) {
+                break;
+            }
+            remaining = remaining.saturating_sub(cost);
+            start = index;
+        }
+
+        turns.into_iter().enumerate().filter(|(index, turn)| *index >= start || turn.role() == Role::System).map(|(_, turn)| turn).collect()
+    }
+}
+
+impl Compaction for SlidingWindow {
+    #[instrument(name = "SlidingWindow::compact", level = "trace", skip(self, turns))]
+    async fn compact(&self, turns: Vec<Turn>, budget: usize) -> Result<Vec<Turn>, Error> {
+        let within_turns = self.max_turns.is_none_or(|max_turns| turns.iter().filter(|turn| turn.role() != Role::System).count() <= max_turns);
+        if within_turns && tokens(&turns) <= budget {
+            return Ok(turns);
+        }
+
+        let before = turns.len();
+        let turns = self.window(turns, budget);
+        debug! { dropped = before - turns.len() };
+        Ok(turns)
+    }
+}
+
+/// Evicts the oldest turns one at a time until the history fits, never evicting the first `keep_first`
+/// non-system turns, which usually state the task.
+#[derive(Clone, Copy, Debug)]
+pub struct OldestFirst {
+    keep_first: usize,
+}
+
+impl Default for OldestFirst {
+    fn default() -> Self {
+        Self { keep_first: 1 }
+    }
+}
+
+impl OldestFirst {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keep_first(self, keep_first: usize) -> Self {
+        Self { keep_first }
+    }
+}
+
+impl Compaction for OldestFirst {
+    #[instrument(name = "OldestFirst::compact", level = "trace", skip(self, turns))]
+    async fn compact(&self, mut turns: Vec<Turn>, budget: usize) -> Result<Vec<Turn>, Error> {
+        let mut total = tokens(&turns);
+        let mut evicted = 0;
+
+        while total > budget {
+            // Oldest evictable turn: past the pinned prefix and not the newest turn.
+            let candidate = turns.iter()
+                .enumerate()
+                .filter(|(_, turn)| turn.role() != Role::System)
+                .skip(self.keep_first)
+                .map(|(index, _)| index)
+                .next()
+                .filter(|index| turns[*index + 1..].iter().any(|turn| turn.role() != Role::System));
+            let Some(index) = candidate else { break };
+
+            total -= turn_tokens(&turns.remove(index));
+            evicted += 1;
+        }
+        debug! { evicted, tokens = total };
+
+        Ok(turns)
+    }
+}
+
+/// Replaces older turns with a summary written by `model`, keeping the newest `keep_recent` turns verbatim.
+///
+/// The summary is a system turn, and is itself folded into the next summary, so the history stays bounded
+/// however long the conversation runs. If the summary and the recent turns still do not fit, the result is
+/// trimmed with a `SlidingWindow`.
+#[derive(Clone, Debug)]
+pub struct RollingSummary<M> {
+    model: M,
+    keep_recent: usize,
+    max_summary_tokens: usize,
+}
+
+impl<M> RollingSummary<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            keep_recent: 4,
+            max_summary_tokens: 512,
+        }
+    }
+
+    pub fn keep_recent(self, keep_recent: usize) -> Self {
+        Self {
+            keep_recent,
+            ..self
+        }
+    }
+
+    pub fn max_summary_tokens(self, max_summary_tokens: usize) -> Self {
+        Self {
+            max_summary_tokens,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+fn is_summary(turn: &Turn) -> bool {
+    turn.role() == Role::System && matches!(turn.message(), Message::Text { text } if text.starts_with(SUMMARY_PREFIX))
+}
+
+impl<M> Compaction for RollingSummary<M>
+where
+    M: LanguageModel,
+{
+    #[instrument(name = "RollingSummary::compact", level = "trace", skip(self, turns))]
+    async fn compact(&self, turns: Vec<Turn>, budget: usize) -> Result<Vec<Turn>, Error> {
+        if tokens(&turns) <= budget {
+            return Ok(turns);
+        }
+
+        let conversational = turns.iter().filter(|turn| turn.role() != Role::System).count();
+        let older = conversational.saturating_sub(self.keep_recent);
+        if older == 0 {
+            return SlidingWindow::new().compact(turns, budget).await;
+        }
+
+        let mut summarized = Vec::new();
+        let mut kept = Vec::new();
+        let mut seen = 0;
+        for turn in turns {
+            if is_summary(&turn) {
+                summarized.push(turn);
+            } else if turn.role() == Role::System {
+                kept.push(turn);
+            } else {
+                seen += 1;
+                match seen <= older {
+                    true => summarized.push(turn),
+                    false => kept.push(turn),
+                }
+            }
+        }
+
+        let transcript = summarized.iter()
+            .map(|turn| match turn.role() {
+                Role::System => turn.message().to_string(),
+                Role::User => format!("User: {}", turn.message()),
+                Role::Assistant => format!("Assistant: {}", turn.message()),
+                Role::Tool => format!("Tool: {}", turn.message()),
+            })
+            .collect::<Vec<String>>()
+            .join("\n\n");
+        let prompt = LanguageModelPrompt::from(transcript)
+            .system(SUMMARIZE_SYSTEM)
+            .max_tokens(self.max_summary_tokens)
+            .temperature(0.0);
+        let summary = self.model.inference(prompt).await?.to_string();
+        debug! { summarized = summarized.len(), summary_tokens = estimate_tokens(&summary) };
+
+        // The summary goes after the other system turns, where the summarized turns were.
+        let position = kept.iter().position(|turn| turn.role() != Role::System).unwrap_or(kept.len());
+        kept.insert(position, Turn::new(Role::System, format!("{}{}", SUMMARY_PREFIX, summary.trim())));
+
+        SlidingWindow::new().compact(kept, budget).await
+    }
+}
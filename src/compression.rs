@@ -0,0 +1,399 @@
+use std::{collections::HashSet, future::Future};
+
+use tracing::{debug, instrument};
+
+use super::{
+    model::{estimate_tokens, Capabilities, CompatibilityReport, LanguageModel, LanguageModelPrompt, RateLimit},
+    Error,
+    Message,
+};
+
+const STOPWORDS: &[&str] = &[
+    "a", "an", "the", "very", "really", "just", "quite", "rather", "somewhat", "basically", "actually", "simply",
+    "that", "which", "so", "then", "also", "indeed", "perhaps", "maybe", "of", "is", "are", "was", "were", "be",
+    "been", "being", "please", "kindly",
+];
+
+/// Words `HeuristicCompressor` removes: articles and fillers whose absence does not change what a sentence says.
+const FILLER_WORDS: &[&str] = &[
+    "a", "an", "the", "very", "really", "just", "quite", "rather", "somewhat", "basically", "actually", "simply",
+    "indeed", "please", "kindly",
+];
+
+pub trait PromptCompressor {
+    fn compress(&self, text: &str) -> impl Future<Output = Result<String, Error>>;
+}
+
+/// Token savings from compressing a prompt, measured with [`estimate_tokens`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CompressionReport {
+    original_tokens: usize,
+    compressed_tokens: usize,
+}
+
+impl CompressionReport {
+    pub fn original_tokens(&self) -> usize {
+        self.original_tokens
+    }
+
+    pub fn compressed_tokens(&self) -> usize {
+        self.compressed_tokens
+    }
+
+    pub fn saved_tokens(&self) -> usize {
+        self.original_tokens.saturating_sub(self.compressed_tokens)
+    }
+
+    pub fn ratio(&self) -> f32 {
+        if self.original_tokens == 0 { 1.0 } else { self.compressed_tokens as f32 / self.original_tokens as f32 }
+    }
+}
+
+/// Drops filler words, blank and immediately repeated lines, and redundant whitespace without calling a model.
+///
+/// Indentation is kept, and fenced code blocks (` ``` ` or `~~~`) are passed through untouched.
+#[derive(Clone, Debug, Default)]
+pub struct HeuristicCompressor {
+    keep_stopwords: bool,
+}
+
+impl HeuristicCompressor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keep_stopwords(self, keep_stopwords: bool) -> Self {
+        Self { keep_stopwords }
+    }
+
+    /// `line` with its indentation, and its words separated by single spaces minus the fillers.
+    fn compress_line(&self, line: &str) -> String {
+        let content = line.trim_start();
+        let words = content.split_whitespace()
+            .filter(|word| self.keep_stopwords || !FILLER_WORDS.contains(&word.to_lowercase().as_str()))
+            .collect::<Vec<&str>>();
+
+        match words.is_empty() {
+            true => String::new(),
+            false => format!("{}{}", &line[..line.len() - content.len()], words.join(" ")),
+        }
+    }
+}
+
+impl PromptCompressor for HeuristicCompressor {
+    async fn compress(&self, text: &str) -> Result<String, Error> {
+        let mut lines = Vec::<String>::new();
+        let mut fence = None;
+
+        for line in text.lines() {
+            let marker = line.trim_start();
+            let opens = ["```", "~~~"].into_iter().find(|fence| marker.starts_with(fence));
+
+            match (fence, opens) {
+                (None, Some(opened)) => fence = Some(opened),
+                (Some(open), Some(closed)) if open == closed => fence = None,
+                (Some(_), _) => {},
+                (None, None) => {
+                    let line = self.compress_line(line);
+                    if !line.is_empty() && lines.last() != Some(&line) {
+                        lines.push(line);
+                    }
+                    continue;
+                },
+            }
+            lines.push(line.to_string());
+        }
+
+        Ok(lines.join("\n"))
+    }
+}
+
+/// Asks a (typically small, cheap) model to rewrite text in as few tokens as possible.
+#[derive(Clone, Debug)]
+pub struct ModelCompressor<M> {
+    model: M,
+}
+
+impl<M> ModelCompressor<M> {
+    pub fn new(model: M) -> Self {
+        Self { model }
+    }
+}
+
+impl<M> PromptCompressor for ModelCompressor<M>
+where
+    M: LanguageModel,
+{
+    async fn compress(&self, text: &str) -> Result<String, Error> {
+        let prompt = LanguageModelPrompt::from(text)
+            .system("Compress the user's text to as few tokens as possible while preserving every fact, name, number and instruction. Reply with only the compressed text.")
+            .max_tokens(estimate_tokens(text).max(256))
+            .temperature(0.0);
+
+        Ok(self.model.inference(prompt).await?.to_string())
+    }
+}
+
+/// Compresses every text message of at least `min_tokens` estimated tokens.
+pub async fn compress_prompt<C>(compressor: &C, prompt: LanguageModelPrompt, min_tokens: usize) -> Result<(LanguageModelPrompt, CompressionReport), Error>
+where
+    C: PromptCompressor,
+{
+    let mut report = CompressionReport::default();
+    let mut messages = Vec::with_capacity(prompt.messages.len());
+
+    for message in prompt.messages.iter() {
+        match message {
+            Message::Text { text } => {
+                let original_tokens = estimate_tokens(text.as_str());
+                report.original_tokens += original_tokens;
+
+                if original_tokens >= min_tokens {
+                    let compressed = compressor.compress(text.as_str()).await?;
+                    let compressed_tokens = estimate_tokens(&compressed);
+
+                    // Never send something larger than what we started with.
+                    if compressed_tokens < original_tokens {
+                        report.compressed_tokens += compressed_tokens;
+                        messages.push(Message::Text { text: compressed });
+                        continue;
+                    }
+                }
+
+                report.compressed_tokens += original_tokens;
+                messages.push(message.clone());
+            },
+            message => messages.push(message.clone()),
+        }
+    }
+
+    Ok((LanguageModelPrompt { messages, ..prompt }, report))
+}
+
+/// What `compress_context` did to fit a prompt into its token budget. Message indices refer to the original prompt.
+#[derive(Clone, Debug, Default)]
+pub struct ContextReport {
+    budget: usize,
+    original_tokens: usize,
+    final_tokens: usize,
+    compressed: Vec<usize>,
+    dropped: Vec<(usize, Message)>,
+}
+
+impl ContextReport {
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    pub fn original_tokens(&self) -> usize {
+        self.original_tokens
+    }
+
+    pub fn final_tokens(&self) -> usize {
+        self.final_tokens
+    }
+
+    /// Indices of the messages that were replaced by their compressed form.
+    pub fn compressed(&self) -> &[usize] {
+        &self.compressed
+    }
+
+    /// The messages that were removed, with their indices, least salient first.
+    pub fn dropped(&self) -> &[(usize, Message)] {
+        &self.dropped
+    }
+
+    /// False when even dropping every earlier message left the prompt over budget.
+    pub fn within_budget(&self) -> bool {
+        self.final_tokens <= self.budget
+    }
+}
+
+fn content_words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.len() > 2)
+        .map(str::to_lowercase)
+        .filter(|word| !STOPWORDS.contains(&word.as_str()))
+        .collect()
+}
+
+/// Share of a message's content words that also appear in the query.
+fn salience(text: &str, query: &HashSet<String>) -> f32 {
+    let words = content_words(text);
+    match words.is_empty() {
+        true => 0.0,
+        false => words.intersection(query).count() as f32 / words.len() as f32,
+    }
+}
+
+/// Fits a prompt into `budget` estimated tokens (system prompt and examples included), doing nothing when it
+/// already fits. Text messages are ranked by how much they share with the final message, the query, older ones
+/// first on ties; the least salient are compressed with `compressor` until the prompt fits, then dropped if
+/// compression was not enough. The final message is always kept as is.
+#[instrument(name = "compression::compress_context", level = "trace", skip(compressor, prompt))]
+pub async fn compress_context<C>(compressor: &C, prompt: LanguageModelPrompt, budget: usize) -> Result<(LanguageModelPrompt, ContextReport), Error>
+where
+    C: PromptCompressor,
+{
+    let tokens = |message: &Message| match message {
+        Message::Text { text } => estimate_tokens(text),
+        _ => 0,
+    };
+
+    let mut messages = prompt.messages.iter().cloned().map(Some).collect::<Vec<Option<Message>>>();
+    let mut total = prompt.preamble_tokens() + prompt.messages.iter().map(tokens).sum::<usize>();
+    let mut report = ContextReport { budget, original_tokens: total, final_tokens: total, ..ContextReport::default() };
+    if total <= budget {
+        return Ok((prompt, report));
+    }
+
+    let query = match prompt.messages.last() {
+        Some(Message::Text { text }) => content_words(text),
+        _ => HashSet::new(),
+    };
+    let mut ranked = prompt.messages.iter()
+        .enumerate()
+        .take(prompt.messages.len().saturating_sub(1))
+        .filter_map(|(index, message)| match message {
+            Message::Text { text } => Some((index, salience(text, &query))),
+            _ => None,
+        })
+        .collect::<Vec<(usize, f32)>>();
+    ranked.sort_by(|(a, a_salience), (b, b_salience)| a_salience.total_cmp(b_salience).then(a.cmp(b)));
+
+    for &(index, _) in ranked.iter() {
+        if total <= budget {
+            break;
+        }
+        let Some(Message::Text { text }) = &messages[index] else {
+            continue;
+        };
+
+        let original_tokens = estimate_tokens(text);
+        let compressed = compressor.compress(text).await?;
+        let compressed_tokens = estimate_tokens(&compressed);
+        if compressed_tokens < original_tokens {
+            total -= original_tokens - compressed_tokens;
+            messages[index] = Some(Message::Text { text: compressed });
+            report.compressed.push(index);
+        }
+    }
+
+    for &(index, _) in ranked.iter() {
+        if total <= budget {
+            break;
+        }
+        if let Some(message) = messages[index].take() {
+            total -= tokens(&message);
+            report.dropped.push((index, prompt.messages[index].clone()));
+        }
+    }
+
+    report.final_tokens = total;
+    debug! { budget, original_tokens = report.original_tokens, final_tokens = total, compressed = report.compressed.len(), dropped = report.dropped.len() };
+
+    let messages = messages.into_iter().flatten().collect();
+    Ok((LanguageModelPrompt { messages, ..prompt }, report))
+}
+
+/// Wraps a model so that long context sections are compressed before every call. Use `inference_with_report`
+/// for the savings of a call.
+#[derive(Clone, Debug)]
+pub struct CompressedModel<M, C> {
+    model: M,
+    compressor: C,
+    min_tokens: usize,
+}
+
+impl<M, C> CompressedModel<M, C> {
+    pub fn new(model: M, compressor: C) -> Self {
+        Self {
+            model,
+            compressor,
+            min_tokens: 512,
+        }
+    }
+
+    pub fn min_tokens(self, min_tokens: usize) -> Self {
+        Self {
+            min_tokens,
+            ..self
+        }
+    }
+
+}
+
+impl<M, C> CompressedModel<M, C>
+where
+    M: LanguageModel,
+    C: PromptCompressor,
+{
+    /// The response together with the token savings of compressing this call's prompt.
+    #[instrument(name = "CompressedModel::inference", level = "trace", skip(self))]
+    pub async fn inference_with_report(&self, prompt: LanguageModelPrompt) -> Result<(Message, CompressionReport), Error> {
+        let (prompt, report) = compress_prompt(&self.compressor, prompt, self.min_tokens).await?;
+        debug! { original_tokens = report.original_tokens, compressed_tokens = report.compressed_tokens };
+
+        let message = self.model.inference(prompt).await?;
+        Ok((message, report))
+    }
+}
+
+impl<M, C> LanguageModel for CompressedModel<M, C>
+where
+    M: LanguageModel,
+    C: PromptCompressor,
+{
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        self.inference_with_report(prompt).await.map(|(message, _)| message)
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn compress(text: &str) -> String {
+        HeuristicCompressor::new().compress(text).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn drops_fillers_and_consecutive_duplicates() {
+        let text = "Please   just read the file.\nPlease   just read the file.\n\nIt is really important.";
+
+        assert_eq!(compress(text).await, "read file.\nIt is important.");
+    }
+
+    #[tokio::test]
+    async fn keeps_repeated_lines_that_are_not_adjacent() {
+        let text = "| x | 1 |\n| y | 2 |\n| x | 1 |\n}\nend\n}\nend";
+
+        assert_eq!(compress(text).await, text);
+    }
+
+    #[tokio::test]
+    async fn keeps_indentation() {
+        let text = "config:\n  name: the app\n  ports:\n    - 80";
+
+        assert_eq!(compress(text).await, "config:\n  name: app\n  ports:\n    - 80");
+    }
+
+    #[tokio::test]
+    async fn leaves_fenced_code_untouched() {
+        let text = "Here is the code:\n```python\nif a:\n    return the\n\n    return the\n```\nThe end.";
+
+        assert_eq!(compress(text).await, "Here is code:\n```python\nif a:\n    return the\n\n    return the\n```\nend.");
+    }
+}
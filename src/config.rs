@@ -0,0 +1,165 @@
+use std::{env, path::Path, sync::OnceLock};
+
+use anyhow::anyhow;
+use regex::{Captures, Regex};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+use super::{Error, SecretSource};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::collections::HashMap,
+    tokio::fs,
+    tracing::instrument,
+    super::LanguageModel,
+};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConfigFormat {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        match path.as_ref().extension()?.to_str()? {
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            "yaml" | "yml" => Some(Self::Yaml),
+            _ => None,
+        }
+    }
+}
+
+fn variable() -> &'static Regex {
+    static VARIABLE: OnceLock<Regex> = OnceLock::new();
+    VARIABLE.get_or_init(|| Regex::new(r"\$(\$)?\{([A-Za-z_][A-Za-z0-9_]*)(?::-([^}]*))?\}").expect("valid variable pattern"))
+}
+
+/// Replaces `${NAME}` with the environment variable `NAME`, or with `fallback` for `${NAME:-fallback}`.
+/// `$${NAME}` is kept literally as `${NAME}`. Unset variables without a fallback are an error.
+pub fn interpolate(text: &str) -> Result<String, Error> {
+    let mut missing = None;
+
+    let interpolated = variable().replace_all(text, |captures: &Captures| {
+        if captures.get(1).is_some() {
+            return captures[0][1..].to_string();
+        }
+
+        match (env::var(&captures[2]), captures.get(3)) {
+            (Ok(value), _) => value,
+            (Err(_), Some(fallback)) => fallback.as_str().to_string(),
+            (Err(_), None) => {
+                missing.get_or_insert_with(|| captures[2].to_string());
+                String::new()
+            },
+        }
+    });
+
+    match missing {
+        Some(name) => Err(Error::Unexpected(anyhow!("missing-env-var: {}", name))),
+        None => Ok(interpolated.into_owned()),
+    }
+}
+
+/// Backends a `from` field may name for a value to count as a secret reference.
+const SECRET_BACKENDS: &[&str] = &["env", "keyring", "aws_secrets_manager"];
+
+fn is_secret_reference(value: &Value) -> bool {
+    match value {
+        Value::Object(fields) => fields.get("from").and_then(Value::as_str).is_some_and(|from| SECRET_BACKENDS.contains(&from)),
+        Value::Array(items) => !items.is_empty() && items.iter().all(is_secret_reference),
+        _ => false,
+    }
+}
+
+/// JSON pointers to every secret reference in `value`.
+fn secret_references(value: &Value, pointer: String, references: &mut Vec<String>) {
+    if is_secret_reference(value) {
+        references.push(pointer);
+        return;
+    }
+
+    let escape = |key: &str| key.replace('~', "~0").replace('/', "~1");
+    match value {
+        Value::Object(fields) => fields.iter().for_each(|(key, value)| secret_references(value, format!("{}/{}", pointer, escape(key)), references)),
+        Value::Array(items) => items.iter().enumerate().for_each(|(index, value)| secret_references(value, format!("{}/{}", pointer, index), references)),
+        _ => {},
+    }
+}
+
+/// Replaces every secret reference in `config` with the secret it names, so that e.g. an `api_key` can be
+/// `{ from = "keyring", service = "april", account = "anthropic" }`, or a list of such sources tried in order.
+pub async fn resolve_secrets(config: &mut Value) -> Result<(), Error> {
+    let mut references = Vec::new();
+    secret_references(config, String::new(), &mut references);
+
+    for pointer in references {
+        let Some(value) = config.pointer_mut(&pointer) else {
+            continue;
+        };
+
+        let sources = match value {
+            Value::Array(_) => serde_json::from_value::<Vec<SecretSource>>(value.take()),
+            _ => serde_json::from_value::<SecretSource>(value.take()).map(|source| vec![source]),
+        };
+        let sources = sources.map_err(|err| Error::Unexpected(anyhow!("invalid-secret-source: {}: {}", pointer, err)))?;
+
+        *value = Value::String(SecretSource::resolve(&sources).await?.expose().to_string());
+    }
+
+    Ok(())
+}
+
+/// Parses `text` as `format` after environment interpolation.
+pub fn from_str<T>(text: &str, format: ConfigFormat) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let text = interpolate(text)?;
+
+    match format {
+        ConfigFormat::Json => serde_json::from_str(&text).map_err(|err| Error::Unexpected(anyhow!(err))),
+        ConfigFormat::Toml => toml::from_str(&text).map_err(|err| Error::Unexpected(anyhow!(err))),
+        ConfigFormat::Yaml => serde_yaml::from_str(&text).map_err(|err| Error::Unexpected(anyhow!(err))),
+    }
+}
+
+/// Reads a JSON, TOML or YAML file (chosen by extension) into any deserializable configuration,
+/// e.g. a `ModelRegistry`. Secret references are resolved first, see `resolve_secrets`.
+#[cfg(not(target_arch = "wasm32"))]
+#[instrument(name = "config::load", level = "trace", skip(path), fields(path = %path.as_ref().display()))]
+pub async fn load<T>(path: impl AsRef<Path>) -> Result<T, Error>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let format = ConfigFormat::from_path(path)
+        .ok_or_else(|| Error::Unexpected(anyhow!("unsupported-config-format: {}", path.display())))?;
+
+    let text = fs::read_to_string(path).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+    let mut config = from_str::<Value>(&text, format)?;
+    resolve_secrets(&mut config).await?;
+
+    serde_json::from_value(config).map_err(|err| Error::Unexpected(anyhow!(err)))
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn load_model(path: impl AsRef<Path>) -> Result<LanguageModel, Error> {
+    LanguageModel::from_config(load(path).await?).await
+}
+
+/// Loads a table of named models, keyed by the name used to look them up.
+#[cfg(not(target_arch = "wasm32"))]
+pub async fn load_models(path: impl AsRef<Path>) -> Result<HashMap<String, LanguageModel>, Error> {
+    let configs = load::<HashMap<String, Value>>(path).await?;
+
+    let mut models = HashMap::with_capacity(configs.len());
+    for (name, config) in configs {
+        models.insert(name, LanguageModel::from_config(config).await?);
+    }
+
+    Ok(models)
+}
\ No newline at end of file
@@ -0,0 +1,252 @@
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::{Error, Message, ToolUse};
+
+/// A document retrieved for the request, e.g. by a RAG step, with where it came from and how well it matched.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Document {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    content: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+}
+
+impl Document {
+    pub fn new(content: impl Into<String>) -> Self {
+        Self { id: None, content: content.into(), source: None, score: None }
+    }
+
+    pub fn identified_as(self, id: impl Into<String>) -> Self {
+        Self {
+            id: Some(id.into()),
+            ..self
+        }
+    }
+
+    pub fn sourced_from(self, source: impl Into<String>) -> Self {
+        Self {
+            source: Some(source.into()),
+            ..self
+        }
+    }
+
+    pub fn scored(self, score: f32) -> Self {
+        Self {
+            score: Some(score),
+            ..self
+        }
+    }
+
+    pub fn id(&self) -> Option<&str> {
+        self.id.as_deref()
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+
+    pub fn source(&self) -> Option<&str> {
+        self.source.as_deref()
+    }
+
+    pub fn score(&self) -> Option<f32> {
+        self.score
+    }
+}
+
+/// What accompanies a query into `Assistant::solve` and comes back on its response.
+///
+/// The typed parts cover what assistants commonly exchange; anything else lives in `extra`, which serializes
+/// as top-level keys, so existing JSON contexts round-trip unchanged through `from_value` and `to_value`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct Context {
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    metadata: Map<String, Value>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Message>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    documents: Vec<Document>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_results: Vec<ToolUse>,
+
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+impl Context {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reads a JSON context; the object's keys other than the typed ones go to `extra`.
+    pub fn from_value(value: Value) -> Result<Self, Error> {
+        if !value.is_object() {
+            return Err(Error::InvalidRequest("context must be a JSON object".to_string()));
+        }
+        serde_json::from_value(value).map_err(|err| Error::InvalidRequest(format!("invalid context: {}", err)))
+    }
+
+    pub fn to_value(&self) -> Value {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metadata.is_empty() && self.attachments.is_empty() && self.documents.is_empty() && self.tool_results.is_empty() && self.extra.is_empty()
+    }
+
+    pub fn metadata(&self) -> &Map<String, Value> {
+        &self.metadata
+    }
+
+    pub fn get_metadata(&self, key: &str) -> Option<&Value> {
+        self.metadata.get(key)
+    }
+
+    pub fn set_metadata(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.metadata.insert(key.into(), value.into());
+    }
+
+    pub fn attachments(&self) -> &[Message] {
+        &self.attachments
+    }
+
+    pub fn attach(&mut self, attachment: impl Into<Message>) {
+        self.attachments.push(attachment.into());
+    }
+
+    pub fn documents(&self) -> &[Document] {
+        &self.documents
+    }
+
+    pub fn add_document(&mut self, document: Document) {
+        self.documents.push(document);
+    }
+
+    pub fn tool_results(&self) -> &[ToolUse] {
+        &self.tool_results
+    }
+
+    pub fn add_tool_result(&mut self, tool_result: ToolUse) {
+        self.tool_results.push(tool_result);
+    }
+
+    /// The untyped escape hatch: top-level keys that are not one of the typed parts.
+    pub fn extra(&self) -> &Map<String, Value> {
+        &self.extra
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        self.extra.get(key)
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, value: impl Into<Value>) {
+        self.extra.insert(key.into(), value.into());
+    }
+
+    /// Combines two contexts, `other` taking precedence: metadata and `extra` keys in `other` replace those
+    /// in `self`, lists are appended, and a document is dropped if one with the same id is already present.
+    pub fn merge(mut self, other: Context) -> Self {
+        self.metadata.extend(other.metadata);
+        self.extra.extend(other.extra);
+        self.attachments.extend(other.attachments);
+        self.tool_results.extend(other.tool_results);
+
+        for document in other.documents {
+            let duplicate = document.id.is_some() && self.documents.iter().any(|existing| existing.id == document.id);
+            if !duplicate {
+                self.documents.push(document);
+            }
+        }
+
+        self
+    }
+
+    /// Checks the context's JSON form against `schema`.
+    ///
+    /// Supports the subset of JSON Schema used for tool inputs: `type`, `properties`, `required`,
+    /// `additionalProperties: false`, `items` and `enum`. Failures are `Error::InvalidRequest` naming the first
+    /// offending path.
+    pub fn validate(&self, schema: &Value) -> Result<(), Error> {
+        check(&self.to_value(), schema, "").map_err(|reason| Error::InvalidRequest(format!("context does not match schema: {}", reason)))
+    }
+}
+
+impl TryFrom<Value> for Context {
+    type Error = Error;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        Self::from_value(value)
+    }
+}
+
+impl From<Context> for Value {
+    fn from(context: Context) -> Self {
+        context.to_value()
+    }
+}
+
+fn type_matches(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn check(value: &Value, schema: &Value, path: &str) -> Result<(), String> {
+    let at = || if path.is_empty() { "/".to_string() } else { path.to_string() };
+
+    let types = match schema.get("type") {
+        Some(Value::String(expected)) => vec![expected.as_str()],
+        Some(Value::Array(expected)) => expected.iter().filter_map(Value::as_str).collect(),
+        _ => Vec::new(),
+    };
+    if !types.is_empty() && !types.iter().any(|expected| type_matches(value, expected)) {
+        return Err(format!("{}: expected {}", at(), types.join(" or ")));
+    }
+
+    if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+        if !allowed.contains(value) {
+            return Err(format!("{}: not one of the allowed values", at()));
+        }
+    }
+
+    if let Value::Object(object) = value {
+        let properties = schema.get("properties").and_then(Value::as_object);
+
+        for required in schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str) {
+            if !object.contains_key(required) {
+                return Err(format!("{}/{}: required", path, required));
+            }
+        }
+        for (key, value) in object {
+            match properties.and_then(|properties| properties.get(key)) {
+                Some(property) => check(value, property, &format!("{}/{}", path, key))?,
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => return Err(format!("{}/{}: not allowed", path, key)),
+                None => {},
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (index, item) in items.iter().enumerate() {
+            check(item, item_schema, &format!("{}/{}", path, index))?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,543 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    future::Future,
+    sync::Mutex,
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{compaction::Compaction, model::LanguageModelPrompt, Error, Message};
+
+/// The persistence format version written by `Conversation::to_json` and `to_jsonl`.
+///
+/// Older versions are read as-is (missing fields take their defaults); newer versions are rejected rather than
+/// silently dropping data this build does not understand.
+pub const CONVERSATION_VERSION: u32 = 1;
+
+fn version() -> u32 {
+    CONVERSATION_VERSION
+}
+
+fn check_version(version: u32) -> Result<(), Error> {
+    match version <= CONVERSATION_VERSION {
+        true => Ok(()),
+        false => Err(Error::Unexpected(anyhow!("unsupported-conversation-version: {} (newest supported is {})", version, CONVERSATION_VERSION))),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    System,
+    User,
+    Assistant,
+    Tool,
+}
+
+/// A tool the assistant invoked during a turn, with its result.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ToolUse {
+    name: String,
+    input: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ToolUse {
+    pub fn new(name: impl Into<String>, input: Value) -> Self {
+        Self { name: name.into(), input, output: None, error: None }
+    }
+
+    pub fn succeeded(self, output: Value) -> Self {
+        Self {
+            output: Some(output),
+            error: None,
+            ..self
+        }
+    }
+
+    pub fn failed(self, error: impl Into<String>) -> Self {
+        Self {
+            output: None,
+            error: Some(error.into()),
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn input(&self) -> &Value {
+        &self.input
+    }
+
+    pub fn output(&self) -> Option<&Value> {
+        self.output.as_ref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Tokens billed for a turn.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Usage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+impl Usage {
+    pub fn new(input_tokens: u64, output_tokens: u64) -> Self {
+        Self { input_tokens, output_tokens }
+    }
+
+    pub fn input_tokens(&self) -> u64 {
+        self.input_tokens
+    }
+
+    pub fn output_tokens(&self) -> u64 {
+        self.output_tokens
+    }
+
+    pub fn total_tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+}
+
+impl std::ops::Add for Usage {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.input_tokens + other.input_tokens, self.output_tokens + other.output_tokens)
+    }
+}
+
+impl std::iter::Sum for Usage {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), std::ops::Add::add)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Turn {
+    role: Role,
+    message: Message,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tool_uses: Vec<ToolUse>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    usage: Option<Usage>,
+}
+
+impl Turn {
+    pub fn new(role: Role, message: impl Into<Message>) -> Self {
+        Self { role, message: message.into(), tool_uses: Vec::new(), usage: None }
+    }
+
+    pub fn add_tool_use(self, tool_use: ToolUse) -> Self {
+        let mut tool_uses = self.tool_uses;
+        tool_uses.push(tool_use);
+
+        Self {
+            tool_uses,
+            ..self
+        }
+    }
+
+    pub fn record_usage(self, usage: Usage) -> Self {
+        Self {
+            usage: Some(usage),
+            ..self
+        }
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn tool_uses(&self) -> &[ToolUse] {
+        &self.tool_uses
+    }
+
+    pub fn usage(&self) -> Option<Usage> {
+        self.usage
+    }
+}
+
+/// The first line of a JSONL export; every following line is one turn.
+#[derive(Deserialize, Serialize)]
+struct JsonlHeader {
+    #[serde(default = "version")]
+    version: u32,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    snapshots: BTreeMap<String, Vec<Turn>>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    notes: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Conversation {
+    #[serde(default = "version")]
+    version: u32,
+
+    #[serde(default)]
+    turns: Vec<Turn>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    snapshots: BTreeMap<String, Vec<Turn>>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    notes: BTreeMap<String, String>,
+}
+
+impl Default for Conversation {
+    fn default() -> Self {
+        Self {
+            version: CONVERSATION_VERSION,
+            turns: Vec::new(),
+            snapshots: BTreeMap::new(),
+            notes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Conversation {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, role: Role, message: impl Into<Message>) {
+        self.turns.push(Turn::new(role, message));
+    }
+
+    pub fn push_turn(&mut self, turn: Turn) {
+        self.turns.push(turn);
+    }
+
+    pub fn turns(&self) -> &Vec<Turn> {
+        &self.turns
+    }
+
+    /// The format version this conversation was read from, or `CONVERSATION_VERSION` if it was created here.
+    pub fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Shrinks the turns to fit `budget` tokens with `compaction`, returning how many turns were removed or
+    /// folded into a summary. Snapshots and notes are left alone.
+    pub async fn compact<C>(&mut self, compaction: &C, budget: usize) -> Result<usize, Error>
+    where
+        C: Compaction,
+    {
+        let before = self.turns.len();
+        self.turns = compaction.compact(std::mem::take(&mut self.turns), budget).await?;
+        Ok(before.saturating_sub(self.turns.len()))
+    }
+
+    /// Checks that user and assistant turns alternate, as chat APIs require of multi-turn input: after any
+    /// system turns the first turn is from the user, and no two consecutive user or assistant turns share a
+    /// role. Tool turns may follow any turn.
+    pub fn validate_roles(&self) -> Result<(), Error> {
+        let mut previous = None;
+        for (index, turn) in self.turns.iter().enumerate() {
+            match (previous, turn.role) {
+                (_, Role::System) if previous.is_some() => {
+                    return Err(Error::InvalidRequest(format!("turn {}: system turns must come first", index)));
+                },
+                (None, Role::Assistant) => return Err(Error::InvalidRequest(format!("turn {}: the first turn must be from the user", index))),
+                (Some(previous), role) if previous == role && role != Role::Tool => {
+                    return Err(Error::InvalidRequest(format!("turn {}: consecutive {:?} turns", index, role)));
+                },
+                _ => {},
+            }
+            if turn.role != Role::System {
+                previous = Some(turn.role);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Merges consecutive text turns of the same role into one, joining them with a blank line and keeping
+    /// their tool uses and summed usage, so the history satisfies `validate_roles` where it can.
+    pub fn normalize(&mut self) {
+        let mut turns = Vec::<Turn>::with_capacity(self.turns.len());
+        for turn in std::mem::take(&mut self.turns) {
+            match (turns.last_mut(), turn) {
+                (Some(previous), turn) if previous.role == turn.role && turn.role != Role::Tool => match (&mut previous.message, turn.message) {
+                    (Message::Text { text: previous_text }, Message::Text { text }) => {
+                        previous_text.push_str("\n\n");
+                        previous_text.push_str(&text);
+                        previous.tool_uses.extend(turn.tool_uses);
+                        previous.usage = match (previous.usage, turn.usage) {
+                            (Some(previous), Some(usage)) => Some(previous + usage),
+                            (previous, usage) => previous.or(usage),
+                        };
+                    },
+                    (_, message) => turns.push(Turn { message, ..turn }),
+                },
+                (_, turn) => turns.push(turn),
+            }
+        }
+        self.turns = turns;
+    }
+
+    /// Tokens billed across every turn that recorded usage.
+    pub fn usage(&self) -> Usage {
+        self.turns.iter().filter_map(Turn::usage).sum()
+    }
+
+    /// Replays the turns as a prompt for any provider.
+    ///
+    /// System turns become the system prompt. User messages are sent as they are; assistant and tool turns,
+    /// which a prompt has no role for, are sent as tagged text so the model can tell who said what.
+    pub fn prompt(&self) -> LanguageModelPrompt {
+        let system = self.turns.iter()
+            .filter(|turn| turn.role == Role::System)
+            .map(|turn| turn.message.to_string())
+            .collect::<Vec<String>>();
+
+        let mut messages = Vec::with_capacity(self.turns.len());
+        for turn in self.turns.iter().filter(|turn| turn.role != Role::System) {
+            match (turn.role, &turn.message) {
+                (Role::Assistant, Message::Text { text }) => messages.push(Message::from(format!("<assistant>\n{}\n</assistant>", text))),
+                (Role::Tool, Message::Text { text }) => messages.push(Message::from(format!("<tool-result>\n{}\n</tool-result>", text))),
+                (_, message) => messages.push(message.clone()),
+            }
+            for tool_use in &turn.tool_uses {
+                let result = match (&tool_use.output, &tool_use.error) {
+                    (Some(output), _) => format!("<output>{}</output>", output),
+                    (None, Some(error)) => format!("<error>{}</error>", error),
+                    (None, None) => String::new(),
+                };
+                messages.push(Message::from(format!("<tool-use name=\"{}\">\n<input>{}</input>{}\n</tool-use>", tool_use.name, tool_use.input, result)));
+            }
+        }
+
+        let mut prompt = LanguageModelPrompt::from(String::new());
+        prompt.messages = messages;
+        match system.is_empty() {
+            true => prompt,
+            false => prompt.system(system.join("\n\n")),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string(self).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let conversation = serde_json::from_str::<Self>(json).map_err(|err| Error::Unexpected(anyhow!("invalid-conversation: {}", err)))?;
+        check_version(conversation.version)?;
+        Ok(conversation)
+    }
+
+    /// One JSON object per line: a header with the version, snapshots and notes, then each turn in order, so
+    /// a transcript can be appended to and streamed without rewriting it.
+    pub fn to_jsonl(&self) -> Result<String, Error> {
+        let header = JsonlHeader { version: self.version, snapshots: self.snapshots.clone(), notes: self.notes.clone() };
+
+        let mut jsonl = serde_json::to_string(&header).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        jsonl.push('\n');
+        for turn in &self.turns {
+            jsonl.push_str(&serde_json::to_string(turn).map_err(|err| Error::Unexpected(anyhow!(err)))?);
+            jsonl.push('\n');
+        }
+
+        Ok(jsonl)
+    }
+
+    pub fn from_jsonl(jsonl: &str) -> Result<Self, Error> {
+        let mut lines = jsonl.lines().enumerate().filter(|(_, line)| !line.trim().is_empty());
+
+        let header = match lines.next() {
+            Some((_, line)) => serde_json::from_str::<JsonlHeader>(line).map_err(|err| Error::Unexpected(anyhow!("invalid-conversation: line 1: {}", err)))?,
+            None => return Ok(Self::default()),
+        };
+        check_version(header.version)?;
+
+        let turns = lines
+            .map(|(number, line)| {
+                serde_json::from_str::<Turn>(line).map_err(|err| Error::Unexpected(anyhow!("invalid-conversation: line {}: {}", number + 1, err)))
+            })
+            .collect::<Result<Vec<Turn>, Error>>()?;
+
+        Ok(Self { version: header.version, turns, snapshots: header.snapshots, notes: header.notes })
+    }
+
+    /// Records the current turns under `name`, replacing any earlier snapshot with that name.
+    pub fn snapshot(&mut self, name: impl Into<String>) {
+        self.snapshots.insert(name.into(), self.turns.clone());
+    }
+
+    /// Rolls the turns back to the snapshot `name`; the snapshot is kept so it can be restored again.
+    pub fn restore(&mut self, name: &str) -> Result<(), Error> {
+        let turns = self.snapshots.get(name).ok_or_else(|| Error::Unexpected(anyhow!("unknown-snapshot: {}", name)))?;
+        self.turns = turns.clone();
+        Ok(())
+    }
+
+    pub fn remove_snapshot(&mut self, name: &str) -> bool {
+        self.snapshots.remove(name).is_some()
+    }
+
+    pub fn snapshots(&self) -> impl Iterator<Item = &str> {
+        self.snapshots.keys().map(String::as_str)
+    }
+
+    /// Named notes kept alongside the turns; they are not sent to the model and survive `restore`.
+    pub fn note(&self, name: &str) -> Option<&str> {
+        self.notes.get(name).map(String::as_str)
+    }
+
+    pub fn set_note(&mut self, name: impl Into<String>, content: impl Into<String>) {
+        self.notes.insert(name.into(), content.into());
+    }
+
+    pub fn remove_note(&mut self, name: &str) -> bool {
+        self.notes.remove(name).is_some()
+    }
+
+    pub fn notes(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.notes.iter().map(|(name, content)| (name.as_str(), content.as_str()))
+    }
+}
+
+/// Bounds on a history supplied by the client in stateless mode, where nothing is kept server-side and each
+/// call carries the whole conversation.
+///
+/// The history is untrusted input: besides the size limits, system turns are rejected unless explicitly
+/// allowed, so a client cannot rewrite the instructions the server puts in front of the model.
+#[derive(Clone, Copy, Debug)]
+pub struct HistoryLimits {
+    max_turns: usize,
+    max_bytes: usize,
+    allow_system: bool,
+}
+
+impl Default for HistoryLimits {
+    fn default() -> Self {
+        Self {
+            max_turns: 200,
+            max_bytes: 1 << 20,
+            allow_system: false,
+        }
+    }
+}
+
+impl HistoryLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_turns(self, max_turns: usize) -> Self {
+        Self {
+            max_turns,
+            ..self
+        }
+    }
+
+    /// Limit on the serialized size of the turns, which includes any inline images or audio.
+    pub fn max_bytes(self, max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            ..self
+        }
+    }
+
+    pub fn allow_system(self, allow_system: bool) -> Self {
+        Self {
+            allow_system,
+            ..self
+        }
+    }
+
+    /// Checks `history` against the limits, failing with `Error::InvalidRequest` so the client gets a 400.
+    pub fn validate(&self, history: &Conversation) -> Result<(), Error> {
+        if history.version > CONVERSATION_VERSION {
+            return Err(Error::InvalidRequest(format!("unsupported history version {}", history.version)));
+        }
+        if history.turns.len() > self.max_turns {
+            return Err(Error::InvalidRequest(format!("history has {} turns, limit is {}", history.turns.len(), self.max_turns)));
+        }
+        if !self.allow_system && history.turns.iter().any(|turn| turn.role == Role::System) {
+            return Err(Error::InvalidRequest("history may not contain system turns".to_string()));
+        }
+
+        let bytes = serde_json::to_vec(&history.turns).map_err(|err| Error::Unexpected(anyhow!(err)))?.len();
+        if bytes > self.max_bytes {
+            return Err(Error::InvalidRequest(format!("history is {} bytes, limit is {}", bytes, self.max_bytes)));
+        }
+
+        Ok(())
+    }
+
+    /// Validates `history` and replays it with `query` appended as the next user turn.
+    pub fn prompt(&self, history: &Conversation, query: impl Into<Message>) -> Result<LanguageModelPrompt, Error> {
+        self.validate(history)?;
+        Ok(history.prompt().add_message(query))
+    }
+}
+
+pub trait SessionStore: Send + Sync {
+    fn load(&self, session_id: &str) -> impl Future<Output = Result<Option<Conversation>, Error>> + Send;
+
+    fn save(&self, session_id: &str, conversation: &Conversation) -> impl Future<Output = Result<(), Error>> + Send;
+
+    fn snapshot(&self, session_id: &str, name: &str) -> impl Future<Output = Result<(), Error>> + Send {
+        async move {
+            let mut conversation = self.load(session_id).await?.unwrap_or_default();
+            conversation.snapshot(name);
+            self.save(session_id, &conversation).await
+        }
+    }
+
+    fn restore(&self, session_id: &str, name: &str) -> impl Future<Output = Result<Conversation, Error>> + Send {
+        async move {
+            let mut conversation = self.load(session_id).await?
+                .ok_or_else(|| Error::Unexpected(anyhow!("unknown-session: {}", session_id)))?;
+            conversation.restore(name)?;
+            self.save(session_id, &conversation).await?;
+            Ok(conversation)
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MemorySessionStore {
+    sessions: Mutex<HashMap<String, Conversation>>,
+}
+
+impl MemorySessionStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemorySessionStore {
+    async fn load(&self, session_id: &str) -> Result<Option<Conversation>, Error> {
+        let sessions = self.sessions.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        Ok(sessions.get(session_id).cloned())
+    }
+
+    async fn save(&self, session_id: &str, conversation: &Conversation) -> Result<(), Error> {
+        let mut sessions = self.sessions.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        sessions.insert(session_id.to_string(), conversation.clone());
+        Ok(())
+    }
+}
\ No newline at end of file
@@ -1,11 +1,176 @@
+use reqwest::StatusCode;
+use serde::Serialize;
+
+/// Not registered with IANA, so `StatusCode` has no constant or reason phrase for it.
+const CLIENT_CLOSED_REQUEST: StatusCode = match StatusCode::from_u16(499) {
+    Ok(status) => status,
+    Err(_) => StatusCode::BAD_REQUEST,
+};
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
+    #[error("authentication failed: {0}")]
+    AuthenticationFailed(String),
+
+    /// The caller stopped waiting for the result, e.g. by dropping the receiving end of a stream.
+    #[error("cancelled")]
+    Cancelled,
+
+    #[error("content blocked: {}", categories.join(", "))]
+    ContentBlocked { categories: Vec<String> },
+
+    #[error("context length exceeded: {0}")]
+    ContextLengthExceeded(String),
+
+    /// The prompt's deadline passed before the model answered.
+    #[error("deadline exceeded")]
+    DeadlineExceeded,
+
+    /// Content broke a `guardrails::Guardrails` rule, before the prompt was sent or the response returned.
+    #[error("guardrail violation: {}", violations.iter().map(ToString::to_string).collect::<Vec<String>>().join("; "))]
+    GuardrailViolation { violations: Vec<crate::guardrails::Violation> },
+
     #[error(transparent)]
     ImageDecode(#[from] base64::DecodeError),
 
+    #[error("invalid request: {0}")]
+    InvalidRequest(String),
+
+    /// A provider error that does not map to one of the more specific variants.
     #[error("{0}")]
     ModelResponse(String),
 
+    #[error("model overloaded: {0}")]
+    Overloaded(String),
+
+    #[error("rate limited")]
+    RateLimited { retry_after: Option<std::time::Duration> },
+
+    /// The request timed out, on the client or at the provider, before the prompt's deadline passed; a later
+    /// attempt may succeed.
+    #[error("request timed out: {0}")]
+    Timeout(String),
+
+    /// The prompt needs something the model does not support, detected before the request is sent.
+    #[error("unsupported content: {kind}")]
+    UnsupportedContent { kind: String },
+
     #[error(transparent)]
     Unexpected(#[from] anyhow::Error),
-}
\ No newline at end of file
+}
+
+impl Error {
+    /// Whether the same request may succeed if sent again later.
+    pub fn is_retriable(&self) -> bool {
+        matches!(self, Self::RateLimited { .. } | Self::Overloaded(_) | Self::Timeout(_))
+    }
+
+    /// A stable snake_case identifier for the variant, used as the `code` of a [`Problem`].
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::AuthenticationFailed(_) => "authentication_failed",
+            Self::Cancelled => "cancelled",
+            Self::ContentBlocked { .. } => "content_blocked",
+            Self::ContextLengthExceeded(_) => "context_length_exceeded",
+            Self::DeadlineExceeded => "deadline_exceeded",
+            Self::GuardrailViolation { .. } => "guardrail_violation",
+            Self::ImageDecode(_) => "image_decode",
+            Self::InvalidRequest(_) => "invalid_request",
+            Self::ModelResponse(_) => "model_response",
+            Self::Overloaded(_) => "overloaded",
+            Self::RateLimited { .. } => "rate_limited",
+            Self::Timeout(_) => "timeout",
+            Self::UnsupportedContent { .. } => "unsupported_content",
+            Self::Unexpected(_) => "unexpected",
+        }
+    }
+
+    /// The status a service should answer with when a request fails with this error.
+    ///
+    /// Failures caused by the caller's input map to 4xx, and a cancelled request to nginx's 499; failures of the
+    /// upstream provider, including its rejection of the service's own credentials, map to 5xx since the caller
+    /// cannot fix them.
+    pub fn status_code(&self) -> StatusCode {
+        match self {
+            Self::Cancelled => CLIENT_CLOSED_REQUEST,
+            Self::ContentBlocked { .. } | Self::GuardrailViolation { .. } | Self::UnsupportedContent { .. } => StatusCode::UNPROCESSABLE_ENTITY,
+            Self::ContextLengthExceeded(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            Self::Overloaded(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::DeadlineExceeded | Self::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+            Self::AuthenticationFailed(_) | Self::ImageDecode(_) | Self::ModelResponse(_) => StatusCode::BAD_GATEWAY,
+            Self::Unexpected(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn problem(&self) -> Problem {
+        Problem::from(self)
+    }
+}
+
+/// An RFC 9457 `application/problem+json` body describing an [`Error`].
+///
+/// Internal errors get a generic `detail` so that provider messages and stack context are not leaked to clients.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Problem {
+    #[serde(rename = "type")]
+    problem_type: String,
+
+    title: String,
+    status: u16,
+    detail: String,
+    code: &'static str,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    retry_after: Option<u64>,
+}
+
+impl Problem {
+    pub const CONTENT_TYPE: &'static str = "application/problem+json";
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn detail(&self) -> &str {
+        &self.detail
+    }
+
+    pub fn code(&self) -> &str {
+        self.code
+    }
+
+    /// Seconds to wait before retrying, for the `Retry-After` header.
+    pub fn retry_after(&self) -> Option<u64> {
+        self.retry_after
+    }
+}
+
+impl From<&Error> for Problem {
+    fn from(err: &Error) -> Self {
+        let status = err.status_code();
+
+        Self {
+            problem_type: "about:blank".to_string(),
+            title: match err {
+                Error::Cancelled => "Client Closed Request",
+                _ => status.canonical_reason().unwrap_or_default(),
+            }.to_string(),
+            status: status.as_u16(),
+            detail: match err {
+                Error::Unexpected(_) => "An unexpected error occurred.".to_string(),
+                err => err.to_string(),
+            },
+            code: err.code(),
+            retry_after: match err {
+                Error::RateLimited { retry_after } => retry_after.map(|retry_after| retry_after.as_secs().max(1)),
+                _ => None,
+            },
+        }
+    }
+}
@@ -0,0 +1,670 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, warn};
+
+use super::{
+    model::{estimate_tokens, LanguageModel, LanguageModelPrompt},
+    time::Instant,
+    Error,
+};
+
+/// Extracts the outermost JSON object from a judge response, tolerating surrounding prose or code fences.
+fn parse_json<T>(text: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let start = text.find('{').ok_or_else(|| Error::Unexpected(anyhow!("judge-response-not-json")))?;
+    let end = text.rfind('}').ok_or_else(|| Error::Unexpected(anyhow!("judge-response-not-json")))?;
+
+    serde_json::from_str(&text[start..=end]).map_err(|err| Error::Unexpected(anyhow!(err)))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preference {
+    A,
+    B,
+    Tie,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Comparison {
+    preference: Preference,
+    confidence: f32,
+    reasoning: String,
+}
+
+impl Comparison {
+    pub fn preference(&self) -> Preference {
+        self.preference
+    }
+
+    /// Confidence in `[0, 1]`, reduced when the two orderings disagree.
+    pub fn confidence(&self) -> f32 {
+        self.confidence
+    }
+
+    pub fn reasoning(&self) -> &str {
+        &self.reasoning
+    }
+}
+
+#[derive(Deserialize)]
+struct JudgeVerdict {
+    winner: String,
+
+    #[serde(default)]
+    confidence: Option<f32>,
+
+    #[serde(default)]
+    reasoning: String,
+}
+
+const COMPARE_SYSTEM: &str = "You are an impartial judge comparing two responses. \
+Evaluate them strictly against the given criteria, ignoring their order and length. \
+Reply with only a JSON object of the form {\"reasoning\": string, \"winner\": \"first\" | \"second\" | \"tie\", \"confidence\": number between 0 and 1}.";
+
+async fn judge_once<M>(first: &str, second: &str, criteria: &str, judge: &M) -> Result<(Option<bool>, f32, String), Error>
+where
+    M: LanguageModel,
+{
+    let prompt = LanguageModelPrompt::from(format!(
+        "<criteria>\n{}\n</criteria>\n\n<first>\n{}\n</first>\n\n<second>\n{}\n</second>",
+        criteria, first, second
+    ))
+        .system(COMPARE_SYSTEM)
+        .temperature(0.0);
+
+    let verdict = parse_json::<JudgeVerdict>(&judge.inference(prompt).await?.to_string())?;
+    let first_wins = match verdict.winner.to_lowercase().as_str() {
+        "first" | "a" => Some(true),
+        "second" | "b" => Some(false),
+        _ => None,
+    };
+
+    Ok((first_wins, verdict.confidence.unwrap_or(0.5).clamp(0.0, 1.0), verdict.reasoning))
+}
+
+/// Asks `judge` which of `a` and `b` better satisfies `criteria`.
+///
+/// The judge is queried twice with the candidates in both orders to cancel out position bias; when the two
+/// verdicts disagree the result is a [`Preference::Tie`] with confidence scaled down accordingly.
+#[instrument(name = "eval::compare", level = "trace", skip(a, b, judge))]
+pub async fn compare<M>(a: &str, b: &str, criteria: &str, judge: &M) -> Result<Comparison, Error>
+where
+    M: LanguageModel,
+{
+    let (forward, forward_confidence, forward_reasoning) = judge_once(a, b, criteria, judge).await?;
+    let (backward, backward_confidence, backward_reasoning) = judge_once(b, a, criteria, judge).await?;
+
+    let forward = forward.map(|first_wins| if first_wins { Preference::A } else { Preference::B }).unwrap_or(Preference::Tie);
+    let backward = backward.map(|first_wins| if first_wins { Preference::B } else { Preference::A }).unwrap_or(Preference::Tie);
+    debug! { ?forward, ?backward };
+
+    let (preference, confidence) = if forward == backward {
+        (forward, (forward_confidence + backward_confidence) / 2.0)
+    } else {
+        (Preference::Tie, (1.0 - (forward_confidence - backward_confidence).abs()) / 2.0)
+    };
+
+    Ok(Comparison {
+        preference,
+        confidence,
+        reasoning: format!("{}\n\n{}", forward_reasoning, backward_reasoning).trim().to_string(),
+    })
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Criterion {
+    name: String,
+    description: String,
+
+    #[serde(default = "Criterion::default_weight")]
+    weight: f32,
+}
+
+impl Criterion {
+    fn default_weight() -> f32 {
+        1.0
+    }
+
+    pub fn new(name: impl Into<String>, description: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: description.into(),
+            weight: Self::default_weight(),
+        }
+    }
+
+    pub fn weight(self, weight: f32) -> Self {
+        Self {
+            weight,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Rubric {
+    name: String,
+    criteria: Vec<Criterion>,
+
+    #[serde(default = "Rubric::default_min_score")]
+    min_score: u32,
+
+    #[serde(default = "Rubric::default_max_score")]
+    max_score: u32,
+}
+
+impl Rubric {
+    fn default_min_score() -> u32 {
+        1
+    }
+
+    fn default_max_score() -> u32 {
+        5
+    }
+
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            criteria: Vec::new(),
+            min_score: Self::default_min_score(),
+            max_score: Self::default_max_score(),
+        }
+    }
+
+    pub fn criterion(self, criterion: Criterion) -> Self {
+        let mut criteria = self.criteria;
+        criteria.push(criterion);
+
+        Self {
+            criteria,
+            ..self
+        }
+    }
+
+    pub fn scale(self, min_score: u32, max_score: u32) -> Self {
+        Self {
+            min_score: min_score.min(max_score),
+            max_score: max_score.max(min_score),
+            ..self
+        }
+    }
+
+    /// Loads a rubric from a JSON file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        serde_json::from_str(&contents).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn criteria(&self) -> &[Criterion] {
+        &self.criteria
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Grade {
+    Poor,
+    Fair,
+    Good,
+    Excellent,
+}
+
+impl From<f32> for Grade {
+    fn from(normalized: f32) -> Self {
+        match normalized {
+            n if n >= 0.85 => Self::Excellent,
+            n if n >= 0.7 => Self::Good,
+            n if n >= 0.5 => Self::Fair,
+            _ => Self::Poor,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CriterionScore {
+    name: String,
+    score: u32,
+
+    #[serde(default)]
+    reasoning: String,
+}
+
+impl CriterionScore {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn score(&self) -> u32 {
+        self.score
+    }
+
+    pub fn reasoning(&self) -> &str {
+        &self.reasoning
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Score {
+    criteria: Vec<CriterionScore>,
+    overall: f32,
+    grade: Grade,
+}
+
+impl Score {
+    pub fn criteria(&self) -> &[CriterionScore] {
+        &self.criteria
+    }
+
+    /// Weighted mean of the criterion scores, normalized to `[0, 1]` over the rubric scale.
+    pub fn overall(&self) -> f32 {
+        self.overall
+    }
+
+    pub fn grade(&self) -> Grade {
+        self.grade
+    }
+}
+
+#[derive(Deserialize)]
+struct JudgeScores {
+    scores: Vec<CriterionScore>,
+}
+
+/// Grades `output` against each criterion of `rubric` using `judge`.
+#[instrument(name = "eval::score", level = "trace", skip(output, judge), fields(rubric = rubric.name))]
+pub async fn score<M>(output: &str, rubric: &Rubric, judge: &M) -> Result<Score, Error>
+where
+    M: LanguageModel,
+{
+    let criteria = rubric.criteria.iter()
+        .map(|criterion| format!("- {}: {}", criterion.name, criterion.description))
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let prompt = LanguageModelPrompt::from(format!("<criteria>\n{}\n</criteria>\n\n<output>\n{}\n</output>", criteria, output))
+        .system(format!(
+            "You are a strict grader. Score the output on every listed criterion with an integer from {} to {}. \
+Reply with only a JSON object of the form {{\"scores\": [{{\"name\": string, \"reasoning\": string, \"score\": integer}}]}}, \
+using the criterion names exactly as given.",
+            rubric.min_score, rubric.max_score
+        ))
+        .temperature(0.0);
+
+    let judged = parse_json::<JudgeScores>(&judge.inference(prompt).await?.to_string())?;
+
+    let mut scores = Vec::with_capacity(rubric.criteria.len());
+    let (mut weighted, mut total_weight) = (0.0, 0.0);
+    for criterion in &rubric.criteria {
+        let mut score = judged.scores.iter()
+            .find(|score| score.name.eq_ignore_ascii_case(&criterion.name))
+            .cloned()
+            .ok_or_else(|| Error::Unexpected(anyhow!("missing-criterion-score: {}", criterion.name)))?;
+        score.name = criterion.name.clone();
+        score.score = score.score.clamp(rubric.min_score, rubric.max_score);
+
+        let span = (rubric.max_score - rubric.min_score).max(1) as f32;
+        weighted += criterion.weight * (score.score - rubric.min_score) as f32 / span;
+        total_weight += criterion.weight;
+        scores.push(score);
+    }
+
+    let overall = if total_weight > 0.0 { weighted / total_weight } else { 0.0 };
+    debug! { overall };
+
+    Ok(Score {
+        criteria: scores,
+        overall,
+        grade: overall.into(),
+    })
+}
+
+/// One input to run a model on, checked against an expected answer, a rubric, or both.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EvalCase {
+    name: String,
+    input: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expected: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rubric: Option<Rubric>,
+
+    #[serde(default = "EvalCase::default_passing_score")]
+    passing_score: f32,
+}
+
+impl EvalCase {
+    fn default_passing_score() -> f32 {
+        0.7
+    }
+
+    pub fn new(name: impl Into<String>, input: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            input: input.into(),
+            system: None,
+            expected: None,
+            rubric: None,
+            passing_score: Self::default_passing_score(),
+        }
+    }
+
+    pub fn system(self, system: impl Into<String>) -> Self {
+        Self {
+            system: Some(system.into()),
+            ..self
+        }
+    }
+
+    pub fn expect(self, expected: impl Into<String>) -> Self {
+        Self {
+            expected: Some(expected.into()),
+            ..self
+        }
+    }
+
+    pub fn graded_by(self, rubric: Rubric) -> Self {
+        Self {
+            rubric: Some(rubric),
+            ..self
+        }
+    }
+
+    /// Lowest rubric `Score::overall` that counts as a pass.
+    pub fn passing_score(self, passing_score: f32) -> Self {
+        Self {
+            passing_score,
+            ..self
+        }
+    }
+
+    /// Loads cases from a JSON array file.
+    pub fn load(path: impl AsRef<std::path::Path>) -> Result<Vec<Self>, Error> {
+        let contents = std::fs::read_to_string(path).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        serde_json::from_str(&contents).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn expected(&self) -> Option<&str> {
+        self.expected.as_deref()
+    }
+
+    pub fn rubric(&self) -> Option<&Rubric> {
+        self.rubric.as_ref()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CaseResult {
+    name: String,
+    output: Option<String>,
+
+    /// `None` when nothing could grade the case: no expected answer, and a rubric but no judge.
+    passed: Option<bool>,
+    score: Option<Score>,
+    reasoning: Option<String>,
+    error: Option<String>,
+    latency_ms: u64,
+    input_tokens: usize,
+    output_tokens: usize,
+}
+
+impl CaseResult {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn output(&self) -> Option<&str> {
+        self.output.as_deref()
+    }
+
+    pub fn passed(&self) -> Option<bool> {
+        self.passed
+    }
+
+    pub fn score(&self) -> Option<&Score> {
+        self.score.as_ref()
+    }
+
+    pub fn reasoning(&self) -> Option<&str> {
+        self.reasoning.as_deref()
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn latency_ms(&self) -> u64 {
+        self.latency_ms
+    }
+}
+
+/// Results of an evaluation run. Token counts are estimates, since `LanguageModel` does not report usage.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EvalReport {
+    cases: Vec<CaseResult>,
+    accuracy: Option<f32>,
+    mean_score: Option<f32>,
+    mean_latency_ms: u64,
+    input_tokens: usize,
+    output_tokens: usize,
+    errors: usize,
+}
+
+impl EvalReport {
+    fn new(cases: Vec<CaseResult>) -> Self {
+        let graded = cases.iter().filter_map(|case| case.passed).collect::<Vec<bool>>();
+        let scores = cases.iter().filter_map(|case| case.score.as_ref().map(Score::overall)).collect::<Vec<f32>>();
+
+        Self {
+            accuracy: (!graded.is_empty()).then(|| graded.iter().filter(|passed| **passed).count() as f32 / graded.len() as f32),
+            mean_score: (!scores.is_empty()).then(|| scores.iter().sum::<f32>() / scores.len() as f32),
+            mean_latency_ms: cases.iter().map(|case| case.latency_ms).sum::<u64>().checked_div(cases.len() as u64).unwrap_or_default(),
+            input_tokens: cases.iter().map(|case| case.input_tokens).sum(),
+            output_tokens: cases.iter().map(|case| case.output_tokens).sum(),
+            errors: cases.iter().filter(|case| case.error.is_some()).count(),
+            cases,
+        }
+    }
+
+    pub fn cases(&self) -> &[CaseResult] {
+        &self.cases
+    }
+
+    /// Share of graded cases that passed; errored cases count as failed.
+    pub fn accuracy(&self) -> Option<f32> {
+        self.accuracy
+    }
+
+    pub fn mean_score(&self) -> Option<f32> {
+        self.mean_score
+    }
+
+    pub fn mean_latency_ms(&self) -> u64 {
+        self.mean_latency_ms
+    }
+
+    pub fn input_tokens(&self) -> usize {
+        self.input_tokens
+    }
+
+    pub fn output_tokens(&self) -> usize {
+        self.output_tokens
+    }
+
+    pub fn errors(&self) -> usize {
+        self.errors
+    }
+
+    /// Names of cases that passed in `baseline` and fail in this report.
+    pub fn regressions(&self, baseline: &EvalReport) -> Vec<&str> {
+        self.cases.iter()
+            .filter(|case| case.passed == Some(false))
+            .filter(|case| baseline.cases.iter().any(|before| before.name == case.name && before.passed == Some(true)))
+            .map(|case| case.name.as_str())
+            .collect()
+    }
+
+    pub fn to_json(&self) -> Result<String, Error> {
+        serde_json::to_string_pretty(self).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json).map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+}
+
+#[derive(Deserialize)]
+struct JudgeMatch {
+    correct: bool,
+
+    #[serde(default)]
+    reasoning: String,
+}
+
+const MATCH_SYSTEM: &str = "You are a strict grader checking a response against a reference answer. \
+The response is correct if it states the same answer, regardless of wording or extra explanation. \
+Reply with only a JSON object of the form {\"reasoning\": string, \"correct\": boolean}.";
+
+fn normalize(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ").to_lowercase()
+}
+
+async fn grade<J>(case: &EvalCase, output: &str, judge: Option<&J>) -> Result<(Option<bool>, Option<Score>, Option<String>), Error>
+where
+    J: LanguageModel,
+{
+    let (mut passed, mut score, mut reasoning) = (None, None, None);
+
+    if let Some(expected) = &case.expected {
+        let matched = match judge {
+            Some(judge) => {
+                let prompt = LanguageModelPrompt::from(format!(
+                    "<input>\n{}\n</input>\n\n<reference>\n{}\n</reference>\n\n<response>\n{}\n</response>",
+                    case.input, expected, output
+                ))
+                    .system(MATCH_SYSTEM)
+                    .temperature(0.0);
+                let verdict = parse_json::<JudgeMatch>(&judge.inference(prompt).await?.to_string())?;
+                reasoning = Some(verdict.reasoning);
+                verdict.correct
+            },
+            None => normalize(output).contains(&normalize(expected)),
+        };
+        passed = Some(matched);
+    }
+
+    if let (Some(rubric), Some(judge)) = (&case.rubric, judge) {
+        let graded = self::score(output, rubric, judge).await?;
+        passed = Some(passed.unwrap_or(true) && graded.overall() >= case.passing_score);
+        score = Some(graded);
+    }
+
+    Ok((passed, score, reasoning))
+}
+
+async fn run_cases<M, J>(cases: &[EvalCase], model: &M, judge: Option<&J>) -> EvalReport
+where
+    M: LanguageModel,
+    J: LanguageModel,
+{
+    let mut results = Vec::with_capacity(cases.len());
+    for case in cases {
+        let mut prompt = LanguageModelPrompt::from(case.input.as_str()).temperature(0.0);
+        if let Some(system) = &case.system {
+            prompt = prompt.system(system.as_str());
+        }
+        let input_tokens = estimate_tokens(&case.input) + case.system.as_deref().map(estimate_tokens).unwrap_or_default();
+
+        let started = Instant::now();
+        let output = model.inference(prompt).await.map(|message| message.to_string());
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let result = match output {
+            Ok(output) => {
+                let graded = grade(case, &output, judge).await;
+                let (passed, score, reasoning, error) = match graded {
+                    Ok((passed, score, reasoning)) => (passed, score, reasoning, None),
+                    Err(err) => (None, None, None, Some(format!("judge: {}", err))),
+                };
+
+                CaseResult {
+                    name: case.name.clone(),
+                    output_tokens: estimate_tokens(&output),
+                    output: Some(output),
+                    passed,
+                    score,
+                    reasoning,
+                    error,
+                    latency_ms,
+                    input_tokens,
+                }
+            },
+            Err(err) => CaseResult {
+                name: case.name.clone(),
+                output: None,
+                passed: Some(false),
+                score: None,
+                reasoning: None,
+                error: Some(err.to_string()),
+                latency_ms,
+                input_tokens,
+                output_tokens: 0,
+            },
+        };
+        if let Some(error) = &result.error {
+            warn! { case = case.name, error };
+        }
+        debug! { case = case.name, passed = ?result.passed, latency_ms };
+
+        results.push(result);
+    }
+
+    EvalReport::new(results)
+}
+
+/// Runs every case against `model` one at a time, so latencies are not skewed by concurrency. Expected
+/// answers are checked by normalized containment; rubric cases are left ungraded without a judge.
+#[instrument(name = "eval::run", level = "trace", skip(cases, model), fields(cases = cases.len()))]
+pub async fn run<M>(cases: &[EvalCase], model: &M) -> EvalReport
+where
+    M: LanguageModel,
+{
+    run_cases::<M, M>(cases, model, None).await
+}
+
+/// Like `run`, with `judge` deciding whether outputs match expected answers and scoring rubric cases.
+#[instrument(name = "eval::run_judged", level = "trace", skip(cases, model, judge), fields(cases = cases.len()))]
+pub async fn run_judged<M, J>(cases: &[EvalCase], model: &M, judge: &J) -> EvalReport
+where
+    M: LanguageModel,
+    J: LanguageModel,
+{
+    run_cases(cases, model, Some(judge)).await
+}
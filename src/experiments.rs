@@ -0,0 +1,189 @@
+use std::{collections::BTreeMap, sync::Mutex};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::{debug, field, instrument, Span};
+
+use super::{
+    model::{LanguageModel, LanguageModelPrompt},
+    Error,
+    Message,
+};
+
+/// One version of a prompt. `{{input}}` in the template is replaced with the caller's input.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PromptVariant {
+    name: String,
+    template: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+
+    #[serde(default = "PromptVariant::default_weight")]
+    weight: u32,
+}
+
+impl PromptVariant {
+    fn default_weight() -> u32 {
+        1
+    }
+
+    pub fn new(name: impl Into<String>, template: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            template: template.into(),
+            system: None,
+            weight: Self::default_weight(),
+        }
+    }
+
+    pub fn system(self, system: impl Into<String>) -> Self {
+        Self {
+            system: Some(system.into()),
+            ..self
+        }
+    }
+
+    /// Share of traffic relative to the other variants; 0 takes the variant out of rotation.
+    pub fn weight(self, weight: u32) -> Self {
+        Self {
+            weight,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn template(&self) -> &str {
+        &self.template
+    }
+
+    pub fn render(&self, input: &str) -> LanguageModelPrompt {
+        let prompt = LanguageModelPrompt::from(self.template.replace("{{input}}", input));
+        match &self.system {
+            Some(system) => prompt.system(system.as_str()),
+            None => prompt,
+        }
+    }
+}
+
+/// A response together with the variant that produced it.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExperimentResponse {
+    experiment: String,
+    variant: String,
+    message: Message,
+}
+
+impl ExperimentResponse {
+    pub fn experiment(&self) -> &str {
+        &self.experiment
+    }
+
+    pub fn variant(&self) -> &str {
+        &self.variant
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+/// Splits traffic between prompt variants by weight.
+///
+/// Assignment hashes the experiment name with the session id, so a session sees the same variant on every
+/// request and across processes, while different experiments split sessions independently. Raising a new
+/// variant's weight step by step rolls it out gradually; sessions only move between variants when the weights
+/// change.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Experiment {
+    name: String,
+    variants: Vec<PromptVariant>,
+
+    #[serde(skip)]
+    exposures: Mutex<BTreeMap<String, u64>>,
+}
+
+impl Experiment {
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            variants: Vec::new(),
+            exposures: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    pub fn variant(self, variant: PromptVariant) -> Self {
+        let mut variants = self.variants;
+        variants.push(variant);
+
+        Self {
+            variants,
+            ..self
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn variants(&self) -> &[PromptVariant] {
+        &self.variants
+    }
+
+    /// The variant `session_id` is assigned to.
+    pub fn assign(&self, session_id: &str) -> Result<&PromptVariant, Error> {
+        let total = self.variants.iter().map(|variant| variant.weight as u64).sum::<u64>();
+        if total == 0 {
+            return Err(Error::InvalidRequest(format!("experiment {} has no variant with weight", self.name)));
+        }
+
+        let digest = Sha256::new().chain_update(self.name.as_bytes()).chain_update([0]).chain_update(session_id.as_bytes()).finalize();
+        let mut bucket = u64::from_le_bytes(digest[..8].try_into().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?) % total;
+
+        self.variants.iter()
+            .find(|variant| match bucket.checked_sub(variant.weight as u64) {
+                Some(rest) => {
+                    bucket = rest;
+                    false
+                },
+                None => true,
+            })
+            .ok_or_else(|| Error::Unexpected(anyhow!("variant-not-found")))
+    }
+
+    /// Renders `input` with the session's variant and runs it on `model`.
+    #[instrument(name = "Experiment::inference", level = "trace", skip(self, model, input), fields(experiment = self.name, variant = field::Empty))]
+    pub async fn inference<M>(&self, model: &M, input: &str, session_id: &str) -> Result<ExperimentResponse, Error>
+    where
+        M: LanguageModel,
+    {
+        let variant = self.assign(session_id)?;
+        Span::current().record("variant", variant.name.as_str());
+
+        if let Ok(mut exposures) = self.exposures.lock() {
+            *exposures.entry(variant.name.clone()).or_default() += 1;
+        }
+        debug! { variant = variant.name };
+
+        let message = model.inference(variant.render(input)).await?;
+
+        Ok(ExperimentResponse {
+            experiment: self.name.clone(),
+            variant: variant.name.clone(),
+            message,
+        })
+    }
+
+    /// Requests served per variant since the experiment was created.
+    pub fn exposures(&self) -> BTreeMap<String, u64> {
+        self.exposures.lock().map(|exposures| exposures.clone()).unwrap_or_default()
+    }
+}
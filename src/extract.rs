@@ -0,0 +1,105 @@
+use anyhow::anyhow;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use tracing::{debug, instrument};
+
+use super::{
+    model::{LanguageModel, LanguageModelPrompt},
+    AssistantResponse,
+    Context,
+    Error,
+    MissingField,
+};
+
+/// Extracts the outermost JSON object from a model response, tolerating surrounding prose or code fences.
+fn parse_json<T>(text: &str) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let start = text.find('{').ok_or_else(|| Error::Unexpected(anyhow!("extraction-response-not-json")))?;
+    let end = text.rfind('}').ok_or_else(|| Error::Unexpected(anyhow!("extraction-response-not-json")))?;
+
+    serde_json::from_str(&text[start..=end]).map_err(|err| Error::Unexpected(anyhow!(err)))
+}
+
+#[derive(Deserialize)]
+struct ExtractorResponse {
+    #[serde(default)]
+    data: Map<String, Value>,
+
+    #[serde(default)]
+    missing: Vec<ExtractorMissing>,
+}
+
+#[derive(Deserialize)]
+struct ExtractorMissing {
+    field: String,
+
+    #[serde(default)]
+    reason: String,
+}
+
+#[derive(Clone, Debug)]
+pub enum Extraction {
+    Complete(Value),
+
+    /// Some required fields could not be filled; `partial` holds the fields that were.
+    Incomplete { partial: Value, missing: Vec<MissingField> },
+}
+
+impl Extraction {
+    pub fn is_complete(&self) -> bool {
+        matches!(self, Self::Complete(_))
+    }
+
+    /// A `MissingFields` query for an incomplete extraction, `None` when it is complete.
+    pub fn query(&self, context: Option<Context>) -> Option<AssistantResponse> {
+        match self {
+            Self::Complete(_) => None,
+            Self::Incomplete { missing, .. } => Some(AssistantResponse::missing_fields(missing.clone(), context)),
+        }
+    }
+}
+
+const EXTRACT_SYSTEM: &str = "You extract structured data from text. \
+Fill the fields of the given JSON schema using only information stated in the text; never guess or invent values. \
+Reply with only a JSON object of the form {\"data\": object, \"missing\": [{\"field\": string, \"reason\": string}]}, \
+listing in `missing` every required field the text does not provide and why it could not be filled.";
+
+/// Extracts an object matching `schema` from `text`.
+///
+/// Required fields (the schema's top-level `required`) are checked after extraction rather than trusting the
+/// model: any that are absent or null are reported as missing, with the model's reason when it gave one.
+#[instrument(name = "extract::extract", level = "trace", skip(model, text, schema))]
+pub async fn extract<M>(model: &M, text: &str, schema: &Value) -> Result<Extraction, Error>
+where
+    M: LanguageModel,
+{
+    let prompt = LanguageModelPrompt::from(format!("<schema>\n{}\n</schema>\n\n<text>\n{}\n</text>", schema, text))
+        .system(EXTRACT_SYSTEM)
+        .temperature(0.0);
+    let response = parse_json::<ExtractorResponse>(&model.inference(prompt).await?.to_string())?;
+
+    let required = schema.get("required").and_then(Value::as_array).into_iter().flatten().filter_map(Value::as_str);
+    let missing = required
+        .filter(|field| response.data.get(*field).is_none_or(Value::is_null))
+        .map(|field| {
+            let reason = response.missing.iter()
+                .find(|missing| missing.field == field && !missing.reason.is_empty())
+                .map(|missing| missing.reason.clone())
+                .unwrap_or_else(|| "not found in the input".to_string());
+
+            match schema.pointer(&format!("/properties/{}", field)) {
+                Some(property) => MissingField::new(field, reason).schema(property.clone()),
+                None => MissingField::new(field, reason),
+            }
+        })
+        .collect::<Vec<MissingField>>();
+    debug! { missing = ?missing.iter().map(MissingField::field).collect::<Vec<&str>>() };
+
+    let data = Value::Object(response.data.into_iter().filter(|(_, value)| !value.is_null()).collect());
+    match missing.is_empty() {
+        true => Ok(Extraction::Complete(data)),
+        false => Ok(Extraction::Incomplete { partial: data, missing }),
+    }
+}
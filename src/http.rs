@@ -0,0 +1,82 @@
+use std::{convert::Infallible, pin::Pin, time::Duration};
+
+use bytes::Bytes;
+use futures_util::stream::{self, Stream, StreamExt};
+use tracing::{debug, instrument};
+
+use super::{
+    model::{LanguageModelPrompt, MessageChunk, StreamingModel, TextStream},
+    Error,
+    Message,
+};
+
+/// The `Content-Type` of an SSE response. Proxies should also be told not to buffer it, e.g. with
+/// `Cache-Control: no-cache` and `X-Accel-Buffering: no`.
+pub const SSE_CONTENT_TYPE: &str = "text/event-stream";
+
+/// How often a comment is sent while the model is silent, so idle-timeouts on proxies do not cut the response.
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+const KEEP_ALIVE_COMMENT: &[u8] = b": keep-alive\n\n";
+
+/// SSE-framed bytes, usable as a response body with e.g. `axum::body::Body::from_stream` or hyper's `StreamBody`.
+pub type SseStream = Pin<Box<dyn Stream<Item = Result<Bytes, Infallible>> + Send>>;
+
+/// Frames one server-sent event. Every line of `data` gets its own `data:` field, as the format requires.
+pub fn event(name: &str, data: &str) -> Bytes {
+    let mut event = format!("event: {}\n", name);
+    for line in data.split('\n') {
+        event.push_str("data: ");
+        event.push_str(line.strip_suffix('\r').unwrap_or(line));
+        event.push('\n');
+    }
+    event.push('\n');
+
+    Bytes::from(event)
+}
+
+fn chunk_event(chunk: &MessageChunk) -> Bytes {
+    let name = match chunk {
+        MessageChunk::Delta { .. } => "delta",
+        MessageChunk::Done { .. } => "done",
+        MessageChunk::Error { .. } => "error",
+    };
+
+    event(name, &serde_json::to_string(chunk).unwrap_or_default())
+}
+
+/// Re-emits `stream` as SSE: a `delta` event per text delta, then a `done` event with the whole message or an
+/// `error` event with a `Problem`, each carrying the `MessageChunk` as JSON. A keep-alive comment is sent
+/// whenever no delta arrives within `keep_alive`.
+pub fn sse(stream: TextStream, keep_alive: Duration) -> SseStream {
+    Box::pin(stream::unfold(Some((stream, String::new())), move |state| async move {
+        let (mut stream, mut text) = state?;
+
+        let (chunk, state) = match tokio::time::timeout(keep_alive, stream.next()).await {
+            Err(_) => return Some((Ok(Bytes::from_static(KEEP_ALIVE_COMMENT)), Some((stream, text)))),
+            Ok(Some(Ok(delta))) => {
+                text.push_str(&delta);
+                (MessageChunk::Delta { text: delta }, Some((stream, text)))
+            },
+            Ok(Some(Err(err))) => {
+                debug! { ?err };
+                (MessageChunk::Error { error: err.problem() }, None)
+            },
+            Ok(None) => (MessageChunk::Done { message: Message::Text { text } }, None),
+        };
+
+        Some((Ok(chunk_event(&chunk)), state))
+    }))
+}
+
+/// Starts streaming `prompt` from `model` as SSE. Errors before the first delta are returned directly, so the
+/// handler can still answer with a proper status code, e.g. from `Error::problem`.
+#[instrument(name = "http::stream_sse", level = "trace", skip(model, prompt))]
+pub async fn stream_sse<M>(model: &M, prompt: LanguageModelPrompt, keep_alive: Duration) -> Result<SseStream, Error>
+where
+    M: StreamingModel,
+{
+    let stream = model.stream(prompt).await?;
+
+    Ok(sse(stream, keep_alive))
+}
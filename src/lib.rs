@@ -1,22 +1,186 @@
 use std::fmt;
 
 use base64::prelude::{BASE64_STANDARD, Engine as _};
-use serde::{Deserialize, Serialize};
+use bytes::Bytes;
+use serde::{de, Deserialize, Deserializer, Serialize};
 
+/// Splits a `data:<media type>;base64,<data>` URL into its media type and decoded bytes.
+fn parse_data_url(url: &str) -> Result<(String, Vec<u8>), String> {
+    let (header, data) = url.strip_prefix("data:")
+        .and_then(|url| url.split_once(','))
+        .ok_or_else(|| "not a data URL".to_string())?;
+    let media_type = header.strip_suffix(";base64").ok_or_else(|| "data URL is not base64-encoded".to_string())?;
+
+    let data = BASE64_STANDARD.decode(data.trim()).map_err(|err| err.to_string())?;
+    Ok((media_type.to_string(), data))
+}
+
+/// The accepted JSON forms of image and audio content: a data URL, the crate's own `{ media_type, data }`
+/// with the data as a byte array or base64 string, or a provider's `{ source: { media_type, data } }`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MediaRepr {
+    Url(String),
+    Fields { media_type: String, data: MediaData },
+    Source { source: MediaSource },
+}
+
+#[derive(Deserialize)]
+struct MediaSource {
+    media_type: String,
+    data: String,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MediaData {
+    Bytes(Vec<u8>),
+    Base64(String),
+}
+
+impl MediaRepr {
+    fn decode(self) -> Result<(String, Vec<u8>), String> {
+        match self {
+            Self::Url(url) => parse_data_url(&url),
+            Self::Fields { media_type, data: MediaData::Bytes(data) } => Ok((media_type, data)),
+            Self::Fields { media_type, data: MediaData::Base64(data) } | Self::Source { source: MediaSource { media_type, data } } => {
+                Ok((media_type, BASE64_STANDARD.decode(data).map_err(|err| err.to_string())?))
+            },
+        }
+    }
+}
+
+/// Image content. The bytes are reference-counted, so cloning an image (or a message or prompt holding one)
+/// does not copy the data.
 #[derive(Clone, Debug, Serialize)]
 pub struct Image {
     media_type: String,
-    data: Vec<u8>,
+    data: Bytes,
+}
+
+/// Input bytes per base64 chunk when streaming a data URL; a multiple of 3 so chunks need no padding.
+const DATA_URL_CHUNK: usize = 3 * 1024;
+
+/// Base64-encodes `data` a chunk at a time, passing each encoded chunk to `write`.
+fn encode_chunks<E>(data: &[u8], mut write: impl FnMut(&str) -> Result<(), E>) -> Result<(), E> {
+    let mut buffer = [0u8; DATA_URL_CHUNK / 3 * 4];
+
+    for chunk in data.chunks(DATA_URL_CHUNK) {
+        let length = BASE64_STANDARD.encode_slice(chunk, &mut buffer).expect("buffer fits an encoded chunk");
+        write(std::str::from_utf8(&buffer[..length]).expect("base64 is ascii"))?;
+    }
+
+    Ok(())
 }
 
+/// Writes the `data:` URL, encoding a chunk at a time rather than building the whole URL in memory.
 impl fmt::Display for Image {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "data:{};base64,", self.media_type())?;
+        encode_chunks(self.data_ref(), |chunk| f.write_str(chunk))
+    }
+}
+
+impl<'de> Deserialize<'de> for Image {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (media_type, data) = MediaRepr::deserialize(deserializer)?.decode().map_err(de::Error::custom)?;
+        Ok(Self::new(media_type, data))
+    }
+}
+
+impl Image {
+    #[inline]
+    pub fn new(media_type: impl Into<String>, data: impl Into<Bytes>) -> Self {
+        Self { media_type: media_type.into(), data: data.into() }
+    }
+
+    #[inline]
+    pub fn media_type(&self) -> &str {
+        &self.media_type
+    }
+
+    /// A copy of the image data; prefer `data_ref` or `bytes` to avoid the copy.
+    #[inline]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    #[inline]
+    pub fn data_ref(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// The image data as a cheaply clonable handle.
+    #[inline]
+    pub fn bytes(&self) -> Bytes {
+        self.data.clone()
+    }
+
+    /// The `data:<media type>;base64,<data>` URL, allocated once at its exact length.
+    pub fn to_data_url(&self) -> String {
+        let prefix = format!("data:{};base64,", self.media_type);
+
+        let mut url = String::with_capacity(prefix.len() + base64::encoded_len(self.data.len(), true).unwrap_or_default());
+        url.push_str(&prefix);
+        BASE64_STANDARD.encode_string(self.data_ref(), &mut url);
+        url
+    }
+
+    /// Streams the data URL to `writer` in fixed-size chunks.
+    pub fn write_data_url<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: std::io::Write,
+    {
+        write!(writer, "data:{};base64,", self.media_type)?;
+        encode_chunks(self.data_ref(), |chunk| writer.write_all(chunk.as_bytes()))
+    }
+
+    /// Streams the data URL to an async `writer` in fixed-size chunks, e.g. into an HTTP response body.
+    pub async fn async_write_data_url<W>(&self, writer: &mut W) -> std::io::Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        writer.write_all(format!("data:{};base64,", self.media_type).as_bytes()).await?;
+
+        let mut buffer = vec![0u8; DATA_URL_CHUNK / 3 * 4];
+        for chunk in self.data.chunks(DATA_URL_CHUNK) {
+            let length = BASE64_STANDARD.encode_slice(chunk, &mut buffer).expect("buffer fits an encoded chunk");
+            writer.write_all(&buffer[..length]).await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Audio {
+    media_type: String,
+    data: Vec<u8>,
+}
+
+impl fmt::Display for Audio {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "data:{};base64, {}", self.media_type(), BASE64_STANDARD.encode(self.data()))
     }
 }
 
-impl Image {
+impl<'de> Deserialize<'de> for Audio {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (media_type, data) = MediaRepr::deserialize(deserializer)?.decode().map_err(de::Error::custom)?;
+        Ok(Self::new(media_type, data))
+    }
+}
+
+impl Audio {
     #[inline]
     pub fn new(media_type: impl Into<String>, data: Vec<u8>) -> Self {
         Self { media_type: media_type.into(), data }
@@ -36,23 +200,72 @@ impl Image {
 #[derive(Clone, Debug, Serialize)]
 #[serde(tag = "type")]
 pub enum Message {
+    #[serde(rename = "audio")]
+    Audio(Audio),
+
     #[serde(rename = "image")]
     Image(Image),
 
     #[serde(rename = "text")]
     Text { text: String },
+
+    /// A content block this crate does not model yet, kept as the provider's raw JSON.
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+/// Accepts the tagged form `Message` serializes to, a bare string (a data URL becomes an image or audio
+/// message, anything else text), and keeps objects of any other `type` as `Message::Unknown`.
+impl<'de> Deserialize<'de> for Message {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let value = serde_json::Value::deserialize(deserializer)?;
+
+        let message = match &value {
+            serde_json::Value::String(url) if url.starts_with("data:") => {
+                let (media_type, data) = parse_data_url(url).map_err(de::Error::custom)?;
+                match media_type.starts_with("audio/") {
+                    true => Message::Audio(Audio::new(media_type, data)),
+                    false => Message::Image(Image::new(media_type, data)),
+                }
+            },
+            serde_json::Value::String(text) => Message::Text { text: text.clone() },
+            serde_json::Value::Object(object) => match object.get("type").and_then(serde_json::Value::as_str) {
+                Some("audio") => Message::Audio(Audio::deserialize(value).map_err(de::Error::custom)?),
+                Some("image") => Message::Image(Image::deserialize(value).map_err(de::Error::custom)?),
+                Some("text") => match object.get("text").and_then(serde_json::Value::as_str) {
+                    Some(text) => Message::Text { text: text.to_string() },
+                    None => return Err(de::Error::missing_field("text")),
+                },
+                _ => Message::Unknown(value),
+            },
+            _ => Message::Unknown(value),
+        };
+
+        Ok(message)
+    }
 }
 
 impl fmt::Display for Message {
     #[inline]
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
+            Message::Audio(audio) => write!(f, "{}", audio),
             Message::Image(image) => write!(f, "{}", image),
             Message::Text { text } => f.write_str(text.as_str()),
+            Message::Unknown(value) => write!(f, "{}", value),
         }
     }
 }
 
+impl From<Audio> for Message {
+    fn from(value: Audio) -> Self {
+        Self::Audio(value)
+    }
+}
+
 impl From<Image> for Message {
     fn from(value: Image) -> Self {
         Self::Image(value)
@@ -71,35 +284,279 @@ impl From<&str> for Message {
     }
 }
 
+pub mod agent;
+
+pub mod anonymize;
+
+mod asset;
+pub use asset::{AssetId, AssetStore};
+
 mod assistant;
-pub use assistant::{Assistant, AssistantResponse};
+pub use assistant::{Assistant, AssistantEvent, AssistantEvents, AssistantResponse, EventSubscription, MissingField, QueryKind};
+
+mod batch;
+pub use batch::{BatchReport, BatchScheduler};
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod broker;
+#[cfg(not(target_arch = "wasm32"))]
+pub use broker::QueryBroker;
+
+mod checkpoint;
+pub use checkpoint::{CheckpointStore, MemoryCheckpointStore};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use checkpoint::FileCheckpointStore;
+
+pub mod compaction;
+
+pub mod compression;
+
+pub mod config;
+
+mod context;
+pub use context::{Context, Document};
+
+mod conversation;
+pub use conversation::{Conversation, HistoryLimits, MemorySessionStore, Role, SessionStore, ToolUse, Turn, Usage, CONVERSATION_VERSION};
 
 mod error;
-pub use error::Error;
+pub use error::{Error, Problem};
+
+pub mod eval;
+
+pub mod experiments;
+
+pub mod extract;
+
+pub mod guardrails;
+
+#[cfg(feature = "http")]
+pub mod http;
+
+#[cfg(feature = "locale")]
+pub mod locale;
 
 pub mod model;
 
+pub mod output;
+
+pub mod pipeline;
+
+mod registry;
+pub use registry::{HedgePolicy, ModelRegistry};
+
+mod secret;
+pub use secret::{Exporting, SecretSource, SecretString};
+
+mod session;
+pub use session::{SessionBudget, SessionManager};
+
+pub mod strategies;
+
+#[cfg(not(target_arch = "wasm32"))]
+pub mod synthetic;
+
+pub mod testing;
+
+pub mod tool;
+
+#[cfg(feature = "opentelemetry")]
+mod telemetry;
+
+pub mod time;
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "provider")]
 pub enum LanguageModel {
     Anthropic(model::anthropic::AnthropicModel),
+
+    DeepSeek(model::deepseek::DeepSeekModel),
+
+    #[cfg(feature = "vertex")]
+    Gemini(model::vertex::GeminiModel),
+
+    HuggingFace(model::huggingface::HuggingFaceModel),
+
+    Perplexity(model::perplexity::PerplexityModel),
+
+    /// Any other `provider`, looked up among the `model::PluginModel` implementations linked into the program.
+    #[serde(untagged)]
+    Plugin(model::Plugin),
+}
+
+impl model::BatchInference for LanguageModel {}
+
+impl model::ModelCatalog for LanguageModel {
+    async fn list_models(&self) -> Result<Vec<model::ModelInfo>, Error> {
+        match self {
+            Self::Anthropic(model) => model.list_models().await,
+            Self::DeepSeek(model) => model.list_models().await,
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.list_models().await,
+
+            Self::HuggingFace(model) => model.list_models().await,
+            Self::Perplexity(model) => model.list_models().await,
+            Self::Plugin(model) => model.list_models().await,
+        }
+    }
 }
 
 impl model::LanguageModel for LanguageModel {
     async fn inference(&self, prompt: model::LanguageModelPrompt) -> Result<Message, Error> {
         match self {
-            Self::Anthropic(model) => model,
-        }.inference(prompt).await
+            Self::Anthropic(model) => model.inference(prompt).await,
+            Self::DeepSeek(model) => model.inference(prompt).await,
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.inference(prompt).await,
+
+            Self::HuggingFace(model) => model.inference(prompt).await,
+            Self::Perplexity(model) => model.inference(prompt).await,
+            Self::Plugin(model) => model.inference(prompt).await,
+        }
+    }
+
+    async fn inference_multi(&self, prompt: model::LanguageModelPrompt) -> Result<Vec<Message>, Error> {
+        match self {
+            Self::Anthropic(model) => model.inference_multi(prompt).await,
+            Self::DeepSeek(model) => model.inference_multi(prompt).await,
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.inference_multi(prompt).await,
+
+            Self::HuggingFace(model) => model.inference_multi(prompt).await,
+            Self::Perplexity(model) => model.inference_multi(prompt).await,
+            Self::Plugin(model) => model.inference_multi(prompt).await,
+        }
+    }
+
+    fn rate_limit(&self) -> Option<model::RateLimit> {
+        match self {
+            Self::Anthropic(model) => model.rate_limit(),
+            Self::DeepSeek(model) => model.rate_limit(),
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.rate_limit(),
+
+            Self::HuggingFace(model) => model.rate_limit(),
+            Self::Perplexity(model) => model.rate_limit(),
+            Self::Plugin(model) => model.rate_limit(),
+        }
+    }
+
+    fn capabilities(&self) -> Option<model::Capabilities> {
+        match self {
+            Self::Anthropic(model) => model.capabilities(),
+            Self::DeepSeek(model) => model.capabilities(),
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.capabilities(),
+
+            Self::HuggingFace(model) => model.capabilities(),
+            Self::Perplexity(model) => model.capabilities(),
+            Self::Plugin(model) => model.capabilities(),
+        }
+    }
+
+    fn compatibility(&self, prompt: &model::LanguageModelPrompt) -> model::CompatibilityReport {
+        match self {
+            Self::Anthropic(model) => model.compatibility(prompt),
+            Self::DeepSeek(model) => model.compatibility(prompt),
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => model.compatibility(prompt),
+
+            Self::HuggingFace(model) => model.compatibility(prompt),
+            Self::Perplexity(model) => model.compatibility(prompt),
+            Self::Plugin(model) => model.compatibility(prompt),
+        }
     }
 }
 
 impl LanguageModel {
-    pub fn anthropic(api_key: impl Into<String>, api_version: impl Into<String>, model: impl Into<String>) -> Self {
+    pub fn anthropic(api_key: impl Into<model::BearerToken>, api_version: impl Into<String>, model: impl Into<String>) -> Self {
         Self::Anthropic(model::anthropic::AnthropicModel::new(api_key, api_version, model))
     }
 
+    pub fn deepseek(api_key: impl Into<model::BearerToken>, model: impl Into<String>) -> Self {
+        Self::DeepSeek(model::deepseek::DeepSeekModel::new(api_key, model))
+    }
+
+    pub fn perplexity(api_key: impl Into<model::BearerToken>, model: impl Into<String>) -> Self {
+        Self::Perplexity(model::perplexity::PerplexityModel::new(api_key, model))
+    }
+
+    pub fn plugin(model: impl model::PluginModel + 'static) -> Self {
+        Self::Plugin(model::Plugin::new(model))
+    }
+
     #[cfg(feature = "aws-bedrock")]
     pub async fn anthropic_bedrock(api_version: impl Into<String>, model: impl Into<String>, aws_config: Option<model::AwsConfig>) -> Self {
         Self::Anthropic(model::anthropic::AnthropicModel::bedrock(api_version, model, aws_config).await)
     }
+
+    /// Sends requests through `client`, e.g. one built from a `model::HttpClientConfig`, for providers that use HTTP.
+    /// Plugins keep their own client.
+    pub fn http_client(self, client: reqwest::Client) -> Self {
+        match self {
+            Self::Anthropic(model) => Self::Anthropic(model.http_client(client)),
+            Self::DeepSeek(model) => Self::DeepSeek(model.http_client(client)),
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(model) => Self::Gemini(model.http_client(client)),
+
+            Self::HuggingFace(model) => Self::HuggingFace(model.http_client(client)),
+            Self::Perplexity(model) => Self::Perplexity(model.http_client(client)),
+            Self::Plugin(model) => Self::Plugin(model),
+        }
+    }
+
+    /// Deserializes a model configuration and eagerly initializes its provider client. An `http` key holds a
+    /// `model::HttpClientConfig` for the provider's HTTP client, and secret references are resolved as in
+    /// `config::resolve_secrets`.
+    pub async fn from_config(mut config: serde_json::Value) -> Result<Self, Error> {
+        config::resolve_secrets(&mut config).await?;
+
+        let http = match config.as_object_mut().and_then(|config| config.remove("http")) {
+            Some(http) => Some(serde_json::from_value::<model::HttpClientConfig>(http).map_err(|err| Error::Unexpected(err.into()))?),
+            None => None,
+        };
+
+        let model = serde_json::from_value::<Self>(config).map_err(|err| Error::Unexpected(err.into()))?;
+        let model = match http {
+            Some(http) => model.http_client(http.build()?),
+            None => model,
+        };
+
+        match &model {
+            Self::Anthropic(model) => model.initialize().await,
+            Self::DeepSeek(_) => {},
+
+            #[cfg(feature = "vertex")]
+            Self::Gemini(_) => {},
+
+            Self::HuggingFace(_) => {},
+            Self::Perplexity(_) => {},
+            Self::Plugin(model) => model.initialize().await,
+        }
+
+        Ok(model)
+    }
+
+    /// Selects Claude or Gemini on Vertex AI from the publisher implied by the model id.
+    #[cfg(feature = "vertex")]
+    pub fn vertex(project_id: impl Into<String>, region: impl Into<String>, model: impl Into<String>) -> Self {
+        let model = model.into();
+
+        if model.starts_with("claude") {
+            Self::Anthropic(model::anthropic::AnthropicModel::vertex(project_id, region, model))
+        } else {
+            Self::Gemini(model::vertex::GeminiModel::new(project_id, region, model))
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+use std::{fmt, ops::Range, sync::OnceLock};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use icu_datetime::{fieldsets::YMD, input::Date, DateTimeFormatter};
+use icu_decimal::{input::Decimal, DecimalFormatter};
+use icu_locale_core::Locale;
+use regex::{Captures, Regex};
+use serde::Serialize;
+use tracing::debug;
+
+use super::{
+    model::{LanguageModelPrompt, ModelMiddleware},
+    Error,
+    Message,
+};
+
+/// Dates (ISO `2024-03-15` or US `3/15/2024`), amounts with a currency symbol, and numbers that are grouped,
+/// have a fractional part or are long enough to need grouping.
+fn pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| Regex::new(concat!(
+        r"(?P<iso>\b(?P<iy>\d{4})-(?P<im>\d{2})-(?P<id>\d{2})\b)",
+        r"|(?P<us>\b(?P<um>\d{1,2})/(?P<ud>\d{1,2})/(?P<uy>\d{4})\b)",
+        r"|(?P<currency>(?P<symbol>[$€£¥₹])\s?(?P<amount>\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+(?:\.\d+)?))",
+        r"|(?P<number>\b(?:\d{1,3}(?:,\d{3})+(?:\.\d+)?|\d+\.\d+|\d{5,})\b)",
+    )).expect("valid locale pattern"))
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Currency,
+    Date,
+    Number,
+}
+
+/// One value rewritten by `LocaleFormatter::localize`, with its byte range in the original text.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Change {
+    kind: ChangeKind,
+    range: Range<usize>,
+    original: String,
+    replacement: String,
+}
+
+impl Change {
+    pub fn kind(&self) -> ChangeKind {
+        self.kind
+    }
+
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    pub fn original(&self) -> &str {
+        &self.original
+    }
+
+    pub fn replacement(&self) -> &str {
+        &self.replacement
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Localized {
+    text: String,
+    changes: Vec<Change>,
+}
+
+impl Localized {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+/// Rewrites numbers, dates and currency amounts in model output into a locale's conventions using ICU data.
+///
+/// Input is assumed to follow the conventions models default to: `,` grouping, `.` decimals and ISO or US
+/// dates. Currency amounts keep their symbol and only have the amount localized. Values that look like
+/// versions or addresses (`1.2.3`, `10.0.0.1`) are left alone.
+pub struct LocaleFormatter {
+    locale: Locale,
+    decimal: DecimalFormatter,
+    date: DateTimeFormatter<YMD>,
+}
+
+impl fmt::Debug for LocaleFormatter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LocaleFormatter").field("locale", &self.locale.to_string()).finish()
+    }
+}
+
+impl LocaleFormatter {
+    /// Creates a formatter for a BCP 47 locale such as `de-DE` or `fr-CH`.
+    pub fn new(locale: &str) -> Result<Self, Error> {
+        let locale = Locale::try_from_str(locale).map_err(|err| Error::Unexpected(anyhow!("invalid-locale: {}: {}", locale, err)))?;
+
+        Ok(Self {
+            decimal: DecimalFormatter::try_new((&locale).into(), Default::default()).map_err(|err| Error::Unexpected(anyhow!(err)))?,
+            date: DateTimeFormatter::try_new((&locale).into(), YMD::medium()).map_err(|err| Error::Unexpected(anyhow!(err)))?,
+            locale,
+        })
+    }
+
+    pub fn locale(&self) -> String {
+        self.locale.to_string()
+    }
+
+    fn number(&self, text: &str) -> Option<String> {
+        let decimal = text.replace(',', "").parse::<Decimal>().ok()?;
+        Some(self.decimal.format(&decimal).to_string())
+    }
+
+    fn date(&self, year: &str, month: &str, day: &str) -> Option<String> {
+        let date = Date::try_new_iso(year.parse().ok()?, month.parse().ok()?, day.parse().ok()?).ok()?;
+        Some(self.date.format(&date).to_string())
+    }
+
+    fn replacement(&self, captures: &Captures) -> Option<(ChangeKind, String)> {
+        if captures.name("iso").is_some() {
+            return self.date(&captures["iy"], &captures["im"], &captures["id"]).map(|date| (ChangeKind::Date, date));
+        }
+        if captures.name("us").is_some() {
+            return self.date(&captures["uy"], &captures["um"], &captures["ud"]).map(|date| (ChangeKind::Date, date));
+        }
+        if captures.name("currency").is_some() {
+            return self.number(&captures["amount"]).map(|amount| (ChangeKind::Currency, format!("{}{}", &captures["symbol"], amount)));
+        }
+
+        self.number(&captures["number"]).map(|number| (ChangeKind::Number, number))
+    }
+
+    pub fn localize(&self, text: &str) -> Localized {
+        let mut localized = String::with_capacity(text.len());
+        let mut changes = Vec::new();
+        let mut last = 0;
+
+        for captures in pattern().captures_iter(text) {
+            let Some(matched) = captures.get(0) else { continue };
+
+            // Part of a longer dotted or alphanumeric token such as a version number, IP address or identifier.
+            let before = text[..matched.start()].chars().next_back();
+            let mut after = text[matched.end()..].chars();
+            let dotted = matches!(after.next(), Some('.') | Some(',')) && after.next().is_some_and(|next| next.is_ascii_digit());
+            if dotted || before.is_some_and(|before| before == '.' || before.is_alphanumeric()) {
+                continue;
+            }
+
+            let Some((kind, replacement)) = self.replacement(&captures) else { continue };
+            if replacement == matched.as_str() {
+                continue;
+            }
+
+            localized.push_str(&text[last..matched.start()]);
+            localized.push_str(&replacement);
+            last = matched.end();
+
+            changes.push(Change {
+                kind,
+                range: matched.range(),
+                original: matched.as_str().to_string(),
+                replacement,
+            });
+        }
+        localized.push_str(&text[last..]);
+
+        Localized { text: localized, changes }
+    }
+}
+
+/// Localizes the text of every response; see [`LocaleFormatter`].
+#[async_trait(?Send)]
+impl ModelMiddleware for LocaleFormatter {
+    async fn after_response(&self, #[allow(unused)] prompt: &LanguageModelPrompt, response: Message) -> Result<Message, Error> {
+        match response {
+            Message::Text { text } => {
+                let localized = self.localize(&text);
+                debug! { locale = %self.locale, changes = ?localized.changes };
+                Ok(Message::Text { text: localized.into_text() })
+            },
+            response => Ok(response),
+        }
+    }
+}
@@ -1,36 +1,119 @@
-use std::future::Future;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
 
-use super::{Error, Image, Message};
+use futures_util::{
+    future,
+    stream::{self, Stream, StreamExt},
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tracing::{debug, error, info, warn, Span};
 
-#[derive(Debug)]
+use super::{Audio, Error, Image, Message, Problem};
+
+#[derive(Clone, Debug)]
 pub struct LanguageModelPrompt {
-    max_tokens: usize,
-    messages: Vec<Message>,
-    temperature: f32,
-    stop_sequences: Vec<String>,
-    system: Option<String>,
+    pub(crate) max_tokens: usize,
+    pub(crate) messages: Vec<Message>,
+    pub(crate) temperature: f32,
+    pub(crate) top_p: Option<f32>,
+    pub(crate) top_k: Option<u32>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) frequency_penalty: Option<f32>,
+    pub(crate) presence_penalty: Option<f32>,
+    pub(crate) logit_bias: HashMap<u32, f32>,
+    pub(crate) banned_phrases: Vec<String>,
+    pub(crate) stop_sequences: Vec<String>,
+    pub(crate) system: Option<String>,
+    pub(crate) examples: Vec<(String, String)>,
+    pub(crate) auto_max_tokens: Option<usize>,
+    pub(crate) defaults: Option<PromptDefaults>,
+    pub(crate) explicit: defaults::ExplicitSettings,
+    pub(crate) model: Option<String>,
+    pub(crate) metadata: BTreeMap<String, String>,
+    pub(crate) user: Option<String>,
+    pub(crate) candidates: usize,
+    pub(crate) logprobs: Option<u32>,
+    pub(crate) server_tools: Vec<ServerTool>,
+    pub(crate) continuations: Option<u32>,
+    pub(crate) deadline: Option<crate::time::Instant>,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) query: Vec<(String, String)>,
 }
 
 impl From<Image> for LanguageModelPrompt {
     fn from(value: Image) -> Self {
+        let defaults = PromptDefaults::global();
+
         Self {
-            max_tokens: 1024,
+            max_tokens: defaults.max_tokens,
             messages: vec![value.into()],
-            temperature: 0.63,
+            temperature: defaults.temperature,
+            top_p: defaults.top_p,
+            top_k: None,
+            seed: None,
+            frequency_penalty: defaults.frequency_penalty,
+            presence_penalty: defaults.presence_penalty,
+            logit_bias: HashMap::new(),
+            banned_phrases: Vec::new(),
             stop_sequences: Vec::new(),
             system: None,
+            examples: Vec::new(),
+            auto_max_tokens: defaults.auto_max_tokens,
+            defaults: None,
+            explicit: defaults::ExplicitSettings::default(),
+            model: None,
+            metadata: BTreeMap::new(),
+            user: None,
+            candidates: 1,
+            logprobs: None,
+            server_tools: Vec::new(),
+            continuations: None,
+            deadline: None,
+            headers: Vec::new(),
+            query: Vec::new(),
         }
     }
 }
 
 impl From<String> for LanguageModelPrompt {
     fn from(value: String) -> Self {
+        let defaults = PromptDefaults::global();
+
         Self {
-            max_tokens: 1024,
+            max_tokens: defaults.max_tokens,
             messages: vec![value.into()],
-            temperature: 0.63,
+            temperature: defaults.temperature,
+            top_p: defaults.top_p,
+            top_k: None,
+            seed: None,
+            frequency_penalty: defaults.frequency_penalty,
+            presence_penalty: defaults.presence_penalty,
+            logit_bias: HashMap::new(),
+            banned_phrases: Vec::new(),
             stop_sequences: Vec::new(),
             system: None,
+            examples: Vec::new(),
+            auto_max_tokens: defaults.auto_max_tokens,
+            defaults: None,
+            explicit: defaults::ExplicitSettings::default(),
+            model: None,
+            metadata: BTreeMap::new(),
+            user: None,
+            candidates: 1,
+            logprobs: None,
+            server_tools: Vec::new(),
+            continuations: None,
+            deadline: None,
+            headers: Vec::new(),
+            query: Vec::new(),
         }
     }
 }
@@ -54,6 +137,18 @@ impl LanguageModelPrompt {
     pub fn max_tokens(self, max_tokens: usize) -> Self {
         Self {
             max_tokens,
+            explicit: defaults::ExplicitSettings { max_tokens: true, ..self.explicit },
+            ..self
+        }
+    }
+
+    /// Sizes `max_tokens` to the room left in the model's context window: the window, less the estimated prompt
+    /// and `margin` tokens held back for estimation error, capped at the model's output limit. Models without
+    /// published limits keep `max_tokens`.
+    pub fn auto_max_tokens(self, margin: usize) -> Self {
+        Self {
+            auto_max_tokens: Some(margin),
+            explicit: defaults::ExplicitSettings { auto_max_tokens: true, ..self.explicit },
             ..self
         }
     }
@@ -61,6 +156,96 @@ impl LanguageModelPrompt {
     pub fn temperature(self, temperature: f32) -> Self {
         Self {
             temperature,
+            explicit: defaults::ExplicitSettings { temperature: true, ..self.explicit },
+            ..self
+        }
+    }
+
+    /// Nucleus sampling cutoff; providers generally recommend adjusting this or `temperature`, not both.
+    pub fn top_p(self, top_p: f32) -> Self {
+        Self {
+            top_p: Some(top_p),
+            explicit: defaults::ExplicitSettings { top_p: true, ..self.explicit },
+            ..self
+        }
+    }
+
+    /// Samples only from the `top_k` most likely tokens; ignored by providers without an equivalent.
+    pub fn top_k(self, top_k: u32) -> Self {
+        Self {
+            top_k: Some(top_k),
+            ..self
+        }
+    }
+
+    /// Requests best-effort deterministic sampling: the same seed and settings should give the same output.
+    /// Ignored by providers without an equivalent.
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Penalizes tokens in proportion to how often they already appear; ignored by providers without an equivalent.
+    pub fn frequency_penalty(self, frequency_penalty: f32) -> Self {
+        Self {
+            frequency_penalty: Some(frequency_penalty),
+            explicit: defaults::ExplicitSettings { frequency_penalty: true, ..self.explicit },
+            ..self
+        }
+    }
+
+    /// Penalizes any token that has already appeared; ignored by providers without an equivalent.
+    pub fn presence_penalty(self, presence_penalty: f32) -> Self {
+        Self {
+            presence_penalty: Some(presence_penalty),
+            explicit: defaults::ExplicitSettings { presence_penalty: true, ..self.explicit },
+            ..self
+        }
+    }
+
+    /// Adds `bias` (-100 to 100) to the logit of a provider token id; only honoured by providers exposing `logit_bias`.
+    pub fn logit_bias(self, token: u32, bias: f32) -> Self {
+        let mut logit_bias = self.logit_bias;
+        logit_bias.insert(token, bias);
+
+        Self {
+            logit_bias,
+            ..self
+        }
+    }
+
+    pub fn ban_token(self, token: u32) -> Self {
+        self.logit_bias(token, -100.0)
+    }
+
+    /// Phrases the response must not contain. No provider enforces these natively; they are checked after
+    /// generation by the `BannedPhrases` middleware.
+    pub fn ban_phrase(self, phrase: impl Into<String>) -> Self {
+        let mut banned_phrases = self.banned_phrases;
+        banned_phrases.push(phrase.into());
+
+        Self {
+            banned_phrases,
+            ..self
+        }
+    }
+
+    /// Applies the sampling settings bundled by `profile`, replacing any set earlier.
+    pub fn profile(self, profile: Profile) -> Self {
+        Self {
+            temperature: profile.temperature(),
+            top_p: profile.top_p(),
+            frequency_penalty: profile.frequency_penalty(),
+            presence_penalty: profile.presence_penalty(),
+            explicit: defaults::ExplicitSettings {
+                temperature: true,
+                top_p: true,
+                frequency_penalty: true,
+                presence_penalty: true,
+                ..self.explicit
+            },
             ..self
         }
     }
@@ -75,28 +260,1358 @@ impl LanguageModelPrompt {
         }
     }
 
+    /// Lets the model call a tool the provider runs itself, such as web search, while generating the response.
+    pub fn server_tool(self, server_tool: ServerTool) -> Self {
+        let mut server_tools = self.server_tools;
+        server_tools.push(server_tool);
+
+        Self {
+            server_tools,
+            ..self
+        }
+    }
+
     pub fn system(self, system: impl Into<String>) -> Self {
         Self {
             system: Some(system.into()),
             ..self
         }
     }
+
+    /// Adds a few-shot example: `user` and `assistant` are sent as a prior user turn and the model's reply to it,
+    /// after the system prompt and before the query, in the order they are added. Completion-only APIs get them
+    /// as `Input:`/`Output:` pairs in the prompt text instead.
+    pub fn example(self, user: impl Into<String>, assistant: impl Into<String>) -> Self {
+        let mut examples = self.examples;
+        examples.push((user.into(), assistant.into()));
+
+        Self {
+            examples,
+            ..self
+        }
+    }
+
+    /// Routes this prompt to a logical model name from the `ModelRegistry` instead of its default; ignored by concrete providers.
+    pub fn model(self, model: impl Into<String>) -> Self {
+        Self {
+            model: Some(model.into()),
+            ..self
+        }
+    }
+
+    /// Starts from `defaults`, e.g. `PromptDefaults::long_form()`, instead of `PromptDefaults::global()`; settings
+    /// already changed on this prompt are kept, and a `ModelRegistry` no longer applies its own defaults.
+    pub fn defaults(self, defaults: PromptDefaults) -> Self {
+        defaults.apply(self)
+    }
+
+    /// When the response is cut off by `max_tokens`, re-prompts up to `max_continuations` times with the partial
+    /// output as the start of the reply and stitches the parts into one response, so long outputs such as JSON
+    /// arrive whole. Only honoured by Anthropic models, which accept a prefilled reply.
+    pub fn auto_continue(self, max_continuations: u32) -> Self {
+        Self {
+            continuations: Some(max_continuations),
+            ..self
+        }
+    }
+
+    /// Adds a header to the provider request, e.g. an `anthropic-beta` flag or a gateway's auth header. Ignored by
+    /// providers called through an SDK rather than plain HTTP.
+    pub fn header(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut headers = self.headers;
+        headers.push((name.into(), value.into()));
+
+        Self {
+            headers,
+            ..self
+        }
+    }
+
+    /// Adds a query parameter to the provider request URL, e.g. a gateway's routing or tenant parameter.
+    pub fn query(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut query = self.query;
+        query.push((name.into(), value.into()));
+
+        Self {
+            query,
+            ..self
+        }
+    }
+
+    /// Fails the call with `Error::DeadlineExceeded` once `deadline` passes, including time spent on retries,
+    /// so an upstream request deadline becomes the model call's budget. Outside wasm32 `time::Instant` is
+    /// `std::time::Instant`.
+    pub fn deadline(self, deadline: crate::time::Instant) -> Self {
+        Self {
+            deadline: Some(deadline),
+            ..self
+        }
+    }
+
+    /// Estimated tokens sent ahead of the messages: the system prompt and the few-shot examples.
+    pub(crate) fn preamble_tokens(&self) -> usize {
+        self.system.as_deref().map(estimate_tokens).unwrap_or_default()
+            + self.examples.iter().map(|(user, assistant)| estimate_tokens(user) + estimate_tokens(assistant)).sum::<usize>()
+    }
+
+    /// Estimated input tokens: the preamble and the text messages.
+    pub(crate) fn input_tokens(&self) -> usize {
+        self.messages.iter()
+            .filter_map(|message| match message {
+                Message::Text { text } => Some(estimate_tokens(text)),
+                _ => None,
+            })
+            .sum::<usize>() + self.preamble_tokens()
+    }
+
+    /// This prompt with `max_tokens` resolved against `capabilities` when `auto_max_tokens` is set.
+    pub(crate) fn fit_max_tokens(&self, capabilities: Option<Capabilities>) -> Cow<'_, Self> {
+        match (self.auto_max_tokens, capabilities) {
+            (Some(margin), Some(capabilities)) => {
+                let max_tokens = capabilities.output_tokens_for(self, margin);
+                debug! { max_tokens, "max_tokens sized to the context window" };
+
+                Cow::Owned(Self { max_tokens, auto_max_tokens: None, ..self.clone() })
+            },
+            _ => Cow::Borrowed(self),
+        }
+    }
+
+    /// Time left before the deadline, `None` without one.
+    pub fn remaining(&self) -> Option<Duration> {
+        self.deadline.map(|deadline| deadline.saturating_duration_since(crate::time::Instant::now()))
+    }
+
+    /// Whether a retry after `delay` would still start before the deadline; always true without one.
+    pub fn can_wait(&self, delay: Duration) -> bool {
+        self.remaining().is_none_or(|remaining| delay < remaining)
+    }
+
+    /// Number of alternative responses `LanguageModel::inference_multi` returns; `inference` always returns one.
+    pub fn candidates(self, candidates: usize) -> Self {
+        Self {
+            candidates,
+            ..self
+        }
+    }
+
+    /// Requests the log probability of each generated token and of the `top_n` likeliest alternatives at each
+    /// position, read back with `LogprobModel::inference_with_logprobs`.
+    pub fn logprobs(self, top_n: u32) -> Self {
+        Self {
+            logprobs: Some(top_n),
+            ..self
+        }
+    }
+
+    /// Attaches request metadata for the provider's logs and abuse monitoring; it does not affect the response.
+    pub fn metadata(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut metadata = self.metadata;
+        metadata.insert(key.into(), value.into());
+
+        Self {
+            metadata,
+            ..self
+        }
+    }
+
+    /// Identifies the end user the request is made for, as Anthropic's `metadata.user_id` and OpenAI's `user`.
+    /// Use an opaque id such as a hash rather than a name or email address.
+    pub fn user(self, user_id: impl Into<String>) -> Self {
+        Self {
+            user: Some(user_id.into()),
+            ..self
+        }
+    }
+
+    /// Rejects settings every provider would refuse, with `Error::InvalidRequest` describing the first problem,
+    /// so a bad prompt fails before any network call.
+    ///
+    /// Ranges are the widest any supported provider accepts (temperature up to 2.0); providers with narrower
+    /// ranges may still reject a prompt that passes.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |reason: String| Err(Error::InvalidRequest(reason));
+
+        if self.max_tokens == 0 {
+            return invalid("max_tokens must be greater than 0".to_string());
+        }
+        if self.remaining().is_some_and(|remaining| remaining.is_zero()) {
+            return Err(Error::DeadlineExceeded);
+        }
+        if !(0.0..=2.0).contains(&self.temperature) {
+            return invalid(format!("temperature must be between 0.0 and 2.0, got {}", self.temperature));
+        }
+        if let Some(top_p) = self.top_p.filter(|top_p| !(*top_p > 0.0 && *top_p <= 1.0)) {
+            return invalid(format!("top_p must be in (0.0, 1.0], got {}", top_p));
+        }
+        if self.candidates == 0 {
+            return invalid("candidates must be greater than 0".to_string());
+        }
+        if self.top_k == Some(0) {
+            return invalid("top_k must be greater than 0".to_string());
+        }
+        for (setting, penalty) in [("frequency_penalty", self.frequency_penalty), ("presence_penalty", self.presence_penalty)] {
+            if let Some(penalty) = penalty.filter(|penalty| !(-2.0..=2.0).contains(penalty)) {
+                return invalid(format!("{} must be between -2.0 and 2.0, got {}", setting, penalty));
+            }
+        }
+
+        if self.messages.is_empty() {
+            return invalid("prompt has no messages".to_string());
+        }
+        if self.messages.iter().all(|message| matches!(message, Message::Text { text } if text.trim().is_empty())) {
+            return invalid("prompt has only empty text".to_string());
+        }
+        if self.stop_sequences.iter().any(|stop_sequence| stop_sequence.is_empty()) {
+            return invalid("stop sequences must not be empty".to_string());
+        }
+        if self.system.as_deref().is_some_and(|system| system.trim().is_empty()) {
+            return invalid("system prompt is set but empty".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Merges adjacent text messages into one, separated by a blank line, and drops empty text.
+    ///
+    /// A prompt's messages are the content of a single user turn, so this is the prompt-level counterpart of
+    /// `Conversation::normalize`, which merges consecutive turns of the same role.
+    pub fn normalize(self) -> Self {
+        let mut messages = Vec::<Message>::with_capacity(self.messages.len());
+        for message in self.messages {
+            match (messages.last_mut(), message) {
+                (_, Message::Text { text }) if text.trim().is_empty() => {},
+                (Some(Message::Text { text: previous }), Message::Text { text }) => {
+                    previous.push_str("\n\n");
+                    previous.push_str(&text);
+                },
+                (_, message) => messages.push(message),
+            }
+        }
+
+        Self {
+            messages,
+            system: self.system.filter(|system| !system.trim().is_empty()),
+            ..self
+        }
+    }
 }
 
-pub trait LanguageModel {
-    fn inference(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<Message, Error>>;
+/// Named sampling settings for common kinds of generation, applied with `LanguageModelPrompt::profile` or used as
+/// `PromptDefaults` through `From`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Profile {
+    /// Greedy decoding for extraction, classification and judging.
+    Deterministic,
+    Balanced,
+    /// Open-ended writing and brainstorming.
+    Creative,
+    /// Low-variance output that still avoids degenerate repetition in long code blocks.
+    Code,
 }
 
-pub mod anthropic;
+impl Profile {
+    pub fn temperature(&self) -> f32 {
+        match self {
+            Self::Deterministic => 0.0,
+            Self::Balanced => 0.7,
+            Self::Creative => 1.0,
+            Self::Code => 0.2,
+        }
+    }
 
-#[cfg(feature = "aws-bedrock")]
-mod bedrock;
+    pub fn top_p(&self) -> Option<f32> {
+        match self {
+            Self::Deterministic | Self::Balanced => None,
+            Self::Creative => Some(0.95),
+            Self::Code => Some(0.9),
+        }
+    }
 
-#[cfg(feature = "aws-bedrock")]
-pub use bedrock::AwsConfig;
+    pub fn frequency_penalty(&self) -> Option<f32> {
+        match self {
+            Self::Deterministic | Self::Balanced | Self::Code => None,
+            Self::Creative => Some(0.3),
+        }
+    }
 
-pub mod cohere;
-pub mod meta;
-pub mod mistral;
-pub mod openai;
-pub mod stability;
\ No newline at end of file
+    pub fn presence_penalty(&self) -> Option<f32> {
+        match self {
+            Self::Deterministic | Self::Balanced | Self::Code => None,
+            Self::Creative => Some(0.5),
+        }
+    }
+}
+
+/// A tool executed on the provider's side during generation, as opposed to a `tool::Tool` the caller runs.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ServerTool {
+    WebSearch {
+        /// Searches allowed per request.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_uses: Option<u32>,
+
+        /// Only search these domains; mutually exclusive with `blocked_domains`.
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        allowed_domains: Vec<String>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        blocked_domains: Vec<String>,
+    },
+
+    CodeExecution,
+}
+
+impl ServerTool {
+    pub fn web_search() -> Self {
+        Self::WebSearch {
+            max_uses: None,
+            allowed_domains: Vec::new(),
+            blocked_domains: Vec::new(),
+        }
+    }
+
+    pub fn code_execution() -> Self {
+        Self::CodeExecution
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::WebSearch { .. } => "web_search",
+            Self::CodeExecution => "code_execution",
+        }
+    }
+}
+
+/// Prompt settings a model will not honour, so callers can tell when a request is silently degraded.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompatibilityReport {
+    ignored: Vec<&'static str>,
+}
+
+impl CompatibilityReport {
+    pub fn ignore(self, setting: &'static str) -> Self {
+        let mut ignored = self.ignored;
+        ignored.push(setting);
+
+        Self {
+            ignored,
+        }
+    }
+
+    /// Marks `setting` as ignored when the prompt actually sets it.
+    pub fn ignore_if<T>(self, setting: &'static str, value: &Option<T>) -> Self {
+        match value {
+            Some(_) => self.ignore(setting),
+            None => self,
+        }
+    }
+
+    pub fn ignore_if_any<T>(self, setting: &'static str, values: &HashMap<u32, T>) -> Self {
+        match values.is_empty() {
+            true => self,
+            false => self.ignore(setting),
+        }
+    }
+
+    pub fn ignore_if_nonempty<T>(self, setting: &'static str, values: &[T]) -> Self {
+        match values.is_empty() {
+            true => self,
+            false => self.ignore(setting),
+        }
+    }
+
+    pub fn ignored(&self) -> &[&'static str] {
+        &self.ignored
+    }
+
+    pub fn is_supported(&self) -> bool {
+        self.ignored.is_empty()
+    }
+}
+
+/// Applies the prompt's transport settings to an HTTP request: its extra headers and query parameters, and
+/// what is left of its deadline as the timeout.
+pub(crate) fn prompt_request(request: reqwest::RequestBuilder, prompt: &LanguageModelPrompt) -> reqwest::RequestBuilder {
+    let request = prompt.headers.iter().fold(request, |request, (name, value)| request.header(name, value));
+    let request = match prompt.query.is_empty() {
+        true => request,
+        false => request.query(&prompt.query),
+    };
+
+    match prompt.remaining() {
+        Some(remaining) => request.timeout(remaining),
+        None => request,
+    }
+}
+
+/// `err`, or `Error::DeadlineExceeded` when it is a timeout and `deadline` has passed, since a retry could no
+/// longer finish in time. Other timeouts, such as the client's or a provider's 408, stay retriable.
+pub(crate) fn timeout_error(deadline: Option<crate::time::Instant>, err: Error) -> Error {
+    match err {
+        Error::Timeout(_) if deadline.is_some_and(|deadline| deadline <= crate::time::Instant::now()) => Error::DeadlineExceeded,
+        err => err,
+    }
+}
+
+/// `prompt` validated against `model`'s limits, with `max_tokens` sized to fit them, warning about settings the
+/// model drops.
+pub(crate) fn checked<'a>(model: &impl LanguageModel, prompt: &'a LanguageModelPrompt) -> Result<Cow<'a, LanguageModelPrompt>, Error> {
+    prompt.validate()?;
+    if let Some(capabilities) = model.capabilities() {
+        capabilities.check(prompt)?;
+    }
+    let prompt = prompt.fit_max_tokens(model.capabilities());
+
+    let compatibility = model.compatibility(&prompt);
+    if !compatibility.is_supported() {
+        warn! { ignored = ?compatibility.ignored() };
+    }
+
+    Ok(prompt)
+}
+
+/// Records a provider's response id and `(input, output)` token usage on the current span and logs the outcome:
+/// retriable errors as warnings, the rest as errors.
+pub(crate) fn record_response<T: fmt::Debug>(response: &Result<T, Error>, id: Option<&str>, usage: Option<(usize, usize)>) {
+    let span = Span::current();
+    if let Some(id) = id {
+        span.record("gen_ai.response.id", id);
+    }
+    if let Some((input_tokens, output_tokens)) = usage {
+        span.record("gen_ai.usage.input_tokens", input_tokens);
+        span.record("gen_ai.usage.output_tokens", output_tokens);
+    }
+
+    match response {
+        Ok(response) => {
+            debug! { ?response };
+            info! { ?usage };
+        },
+        Err(err) if err.is_retriable() => warn! { ?err },
+        Err(err) => error! { ?err },
+    }
+}
+
+/// Runs an SDK call within what is left of the prompt's deadline, `None` when the deadline passes first.
+#[cfg(any(feature = "aws-bedrock", feature = "aws-sagemaker"))]
+pub(crate) async fn within_deadline<T>(prompt: &LanguageModelPrompt, call: impl Future<Output = T>) -> Option<T> {
+    match prompt.remaining() {
+        Some(remaining) => tokio::time::timeout(remaining, call).await.ok(),
+        None => Some(call.await),
+    }
+}
+
+/// OpenAI-style chat messages: the system prompt, the few-shot examples as alternating user and assistant
+/// turns, then `content` as the user's query.
+pub(crate) fn chat_messages(prompt: &LanguageModelPrompt, content: &str) -> Vec<serde_json::Value> {
+    let mut messages = Vec::new();
+    if let Some(system) = &prompt.system {
+        messages.push(serde_json::json!({ "role": "system", "content": system }));
+    }
+    for (user, assistant) in &prompt.examples {
+        messages.push(serde_json::json!({ "role": "user", "content": user }));
+        messages.push(serde_json::json!({ "role": "assistant", "content": assistant }));
+    }
+    messages.push(serde_json::json!({ "role": "user", "content": content }));
+
+    messages
+}
+
+/// A single completion input for APIs without roles: the system prompt, then the few-shot examples as
+/// `Input:`/`Output:` pairs ending in an open `Output:` for `text`.
+pub(crate) fn completion_text(prompt: &LanguageModelPrompt, text: &str) -> String {
+    let mut sections = prompt.system.iter().cloned().collect::<Vec<String>>();
+    match prompt.examples.is_empty() {
+        true => sections.push(text.to_string()),
+        false => {
+            sections.extend(prompt.examples.iter().map(|(user, assistant)| format!("Input: {}\nOutput: {}", user, assistant)));
+            sections.push(format!("Input: {}\nOutput:", text));
+        },
+    }
+
+    sections.join("\n\n")
+}
+
+/// Rough token count for budgeting when no provider tokenizer is available (about four characters per token).
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RateLimit {
+    requests_limit: Option<u64>,
+    requests_remaining: Option<u64>,
+    tokens_limit: Option<u64>,
+    tokens_remaining: Option<u64>,
+    retry_after: Option<Duration>,
+}
+
+impl RateLimit {
+    pub fn from_headers(headers: &reqwest::header::HeaderMap, prefix: &str) -> Self {
+        let value = |name: &str| headers.get(name).and_then(|value| value.to_str().ok()).and_then(|value| value.trim().parse::<u64>().ok());
+
+        Self {
+            requests_limit: value(&format!("{}requests-limit", prefix)),
+            requests_remaining: value(&format!("{}requests-remaining", prefix)),
+            tokens_limit: value(&format!("{}tokens-limit", prefix)),
+            tokens_remaining: value(&format!("{}tokens-remaining", prefix)),
+            retry_after: value("retry-after").map(Duration::from_secs),
+        }
+    }
+
+    pub fn requests_limit(&self) -> Option<u64> {
+        self.requests_limit
+    }
+
+    pub fn requests_remaining(&self) -> Option<u64> {
+        self.requests_remaining
+    }
+
+    pub fn tokens_limit(&self) -> Option<u64> {
+        self.tokens_limit
+    }
+
+    pub fn tokens_remaining(&self) -> Option<u64> {
+        self.tokens_remaining
+    }
+
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Whether the provider reported an exhausted request or token budget.
+    pub fn exhausted(&self) -> bool {
+        self.requests_remaining == Some(0) || self.tokens_remaining == Some(0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ImageOptions {
+    width: u32,
+    height: u32,
+    count: usize,
+    negative_prompt: Option<String>,
+    seed: Option<u64>,
+}
+
+impl Default for ImageOptions {
+    fn default() -> Self {
+        Self {
+            width: 1024,
+            height: 1024,
+            count: 1,
+            negative_prompt: None,
+            seed: None,
+        }
+    }
+}
+
+impl ImageOptions {
+    pub fn size(self, width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            ..self
+        }
+    }
+
+    pub fn count(self, count: usize) -> Self {
+        Self {
+            count: count.max(1),
+            ..self
+        }
+    }
+
+    pub fn negative_prompt(self, negative_prompt: impl Into<String>) -> Self {
+        Self {
+            negative_prompt: Some(negative_prompt.into()),
+            ..self
+        }
+    }
+
+    pub fn seed(self, seed: u64) -> Self {
+        Self {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+}
+
+pub trait ImageModel {
+    fn generate(&self, prompt: &str, options: ImageOptions) -> impl Future<Output = Result<Vec<Image>, Error>>;
+}
+
+pub trait LanguageModel {
+    fn inference(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<Message, Error>>;
+
+    /// Returns `prompt.candidates` alternative responses, e.g. for best-of-n sampling or self-consistency.
+    ///
+    /// Providers with a native candidate count override this to make one request; the default makes the calls
+    /// in parallel, offsetting the seed for each call when the prompt has one so the candidates differ.
+    fn inference_multi(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<Vec<Message>, Error>> {
+        async move {
+            let calls = (0..prompt.candidates.max(1) as u64).map(|index| {
+                self.inference(LanguageModelPrompt {
+                    seed: prompt.seed.map(|seed| seed.wrapping_add(index)),
+                    candidates: 1,
+                    ..prompt.clone()
+                })
+            });
+
+            future::try_join_all(calls).await
+        }
+    }
+
+    /// Runs `inference` on a runtime managed by the crate, for callers without one of their own; see
+    /// `blocking::block_on`.
+    #[cfg(feature = "blocking")]
+    fn inference_blocking(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        crate::blocking::block_on(self.inference(prompt))?
+    }
+
+    /// Rate-limit state reported by the provider on the most recent call, if it exposes one.
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+
+    /// Published limits of the configured model, checked before a request is dispatched.
+    fn capabilities(&self) -> Option<Capabilities> {
+        None
+    }
+
+    /// Settings in `prompt` that this model drops rather than sends to the provider.
+    fn compatibility(&self, #[allow(unused)] prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        CompatibilityReport::default()
+    }
+
+    /// Sends a one-token request to verify credentials and connectivity, e.g. from a readiness probe.
+    fn health_check(&self) -> impl Future<Output = HealthStatus> {
+        async move {
+            let started = crate::time::Instant::now();
+            let result = self.inference(LanguageModelPrompt::from("ping").max_tokens(1).temperature(0.0)).await;
+
+            HealthStatus {
+                latency: started.elapsed(),
+                error: result.err().map(|err| err.to_string()),
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HealthStatus {
+    latency: Duration,
+    error: Option<String>,
+}
+
+impl HealthStatus {
+    pub fn is_healthy(&self) -> bool {
+        self.error.is_none()
+    }
+
+    pub fn latency(&self) -> Duration {
+        self.latency
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+}
+
+/// Retriable failures in `inference_many` are retried at most this many times before the error is returned.
+const INFERENCE_MANY_RETRIES: usize = 3;
+
+/// Runs `prompts` with at most `max_concurrency` requests in flight and returns the results in input order.
+///
+/// Requests are retried like `BatchInference::batch_inference` does: retriable errors after the provider's
+/// `retry_after` or a linear backoff, and new attempts held back while the rate-limit budget is exhausted.
+pub async fn inference_many<M>(model: &M, prompts: impl IntoIterator<Item = LanguageModelPrompt>, max_concurrency: usize) -> Vec<Result<Message, Error>>
+where
+    M: LanguageModel,
+{
+    let options = BatchOptions::default()
+        .concurrency(max_concurrency)
+        .max_retries(INFERENCE_MANY_RETRIES);
+
+    fan_out(model, prompts, &options).await
+}
+
+/// How long to wait before retrying a request that failed with `err` on its `attempts`-th try: the provider's
+/// `retry_after` when throttled, otherwise `backoff` times `attempts`. `None` when `err` is not retriable.
+pub(crate) fn retry_delay(err: &Error, attempts: usize, backoff: Duration) -> Option<Duration> {
+    match err {
+        Error::RateLimited { retry_after: Some(retry_after) } => Some(*retry_after),
+        err if err.is_retriable() => Some(backoff * attempts as u32),
+        _ => None,
+    }
+}
+
+/// Sends `prompt`, waiting out an exhausted rate-limit budget first and retrying retriable failures while the
+/// prompt's deadline allows.
+async fn inference_with_retries<M>(model: &M, prompt: LanguageModelPrompt, options: &BatchOptions) -> Result<Message, Error>
+where
+    M: LanguageModel + ?Sized,
+{
+    let mut attempts = 0;
+    loop {
+        if let Some(rate_limit) = model.rate_limit().filter(RateLimit::exhausted) {
+            let pause = rate_limit.retry_after().unwrap_or(options.backoff);
+            if prompt.can_wait(pause) {
+                debug! { ?pause };
+                tokio::time::sleep(pause).await;
+            }
+        }
+
+        match model.inference(prompt.clone()).await {
+            Err(err) if attempts < options.max_retries => {
+                attempts += 1;
+                match retry_delay(&err, attempts, options.backoff).filter(|delay| prompt.can_wait(*delay)) {
+                    Some(delay) => {
+                        warn! { attempts, ?delay, ?err };
+                        tokio::time::sleep(delay).await;
+                    },
+                    None => return Err(err),
+                }
+            },
+            result => return result,
+        }
+    }
+}
+
+/// Runs `prompts` through `inference_with_retries` with at most `options.concurrency` in flight, in input order.
+async fn fan_out<M>(model: &M, prompts: impl IntoIterator<Item = LanguageModelPrompt>, options: &BatchOptions) -> Vec<Result<Message, Error>>
+where
+    M: LanguageModel + ?Sized,
+{
+    stream::iter(prompts)
+        .map(|prompt| inference_with_retries(model, prompt, options))
+        .buffered(options.concurrency)
+        .collect()
+        .await
+}
+
+#[derive(Clone, Debug)]
+pub struct BatchOptions {
+    concurrency: usize,
+    max_retries: usize,
+    backoff: Duration,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: 4,
+            max_retries: 2,
+            backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl BatchOptions {
+    pub fn concurrency(self, concurrency: usize) -> Self {
+        Self {
+            concurrency: concurrency.max(1),
+            ..self
+        }
+    }
+
+    pub fn max_retries(self, max_retries: usize) -> Self {
+        Self {
+            max_retries,
+            ..self
+        }
+    }
+
+    pub fn backoff(self, backoff: Duration) -> Self {
+        Self {
+            backoff,
+            ..self
+        }
+    }
+}
+
+pub trait BatchInference: LanguageModel {
+    /// Runs every prompt and returns the results in input order.
+    ///
+    /// Providers with a native batch API override this; the default fans the prompts out over `inference`
+    /// with at most `options.concurrency` requests in flight, retrying requests that fail with a retriable error
+    /// after a linear backoff (or the provider's `retry_after` when throttled).
+    fn batch_inference(&self, prompts: Vec<LanguageModelPrompt>, options: BatchOptions) -> impl Future<Output = Vec<Result<Message, Error>>> {
+        async move {
+            fan_out(self, prompts, &options).await
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationSource {
+    Input,
+    Output,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Moderation {
+    flagged: bool,
+    categories: Vec<String>,
+}
+
+impl Moderation {
+    pub fn new(flagged: bool, categories: Vec<String>) -> Self {
+        Self { flagged, categories }
+    }
+
+    pub fn flagged(&self) -> bool {
+        self.flagged
+    }
+
+    pub fn categories(&self) -> &[String] {
+        &self.categories
+    }
+}
+
+/// Text deltas of a streamed response, in order; an `Err` item ends the stream early.
+pub type TextStream = Pin<Box<dyn Stream<Item = Result<String, Error>> + Send>>;
+
+/// A piece of a streamed response sent by `StreamingModel::inference_to_channel`.
+///
+/// Serialized with a `chunk` tag, e.g. `{"chunk": "delta", "text": "..."}`, so it can be forwarded to clients as is.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "chunk", rename_all = "snake_case")]
+pub enum MessageChunk {
+    Delta { text: String },
+    Done { message: Message },
+    Error { error: Problem },
+}
+
+impl MessageChunk {
+    /// Whether this is the last chunk of a response.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Done { .. } | Self::Error { .. })
+    }
+}
+
+/// A `TextStream` that keeps the text received so far, so a response cancelled or failed mid-stream can still be
+/// used. Dropping it, or calling `cancel`, stops reading, which cancels the request.
+pub struct ResponseStream {
+    stream: Option<TextStream>,
+    text: String,
+}
+
+impl ResponseStream {
+    pub fn new(stream: TextStream) -> Self {
+        Self {
+            stream: Some(stream),
+            text: String::new(),
+        }
+    }
+
+    /// The text of the deltas received so far.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether the stream has ended, been cancelled or failed.
+    pub fn is_finished(&self) -> bool {
+        self.stream.is_none()
+    }
+
+    /// Stops reading and cancels the request, returning the partial response.
+    pub fn cancel(&mut self) -> Message {
+        if self.stream.take().is_some() {
+            debug! { received = self.text.len(), "stream cancelled" };
+        }
+
+        Message::Text { text: self.text.clone() }
+    }
+
+    pub fn into_text(self) -> String {
+        self.text
+    }
+}
+
+impl fmt::Debug for ResponseStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseStream")
+            .field("text", &self.text)
+            .field("finished", &self.is_finished())
+            .finish()
+    }
+}
+
+impl Stream for ResponseStream {
+    type Item = Result<String, Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let Some(stream) = this.stream.as_mut() else {
+            return Poll::Ready(None);
+        };
+
+        let item = match stream.as_mut().poll_next(cx) {
+            Poll::Ready(item) => item,
+            Poll::Pending => return Poll::Pending,
+        };
+        match &item {
+            Some(Ok(delta)) => this.text.push_str(delta),
+            Some(Err(_)) | None => this.stream = None,
+        }
+
+        Poll::Ready(item)
+    }
+}
+
+pub trait StreamingModel: LanguageModel {
+    /// Starts a response and yields its text as it is generated. Errors before the first delta, such as a
+    /// rejected request, are returned directly; later failures arrive as the stream's last item.
+    fn stream(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<TextStream, Error>>;
+
+    /// `stream` as a `ResponseStream`, for callers that may stop early or need the text received before a failure.
+    fn stream_handle(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<ResponseStream, Error>> {
+        async move {
+            self.stream(prompt).await.map(ResponseStream::new)
+        }
+    }
+
+    /// Streams the response into `sender` as `Delta` chunks followed by one `Done` or `Error` chunk, and also
+    /// returns the result. Stops reading, which cancels the request, as soon as the receiver is dropped.
+    fn inference_to_channel(&self, prompt: LanguageModelPrompt, sender: mpsc::Sender<MessageChunk>) -> impl Future<Output = Result<Message, Error>> {
+        async move {
+            let result = async {
+                let mut stream = self.stream(prompt).await?;
+
+                let mut text = String::new();
+                while let Some(delta) = stream.next().await {
+                    let delta = delta?;
+                    text.push_str(&delta);
+                    if sender.send(MessageChunk::Delta { text: delta }).await.is_err() {
+                        debug! { "message chunk receiver dropped" };
+                        return Err(Error::Cancelled);
+                    }
+                }
+
+                Ok(Message::Text { text })
+            }.await;
+
+            let chunk = match &result {
+                Ok(message) => MessageChunk::Done { message: message.clone() },
+                Err(err) => MessageChunk::Error { error: err.problem() },
+            };
+            let _ = sender.send(chunk).await;
+
+            result
+        }
+    }
+}
+
+/// A generated token with its log probability and, when requested, the likeliest tokens at its position.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct TokenLogprob {
+    token: String,
+    logprob: f64,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    alternatives: Vec<TokenLogprob>,
+}
+
+impl TokenLogprob {
+    pub fn new(token: impl Into<String>, logprob: f64) -> Self {
+        Self {
+            token: token.into(),
+            logprob,
+            alternatives: Vec::new(),
+        }
+    }
+
+    pub fn alternative(self, alternative: TokenLogprob) -> Self {
+        let mut alternatives = self.alternatives;
+        alternatives.push(alternative);
+
+        Self {
+            alternatives,
+            ..self
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    pub fn logprob(&self) -> f64 {
+        self.logprob
+    }
+
+    /// The token's probability, between 0 and 1.
+    pub fn probability(&self) -> f64 {
+        self.logprob.exp()
+    }
+
+    pub fn alternatives(&self) -> &[TokenLogprob] {
+        &self.alternatives
+    }
+}
+
+/// A response together with the log probability of every token in it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LogprobResponse {
+    message: Message,
+    tokens: Vec<TokenLogprob>,
+}
+
+impl LogprobResponse {
+    pub fn new(message: Message, tokens: Vec<TokenLogprob>) -> Self {
+        Self {
+            message,
+            tokens,
+        }
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn tokens(&self) -> &[TokenLogprob] {
+        &self.tokens
+    }
+
+    /// Average log probability per token, `None` for an empty response.
+    pub fn mean_logprob(&self) -> Option<f64> {
+        match self.tokens.is_empty() {
+            true => None,
+            false => Some(self.tokens.iter().map(TokenLogprob::logprob).sum::<f64>() / self.tokens.len() as f64),
+        }
+    }
+
+    /// Perplexity of the response under the model; lower means the model was more certain.
+    pub fn perplexity(&self) -> Option<f64> {
+        self.mean_logprob().map(|mean_logprob| (-mean_logprob).exp())
+    }
+
+    /// The least likely token, where a hallucination is most likely to start.
+    pub fn least_confident(&self) -> Option<&TokenLogprob> {
+        self.tokens.iter().min_by(|a, b| a.logprob.total_cmp(&b.logprob))
+    }
+}
+
+pub trait LogprobModel: LanguageModel {
+    /// Like `inference`, with token log probabilities; the prompt's `logprobs` sets how many alternatives are
+    /// returned per token and defaults to none. Fails when the provider returns no log probabilities.
+    fn inference_with_logprobs(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<LogprobResponse, Error>>;
+}
+
+/// A response with the model's reasoning kept apart from its final answer.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ReasonedResponse {
+    reasoning: Option<String>,
+    message: Message,
+}
+
+impl ReasonedResponse {
+    pub fn new(reasoning: Option<String>, message: Message) -> Self {
+        Self {
+            reasoning,
+            message,
+        }
+    }
+
+    /// The reasoning the model produced before answering, `None` when it produced none.
+    pub fn reasoning(&self) -> Option<&str> {
+        self.reasoning.as_deref()
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+pub trait ReasoningModel: LanguageModel {
+    /// Like `inference`, which returns only the answer, with the reasoning that led to it alongside.
+    fn inference_with_reasoning(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<ReasonedResponse, Error>>;
+}
+
+/// Where in a source document the cited text sits. Ranges are end-exclusive; pages count from 1, characters
+/// and content blocks from 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CitationLocation {
+    Characters { start: usize, end: usize },
+    Pages { start: usize, end: usize },
+    Blocks { start: usize, end: usize },
+}
+
+/// A source a response drew on.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Citation {
+    source: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    cited_text: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    date: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    document_index: Option<usize>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    location: Option<CitationLocation>,
+}
+
+impl Citation {
+    pub fn new(source: impl Into<String>) -> Self {
+        Self {
+            source: source.into(),
+            title: None,
+            cited_text: None,
+            date: None,
+            document_index: None,
+            location: None,
+        }
+    }
+
+    pub fn titled(self, title: impl Into<String>) -> Self {
+        Self {
+            title: Some(title.into()),
+            ..self
+        }
+    }
+
+    pub fn quoting(self, cited_text: impl Into<String>) -> Self {
+        Self {
+            cited_text: Some(cited_text.into()),
+            ..self
+        }
+    }
+
+    pub fn dated(self, date: impl Into<String>) -> Self {
+        Self {
+            date: Some(date.into()),
+            ..self
+        }
+    }
+
+    /// Points the citation at a passage of the `document_index`th document sent with the prompt.
+    pub fn located(self, document_index: usize, location: CitationLocation) -> Self {
+        Self {
+            document_index: Some(document_index),
+            location: Some(location),
+            ..self
+        }
+    }
+
+    /// Where the cited content came from, such as a URL or document title.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// The passage the response relied on, when the provider returns one.
+    pub fn cited_text(&self) -> Option<&str> {
+        self.cited_text.as_deref()
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn document_index(&self) -> Option<usize> {
+        self.document_index
+    }
+
+    pub fn location(&self) -> Option<CitationLocation> {
+        self.location
+    }
+}
+
+/// A response together with the sources it cites.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CitedResponse {
+    message: Message,
+    citations: Vec<Citation>,
+}
+
+impl CitedResponse {
+    pub fn new(message: Message, citations: Vec<Citation>) -> Self {
+        Self {
+            message,
+            citations,
+        }
+    }
+
+    pub fn message(&self) -> &Message {
+        &self.message
+    }
+
+    pub fn citations(&self) -> &[Citation] {
+        &self.citations
+    }
+
+    pub fn into_message(self) -> Message {
+        self.message
+    }
+}
+
+pub trait CitingModel: LanguageModel {
+    /// Like `inference`, with the sources the response cites rather than only the text that refers to them.
+    fn inference_with_citations(&self, prompt: LanguageModelPrompt) -> impl Future<Output = Result<CitedResponse, Error>>;
+}
+
+pub trait EmbeddingModel {
+    fn embed(&self, texts: &[String]) -> impl Future<Output = Result<Vec<Vec<f32>>, Error>>;
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    Opus,
+    Aac,
+    Flac,
+    Wav,
+    Pcm,
+}
+
+impl AudioFormat {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "audio/mpeg",
+            Self::Opus => "audio/opus",
+            Self::Aac => "audio/aac",
+            Self::Flac => "audio/flac",
+            Self::Wav => "audio/wav",
+            Self::Pcm => "audio/pcm",
+        }
+    }
+}
+
+pub trait SpeechModel {
+    fn synthesize(&self, text: &str, voice: &str, format: AudioFormat) -> impl Future<Output = Result<Audio, Error>>;
+}
+
+pub trait TranscriptionModel {
+    fn transcribe(&self, audio: &Audio, language: Option<&str>) -> impl Future<Output = Result<String, Error>>;
+}
+
+pub trait ModerationModel {
+    fn moderate(&self, messages: &[Message], source: ModerationSource) -> impl Future<Output = Result<Moderation, Error>>;
+}
+
+pub mod anthropic;
+
+mod auth;
+pub use auth::{BearerToken, ClientCredentials, TokenProvider};
+
+#[cfg(any(feature = "aws-bedrock", feature = "aws-polly", feature = "aws-sagemaker", feature = "aws-secrets-manager"))]
+mod aws;
+
+#[cfg(any(feature = "aws-bedrock", feature = "aws-polly", feature = "aws-sagemaker", feature = "aws-secrets-manager"))]
+pub use aws::AwsConfig;
+
+#[cfg(feature = "aws-secrets-manager")]
+pub(crate) use aws::secret_value;
+
+#[cfg(feature = "aws-bedrock")]
+mod bedrock;
+
+#[cfg(feature = "aws-bedrock")]
+pub use bedrock::{BedrockGuardrail, BedrockOptions, GuardrailConfig, TitanImageModel};
+
+#[cfg(feature = "aws-polly")]
+mod polly;
+
+#[cfg(feature = "aws-polly")]
+pub use polly::PollySpeechModel;
+
+mod catalog;
+pub use catalog::{Capabilities, ModelCatalog, ModelInfo};
+
+mod compacting;
+pub use compacting::CompactingModel;
+
+pub mod deepseek;
+
+mod defaults;
+pub use defaults::PromptDefaults;
+
+mod guarded;
+pub use guarded::GuardedModel;
+
+#[cfg(feature = "observability")]
+mod observability;
+
+#[cfg(feature = "observability")]
+pub use observability::{LangfuseSink, OtlpSink};
+
+mod http_client;
+pub use http_client::HttpClientConfig;
+
+pub mod huggingface;
+
+mod layered;
+pub use layered::{BannedPhrases, LayeredModel, ModelMiddleware};
+
+pub mod perplexity;
+
+mod plugin;
+pub use plugin::{Plugin, PluginModel};
+
+pub mod cohere;
+pub mod meta;
+pub mod mistral;
+pub mod openai;
+
+#[cfg(not(target_arch = "wasm32"))]
+mod rate_limited;
+#[cfg(not(target_arch = "wasm32"))]
+pub use rate_limited::{RateLimitedModel, RateLimiter};
+
+#[cfg(feature = "record")]
+mod record;
+
+#[cfg(feature = "record")]
+pub use record::{RecordingModel, ReplayModel};
+
+#[cfg(feature = "aws-sagemaker")]
+pub mod sagemaker;
+
+pub mod stability;
+
+mod transcript;
+pub use transcript::{Transcript, TranscriptModel, TranscriptSink, WebhookSink, SESSION_ID};
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use transcript::JsonlSink;
+
+mod usage;
+pub use usage::{UsageGroup, UsageSummary, UsageTracker};
+
+#[cfg(feature = "vertex")]
+pub mod vertex;
+
+#[cfg(feature = "whisper-cpp")]
+mod whisper;
+
+#[cfg(feature = "whisper-cpp")]
+pub use whisper::WhisperCppModel;
\ No newline at end of file
@@ -1,4 +1,8 @@
-use std::fmt;
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use base64::prelude::{BASE64_STANDARD, Engine as _};
@@ -9,9 +13,40 @@ use serde::{
     Deserializer,
     Serialize,
 };
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, error, field, instrument, warn};
+
+use super::{
+    BatchInference,
+    BearerToken,
+    Capabilities,
+    Citation,
+    CitationLocation,
+    CitedResponse,
+    CitingModel,
+    CompatibilityReport,
+    Error,
+    Image,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    RateLimit,
+    ServerTool,
+    TokenProvider,
+};
+
+#[cfg(not(target_arch = "wasm32"))]
+use {
+    std::collections::VecDeque,
+    futures_util::{stream, StreamExt},
+    super::{StreamingModel, TextStream},
+};
+
+const API_URL: &str = "https://api.anthropic.com";
 
-use super::{Error, Image, LanguageModel, LanguageModelPrompt, Message};
+#[cfg(feature = "vertex")]
+const VERTEX_API_VERSION: &str = "vertex-2023-10-16";
 
 #[derive(Debug, Deserialize)]
 pub struct AnthropicErrorResponse {
@@ -29,6 +64,18 @@ impl AnthropicErrorResponse {
     pub fn message(&self) -> &str {
         &self.message
     }
+
+    fn into_error(self, retry_after: Option<Duration>) -> Error {
+        match self.error_type.as_str() {
+            "rate_limit_error" => Error::RateLimited { retry_after },
+            "overloaded_error" => Error::Overloaded(self.message),
+            "timeout_error" => Error::Timeout(self.message),
+            "authentication_error" | "permission_error" => Error::AuthenticationFailed(self.message),
+            "invalid_request_error" | "request_too_large" if self.message.to_lowercase().contains("too long") => Error::ContextLengthExceeded(self.message),
+            "invalid_request_error" | "not_found_error" | "request_too_large" => Error::InvalidRequest(self.message),
+            _ => Error::ModelResponse(self.message),
+        }
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -61,12 +108,184 @@ impl AnthropicImageContent {
     }
 }
 
+impl From<&Image> for AnthropicImageContent {
+    fn from(image: &Image) -> Self {
+        Self::new(image.media_type(), BASE64_STANDARD.encode(image.data_ref()))
+    }
+}
+
 impl From<Image> for AnthropicImageContent {
     fn from(image: Image) -> Self {
-        Self::new(&image.media_type, BASE64_STANDARD.encode(image.data))
+        Self::from(&image)
+    }
+}
+
+/// Where a cited passage sits in a document sent with the prompt, or the search result it came from.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AnthropicCitation {
+    CharLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_char_index: usize,
+        end_char_index: usize,
+    },
+
+    PageLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_page_number: usize,
+        end_page_number: usize,
+    },
+
+    ContentBlockLocation {
+        cited_text: String,
+        document_index: usize,
+        document_title: Option<String>,
+        start_block_index: usize,
+        end_block_index: usize,
+    },
+
+    WebSearchResultLocation {
+        cited_text: String,
+        url: String,
+        title: Option<String>,
+    },
+
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+impl AnthropicCitation {
+    /// Documents without a title are named by their position in the prompt, e.g. `document 0`.
+    fn to_citation(&self) -> Option<Citation> {
+        let document = |cited_text: &str, document_index: usize, document_title: &Option<String>, location: CitationLocation| {
+            let citation = match document_title {
+                Some(title) => Citation::new(title).titled(title),
+                None => Citation::new(format!("document {}", document_index)),
+            };
+            citation.quoting(cited_text).located(document_index, location)
+        };
+
+        match self {
+            Self::CharLocation { cited_text, document_index, document_title, start_char_index, end_char_index } =>
+                Some(document(cited_text, *document_index, document_title, CitationLocation::Characters { start: *start_char_index, end: *end_char_index })),
+            Self::PageLocation { cited_text, document_index, document_title, start_page_number, end_page_number } =>
+                Some(document(cited_text, *document_index, document_title, CitationLocation::Pages { start: *start_page_number, end: *end_page_number })),
+            Self::ContentBlockLocation { cited_text, document_index, document_title, start_block_index, end_block_index } =>
+                Some(document(cited_text, *document_index, document_title, CitationLocation::Blocks { start: *start_block_index, end: *end_block_index })),
+            Self::WebSearchResultLocation { cited_text, url, title } => {
+                let citation = Citation::new(url).quoting(cited_text);
+                Some(match title {
+                    Some(title) => citation.titled(title),
+                    None => citation,
+                })
+            },
+            Self::Unknown(_) => None,
+        }
+    }
+}
+
+/// A plain-text document block with citations enabled, for `CitingModel::inference_with_citations`.
+pub fn cited_document(title: impl Into<String>, text: impl Into<String>) -> Message {
+    Message::Unknown(serde_json::json!({
+        "type": "document",
+        "source": { "type": "text", "media_type": "text/plain", "data": text.into() },
+        "title": title.into(),
+        "citations": { "enabled": true },
+    }))
+}
+
+/// A server tool that failed, e.g. with `max_uses_exceeded` or `unavailable`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ServerToolError {
+    #[serde(rename = "type")]
+    error_type: String,
+
+    error_code: String,
+}
+
+impl ServerToolError {
+    pub fn error_code(&self) -> &str {
+        &self.error_code
+    }
+}
+
+/// One page found by the web search tool. `encrypted_content` must be passed back unchanged in a follow-up turn
+/// for Claude to cite the page.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename = "web_search_result")]
+pub struct WebSearchResult {
+    url: String,
+    title: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    page_age: Option<String>,
+
+    encrypted_content: String,
+}
+
+impl WebSearchResult {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn title(&self) -> &str {
+        &self.title
+    }
+
+    pub fn page_age(&self) -> Option<&str> {
+        self.page_age.as_deref()
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum WebSearchToolResultContent {
+    Results(Vec<WebSearchResult>),
+    Error(ServerToolError),
+}
+
+/// The output of a script run by the code execution tool.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename = "code_execution_result")]
+pub struct CodeExecutionResult {
+    stdout: String,
+    stderr: String,
+    return_code: i32,
+
+    #[serde(default)]
+    content: Vec<serde_json::Value>,
+}
+
+impl CodeExecutionResult {
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    pub fn return_code(&self) -> i32 {
+        self.return_code
+    }
+
+    /// Files the script wrote, as `code_execution_output` blocks.
+    pub fn content(&self) -> &[serde_json::Value] {
+        &self.content
     }
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CodeExecutionToolResultContent {
+    Result(CodeExecutionResult),
+    Error(ServerToolError),
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(tag = "type")]
 pub enum AnthropicContent {
@@ -74,13 +293,65 @@ pub enum AnthropicContent {
     Image { source: AnthropicImageContent },
 
     #[serde(rename = "text")]
-    Text { text: String },
+    Text {
+        text: String,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        citations: Vec<AnthropicCitation>,
+    },
+
+    /// A call Claude made to a server tool; the API runs it and returns the result in the next block.
+    #[serde(rename = "server_tool_use")]
+    ServerToolUse {
+        id: String,
+        name: String,
+        input: serde_json::Value,
+    },
+
+    #[serde(rename = "web_search_tool_result")]
+    WebSearchToolResult {
+        tool_use_id: String,
+        content: WebSearchToolResultContent,
+    },
+
+    #[serde(rename = "code_execution_tool_result")]
+    CodeExecutionToolResult {
+        tool_use_id: String,
+        content: CodeExecutionToolResultContent,
+    },
+
+    /// Any block type not listed above, preserved verbatim so new API content does not break deserialization.
+    #[serde(untagged)]
+    Unknown(serde_json::Value),
+}
+
+impl AnthropicContent {
+    /// The block's `type` tag, including for blocks this crate does not model.
+    pub fn block_type(&self) -> Option<&str> {
+        match self {
+            Self::Image { .. } => Some("image"),
+            Self::Text { .. } => Some("text"),
+            Self::ServerToolUse { .. } => Some("server_tool_use"),
+            Self::WebSearchToolResult { .. } => Some("web_search_tool_result"),
+            Self::CodeExecutionToolResult { .. } => Some("code_execution_tool_result"),
+            Self::Unknown(value) => value.get("type").and_then(serde_json::Value::as_str),
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct AnthropicServerToolUsage {
+    #[serde(default)]
+    web_search_requests: usize,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct AnthropicUsage {
     input_tokens: usize,
     output_tokens: usize,
+
+    #[serde(default)]
+    server_tool_use: Option<AnthropicServerToolUsage>,
 }
 
 impl AnthropicUsage {
@@ -91,6 +362,11 @@ impl AnthropicUsage {
     pub fn output_tokens(&self) -> usize {
         self.output_tokens
     }
+
+    /// Web searches run for the request, which are billed separately from tokens.
+    pub fn web_search_requests(&self) -> usize {
+        self.server_tool_use.as_ref().map(|usage| usage.web_search_requests).unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Deserialize)]
@@ -132,6 +408,70 @@ impl AnthropicMessageResponse {
     pub fn content(&self) -> &Vec<AnthropicContent> {
         &self.content
     }
+
+    /// The response as one message: its text blocks joined in order, or the first other block when there is no
+    /// text. Thinking, tool use and tool result blocks next to the text are left out; `content` has them all.
+    pub fn message(&self) -> Option<Message> {
+        let text = self.text();
+        if !text.is_empty() {
+            return Some(Message::Text { text });
+        }
+
+        self.content.iter().find_map(|content| match content {
+            AnthropicContent::Image { source } => match BASE64_STANDARD.decode(&source.data) {
+                Ok(data) => Ok(Message::Image(Image::new(&source.media_type, data))),
+                Err(err) => {
+                    warn! { ?err };
+                    Err(err)
+                }
+            }.ok(),
+            AnthropicContent::Text { .. } => None,
+            AnthropicContent::ServerToolUse { .. } | AnthropicContent::WebSearchToolResult { .. } | AnthropicContent::CodeExecutionToolResult { .. } =>
+                serde_json::to_value(content).ok().map(Message::Unknown),
+            AnthropicContent::Unknown(value) => Some(Message::Unknown(value.clone())),
+        })
+    }
+
+    /// The text blocks joined together.
+    pub fn text(&self) -> String {
+        self.content.iter()
+            .filter_map(|content| match content {
+                AnthropicContent::Text { text, .. } => Some(text.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every page returned by the web search tool, across all of its calls.
+    pub fn web_search_results(&self) -> Vec<&WebSearchResult> {
+        self.content.iter()
+            .filter_map(|content| match content {
+                AnthropicContent::WebSearchToolResult { content: WebSearchToolResultContent::Results(results), .. } => Some(results),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn code_execution_results(&self) -> Vec<&CodeExecutionResult> {
+        self.content.iter()
+            .filter_map(|content| match content {
+                AnthropicContent::CodeExecutionToolResult { content: CodeExecutionToolResultContent::Result(result), .. } => Some(result),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Server tool calls that failed, with the id of the `server_tool_use` block they answer.
+    pub fn server_tool_errors(&self) -> Vec<(&str, &ServerToolError)> {
+        self.content.iter()
+            .filter_map(|content| match content {
+                AnthropicContent::WebSearchToolResult { tool_use_id, content: WebSearchToolResultContent::Error(error) } => Some((tool_use_id.as_str(), error)),
+                AnthropicContent::CodeExecutionToolResult { tool_use_id, content: CodeExecutionToolResultContent::Error(error) } => Some((tool_use_id.as_str(), error)),
+                _ => None,
+            })
+            .collect()
+    }
 }
 
 #[derive(Deserialize)]
@@ -146,17 +486,22 @@ enum AnthropicResponse {
 
 #[derive(Debug, Serialize)]
 #[serde(untagged)]
-enum AnthropicMessageContent {
+pub enum AnthropicMessageContent {
     Single(AnthropicContent),
     Multiple(Vec<AnthropicContent>),
 }
 
 #[derive(Debug, Serialize)]
-struct AnthropicMessage {
+pub struct AnthropicMessage {
     role: String,
     content: AnthropicMessageContent,
 }
 
+#[derive(Serialize)]
+struct AnthropicMetadata {
+    user_id: String,
+}
+
 #[derive(Serialize)]
 struct AnthropicRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -175,30 +520,107 @@ struct AnthropicRequest {
     system: Option<String>,
 
     temperature: f32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_k: Option<u32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    metadata: Option<AnthropicMetadata>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Serialize)]
+struct AnthropicCountTokensRequest {
+    model: String,
+    messages: Vec<AnthropicMessage>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    system: Option<String>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicTokenCount {
+    input_tokens: usize,
+}
+
+/// The versioned definition the Messages API expects for a server tool.
+fn server_tool_definition(tool: &ServerTool) -> serde_json::Value {
+    match tool {
+        ServerTool::WebSearch { max_uses, allowed_domains, blocked_domains } => {
+            let mut definition = serde_json::json!({ "type": "web_search_20250305", "name": tool.name() });
+            if let Some(max_uses) = max_uses {
+                definition["max_uses"] = serde_json::json!(max_uses);
+            }
+            if !allowed_domains.is_empty() {
+                definition["allowed_domains"] = serde_json::json!(allowed_domains);
+            }
+            if !blocked_domains.is_empty() {
+                definition["blocked_domains"] = serde_json::json!(blocked_domains);
+            }
+            definition
+        },
+        ServerTool::CodeExecution => serde_json::json!({ "type": "code_execution_20250522", "name": tool.name() }),
+    }
 }
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(untagged)]
 pub enum AnthropicModel {
     Anthropic {
-        api_key: String,
+        api_key: BearerToken,
         api_version: String,
         model: String,
+
+        /// Replaces `https://api.anthropic.com`, e.g. to go through a gateway or a mock server.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        base_url: Option<String>,
         
         #[serde(skip)]
         client: Client,
+
+        #[serde(skip)]
+        rate_limit: Arc<Mutex<Option<RateLimit>>>,
     },
     
     #[cfg(feature = "aws-bedrock")]
     Bedrock {
         #[serde(skip_serializing_if = "Option::is_none")]
-        aws_config: Option<super::bedrock::AwsConfig>,
+        aws_config: Option<super::AwsConfig>,
 
         api_version: String,
         model: String,
+
+        #[serde(flatten)]
+        options: Box<super::BedrockOptions>,
         
+        /// Built on first use, since resolving AWS credentials is async and deserialization is not.
         #[serde(skip_serializing)]
-        client: aws_sdk_bedrockruntime::Client,
+        client: Arc<tokio::sync::OnceCell<aws_sdk_bedrockruntime::Client>>,
+    },
+
+    #[cfg(feature = "vertex")]
+    Vertex {
+        project_id: String,
+        region: String,
+        api_version: String,
+        model: String,
+
+        #[serde(skip)]
+        client: Client,
+
+        #[serde(skip)]
+        auth: super::vertex::VertexAuth,
     },
 }
 
@@ -207,11 +629,24 @@ impl<'de> Deserialize<'de> for AnthropicModel {
     where
         D: Deserializer<'de>,
     {
-        const FIELDS: &[&str] = &["api_key", "api_version", "model"];
+        /// The fields accepted with the enabled features, for `unknown_field` errors.
+        const FIELDS: &[&str] = &[
+            "api_key",
+            "api_version",
+            "model",
+            "base_url",
+            #[cfg(feature = "aws-bedrock")] "aws_config",
+            #[cfg(feature = "aws-bedrock")] "inference_profile",
+            #[cfg(feature = "aws-bedrock")] "guardrail",
+            #[cfg(feature = "aws-bedrock")] "request_tags",
+            #[cfg(feature = "aws-bedrock")] "latency_optimized",
+            #[cfg(feature = "vertex")] "project_id",
+            #[cfg(feature = "vertex")] "region",
+        ];
         
         #[derive(Deserialize)]
         #[serde(field_identifier, rename_all = "snake_case")]
-        enum Field { ApiKey, AwsConfig, ApiVersion, Model }
+        enum Field { ApiKey, AwsConfig, ProjectId, Region, ApiVersion, Model, BaseUrl, InferenceProfile, Guardrail, RequestTags, LatencyOptimized }
 
         struct AnthropicModelVisitor;
 
@@ -230,8 +665,18 @@ impl<'de> Deserialize<'de> for AnthropicModel {
                 let mut model = None;
 
                 let mut api_key = None;
+                let mut base_url: Option<String> = None;
+                let mut project_id: Option<String> = None;
+                let mut region: Option<String> = None;
+
+                #[cfg(feature = "aws-bedrock")]
+                let mut aws_config: Option<super::AwsConfig> = None;
+
+                #[cfg(not(feature = "aws-bedrock"))]
+                let mut aws_config: Option<de::IgnoredAny> = None;
 
-                let mut aws_config = None;
+                #[cfg(feature = "aws-bedrock")]
+                let mut options = super::BedrockOptions::default();
 
                 while let Some(key) = map.next_key()? {
                     match key {
@@ -253,6 +698,22 @@ impl<'de> Deserialize<'de> for AnthropicModel {
                             }
                             aws_config = Some(map.next_value()?);
                         },
+                        Field::ProjectId => {
+                            if !cfg!(feature = "vertex") {
+                                return Err(de::Error::unknown_field("project_id", FIELDS));
+                            } else if project_id.is_some() {
+                                return Err(de::Error::duplicate_field("project_id"));
+                            }
+                            project_id = Some(map.next_value()?);
+                        },
+                        Field::Region => {
+                            if !cfg!(feature = "vertex") {
+                                return Err(de::Error::unknown_field("region", FIELDS));
+                            } else if region.is_some() {
+                                return Err(de::Error::duplicate_field("region"));
+                            }
+                            region = Some(map.next_value()?);
+                        },
                         Field::ApiVersion => {
                             if api_version.is_some() {
                                 return Err(de::Error::duplicate_field("api_version"));
@@ -265,28 +726,86 @@ impl<'de> Deserialize<'de> for AnthropicModel {
                             }
                             model = Some(map.next_value()?);
                         }
+                        Field::BaseUrl => {
+                            if base_url.is_some() {
+                                return Err(de::Error::duplicate_field("base_url"));
+                            }
+                            base_url = Some(map.next_value()?);
+                        }
+                        Field::InferenceProfile => {
+                            #[cfg(feature = "aws-bedrock")]
+                            options.set_inference_profile(map.next_value()?);
+
+                            #[cfg(not(feature = "aws-bedrock"))]
+                            return Err(de::Error::unknown_field("inference_profile", FIELDS));
+                        }
+                        Field::Guardrail => {
+                            #[cfg(feature = "aws-bedrock")]
+                            options.set_guardrail(map.next_value()?);
+
+                            #[cfg(not(feature = "aws-bedrock"))]
+                            return Err(de::Error::unknown_field("guardrail", FIELDS));
+                        }
+                        Field::RequestTags => {
+                            #[cfg(feature = "aws-bedrock")]
+                            options.set_request_tags(map.next_value()?);
+
+                            #[cfg(not(feature = "aws-bedrock"))]
+                            return Err(de::Error::unknown_field("request_tags", FIELDS));
+                        }
+                        Field::LatencyOptimized => {
+                            #[cfg(feature = "aws-bedrock")]
+                            options.set_latency_optimized(map.next_value()?);
+
+                            #[cfg(not(feature = "aws-bedrock"))]
+                            return Err(de::Error::unknown_field("latency_optimized", FIELDS));
+                        }
                     }
                 }
 
+                if project_id.is_some() && (api_key.is_some() || aws_config.is_some()) {
+                    return Err(de::Error::custom("`project_id` should not be present alongside `api_key` or `aws_config`"));
+                }
+                if base_url.is_some() && api_key.is_none() {
+                    return Err(de::Error::custom("`base_url` requires `api_key`"));
+                }
+
+                #[cfg(feature = "vertex")]
+                if let Some(project_id) = project_id {
+                    return Ok(AnthropicModel::Vertex {
+                        project_id,
+                        region: region.ok_or_else(|| de::Error::missing_field("region"))?,
+                        api_version: api_version.unwrap_or_else(|| VERTEX_API_VERSION.into()),
+                        model: model.ok_or_else(|| de::Error::missing_field("model"))?,
+                        client: Client::new(),
+                        auth: Default::default(),
+                    });
+                }
+
+                #[cfg(not(feature = "vertex"))]
+                let _ = region;
+
                 if let Some(api_key) = api_key {
                     Ok(AnthropicModel::Anthropic {
                         api_key,
                         api_version: api_version.ok_or_else(|| de::Error::missing_field("api_version"))?,
                         model: model.ok_or_else(|| de::Error::missing_field("model"))?,
+                        base_url: base_url.map(|base_url| base_url.trim_end_matches('/').to_string()),
                         client: Client::new(),
+                        rate_limit: Arc::default(),
                     })
                 } else {
                     #[cfg(feature = "aws-bedrock")]
                     {
-                        let client = tokio::runtime::Runtime::new()
-                            .map_err(|err| de::Error::custom(format!("{}", err)))?
-                            .block_on(super::bedrock::bedrock_client(&aws_config));
+                        let model: String = model.ok_or_else(|| de::Error::missing_field("model"))?;
+                        options.validate(&model).map_err(de::Error::custom)?;
 
                         Ok(AnthropicModel::Bedrock {
                             aws_config,
                             api_version: api_version.ok_or_else(|| de::Error::missing_field("api_version"))?,
-                            model: model.ok_or_else(|| de::Error::missing_field("model"))?,
-                            client,
+                            model,
+                            options: Box::new(options),
+                            client: Arc::default(),
                         })
                     }
 
@@ -296,22 +815,39 @@ impl<'de> Deserialize<'de> for AnthropicModel {
             }
         }
 
-        deserializer.deserialize_enum("AnthropicModel", &["Anthropic", "Bedrock"], AnthropicModelVisitor)
+        deserializer.deserialize_map(AnthropicModelVisitor)
     }
 }
 
 impl AnthropicModel {
-    pub fn new(api_key: impl Into<String>, api_version: impl Into<String>, model: impl Into<String>) -> Self {
+    pub fn new(api_key: impl Into<BearerToken>, api_version: impl Into<String>, model: impl Into<String>) -> Self {
         Self::Anthropic {
             api_key: api_key.into(),
             api_version: api_version.into(),
             model: model.into(),
+            base_url: None,
             client: Client::new(),
+            rate_limit: Arc::default(),
+        }
+    }
+
+    /// Sends first-party API requests to `base_url` instead of `https://api.anthropic.com`, e.g. a corporate
+    /// gateway, a LiteLLM proxy or a mock server speaking the Messages API. Bedrock and Vertex models ignore it.
+    pub fn base_url(mut self, url: impl Into<String>) -> Self {
+        match &mut self {
+            Self::Anthropic { base_url, .. } => *base_url = Some(url.into().trim_end_matches('/').to_string()),
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { .. } => {},
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { .. } => {},
         }
+        self
     }
 
     #[cfg(feature = "aws-bedrock")]
-    pub async fn bedrock(api_version: impl Into<String>, model: impl Into<String>, aws_config: Option<super::bedrock::AwsConfig>) -> Self {
+    pub async fn bedrock(api_version: impl Into<String>, model: impl Into<String>, aws_config: Option<super::AwsConfig>) -> Self {
         let client = super::bedrock::bedrock_client(&aws_config).await;
 
         Self::Bedrock {
@@ -319,87 +855,172 @@ impl AnthropicModel {
 
             api_version: api_version.into(),
             model: model.into(),
-            client,
+            options: Box::default(),
+            client: Arc::new(tokio::sync::OnceCell::new_with(Some(client))),
         }
     }
 
-    #[instrument(name = "AnthropicModel::create", level = "trace", skip(self))]
-    pub async fn create(&self, messages: Vec<AnthropicContent>, max_tokens: usize, stop_sequences: Vec<String>, system: Option<String>, temperature: f32, conversation: Option<Vec<AnthropicMessage>>) -> Result<AnthropicMessageResponse, AnthropicErrorResponse> {
-        let mut request_messages: Vec<AnthropicMessage> = vec![];
-        if let Some(mut conversation) = conversation {
-            request_messages.append(&mut conversation);
-        }
-        match messages.len() {
-            0 => {},
-            1 => request_messages.push(AnthropicMessage { role: "user".into(), content: AnthropicMessageContent::Single(messages[0].clone()) }),
-            _ => request_messages.push(AnthropicMessage { role: "user".into(), content: AnthropicMessageContent::Multiple(messages.clone()) }),
+    /// Claude on Vertex AI, authenticated through Application Default Credentials.
+    #[cfg(feature = "vertex")]
+    pub fn vertex(project_id: impl Into<String>, region: impl Into<String>, model: impl Into<String>) -> Self {
+        Self::Vertex {
+            project_id: project_id.into(),
+            region: region.into(),
+            api_version: VERTEX_API_VERSION.into(),
+            model: model.into(),
+            client: Client::new(),
+            auth: Default::default(),
+        }
+    }
+
+    /// Sends requests through `http_client`, e.g. one built from an `HttpClientConfig`. Bedrock models go through
+    /// the AWS SDK and keep its client.
+    pub fn http_client(mut self, http_client: Client) -> Self {
+        match &mut self {
+            Self::Anthropic { client, .. } => *client = http_client,
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { .. } => {},
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { client, .. } => *client = http_client,
+        }
+        self
+    }
+
+    /// Builds any lazily created SDK client now, so credential problems surface before the first request.
+    pub async fn initialize(&self) {
+        match self {
+            Self::Anthropic { .. } => {},
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { aws_config, client, .. } => {
+                client.get_or_init(|| super::bedrock::bedrock_client(aws_config)).await;
+            },
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { .. } => {},
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        match self {
+            Self::Anthropic { model, .. } => model,
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { model, .. } => model,
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { model, .. } => model,
+        }
+    }
+
+    /// The serving platform, named as in the OpenTelemetry `gen_ai.system` attribute.
+    pub fn system(&self) -> &'static str {
+        match self {
+            Self::Anthropic { .. } => "anthropic",
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { .. } => "aws.bedrock",
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { .. } => "gcp.vertex_ai",
+        }
+    }
+
+    /// Replaces the Bedrock invocation options; has no effect on the direct API variant.
+    #[cfg(feature = "aws-bedrock")]
+    pub fn bedrock_options(self, bedrock_options: super::BedrockOptions) -> Self {
+        match self {
+            Self::Bedrock { aws_config, api_version, model, client, .. } => Self::Bedrock { aws_config, api_version, model, options: Box::new(bedrock_options), client },
+            model => model,
+        }
+    }
+
+    /// The Messages API body for this deployment. Only the first-party API takes the model name, `metadata` and
+    /// server tools; Bedrock and Vertex take the API version in the body instead.
+    fn request(&self, prompt: &LanguageModelPrompt, messages: Vec<AnthropicMessage>, stream: bool) -> AnthropicRequest {
+        let request = AnthropicRequest {
+            anthropic_version: None,
+            model: None,
+            max_tokens: prompt.max_tokens,
+            stop_sequences: prompt.stop_sequences.clone(),
+            system: prompt.system.clone(),
+            temperature: prompt.temperature,
+            top_p: prompt.top_p,
+            top_k: prompt.top_k,
+            metadata: None,
+            tools: Vec::new(),
+            stream,
+
+            messages,
         };
 
         match self {
-            Self::Anthropic { api_key, api_version, model, client } => {
-                let request = AnthropicRequest {
-                    anthropic_version: None,
-                    model: Some(model.clone()),
-                    max_tokens,
-                    stop_sequences,
-                    system,
-                    temperature,
-    
-                    messages: request_messages,
-                };
+            Self::Anthropic { model, .. } => AnthropicRequest {
+                model: Some(model.clone()),
+                metadata: prompt.user.clone().map(|user_id| AnthropicMetadata { user_id }),
+                tools: prompt.server_tools.iter().map(server_tool_definition).collect(),
+                ..request
+            },
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { api_version, .. } => AnthropicRequest { anthropic_version: Some(api_version.clone()), ..request },
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { api_version, .. } => AnthropicRequest { anthropic_version: Some(api_version.clone()), ..request },
+        }
+    }
+
+    /// Sends `messages` after the prompt's few-shot examples and any `conversation` turns, taking the generation
+    /// settings from `prompt` (its own messages are not sent).
+    #[instrument(name = "AnthropicModel::create", level = "trace", skip(self))]
+    pub async fn create(&self, messages: Vec<AnthropicContent>, prompt: &LanguageModelPrompt, conversation: Option<Vec<AnthropicMessage>>) -> Result<AnthropicMessageResponse, AnthropicErrorResponse> {
+        let request_messages = request_messages(prompt, messages, conversation);
+
+        match self {
+            Self::Anthropic { api_key, api_version, base_url, client, rate_limit, .. } => {
+                let request = self.request(prompt, request_messages, false);
 
-                let response = client
-                    .post("https://api.anthropic.com/v1/messages")
-                    .header("x-api-key", api_key)
+                let token = api_key.token().await
+                    .map_err(|err| AnthropicErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err) })?;
+                let request_builder = super::prompt_request(client.post(format!("{}/v1/messages", base_url.as_deref().unwrap_or(API_URL))), prompt)
+                    .header("x-api-key", token.expose())
                     .header("anthropic-version", api_version)
                     .header("Accept", "application/json")
-                    .header("Content-Type", "application/json")
+                    .header("Content-Type", "application/json");
+                let request_builder = match prompt.server_tools.contains(&ServerTool::CodeExecution) {
+                    true => request_builder.header("anthropic-beta", "code-execution-2025-05-22"),
+                    false => request_builder,
+                };
+                let response = request_builder
                     .json(&request)
                     .send()
                     .await;
 
-                match response {
-                    Ok(response) => match response.status() {
-                        StatusCode::OK => match response.json::<AnthropicResponse>().await {
-                            Ok(response) => match response {
-                                AnthropicResponse::Error { error } => Err(error),
-                                AnthropicResponse::Message(message) => Ok(message)
-                            },
-                            Err(err) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
-                        },
-                        status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<AnthropicResponse>().await {
-                            Ok(response) => match response {
-                                AnthropicResponse::Error { error } => Err(error),
-                                AnthropicResponse::Message(message) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{:?}", message) })
-                            },
-                            Err(err) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
-                        },
-                        status_code => Err(AnthropicErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
-                    },
-                    Err(err) => Err(AnthropicErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+                if let Ok(response) = &response {
+                    if let Ok(mut rate_limit) = rate_limit.lock() {
+                        *rate_limit = Some(RateLimit::from_headers(response.headers(), "anthropic-ratelimit-"));
+                    }
                 }
+
+                http_response(response).await
             },
 
             #[cfg(feature = "aws-bedrock")]
-            Self::Bedrock { aws_config: _, api_version, model, client } => {
-                let request = AnthropicRequest {
-                    anthropic_version: Some(api_version.clone()),
-                    model: None,
-                    max_tokens,
-                    stop_sequences,
-                    system,
-                    temperature,
-        
-                    messages: request_messages,
-                };
+            Self::Bedrock { aws_config, model, options, client, .. } => {
+                let client = client.get_or_init(|| super::bedrock::bedrock_client(aws_config)).await;
+
+                let request = self.request(prompt, request_messages, false);
 
-                let response = client.invoke_model()
+                let invocation = options.apply(client.invoke_model(), model)
+                    .map_err(|err| AnthropicErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })?
                     .accept("application/json")
                     .content_type("application/json")
-                    .model_id(model)
                     .body(aws_sdk_bedrockruntime::primitives::Blob::new(serde_json::to_vec(&request).map_err(|err| AnthropicErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })?))
-                    .send()
-                    .await;
+                    .send();
+                let response = super::within_deadline(prompt, invocation).await
+                    .ok_or_else(|| AnthropicErrorResponse { error_type: "timeout_error".into(), message: "deadline exceeded".into() })?;
 
                 match response {
                     Ok(response) => match serde_json::from_slice::<AnthropicResponse>(&response.body().clone().into_inner()) {
@@ -409,46 +1030,873 @@ impl AnthropicModel {
                         },
                         Err(err) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
                     },
-                    Err(err) => Err(AnthropicErrorResponse { error_type: "bedrock_sdk_error".into(), message: format!("{}", err) })
+                    Err(err) => {
+                        let error_type = match err.as_service_error() {
+                            Some(err) if err.is_throttling_exception() => "rate_limit_error",
+                            Some(err) if err.is_service_unavailable_exception() || err.is_model_not_ready_exception() => "overloaded_error",
+                            Some(err) if err.is_access_denied_exception() => "permission_error",
+                            Some(err) if err.is_validation_exception() => "invalid_request_error",
+                            _ => "bedrock_sdk_error",
+                        };
+                        Err(AnthropicErrorResponse { error_type: error_type.into(), message: format!("{}", err) })
+                    }
                 }
             },
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { project_id, region, model, client, auth, .. } => {
+                let request = self.request(prompt, request_messages, false);
+
+                let token = auth.token().await
+                    .map_err(|err| AnthropicErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err) })?;
+
+                let response = super::prompt_request(client.post(super::vertex::endpoint(project_id, region, "anthropic", model, "rawPredict")), prompt)
+                    .bearer_auth(token)
+                    .header("Accept", "application/json")
+                    .header("Content-Type", "application/json")
+                    .json(&request)
+                    .send()
+                    .await;
+
+                http_response(response).await
+            },
+        }
+    }
+
+    /// Sends `messages`, then while the response stops at `max_tokens` and `prompt.auto_continue` allows, sends
+    /// them again with the text so far as a prefilled assistant turn and appends the continuation. Usage is summed
+    /// over all requests.
+    async fn create_continued(&self, messages: Vec<AnthropicContent>, prompt: &LanguageModelPrompt) -> Result<AnthropicMessageResponse, AnthropicErrorResponse> {
+        let mut response = self.create(messages.clone(), prompt, None).await?;
+
+        for continuation in 0..prompt.continuations.unwrap_or_default() {
+            if response.stop_reason != "max_tokens" {
+                break;
+            }
+
+            // The API rejects a prefilled assistant turn that ends in whitespace.
+            let partial = response.text();
+            let partial = partial.trim_end();
+            if partial.is_empty() {
+                break;
+            }
+
+            let conversation = vec![
+                AnthropicMessage { role: "user".into(), content: AnthropicMessageContent::Multiple(messages.clone()) },
+                AnthropicMessage { role: "assistant".into(), content: AnthropicMessageContent::Single(AnthropicContent::Text { text: partial.to_string(), citations: Vec::new() }) },
+            ];
+            let next = self.create(Vec::new(), prompt, Some(conversation)).await?;
+            debug! { continuation, stop_reason = next.stop_reason };
+
+            let continued = next.text();
+            if let Some(text) = response.content.iter_mut().rev().find_map(|content| match content {
+                AnthropicContent::Text { text, .. } => Some(text),
+                _ => None,
+            }) {
+                text.truncate(text.trim_end().len());
+                text.push_str(&continued);
+            }
+
+            response.usage.input_tokens += next.usage.input_tokens;
+            response.usage.output_tokens += next.usage.output_tokens;
+            response.stop_reason = next.stop_reason;
+            response.stop_sequence = next.stop_sequence;
+        }
+
+        Ok(response)
+    }
+
+    /// Exact input tokens for `prompt` (system prompt, examples and messages) from the provider's token counting
+    /// API, which is free and does not create a message.
+    #[instrument(name = "AnthropicModel::count_tokens", level = "trace", skip(self, prompt))]
+    pub async fn count_tokens(&self, prompt: &LanguageModelPrompt) -> Result<usize, Error> {
+        let messages = request_messages(prompt, prompt_content(prompt)?, None);
+
+        let count = match self {
+            Self::Anthropic { api_key, api_version, model, base_url, client, .. } => {
+                let request = AnthropicCountTokensRequest {
+                    model: model.clone(),
+                    system: prompt.system.clone(),
+                    tools: prompt.server_tools.iter().map(server_tool_definition).collect(),
+                    messages,
+                };
+
+                let token = api_key.token().await?;
+                let request_builder = super::prompt_request(client.post(format!("{}/v1/messages/count_tokens", base_url.as_deref().unwrap_or(API_URL))), prompt)
+                    .header("x-api-key", token.expose())
+                    .header("anthropic-version", api_version)
+                    .header("Accept", "application/json");
+                let request_builder = match prompt.server_tools.contains(&ServerTool::CodeExecution) {
+                    true => request_builder.header("anthropic-beta", "code-execution-2025-05-22"),
+                    false => request_builder,
+                };
+                let response = request_builder
+                    .json(&request)
+                    .send()
+                    .await;
+
+                count_response(response).await
+            },
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { aws_config, model, options, client, .. } => {
+                let client = client.get_or_init(|| super::bedrock::bedrock_client(aws_config)).await;
+
+                // Bedrock counts the body that `InvokeModel` would be sent.
+                let request = self.request(prompt, messages, false);
+                let body = serde_json::to_vec(&request).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+                let input = aws_sdk_bedrockruntime::types::InvokeModelTokensRequest::builder()
+                    .body(aws_sdk_bedrockruntime::primitives::Blob::new(body))
+                    .build()
+                    .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+                let count = client.count_tokens()
+                    .model_id(options.model_id(model))
+                    .input(aws_sdk_bedrockruntime::types::CountTokensInput::InvokeModel(input))
+                    .send();
+                match super::within_deadline(prompt, count).await {
+                    Some(Ok(output)) => Ok(output.input_tokens().max(0) as usize),
+                    Some(Err(err)) => {
+                        let error_type = match err.as_service_error() {
+                            Some(err) if err.is_throttling_exception() => "rate_limit_error",
+                            Some(err) if err.is_service_unavailable_exception() => "overloaded_error",
+                            Some(err) if err.is_access_denied_exception() => "permission_error",
+                            Some(err) if err.is_validation_exception() => "invalid_request_error",
+                            _ => "bedrock_sdk_error",
+                        };
+                        Err(AnthropicErrorResponse { error_type: error_type.into(), message: format!("{}", err) })
+                    },
+                    None => Err(AnthropicErrorResponse { error_type: "timeout_error".into(), message: "deadline exceeded".into() }),
+                }
+            },
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { project_id, region, model, client, auth, .. } => {
+                let request = AnthropicCountTokensRequest {
+                    model: model.clone(),
+                    system: prompt.system.clone(),
+                    tools: Vec::new(),
+                    messages,
+                };
+
+                let token = auth.token().await
+                    .map_err(|err| AnthropicErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err) });
+
+                match token {
+                    Ok(token) => {
+                        let response = super::prompt_request(client.post(super::vertex::endpoint(project_id, region, "anthropic", "count-tokens", "rawPredict")), prompt)
+                            .bearer_auth(token)
+                            .header("Accept", "application/json")
+                            .json(&request)
+                            .send()
+                            .await;
+
+                        count_response(response).await
+                    },
+                    Err(err) => Err(err),
+                }
+            },
+        };
+
+        let count = count.map_err(|err| super::timeout_error(prompt.deadline, err.into_error(None)))?;
+        debug! { input_tokens = count };
+
+        Ok(count)
+    }
+
+    /// Rejects `prompt` when its exact token count plus `max_tokens` exceeds the model's context window, like
+    /// `Capabilities::check` does with estimates. Returns the input token count; models without published limits
+    /// are only counted.
+    pub async fn check_context_window(&self, prompt: &LanguageModelPrompt) -> Result<usize, Error> {
+        let input_tokens = self.count_tokens(prompt).await?;
+
+        if let Some(capabilities) = self.capabilities() {
+            if input_tokens + prompt.max_tokens > capabilities.context_window() {
+                return Err(Error::UnsupportedContent { kind: format!("context-window: {} tokens exceeds {}", input_tokens + prompt.max_tokens, capabilities.context_window()) });
+            }
         }
+
+        Ok(input_tokens)
+    }
+
+    /// The full Messages API response for `prompt`, continued past `max_tokens` when the prompt allows it. Unlike
+    /// `inference`, the response keeps every content block, including server tool calls and their results.
+    pub async fn respond(&self, prompt: &LanguageModelPrompt) -> Result<AnthropicMessageResponse, Error> {
+        let prompt = &*super::checked(self, prompt)?;
+        let messages = prompt_content(prompt)?;
+
+        #[cfg(feature = "opentelemetry")]
+        let started = crate::time::Instant::now();
+
+        let response = self.create_continued(messages, prompt).await;
+        let usage = response.as_ref().ok().map(|message| (message.usage.input_tokens, message.usage.output_tokens));
+
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::record_inference(self.system(), self.model(), started.elapsed(), usage, response.as_ref().err().map(|err| err.error_type.as_str()));
+
+        let retry_after = self.rate_limit().and_then(|rate_limit| rate_limit.retry_after());
+        let response = response.map_err(|err| super::timeout_error(prompt.deadline, err.into_error(retry_after)));
+        super::record_response(&response, response.as_ref().ok().map(|message| message.id.as_str()), usage);
+        response
     }
 }
 
-impl LanguageModel for AnthropicModel {
-    #[instrument(name = "AnthropicModel::inference", level = "trace", skip(self))]
-    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
-        let LanguageModelPrompt { max_tokens, messages, temperature, stop_sequences, system } = prompt;
-        
-        let messages = messages.into_iter().map(|message| match message {
-            Message::Image(image) => AnthropicContent::Image { source: image.into() },
-            Message::Text { text } => AnthropicContent::Text { text },
-        }).collect::<Vec<AnthropicContent>>();
-
-        match self.create(messages, max_tokens, stop_sequences, system, temperature, None).await.map(|message| {
-            debug! { response = ?message };
-            info! { usage = ?message.usage };
-
-            message.content.first().and_then(|content| match content {
-                AnthropicContent::Image { source } => match BASE64_STANDARD.decode(&source.data) {
-                    Ok(data) => Ok(Message::Image(Image::new(&source.media_type, data))),
-                    Err(err) => {
-                        warn! { ?err };
-                        Err(err)
+/// The prompt's messages as content blocks of the user turn.
+fn prompt_content(prompt: &LanguageModelPrompt) -> Result<Vec<AnthropicContent>, Error> {
+    prompt.messages.iter().map(|message| match message {
+        Message::Audio(_) => Err(Error::UnsupportedContent { kind: "audio".to_string() }),
+        Message::Image(image) => Ok(AnthropicContent::Image { source: image.into() }),
+        Message::Text { text } => Ok(AnthropicContent::Text { text: text.clone(), citations: Vec::new() }),
+        Message::Unknown(value) => Ok(AnthropicContent::Unknown(value.clone())),
+    }).collect()
+}
+
+/// The prompt's few-shot examples as alternating turns, then any `conversation` turns, then `messages` as the user turn.
+fn request_messages(prompt: &LanguageModelPrompt, messages: Vec<AnthropicContent>, conversation: Option<Vec<AnthropicMessage>>) -> Vec<AnthropicMessage> {
+    let mut request_messages = prompt.examples.iter()
+        .flat_map(|(user, assistant)| [("user", user), ("assistant", assistant)])
+        .map(|(role, text)| AnthropicMessage { role: role.into(), content: AnthropicMessageContent::Single(AnthropicContent::Text { text: text.clone(), citations: Vec::new() }) })
+        .collect::<Vec<AnthropicMessage>>();
+    if let Some(mut conversation) = conversation {
+        request_messages.append(&mut conversation);
+    }
+    match messages.len() {
+        0 => {},
+        1 => request_messages.push(AnthropicMessage { role: "user".into(), content: AnthropicMessageContent::Single(messages[0].clone()) }),
+        _ => request_messages.push(AnthropicMessage { role: "user".into(), content: AnthropicMessageContent::Multiple(messages) }),
+    };
+
+    request_messages
+}
+
+async fn count_response(response: Result<reqwest::Response, reqwest::Error>) -> Result<usize, AnthropicErrorResponse> {
+    match response {
+        Ok(response) if response.status().is_success() => response.json::<AnthropicTokenCount>().await
+            .map(|count| count.input_tokens)
+            .map_err(|err| AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+        Ok(response) => {
+            let status = response.status();
+            match response.json::<AnthropicResponse>().await {
+                Ok(AnthropicResponse::Error { error }) => Err(error),
+                _ => Err(AnthropicErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status) }),
+            }
+        },
+        Err(err) if err.is_timeout() => Err(AnthropicErrorResponse { error_type: "timeout_error".into(), message: format!("{}", err) }),
+        Err(err) => Err(AnthropicErrorResponse { error_type: "request_error".into(), message: format!("{}", err) }),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicStreamEvent {
+    ContentBlockDelta { delta: AnthropicStreamDelta },
+    MessageDelta { delta: AnthropicMessageDelta },
+    MessageStop,
+    Error { error: AnthropicErrorResponse },
+
+    #[serde(other)]
+    Other,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum AnthropicStreamDelta {
+    TextDelta { text: String },
+
+    #[serde(other)]
+    Other,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+struct AnthropicMessageDelta {
+    stop_reason: Option<String>,
+}
+
+/// Turns Messages API stream events into text deltas. `error` events, a stop reason that means the response is
+/// unusable, and a stream that closes before `message_stop` become typed errors, after which nothing more is read.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct StreamDecoder {
+    buffer: Vec<u8>,
+    pending: VecDeque<Result<String, Error>>,
+    finished: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamDecoder {
+    /// Decodes the server-sent events completed by `bytes`.
+    fn push_sse(&mut self, bytes: &[u8]) {
+        self.buffer.extend(bytes.iter().filter(|&&byte| byte != b'\r'));
+
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let event = self.buffer.drain(..end + 2).collect::<Vec<u8>>();
+            let data = String::from_utf8_lossy(&event).lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect::<Vec<&str>>()
+                .join("\n");
+            if !data.is_empty() {
+                self.push_event(data.as_bytes());
+            }
+        }
+    }
+
+    /// Decodes one JSON event.
+    fn push_event(&mut self, data: &[u8]) {
+        if self.finished {
+            return;
+        }
+
+        let item = match serde_json::from_slice::<AnthropicStreamEvent>(data) {
+            Ok(AnthropicStreamEvent::ContentBlockDelta { delta: AnthropicStreamDelta::TextDelta { text } }) => Ok(text),
+            Ok(AnthropicStreamEvent::MessageDelta { delta }) => match delta.stop_reason.as_deref() {
+                Some("refusal") => Err(Error::ContentBlocked { categories: vec!["refusal".to_string()] }),
+                Some("model_context_window_exceeded") => Err(Error::ContextLengthExceeded("the response reached the context window".to_string())),
+                _ => return,
+            },
+            Ok(AnthropicStreamEvent::MessageStop) => {
+                self.finished = true;
+                return;
+            },
+            Ok(AnthropicStreamEvent::Error { error }) => Err(error.into_error(None)),
+            Ok(_) => return,
+            Err(err) => Err(Error::ModelResponse(format!("invalid stream event: {}", err))),
+        };
+        self.push(item);
+    }
+
+    fn push(&mut self, item: Result<String, Error>) {
+        if item.is_err() {
+            self.finished = true;
+        }
+        self.pending.push_back(item);
+    }
+
+    /// The connection closed, which is an error unless `message_stop` arrived first.
+    fn close(&mut self) {
+        if !self.finished {
+            self.push(Err(Error::ModelResponse("the stream ended before the response was complete".to_string())));
+        }
+    }
+}
+
+/// The text deltas of an event-stream response, or the error the request was rejected with.
+#[cfg(not(target_arch = "wasm32"))]
+async fn sse_stream(response: Result<reqwest::Response, reqwest::Error>) -> Result<TextStream, AnthropicErrorResponse> {
+    let response = match response {
+        Ok(response) if response.status().is_success() => response,
+        response => return Err(http_response(response).await.err().unwrap_or_else(|| AnthropicErrorResponse {
+            error_type: "invalid_response_error".into(),
+            message: "expected an event stream".into(),
+        })),
+    };
+
+    let stream = stream::unfold((response, StreamDecoder::default()), |(mut response, mut decoder)| async move {
+        loop {
+            if let Some(item) = decoder.pending.pop_front() {
+                return Some((item, (response, decoder)));
+            }
+            if decoder.finished {
+                return None;
+            }
+
+            match response.chunk().await {
+                Ok(Some(bytes)) => decoder.push_sse(&bytes),
+                Ok(None) => decoder.close(),
+                Err(err) if err.is_timeout() => decoder.push(Err(Error::Timeout(format!("{}", err)))),
+                Err(err) => decoder.push(Err(Error::ModelResponse(format!("{}", err)))),
+            }
+        }
+    });
+
+    Ok(Box::pin(stream))
+}
+
+/// The text deltas of a Bedrock response stream, whose chunks are the Messages API's JSON events.
+#[cfg(all(feature = "aws-bedrock", not(target_arch = "wasm32")))]
+fn bedrock_stream(output: aws_sdk_bedrockruntime::operation::invoke_model_with_response_stream::InvokeModelWithResponseStreamOutput) -> TextStream {
+    let stream = stream::unfold((output.body, StreamDecoder::default()), |(mut receiver, mut decoder)| async move {
+        loop {
+            if let Some(item) = decoder.pending.pop_front() {
+                return Some((item, (receiver, decoder)));
+            }
+            if decoder.finished {
+                return None;
+            }
+
+            match receiver.recv().await {
+                Ok(Some(aws_sdk_bedrockruntime::types::ResponseStream::Chunk(part))) => {
+                    if let Some(bytes) = part.bytes() {
+                        decoder.push_event(bytes.as_ref());
                     }
-                }.ok(),
-                AnthropicContent::Text { text } => Some(Message::Text { text: text.clone() })
-            })
-        }) {
-            Ok(message) => match message {
-                Some(message) => Ok(message),
-                None => Err(Error::Unexpected(anyhow!("no-content")))
+                },
+                Ok(Some(_)) => {},
+                Ok(None) => decoder.close(),
+                Err(err) => {
+                    let error_type = match err.as_service_error() {
+                        Some(err) if err.is_throttling_exception() => "rate_limit_error",
+                        Some(err) if err.is_service_unavailable_exception() => "overloaded_error",
+                        Some(err) if err.is_validation_exception() => "invalid_request_error",
+                        _ => "bedrock_sdk_error",
+                    };
+                    decoder.push(Err(AnthropicErrorResponse { error_type: error_type.into(), message: format!("{}", err) }.into_error(None)));
+                },
+            }
+        }
+    });
+
+    Box::pin(stream)
+}
+
+async fn http_response(response: Result<reqwest::Response, reqwest::Error>) -> Result<AnthropicMessageResponse, AnthropicErrorResponse> {
+    match response {
+        Ok(response) => match response.status() {
+            StatusCode::OK => match response.json::<AnthropicResponse>().await {
+                Ok(response) => match response {
+                    AnthropicResponse::Error { error } => Err(error),
+                    AnthropicResponse::Message(message) => Ok(message)
+                },
+                Err(err) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+            },
+            status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<AnthropicResponse>().await {
+                Ok(response) => match response {
+                    AnthropicResponse::Error { error } => Err(error),
+                    AnthropicResponse::Message(message) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{:?}", message) })
+                },
+                Err(err) => Err(AnthropicErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+            },
+            status_code => Err(AnthropicErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+        },
+        Err(err) if err.is_timeout() => Err(AnthropicErrorResponse { error_type: "timeout_error".into(), message: format!("{}", err) }),
+        Err(err) => Err(AnthropicErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+    }
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelEntry {
+    id: String,
+    display_name: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AnthropicModelList {
+    data: Vec<AnthropicModelEntry>,
+    has_more: bool,
+    last_id: Option<String>,
+}
+
+impl ModelCatalog for AnthropicModel {
+    /// Pages through `/v1/models` for the direct API. Bedrock and Vertex only expose model listing through
+    /// their control-plane APIs, so those variants report just the configured model.
+    #[instrument(name = "AnthropicModel::list_models", level = "trace", skip(self))]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        let (api_key, api_version, base_url, client) = match self {
+            Self::Anthropic { api_key, api_version, base_url, client, .. } => (api_key, api_version, base_url.as_deref().unwrap_or(API_URL), client),
+
+            #[cfg(any(feature = "aws-bedrock", feature = "vertex"))]
+            _ => return Ok(vec![ModelInfo::new(self.model(), None)]),
+        };
+
+        let mut models = Vec::new();
+        let mut after_id: Option<String> = None;
+        loop {
+            let token = api_key.token().await?;
+            let mut request = client
+                .get(format!("{}/v1/models", base_url))
+                .header("x-api-key", token.expose())
+                .header("anthropic-version", api_version)
+                .query(&[("limit", "1000")]);
+            if let Some(after_id) = &after_id {
+                request = request.query(&[("after_id", after_id)]);
+            }
+
+            let response = request.send().await.map_err(|err| Error::ModelResponse(format!("{}", err)))?;
+            if !response.status().is_success() {
+                let status = response.status();
+                error! { %status };
+                return Err(Error::ModelResponse(format!("{}", status)));
+            }
+
+            let page = response.json::<AnthropicModelList>().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+            models.extend(page.data.into_iter().map(|entry| ModelInfo::new(entry.id, entry.display_name)));
+
+            match (page.has_more, page.last_id) {
+                (true, Some(last_id)) => after_id = Some(last_id),
+                _ => return Ok(models),
+            }
+        }
+    }
+}
+
+impl BatchInference for AnthropicModel {}
+
+/// Streams through the Messages API's server-sent events, and `InvokeModelWithResponseStream` on Bedrock.
+#[cfg(not(target_arch = "wasm32"))]
+impl StreamingModel for AnthropicModel {
+    #[instrument(name = "AnthropicModel::stream", level = "trace", skip(self, prompt))]
+    async fn stream(&self, prompt: LanguageModelPrompt) -> Result<TextStream, Error> {
+        let prompt = &*super::checked(self, &prompt)?;
+        let request = self.request(prompt, request_messages(prompt, prompt_content(prompt)?, None), true);
+
+        let stream = match self {
+            Self::Anthropic { api_key, api_version, base_url, client, rate_limit, .. } => {
+                let token = api_key.token().await?;
+                let request_builder = super::prompt_request(client.post(format!("{}/v1/messages", base_url.as_deref().unwrap_or(API_URL))), prompt)
+                    .header("x-api-key", token.expose())
+                    .header("anthropic-version", api_version)
+                    .header("Accept", "text/event-stream");
+                let request_builder = match prompt.server_tools.contains(&ServerTool::CodeExecution) {
+                    true => request_builder.header("anthropic-beta", "code-execution-2025-05-22"),
+                    false => request_builder,
+                };
+                let response = request_builder
+                    .json(&request)
+                    .send()
+                    .await;
+
+                if let Ok(response) = &response {
+                    if let Ok(mut rate_limit) = rate_limit.lock() {
+                        *rate_limit = Some(RateLimit::from_headers(response.headers(), "anthropic-ratelimit-"));
+                    }
+                }
+
+                sse_stream(response).await
+            },
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { aws_config, model, options, client, .. } => {
+                let client = client.get_or_init(|| super::bedrock::bedrock_client(aws_config)).await;
+
+                let body = serde_json::to_vec(&request).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+                let invocation = options.apply_stream(client.invoke_model_with_response_stream(), model)?
+                    .accept("application/json")
+                    .content_type("application/json")
+                    .body(aws_sdk_bedrockruntime::primitives::Blob::new(body))
+                    .send();
+
+                match super::within_deadline(prompt, invocation).await {
+                    Some(Ok(output)) => Ok(bedrock_stream(output)),
+                    Some(Err(err)) => {
+                        let error_type = match err.as_service_error() {
+                            Some(err) if err.is_throttling_exception() => "rate_limit_error",
+                            Some(err) if err.is_service_unavailable_exception() || err.is_model_not_ready_exception() => "overloaded_error",
+                            Some(err) if err.is_access_denied_exception() => "permission_error",
+                            Some(err) if err.is_validation_exception() => "invalid_request_error",
+                            _ => "bedrock_sdk_error",
+                        };
+                        Err(AnthropicErrorResponse { error_type: error_type.into(), message: format!("{}", err) })
+                    },
+                    None => Err(AnthropicErrorResponse { error_type: "timeout_error".into(), message: "deadline exceeded".into() }),
+                }
             },
-            Err(err) => {
-                error! { ?err };
-                Err(Error::ModelResponse(err.message))
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { project_id, region, model, client, auth, .. } => {
+                let token = auth.token().await
+                    .map_err(|err| Error::AuthenticationFailed(format!("{}", err)))?;
+
+                let response = super::prompt_request(client.post(super::vertex::endpoint(project_id, region, "anthropic", model, "streamRawPredict")), prompt)
+                    .bearer_auth(token)
+                    .header("Accept", "text/event-stream")
+                    .json(&request)
+                    .send()
+                    .await;
+
+                sse_stream(response).await
+            },
+        };
+
+        let deadline = prompt.deadline;
+        match stream {
+            Ok(stream) => Ok(Box::pin(stream.map(move |item| item.map_err(|err| super::timeout_error(deadline, err))))),
+            Err(err) => Err(super::timeout_error(deadline, err.into_error(self.rate_limit().and_then(|rate_limit| rate_limit.retry_after())))),
+        }
+    }
+}
+
+impl LanguageModel for AnthropicModel {
+    #[instrument(
+        name = "AnthropicModel::inference",
+        level = "trace",
+        skip(self),
+        fields(
+            gen_ai.system = self.system(),
+            gen_ai.request.model = self.model(),
+            gen_ai.request.max_tokens = prompt.max_tokens,
+            gen_ai.request.temperature = prompt.temperature,
+            gen_ai.response.id = field::Empty,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+        ),
+    )]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let message = self.respond(&prompt).await?;
+
+        message.message().ok_or_else(|| Error::Unexpected(anyhow!("no-content")))
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        Capabilities::lookup(self.model())
+    }
+
+    /// Only the first-party API takes `metadata`, and only its `user_id`; arbitrary metadata keys have no
+    /// equivalent. Server tools are likewise only sent to the first-party API. Bedrock is called through the AWS
+    /// SDK, so extra headers and query parameters are dropped there.
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = CompatibilityReport::default()
+            .ignore_if("frequency_penalty", &prompt.frequency_penalty)
+            .ignore_if("presence_penalty", &prompt.presence_penalty)
+            .ignore_if_any("logit_bias", &prompt.logit_bias)
+            .ignore_if("seed", &prompt.seed)
+            .ignore_if("logprobs", &prompt.logprobs);
+        let report = match matches!(self, Self::Anthropic { .. }) {
+            true => report,
+            false => report
+                .ignore_if("user", &prompt.user)
+                .ignore_if_nonempty("server_tools", &prompt.server_tools),
+        };
+
+        #[cfg(feature = "aws-bedrock")]
+        let report = match matches!(self, Self::Bedrock { .. }) {
+            true => report
+                .ignore_if_nonempty("headers", &prompt.headers)
+                .ignore_if_nonempty("query", &prompt.query),
+            false => report,
+        };
+
+        match prompt.metadata.is_empty() {
+            true => report,
+            false => report.ignore("metadata"),
+        }
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        match self {
+            Self::Anthropic { rate_limit, .. } => rate_limit.lock().ok().and_then(|rate_limit| rate_limit.clone()),
+
+            #[cfg(feature = "aws-bedrock")]
+            Self::Bedrock { .. } => None,
+
+            #[cfg(feature = "vertex")]
+            Self::Vertex { .. } => None,
+        }
+    }
+}
+
+impl CitingModel for AnthropicModel {
+    /// Send documents with citations enabled, e.g. built with `cited_document`. Claude splits a cited answer
+    /// into several text blocks; their texts are joined into one message and their citations collected in order.
+    #[instrument(name = "AnthropicModel::inference_with_citations", level = "trace", skip(self, prompt))]
+    async fn inference_with_citations(&self, prompt: LanguageModelPrompt) -> Result<CitedResponse, Error> {
+        let message = self.respond(&prompt).await?;
+
+        let (mut text, mut citations) = (String::new(), Vec::new());
+        for content in &message.content {
+            if let AnthropicContent::Text { text: block, citations: cited } = content {
+                text.push_str(block);
+                citations.extend(cited.iter().filter_map(AnthropicCitation::to_citation));
             }
         }
+        if text.is_empty() {
+            return Err(Error::Unexpected(anyhow!("no-content")));
+        }
+        debug! { citations = citations.len() };
+
+        Ok(CitedResponse::new(Message::Text { text }, citations))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+    use crate::model::ResponseStream;
+
+    fn response(content: serde_json::Value) -> AnthropicMessageResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": "msg_01",
+            "type": "message",
+            "model": "claude-sonnet-4-5",
+            "role": "assistant",
+            "stop_reason": "end_turn",
+            "stop_sequence": null,
+            "usage": { "input_tokens": 12, "output_tokens": 34 },
+            "content": content,
+        })).unwrap()
+    }
+
+    fn decode(sse: &str) -> Vec<Result<String, Error>> {
+        let mut decoder = StreamDecoder::default();
+        decoder.push_sse(sse.as_bytes());
+        decoder.close();
+        decoder.pending.into_iter().collect()
+    }
+
+    fn stream(items: Vec<Result<String, Error>>) -> ResponseStream {
+        ResponseStream::new(Box::pin(futures_util::stream::iter(items)))
+    }
+
+    const DELTAS: &str = concat!(
+        "event: message_start\r\ndata: {\"type\":\"message_start\",\"message\":{}}\r\n\r\n",
+        "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\"Hello\"}}\n\n",
+        "event: ping\ndata: {\"type\":\"ping\"}\n\n",
+        "event: content_block_delta\ndata: {\"type\":\"content_block_delta\",\"index\":0,\"delta\":{\"type\":\"text_delta\",\"text\":\", world\"}}\n\n",
+    );
+
+    #[test]
+    fn decodes_text_deltas_until_message_stop() {
+        let items = decode(&format!("{DELTAS}event: message_stop\ndata: {{\"type\":\"message_stop\"}}\n\n"));
+
+        let texts = items.into_iter().collect::<Result<Vec<String>, Error>>().unwrap();
+        assert_eq!(texts, ["Hello", ", world"]);
+    }
+
+    #[test]
+    fn decodes_events_split_across_chunks() {
+        let mut decoder = StreamDecoder::default();
+        let (head, tail) = DELTAS.split_at(100);
+        decoder.push_sse(head.as_bytes());
+        decoder.push_sse(tail.as_bytes());
+        decoder.push_event(br#"{"type":"message_stop"}"#);
+        decoder.close();
+
+        let texts = decoder.pending.into_iter().collect::<Result<Vec<String>, Error>>().unwrap();
+        assert_eq!(texts, ["Hello", ", world"]);
+    }
+
+    #[test]
+    fn error_event_ends_the_stream() {
+        let items = decode(&format!(
+            "{DELTAS}event: error\ndata: {{\"type\":\"error\",\"error\":{{\"type\":\"overloaded_error\",\"message\":\"Overloaded\"}}}}\n\n\
+             event: content_block_delta\ndata: {{\"type\":\"content_block_delta\",\"index\":0,\"delta\":{{\"type\":\"text_delta\",\"text\":\"!\"}}}}\n\n"
+        ));
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items.last(), Some(Err(Error::Overloaded(message))) if message == "Overloaded"));
+    }
+
+    #[test]
+    fn refusal_stop_reason_is_content_blocked() {
+        let items = decode(&format!(
+            "{DELTAS}event: message_delta\ndata: {{\"type\":\"message_delta\",\"delta\":{{\"stop_reason\":\"refusal\"}}}}\n\n\
+             event: message_stop\ndata: {{\"type\":\"message_stop\"}}\n\n"
+        ));
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items.last(), Some(Err(Error::ContentBlocked { categories })) if categories == &["refusal"]));
+    }
+
+    #[test]
+    fn end_turn_stop_reason_is_not_an_error() {
+        let items = decode(&format!(
+            "{DELTAS}event: message_delta\ndata: {{\"type\":\"message_delta\",\"delta\":{{\"stop_reason\":\"end_turn\"}}}}\n\n\
+             event: message_stop\ndata: {{\"type\":\"message_stop\"}}\n\n"
+        ));
+
+        assert!(items.iter().all(Result::is_ok));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn stream_cut_before_message_stop_is_an_error() {
+        let items = decode(DELTAS);
+
+        assert_eq!(items.len(), 3);
+        assert!(matches!(items.last(), Some(Err(Error::ModelResponse(_)))));
+    }
+
+    #[tokio::test]
+    async fn failed_stream_keeps_partial_text() {
+        let mut stream = stream(decode(DELTAS));
+
+        while let Some(item) = stream.next().await {
+            if item.is_err() {
+                break;
+            }
+        }
+
+        assert!(stream.is_finished());
+        assert_eq!(stream.text(), "Hello, world");
+    }
+
+    #[tokio::test]
+    async fn cancel_returns_partial_text() {
+        let mut stream = stream(decode(DELTAS));
+
+        assert_eq!(stream.next().await.unwrap().unwrap(), "Hello");
+        let message = stream.cancel();
+
+        assert!(stream.is_finished());
+        assert!(matches!(message, Message::Text { text } if text == "Hello"));
+        assert!(stream.next().await.is_none());
+    }
+
+    #[test]
+    fn thinking_is_left_out_of_the_message() {
+        let response = response(serde_json::json!([
+            { "type": "thinking", "thinking": "The user greets me.", "signature": "c2ln" },
+            { "type": "text", "text": "Hello!" },
+        ]));
+
+        assert_eq!(response.content().len(), 2);
+        assert_eq!(response.content()[0].block_type(), Some("thinking"));
+        assert_eq!(response.text(), "Hello!");
+        assert!(matches!(response.message(), Some(Message::Text { text }) if text == "Hello!"));
+    }
+
+    #[test]
+    fn tool_use_is_left_out_of_the_message() {
+        let response = response(serde_json::json!([
+            { "type": "text", "text": "Let me check the weather." },
+            { "type": "tool_use", "id": "toolu_01", "name": "get_weather", "input": { "city": "Paris" } },
+        ]));
+
+        assert_eq!(response.content()[1].block_type(), Some("tool_use"));
+        assert!(matches!(response.message(), Some(Message::Text { text }) if text == "Let me check the weather."));
+    }
+
+    #[test]
+    fn text_blocks_are_joined_in_order() {
+        let response = response(serde_json::json!([
+            { "type": "text", "text": "The capital of France " },
+            { "type": "text", "text": "is Paris", "citations": [] },
+            { "type": "text", "text": "." },
+        ]));
+
+        assert_eq!(response.text(), "The capital of France is Paris.");
+        assert!(matches!(response.message(), Some(Message::Text { text }) if text == "The capital of France is Paris."));
+    }
+
+    #[test]
+    fn image_only_response_is_an_image_message() {
+        let response = response(serde_json::json!([
+            { "type": "image", "source": { "type": "base64", "media_type": "image/png", "data": BASE64_STANDARD.encode(b"png") } },
+        ]));
+
+        assert_eq!(response.text(), "");
+        match response.message() {
+            Some(Message::Image(image)) => {
+                assert_eq!(image.media_type(), "image/png");
+                assert_eq!(image.data_ref(), b"png");
+            },
+            message => panic!("expected an image, got {:?}", message),
+        }
+    }
+
+    #[test]
+    fn empty_response_has_no_message() {
+        assert!(response(serde_json::json!([])).message().is_none());
+    }
+
+    #[cfg(not(feature = "vertex"))]
+    #[test]
+    fn disabled_field_error_lists_the_accepted_fields() {
+        let err = serde_json::from_value::<AnthropicModel>(serde_json::json!({ "project_id": "p", "model": "m" })).unwrap_err();
+
+        assert!(err.to_string().starts_with("unknown field `project_id`, expected one of `api_key`, `api_version`, `model`, `base_url`"));
+        assert_eq!(err.to_string().contains("`aws_config`"), cfg!(feature = "aws-bedrock"));
+    }
+}
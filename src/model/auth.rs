@@ -0,0 +1,181 @@
+use std::{
+    future::Future,
+    sync::Arc,
+    time::Duration,
+};
+
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use tracing::{debug, instrument};
+
+use super::Error;
+use crate::{time::Instant, SecretString};
+
+/// Tokens are refreshed this long before they expire, so a request never leaves with a token about to lapse.
+const EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+
+/// A source of bearer tokens, asked for a token before every request.
+pub trait TokenProvider: Send + Sync {
+    fn token(&self) -> impl Future<Output = Result<SecretString, Error>>;
+}
+
+/// Bearer credentials for providers and gateways: a static key, an environment variable read on every request,
+/// or an OAuth2 client-credentials grant.
+///
+/// In config files a plain string is a static key, `{ env = "VAR" }` reads `VAR`, and a table with `token_url`,
+/// `client_id` and `client_secret` uses client credentials.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum BearerToken {
+    Static(SecretString),
+    Env { env: String },
+    ClientCredentials(ClientCredentials),
+}
+
+impl BearerToken {
+    pub fn env(var: impl Into<String>) -> Self {
+        Self::Env { env: var.into() }
+    }
+}
+
+impl From<SecretString> for BearerToken {
+    fn from(value: SecretString) -> Self {
+        Self::Static(value)
+    }
+}
+
+impl From<String> for BearerToken {
+    fn from(value: String) -> Self {
+        Self::Static(value.into())
+    }
+}
+
+impl From<&str> for BearerToken {
+    fn from(value: &str) -> Self {
+        Self::Static(value.into())
+    }
+}
+
+impl From<ClientCredentials> for BearerToken {
+    fn from(value: ClientCredentials) -> Self {
+        Self::ClientCredentials(value)
+    }
+}
+
+impl TokenProvider for BearerToken {
+    async fn token(&self) -> Result<SecretString, Error> {
+        match self {
+            Self::Static(token) => Ok(token.clone()),
+            Self::Env { env } => std::env::var(env)
+                .map(SecretString::from)
+                .map_err(|err| Error::AuthenticationFailed(format!("{}: {}", env, err))),
+            Self::ClientCredentials(credentials) => credentials.token().await,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct CachedToken {
+    token: SecretString,
+    expires_at: Option<Instant>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+
+    #[serde(default)]
+    expires_in: Option<u64>,
+}
+
+/// An OAuth2 client-credentials grant against `token_url`. The access token is cached until shortly before it
+/// expires and shared between clones; concurrent requests wait for a single refresh.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClientCredentials {
+    token_url: String,
+    client_id: String,
+    client_secret: SecretString,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    scopes: Vec<String>,
+
+    #[serde(skip)]
+    cache: Arc<Mutex<Option<CachedToken>>>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl ClientCredentials {
+    pub fn new(token_url: impl Into<String>, client_id: impl Into<String>, client_secret: impl Into<SecretString>) -> Self {
+        Self {
+            token_url: token_url.into(),
+            client_id: client_id.into(),
+            client_secret: client_secret.into(),
+            scopes: Vec::new(),
+            cache: Arc::default(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn scope(self, scope: impl Into<String>) -> Self {
+        let mut scopes = self.scopes;
+        scopes.push(scope.into());
+
+        Self {
+            scopes,
+            ..self
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    async fn request(&self) -> Result<TokenResponse, Error> {
+        let mut form = vec![("grant_type", "client_credentials".to_string())];
+        if !self.scopes.is_empty() {
+            form.push(("scope", self.scopes.join(" ")));
+        }
+
+        let response = self.client
+            .post(&self.token_url)
+            .basic_auth(&self.client_id, Some(self.client_secret.expose()))
+            .header("Accept", "application/json")
+            .form(&form)
+            .send()
+            .await
+            .map_err(|err| Error::AuthenticationFailed(format!("{}", err)))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(Error::AuthenticationFailed(format!("{}: {}", status, body)));
+        }
+
+        response.json::<TokenResponse>().await.map_err(|err| Error::AuthenticationFailed(format!("{}", err)))
+    }
+}
+
+impl TokenProvider for ClientCredentials {
+    #[instrument(name = "ClientCredentials::token", level = "trace", skip(self))]
+    async fn token(&self) -> Result<SecretString, Error> {
+        let mut cache = self.cache.lock().await;
+        if let Some(cached) = cache.as_ref().filter(|cached| cached.expires_at.is_none_or(|expires_at| Instant::now() < expires_at)) {
+            return Ok(cached.token.clone());
+        }
+
+        let response = self.request().await?;
+        let expires_at = response.expires_in.map(|expires_in| Instant::now() + Duration::from_secs(expires_in).saturating_sub(EXPIRY_MARGIN));
+        debug! { token_url = self.token_url, ?expires_at, "refreshed access token" };
+
+        let token = SecretString::from(response.access_token);
+        *cache = Some(CachedToken { token: token.clone(), expires_at });
+
+        Ok(token)
+    }
+}
@@ -0,0 +1,220 @@
+use aws_config::{
+    profile::ProfileFileCredentialsProvider,
+    provider_config::ProviderConfig,
+    sts::AssumeRoleProvider,
+    web_identity_token::{StaticConfiguration, WebIdentityTokenCredentialsProvider},
+    Region,
+    SdkConfig,
+};
+use aws_credential_types::{
+    provider::{future, ProvideCredentials, SharedCredentialsProvider},
+    Credentials,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::SecretString;
+
+#[cfg(feature = "aws-secrets-manager")]
+use {
+    anyhow::anyhow,
+    aws_sigv4::{
+        http_request::{sign, SignableBody, SignableRequest, SigningSettings},
+        sign::v4,
+    },
+    serde_json::{json, Value},
+    std::time::SystemTime,
+    tracing::instrument,
+    super::Error,
+};
+
+#[derive(Debug)]
+struct CredentialParams {
+    access_key: String,
+    secret_key: SecretString,
+    session_token: Option<SecretString>,
+}
+
+impl ProvideCredentials for CredentialParams {
+    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
+    where
+        Self: 'a
+    {
+        future::ProvideCredentials::ready(Ok(Credentials::new(self.access_key.clone(), self.secret_key.expose(), self.session_token.as_ref().map(|token| token.expose().to_string()), None, "ArgumentVariable")))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum AwsConfig {
+    Profile {
+        profile_name: String,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+    WebIdentity {
+        role_arn: String,
+        web_identity_token_file: String,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session_name: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+    AssumeRole {
+        role_arn: String,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        external_id: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session_name: Option<String>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    },
+    Credential {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        access_key: Option<String>,
+        
+        #[serde(skip_serializing_if = "Option::is_none")]
+        secret_key: Option<SecretString>,
+
+        #[serde(skip_serializing_if = "Option::is_none")]
+        session_token: Option<SecretString>,
+        
+        #[serde(skip_serializing_if = "Option::is_none")]
+        region: Option<String>,
+    }
+}
+
+pub async fn sdk_config(aws_config: &Option<AwsConfig>) -> SdkConfig {
+    if let Some(aws_config) = aws_config {
+        match aws_config {
+            AwsConfig::Credential { access_key, secret_key, session_token, region } => {
+                if (access_key.is_some() && secret_key.is_some()) || region.is_some() {
+                    let mut builder = aws_config::load_from_env().await.into_builder();
+
+                    if let (Some(access_key), Some(secret_key)) = (access_key, secret_key) {
+                        builder = builder.credentials_provider(SharedCredentialsProvider::new(CredentialParams {
+                            access_key: access_key.clone(),
+                            secret_key: secret_key.clone(),
+                            session_token: session_token.clone(),
+                        }));
+                    }
+
+                    if let Some(region) = region {
+                        builder = builder.region(Region::new(region.clone()));
+                    }
+
+                    builder.build()
+                } else {
+                    aws_config::load_from_env().await
+                }
+            },
+            AwsConfig::Profile { profile_name, region } => {
+                let mut builder = aws_config::load_from_env().await.into_builder();
+
+                builder = builder.credentials_provider(SharedCredentialsProvider::new(ProfileFileCredentialsProvider::builder().profile_name(profile_name).build()));
+
+                if let Some(region) = region {
+                    builder = builder.region(Region::new(region.clone()));
+                }
+
+                builder.build()
+            },
+            AwsConfig::AssumeRole { role_arn, external_id, session_name, region } => {
+                let base_config = aws_config::load_from_env().await;
+                let mut builder = base_config.clone().into_builder();
+
+                let mut provider = AssumeRoleProvider::builder(role_arn)
+                    .session_name(session_name.clone().unwrap_or_else(|| "april-core".into()))
+                    .configure(&base_config);
+                if let Some(external_id) = external_id {
+                    provider = provider.external_id(external_id);
+                }
+                if let Some(region) = region {
+                    provider = provider.region(Region::new(region.clone()));
+                    builder = builder.region(Region::new(region.clone()));
+                }
+
+                builder = builder.credentials_provider(SharedCredentialsProvider::new(provider.build().await));
+
+                builder.build()
+            },
+            AwsConfig::WebIdentity { role_arn, web_identity_token_file, session_name, region } => {
+                let mut builder = aws_config::load_from_env().await.into_builder();
+
+                let region = region.clone().map(Region::new);
+                let provider = WebIdentityTokenCredentialsProvider::builder()
+                    .configure(&ProviderConfig::default().with_region(region.clone()))
+                    .static_configuration(StaticConfiguration {
+                        web_identity_token_file: web_identity_token_file.into(),
+                        role_arn: role_arn.clone(),
+                        session_name: session_name.clone().unwrap_or_else(|| "april-core".into()),
+                    })
+                    .build();
+
+                builder = builder.credentials_provider(SharedCredentialsProvider::new(provider));
+                if let Some(region) = region {
+                    builder = builder.region(region);
+                }
+
+                builder.build()
+            },
+        }
+    } else {
+        aws_config::load_from_env().await
+    }
+}
+/// Reads the `SecretString` of `secret_id` from AWS Secrets Manager, `None` when the secret does not exist.
+///
+/// Signs the `GetSecretValue` call itself rather than pulling in the Secrets Manager SDK for a single request.
+#[cfg(feature = "aws-secrets-manager")]
+#[instrument(name = "aws::secret_value", level = "trace", skip(aws_config))]
+pub async fn secret_value(secret_id: &str, aws_config: &Option<AwsConfig>) -> Result<Option<String>, Error> {
+    let config = sdk_config(aws_config).await;
+    let region = config.region().ok_or_else(|| Error::InvalidRequest("no AWS region configured".into()))?;
+    let credentials = config.credentials_provider()
+        .ok_or_else(|| Error::AuthenticationFailed("no AWS credentials configured".into()))?
+        .provide_credentials()
+        .await
+        .map_err(|err| Error::AuthenticationFailed(format!("{}", err)))?;
+    let identity = credentials.into();
+
+    let url = format!("https://secretsmanager.{}.amazonaws.com/", region);
+    let body = serde_json::to_vec(&json!({ "SecretId": secret_id })).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+    let headers = [("content-type", "application/x-amz-json-1.1"), ("x-amz-target", "secretsmanager.GetSecretValue")];
+
+    let params = v4::SigningParams::builder()
+        .identity(&identity)
+        .region(region.as_ref())
+        .name("secretsmanager")
+        .time(SystemTime::now())
+        .settings(SigningSettings::default())
+        .build()
+        .map_err(|err| Error::Unexpected(anyhow!(err)))?
+        .into();
+    let signable = SignableRequest::new("POST", &url, headers.into_iter(), SignableBody::Bytes(&body))
+        .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+    let (instructions, _) = sign(signable, &params).map_err(|err| Error::Unexpected(anyhow!(err)))?.into_parts();
+
+    let request = headers.into_iter()
+        .chain(instructions.headers())
+        .fold(reqwest::Client::new().post(&url), |request, (name, value)| request.header(name, value));
+    let response = request.body(body).send().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+    let status = response.status();
+    let body = response.json::<Value>().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+    if status.is_success() {
+        return Ok(body.get("SecretString").and_then(Value::as_str).map(str::to_string));
+    }
+
+    let error_type = body.get("__type").and_then(Value::as_str).unwrap_or_default();
+    let message = body.get("message").or_else(|| body.get("Message")).and_then(Value::as_str).unwrap_or_default();
+    match error_type.ends_with("ResourceNotFoundException") {
+        true => Ok(None),
+        false => Err(Error::Unexpected(anyhow!("secrets-manager-error: {} {}", error_type, message))),
+    }
+}
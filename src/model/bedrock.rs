@@ -1,88 +1,386 @@
-use aws_config::{profile::ProfileFileCredentialsProvider, Region};
-use aws_credential_types::{
-    provider::future,
-    Credentials,
-};
+use std::collections::HashMap;
+
 use aws_sdk_bedrockruntime::{
-    config::{ProvideCredentials, SharedCredentialsProvider},
+    operation::{
+        invoke_model::builders::InvokeModelFluentBuilder,
+        invoke_model_with_response_stream::builders::InvokeModelWithResponseStreamFluentBuilder,
+    },
+    types::{GuardrailAction, GuardrailContentBlock, GuardrailContentSource, GuardrailTextBlock, PerformanceConfigLatency, Trace},
     Client,
 };
+use anyhow::anyhow;
+use base64::prelude::{BASE64_STANDARD, Engine as _};
 use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{debug, error, instrument};
+
+use super::{aws::sdk_config, AwsConfig, Error, Image, ImageModel, ImageOptions, Message, Moderation, ModerationModel, ModerationSource};
+
+pub async fn bedrock_client(aws_config: &Option<AwsConfig>) -> Client {
+    Client::new(&sdk_config(aws_config).await)
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GuardrailConfig {
+    identifier: String,
+    version: String,
 
-#[derive(Debug)]
-struct CredentialParams {
-    access_key: String,
-    secret_key: String,
+    #[serde(default)]
+    trace: bool,
 }
 
-impl ProvideCredentials for CredentialParams {
-    fn provide_credentials<'a>(&'a self) -> future::ProvideCredentials<'a>
-    where
-        Self: 'a
-    {
-        future::ProvideCredentials::ready(Ok(Credentials::new(self.access_key.clone(), self.secret_key.clone(), None, None, "ArgumentVariable")))
+impl GuardrailConfig {
+    pub fn new(identifier: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            identifier: identifier.into(),
+            version: version.into(),
+            trace: false,
+        }
+    }
+
+    pub fn trace(self, trace: bool) -> Self {
+        Self {
+            trace,
+            ..self
+        }
+    }
+
+    pub fn identifier(&self) -> &str {
+        &self.identifier
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-#[serde(untagged)]
-pub enum AwsConfig {
-    Profile {
-        profile_name: String,
+/// Per-model settings applied to every `invoke_model` call.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct BedrockOptions {
+    /// Cross-region or application inference profile ID/ARN used in place of the model ID.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inference_profile: Option<String>,
 
-        #[serde(skip_serializing_if = "Option::is_none")]
-        region: Option<String>,
-    },
-    Credential {
-        #[serde(skip_serializing_if = "Option::is_none")]
-        access_key: Option<String>,
-        
-        #[serde(skip_serializing_if = "Option::is_none")]
-        secret_key: Option<String>,
-        
-        #[serde(skip_serializing_if = "Option::is_none")]
-        region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guardrail: Option<GuardrailConfig>,
+
+    /// Key-value pairs attached as request metadata for filtering invocation logs.
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    request_tags: HashMap<String, String>,
+
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    latency_optimized: bool,
+}
+
+/// Model families for which Bedrock offers latency-optimized inference.
+const LATENCY_OPTIMIZED_MODELS: &[&str] = &["claude-3-5-haiku", "llama3-1-405b", "llama3-1-70b", "nova-pro"];
+
+pub fn supports_latency_optimized(model: &str) -> bool {
+    LATENCY_OPTIMIZED_MODELS.iter().any(|supported| model.contains(supported))
+}
+
+impl BedrockOptions {
+    pub fn inference_profile(self, inference_profile: impl Into<String>) -> Self {
+        Self {
+            inference_profile: Some(inference_profile.into()),
+            ..self
+        }
+    }
+
+    pub fn guardrail(self, guardrail: GuardrailConfig) -> Self {
+        Self {
+            guardrail: Some(guardrail),
+            ..self
+        }
+    }
+
+    pub fn request_tag(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut request_tags = self.request_tags;
+        request_tags.insert(key.into(), value.into());
+
+        Self {
+            request_tags,
+            ..self
+        }
+    }
+
+    /// Requests Bedrock's latency-optimized inference tier; calls fail for models that do not support it.
+    pub fn latency_optimized(self, latency_optimized: bool) -> Self {
+        Self {
+            latency_optimized,
+            ..self
+        }
+    }
+
+    pub(crate) fn set_latency_optimized(&mut self, latency_optimized: bool) {
+        self.latency_optimized = latency_optimized;
+    }
+
+    pub(crate) fn set_inference_profile(&mut self, inference_profile: Option<String>) {
+        self.inference_profile = inference_profile;
+    }
+
+    pub(crate) fn set_guardrail(&mut self, guardrail: Option<GuardrailConfig>) {
+        self.guardrail = guardrail;
+    }
+
+    pub(crate) fn set_request_tags(&mut self, request_tags: HashMap<String, String>) {
+        self.request_tags = request_tags;
+    }
+
+    /// The inference profile when one is set, otherwise `model`.
+    pub(crate) fn model_id<'a>(&'a self, model: &'a str) -> &'a str {
+        self.inference_profile.as_deref().unwrap_or(model)
+    }
+
+    pub(crate) fn validate(&self, model: &str) -> Result<(), Error> {
+        let model_id = self.model_id(model);
+
+        if self.latency_optimized && !supports_latency_optimized(model) && !supports_latency_optimized(model_id) {
+            Err(Error::Unexpected(anyhow!("latency-optimized inference is not supported for {}", model_id)))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn apply(&self, builder: InvokeModelFluentBuilder, model: &str) -> Result<InvokeModelFluentBuilder, Error> {
+        self.validate(model)?;
+
+        let mut builder = builder.model_id(self.model_id(model));
+
+        if self.latency_optimized {
+            builder = builder.performance_config_latency(PerformanceConfigLatency::Optimized);
+        }
+
+        if let Some(guardrail) = &self.guardrail {
+            builder = builder
+                .guardrail_identifier(&guardrail.identifier)
+                .guardrail_version(&guardrail.version)
+                .trace(if guardrail.trace { Trace::Enabled } else { Trace::Disabled });
+        }
+
+        if !self.request_tags.is_empty() {
+            builder = builder.request_metadata(serde_json::to_string(&self.request_tags).map_err(|err| Error::Unexpected(err.into()))?);
+        }
+
+        Ok(builder)
+    }
+
+    /// `apply` for streamed invocations.
+    pub(crate) fn apply_stream(&self, builder: InvokeModelWithResponseStreamFluentBuilder, model: &str) -> Result<InvokeModelWithResponseStreamFluentBuilder, Error> {
+        self.validate(model)?;
+
+        let mut builder = builder.model_id(self.model_id(model));
+
+        if self.latency_optimized {
+            builder = builder.performance_config_latency(PerformanceConfigLatency::Optimized);
+        }
+
+        if let Some(guardrail) = &self.guardrail {
+            builder = builder
+                .guardrail_identifier(&guardrail.identifier)
+                .guardrail_version(&guardrail.version)
+                .trace(if guardrail.trace { Trace::Enabled } else { Trace::Disabled });
+        }
+
+        if !self.request_tags.is_empty() {
+            builder = builder.request_metadata(serde_json::to_string(&self.request_tags).map_err(|err| Error::Unexpected(err.into()))?);
+        }
+
+        Ok(builder)
     }
 }
 
-pub async fn bedrock_client(aws_config: &Option<AwsConfig>) -> Client {
-    let sdk_config = if let Some(aws_config) = aws_config {
-        match aws_config {
-            AwsConfig::Credential { access_key, secret_key, region } => {
-                if (access_key.is_some() && secret_key.is_some()) || region.is_some() {
-                    let mut builder = aws_config::load_from_env().await.into_builder();
-
-                    if let (Some(access_key), Some(secret_key)) = (access_key, secret_key) {
-                        builder = builder.credentials_provider(SharedCredentialsProvider::new(CredentialParams {
-                            access_key: access_key.clone(),
-                            secret_key: secret_key.clone(),
-                        }));
-                    }
-
-                    if let Some(region) = region {
-                        builder = builder.region(Region::new(region.clone()));
-                    }
-
-                    builder.build()
-                } else {
-                    aws_config::load_from_env().await
-                }
-            },
-            AwsConfig::Profile { profile_name, region } => {
-                let mut builder = aws_config::load_from_env().await.into_builder();
+#[derive(Clone, Debug, Serialize)]
+pub struct BedrockGuardrail {
+    guardrail_id: String,
+    guardrail_version: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aws_config: Option<AwsConfig>,
 
-                builder = builder.credentials_provider(SharedCredentialsProvider::new(ProfileFileCredentialsProvider::builder().profile_name(profile_name).build()));
+    #[serde(skip)]
+    client: Client,
+}
+
+impl BedrockGuardrail {
+    pub async fn new(guardrail_id: impl Into<String>, guardrail_version: impl Into<String>, aws_config: Option<AwsConfig>) -> Self {
+        let client = bedrock_client(&aws_config).await;
 
-                if let Some(region) = region {
-                    builder = builder.region(Region::new(region.clone()));
-                }
+        Self {
+            guardrail_id: guardrail_id.into(),
+            guardrail_version: guardrail_version.into(),
+            aws_config,
+            client,
+        }
+    }
 
-                builder.build()
+    pub fn guardrail_id(&self) -> &str {
+        &self.guardrail_id
+    }
+
+    pub fn guardrail_version(&self) -> &str {
+        &self.guardrail_version
+    }
+}
+
+impl ModerationModel for BedrockGuardrail {
+    #[instrument(name = "BedrockGuardrail::moderate", level = "trace", skip(self, messages))]
+    async fn moderate(&self, messages: &[Message], source: ModerationSource) -> Result<Moderation, Error> {
+        let content = messages.iter().filter_map(|message| match message {
+            Message::Text { text } => GuardrailTextBlock::builder().text(text).build().ok().map(GuardrailContentBlock::Text),
+            Message::Audio(_) | Message::Image(_) | Message::Unknown(_) => {
+                debug!("skipping non-text content for guardrail assessment");
+                None
             },
+        }).collect::<Vec<GuardrailContentBlock>>();
+
+        if content.is_empty() {
+            return Ok(Moderation::default());
         }
-    } else {
-        aws_config::load_from_env().await
-    };
 
-    Client::new(&sdk_config)
-}
\ No newline at end of file
+        let response = self.client.apply_guardrail()
+            .guardrail_identifier(&self.guardrail_id)
+            .guardrail_version(&self.guardrail_version)
+            .source(match source {
+                ModerationSource::Input => GuardrailContentSource::Input,
+                ModerationSource::Output => GuardrailContentSource::Output,
+            })
+            .set_content(Some(content))
+            .send()
+            .await
+            .map_err(|err| {
+                error! { ?err };
+                Error::ModelResponse(format!("{}", err))
+            })?;
+
+        debug! { ?response };
+
+        let mut categories = Vec::new();
+        for assessment in response.assessments() {
+            if let Some(policy) = assessment.topic_policy() {
+                categories.extend(policy.topics().iter().map(|topic| format!("topic:{}", topic.name())));
+            }
+            if let Some(policy) = assessment.content_policy() {
+                categories.extend(policy.filters().iter().map(|filter| format!("content:{}", filter.r#type().as_str().to_lowercase())));
+            }
+            if let Some(policy) = assessment.word_policy() {
+                categories.extend(policy.custom_words().iter().map(|_| "word:custom".to_string()));
+                categories.extend(policy.managed_word_lists().iter().map(|word| format!("word:{}", word.r#type().as_str().to_lowercase())));
+            }
+            if let Some(policy) = assessment.sensitive_information_policy() {
+                categories.extend(policy.pii_entities().iter().map(|entity| format!("pii:{}", entity.r#type().as_str().to_lowercase())));
+                categories.extend(policy.regexes().iter().map(|regex| format!("regex:{}", regex.name().unwrap_or("custom"))));
+            }
+        }
+        categories.sort();
+        categories.dedup();
+
+        Ok(Moderation::new(*response.action() == GuardrailAction::GuardrailIntervened, categories))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TitanImageResponse {
+    #[serde(default)]
+    images: Vec<String>,
+
+    #[serde(default)]
+    error: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct TitanImageModel {
+    model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cfg_scale: Option<f32>,
+
+    #[serde(flatten)]
+    options: BedrockOptions,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aws_config: Option<AwsConfig>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl TitanImageModel {
+    pub async fn new(model: impl Into<String>, aws_config: Option<AwsConfig>) -> Self {
+        let client = bedrock_client(&aws_config).await;
+
+        Self {
+            model: model.into(),
+            cfg_scale: None,
+            options: BedrockOptions::default(),
+            aws_config,
+            client,
+        }
+    }
+
+    pub fn cfg_scale(self, cfg_scale: f32) -> Self {
+        Self {
+            cfg_scale: Some(cfg_scale),
+            ..self
+        }
+    }
+
+    pub fn options(self, options: BedrockOptions) -> Self {
+        Self {
+            options,
+            ..self
+        }
+    }
+}
+
+impl ImageModel for TitanImageModel {
+    #[instrument(name = "TitanImageModel::generate", level = "trace", skip(self))]
+    async fn generate(&self, prompt: &str, options: ImageOptions) -> Result<Vec<Image>, Error> {
+        let mut text_to_image_params = json!({ "text": prompt });
+        if let Some(negative_prompt) = &options.negative_prompt {
+            text_to_image_params["negativeText"] = json!(negative_prompt);
+        }
+
+        let mut image_generation_config = json!({
+            "numberOfImages": options.count,
+            "width": options.width,
+            "height": options.height,
+        });
+        if let Some(seed) = options.seed {
+            image_generation_config["seed"] = json!(seed);
+        }
+        if let Some(cfg_scale) = self.cfg_scale {
+            image_generation_config["cfgScale"] = json!(cfg_scale);
+        }
+
+        let request = json!({
+            "taskType": "TEXT_IMAGE",
+            "textToImageParams": text_to_image_params,
+            "imageGenerationConfig": image_generation_config,
+        });
+
+        let response = self.options.apply(self.client.invoke_model(), &self.model)?
+            .accept("application/json")
+            .content_type("application/json")
+            .body(aws_sdk_bedrockruntime::primitives::Blob::new(serde_json::to_vec(&request).map_err(|err| Error::Unexpected(err.into()))?))
+            .send()
+            .await
+            .map_err(|err| {
+                error! { ?err };
+                Error::ModelResponse(format!("{}", err))
+            })?;
+
+        let response = serde_json::from_slice::<TitanImageResponse>(response.body().as_ref())
+            .map_err(|err| Error::Unexpected(err.into()))?;
+
+        if let Some(err) = response.error {
+            error! { ?err };
+            return Err(Error::ModelResponse(err));
+        }
+
+        response.images.into_iter()
+            .map(|data| Ok(Image::new("image/png", BASE64_STANDARD.decode(data)?)))
+            .collect()
+    }
+}
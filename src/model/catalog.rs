@@ -0,0 +1,133 @@
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Error, LanguageModelPrompt};
+use crate::Message;
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct Capabilities {
+    context_window: usize,
+    max_output_tokens: usize,
+    vision: bool,
+    tools: bool,
+}
+
+/// Published limits by model family, matched against the longest family name contained in a model id so that
+/// dated ids, Bedrock ids (`anthropic.claude-...-v1:0`) and inference profiles (`us.anthropic...`) all resolve.
+const KNOWN_MODELS: &[(&str, Capabilities)] = &[
+    ("claude-opus-4", Capabilities::new(200_000, 32_000, true, true)),
+    ("claude-sonnet-4", Capabilities::new(200_000, 64_000, true, true)),
+    ("claude-3-7-sonnet", Capabilities::new(200_000, 64_000, true, true)),
+    ("claude-3-5-sonnet", Capabilities::new(200_000, 8_192, true, true)),
+    ("claude-3-5-haiku", Capabilities::new(200_000, 8_192, true, true)),
+    ("claude-3-opus", Capabilities::new(200_000, 4_096, true, true)),
+    ("claude-3-sonnet", Capabilities::new(200_000, 4_096, true, true)),
+    ("claude-3-haiku", Capabilities::new(200_000, 4_096, true, true)),
+    ("claude-2", Capabilities::new(100_000, 4_096, false, false)),
+    ("claude-instant", Capabilities::new(100_000, 4_096, false, false)),
+    ("deepseek-chat", Capabilities::new(131_072, 8_192, false, true)),
+    ("deepseek-reasoner", Capabilities::new(131_072, 65_536, false, true)),
+    ("gemini-2.5-pro", Capabilities::new(1_048_576, 65_536, true, true)),
+    ("gemini-2.5-flash", Capabilities::new(1_048_576, 65_536, true, true)),
+    ("gemini-2.0-flash", Capabilities::new(1_048_576, 8_192, true, true)),
+    ("gemini-1.5-pro", Capabilities::new(2_097_152, 8_192, true, true)),
+    ("gemini-1.5-flash", Capabilities::new(1_048_576, 8_192, true, true)),
+    ("sonar-pro", Capabilities::new(200_000, 8_192, false, false)),
+    ("sonar-reasoning-pro", Capabilities::new(128_000, 8_192, false, false)),
+    ("sonar", Capabilities::new(128_000, 8_192, false, false)),
+];
+
+impl Capabilities {
+    pub const fn new(context_window: usize, max_output_tokens: usize, vision: bool, tools: bool) -> Self {
+        Self { context_window, max_output_tokens, vision, tools }
+    }
+
+    pub fn lookup(model_id: &str) -> Option<Self> {
+        KNOWN_MODELS.iter()
+            .filter(|(family, _)| model_id.contains(family))
+            .max_by_key(|(family, _)| family.len())
+            .map(|(_, capabilities)| *capabilities)
+    }
+
+    pub fn context_window(&self) -> usize {
+        self.context_window
+    }
+
+    pub fn max_output_tokens(&self) -> usize {
+        self.max_output_tokens
+    }
+
+    pub fn vision(&self) -> bool {
+        self.vision
+    }
+
+    pub fn tools(&self) -> bool {
+        self.tools
+    }
+
+    /// Rejects prompts the model cannot accept: images for text-only models, and input whose estimated size
+    /// exceeds the context window.
+    pub fn check(&self, prompt: &LanguageModelPrompt) -> Result<(), Error> {
+        if !self.vision && prompt.messages.iter().any(|message| matches!(message, Message::Image(_))) {
+            return Err(Error::UnsupportedContent { kind: "image".to_string() });
+        }
+
+        let input_tokens = prompt.input_tokens();
+        if input_tokens > self.context_window {
+            return Err(Error::UnsupportedContent { kind: format!("context-window: ~{} tokens exceeds {}", input_tokens, self.context_window) });
+        }
+
+        Ok(())
+    }
+
+    /// Output tokens left for `prompt` after its estimated input and `margin`, at most `max_output_tokens` and at
+    /// least one.
+    pub fn output_tokens_for(&self, prompt: &LanguageModelPrompt, margin: usize) -> usize {
+        self.context_window
+            .saturating_sub(prompt.input_tokens())
+            .saturating_sub(margin)
+            .clamp(1, self.max_output_tokens)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModelInfo {
+    id: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    display_name: Option<String>,
+
+    /// `None` for models this crate has no published limits for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    capabilities: Option<Capabilities>,
+}
+
+impl ModelInfo {
+    pub fn new(id: impl Into<String>, display_name: Option<String>) -> Self {
+        let id = id.into();
+
+        Self {
+            capabilities: Capabilities::lookup(&id),
+            id,
+            display_name,
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn display_name(&self) -> Option<&str> {
+        self.display_name.as_deref()
+    }
+
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+}
+
+pub trait ModelCatalog {
+    /// Models available to the configured credentials, with capabilities where known.
+    fn list_models(&self) -> impl Future<Output = Result<Vec<ModelInfo>, Error>>;
+}
\ No newline at end of file
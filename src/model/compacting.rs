@@ -0,0 +1,113 @@
+use tracing::{debug, instrument};
+
+use super::{
+    BatchInference,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    RateLimit,
+};
+use crate::{
+    compaction::{turn_tokens, Compaction},
+    Role,
+    Turn,
+};
+
+/// Compacts a prompt's messages with `compaction` before inference whenever they would not fit the model's
+/// context window alongside the system prompt and `max_tokens` of output.
+///
+/// The window comes from the model's `capabilities`, or from `context_window` for models without published
+/// limits; prompts for models with neither pass through unchanged. System turns produced by the compaction,
+/// such as a `RollingSummary`, are appended to the system prompt.
+#[derive(Debug)]
+pub struct CompactingModel<M, C> {
+    model: M,
+    compaction: C,
+    context_window: Option<usize>,
+}
+
+impl<M, C> CompactingModel<M, C> {
+    pub fn new(model: M, compaction: C) -> Self {
+        Self {
+            model,
+            compaction,
+            context_window: None,
+        }
+    }
+
+    pub fn context_window(self, context_window: usize) -> Self {
+        Self {
+            context_window: Some(context_window),
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn compaction(&self) -> &C {
+        &self.compaction
+    }
+}
+
+impl<M, C> BatchInference for CompactingModel<M, C>
+where
+    M: LanguageModel,
+    C: Compaction,
+{}
+
+impl<M, C> LanguageModel for CompactingModel<M, C>
+where
+    M: LanguageModel,
+    C: Compaction,
+{
+    #[instrument(name = "CompactingModel::inference", level = "trace", skip(self, prompt))]
+    async fn inference(&self, mut prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let Some(window) = self.context_window.or_else(|| self.model.capabilities().map(|capabilities| capabilities.context_window())) else {
+            return self.model.inference(prompt).await;
+        };
+
+        let budget = window
+            .saturating_sub(prompt.max_tokens)
+            .saturating_sub(prompt.preamble_tokens());
+        let turns = prompt.messages.drain(..).map(|message| Turn::new(Role::User, message)).collect::<Vec<Turn>>();
+
+        let before = turns.iter().map(turn_tokens).sum::<usize>();
+        let turns = match before > budget {
+            true => self.compaction.compact(turns, budget).await?,
+            false => turns,
+        };
+
+        let after = turns.iter().map(turn_tokens).sum::<usize>();
+        debug! { budget, before, after };
+
+        let mut system = prompt.system.take().into_iter().collect::<Vec<String>>();
+        for turn in turns {
+            match turn.role() {
+                Role::System => system.push(turn.message().to_string()),
+                _ => prompt.messages.push(turn.message().clone()),
+            }
+        }
+        if !system.is_empty() {
+            prompt.system = Some(system.join("\n\n"));
+        }
+
+        self.model.inference(prompt).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
@@ -0,0 +1,400 @@
+use anyhow::anyhow;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, field, instrument};
+
+use super::{
+    BatchInference,
+    BearerToken,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    LogprobModel,
+    LogprobResponse,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    ReasonedResponse,
+    ReasoningModel,
+    TokenLogprob,
+    TokenProvider,
+};
+
+const API_URL: &str = "https://api.deepseek.com";
+
+#[derive(Debug, Deserialize)]
+pub struct DeepSeekErrorResponse {
+    #[serde(rename = "type", default)]
+    error_type: String,
+
+    message: String,
+
+    #[serde(skip)]
+    status: u16,
+}
+
+impl DeepSeekErrorResponse {
+    pub fn error_type(&self) -> &str {
+        &self.error_type
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// DeepSeek's error `type`s are not documented, so errors are classified by HTTP status.
+    fn into_error(self) -> Error {
+        match self.status {
+            429 => Error::RateLimited { retry_after: None },
+            503 => Error::Overloaded(self.message),
+            408 => Error::Timeout(self.message),
+            401 | 402 => Error::AuthenticationFailed(self.message),
+            400 | 422 if self.message.contains("context length") => Error::ContextLengthExceeded(self.message),
+            400 | 404 | 422 => Error::InvalidRequest(self.message),
+            _ => Error::ModelResponse(self.message),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct DeepSeekErrorEnvelope {
+    error: DeepSeekErrorResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeepSeekUsage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+
+    #[serde(default)]
+    prompt_cache_hit_tokens: usize,
+}
+
+impl DeepSeekUsage {
+    pub fn input_tokens(&self) -> usize {
+        self.prompt_tokens
+    }
+
+    pub fn output_tokens(&self) -> usize {
+        self.completion_tokens
+    }
+
+    /// Input tokens served from DeepSeek's context cache, billed at a lower rate.
+    pub fn cached_tokens(&self) -> usize {
+        self.prompt_cache_hit_tokens
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepSeekResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+
+    #[serde(default)]
+    reasoning_content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DeepSeekChoice {
+    message: DeepSeekResponseMessage,
+
+    #[serde(default)]
+    finish_reason: Option<String>,
+
+    #[serde(default)]
+    logprobs: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeepSeekChatResponse {
+    id: String,
+    model: String,
+    choices: Vec<DeepSeekChoice>,
+    usage: DeepSeekUsage,
+}
+
+impl DeepSeekChatResponse {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn usage(&self) -> &DeepSeekUsage {
+        &self.usage
+    }
+
+    pub fn content(&self) -> Option<&str> {
+        self.choices.first().and_then(|choice| choice.message.content.as_deref())
+    }
+
+    /// The chain of thought `deepseek-reasoner` returns separately from its answer.
+    pub fn reasoning_content(&self) -> Option<&str> {
+        self.choices.first().and_then(|choice| choice.message.reasoning_content.as_deref())
+    }
+
+    pub fn finish_reason(&self) -> Option<&str> {
+        self.choices.first().and_then(|choice| choice.finish_reason.as_deref())
+    }
+}
+
+#[derive(Deserialize)]
+struct DeepSeekModelEntry {
+    id: String,
+}
+
+#[derive(Deserialize)]
+struct DeepSeekModelList {
+    data: Vec<DeepSeekModelEntry>,
+}
+
+/// DeepSeek's chat completions API, for both `deepseek-chat` and `deepseek-reasoner`.
+///
+/// The reasoner returns its chain of thought in `reasoning_content`; `inference` returns only the answer, and
+/// `ReasoningModel::inference_with_reasoning` returns both. The reasoner accepts but ignores the sampling
+/// settings, which `compatibility` reports.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct DeepSeekModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl DeepSeekModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn is_reasoner(&self) -> bool {
+        self.model.contains("reasoner")
+    }
+
+    #[instrument(name = "DeepSeekModel::create", level = "trace", skip(self, prompt))]
+    pub async fn create(&self, prompt: &LanguageModelPrompt) -> Result<DeepSeekChatResponse, DeepSeekErrorResponse> {
+        let invalid = |message: String| DeepSeekErrorResponse { error_type: "invalid_request_error".into(), message, status: 400 };
+
+        let content = prompt.messages.iter()
+            .map(|message| match message {
+                Message::Text { text } => Ok(text.as_str()),
+                Message::Audio(_) => Err(invalid("audio content is not supported".to_string())),
+                Message::Image(_) => Err(invalid("image content is not supported".to_string())),
+                Message::Unknown(_) => Err(invalid("unknown content is not supported".to_string())),
+            })
+            .collect::<Result<Vec<&str>, DeepSeekErrorResponse>>()?
+            .join("\n\n");
+
+        let messages = super::chat_messages(prompt, &content);
+
+        let mut request = json!({
+            "model": self.model,
+            "messages": messages,
+            "max_tokens": prompt.max_tokens,
+            "temperature": prompt.temperature,
+        });
+        if let Some(top_p) = prompt.top_p {
+            request["top_p"] = json!(top_p);
+        }
+        if let Some(frequency_penalty) = prompt.frequency_penalty {
+            request["frequency_penalty"] = json!(frequency_penalty);
+        }
+        if let Some(presence_penalty) = prompt.presence_penalty {
+            request["presence_penalty"] = json!(presence_penalty);
+        }
+        if !prompt.stop_sequences.is_empty() {
+            request["stop"] = json!(prompt.stop_sequences);
+        }
+        if let Some(top_n) = prompt.logprobs.filter(|_| !self.is_reasoner()) {
+            request["logprobs"] = json!(true);
+            if top_n > 0 {
+                request["top_logprobs"] = json!(top_n);
+            }
+        }
+
+        let token = self.api_key.token().await
+            .map_err(|err| DeepSeekErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err), status: 401 })?;
+        let response = super::prompt_request(self.client.post(format!("{}/chat/completions", API_URL)), prompt)
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<DeepSeekChatResponse>().await
+                    .map_err(|err| DeepSeekErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: 200 }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<DeepSeekErrorEnvelope>().await {
+                    Ok(envelope) => Err(DeepSeekErrorResponse { status: status_code.as_u16(), ..envelope.error }),
+                    Err(err) => Err(DeepSeekErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: status_code.as_u16() })
+                },
+                status_code => Err(DeepSeekErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code), status: status_code.as_u16() })
+            },
+            Err(err) if err.is_timeout() => Err(DeepSeekErrorResponse { error_type: "timeout_error".into(), message: format!("{}", err), status: 408 }),
+            Err(err) => Err(DeepSeekErrorResponse { error_type: "request_error".into(), message: format!("{}", err), status: 0 })
+        }
+    }
+
+    /// One chat completion for `prompt`, after checking it against the model. DeepSeek counts the reasoning of
+    /// `deepseek-reasoner` as completion tokens, so that is the output usage recorded.
+    async fn chat(&self, prompt: &LanguageModelPrompt) -> Result<DeepSeekChatResponse, Error> {
+        let prompt = &*super::checked(self, prompt)?;
+
+        #[cfg(feature = "opentelemetry")]
+        let started = crate::time::Instant::now();
+
+        let response = self.create(prompt).await;
+        let usage = response.as_ref().ok().map(|response| (response.usage.prompt_tokens, response.usage.completion_tokens));
+
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::record_inference("deepseek", &self.model, started.elapsed(), usage, response.as_ref().err().map(|err| err.error_type.as_str()));
+
+        let response = response.map_err(|err| super::timeout_error(prompt.deadline, err.into_error()));
+        super::record_response(&response, response.as_ref().ok().map(|response| response.id.as_str()), usage);
+        response
+    }
+}
+
+impl ModelCatalog for DeepSeekModel {
+    #[instrument(name = "DeepSeekModel::list_models", level = "trace", skip(self))]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        let token = self.api_key.token().await?;
+        let response = self.client
+            .get(format!("{}/models", API_URL))
+            .bearer_auth(token.expose())
+            .send()
+            .await
+            .map_err(|err| Error::ModelResponse(format!("{}", err)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            error! { %status };
+            return Err(Error::ModelResponse(format!("{}", status)));
+        }
+
+        let models = response.json::<DeepSeekModelList>().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        Ok(models.data.into_iter().map(|entry| ModelInfo::new(entry.id, None)).collect())
+    }
+}
+
+impl BatchInference for DeepSeekModel {}
+
+impl LanguageModel for DeepSeekModel {
+    #[instrument(
+        name = "DeepSeekModel::inference",
+        level = "trace",
+        skip(self),
+        fields(
+            gen_ai.system = "deepseek",
+            gen_ai.request.model = self.model,
+            gen_ai.request.max_tokens = prompt.max_tokens,
+            gen_ai.request.temperature = prompt.temperature,
+            gen_ai.response.id = field::Empty,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+        ),
+    )]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let response = self.chat(&prompt).await?;
+
+        response.content()
+            .filter(|text| !text.is_empty())
+            .map(|text| Message::Text { text: text.to_string() })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        Capabilities::lookup(&self.model)
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = CompatibilityReport::default()
+            .ignore_if("top_k", &prompt.top_k)
+            .ignore_if("seed", &prompt.seed)
+            .ignore_if_any("logit_bias", &prompt.logit_bias)
+            .ignore_if("user", &prompt.user)
+            .ignore_if_nonempty("server_tools", &prompt.server_tools)
+            .ignore_if("continuations", &prompt.continuations);
+        let report = match self.is_reasoner() {
+            true => report
+                .ignore_if("top_p", &prompt.top_p)
+                .ignore_if("frequency_penalty", &prompt.frequency_penalty)
+                .ignore_if("presence_penalty", &prompt.presence_penalty)
+                .ignore_if("logprobs", &prompt.logprobs),
+            false => report,
+        };
+
+        match prompt.metadata.is_empty() {
+            true => report,
+            false => report.ignore("metadata"),
+        }
+    }
+}
+
+impl ReasoningModel for DeepSeekModel {
+    #[instrument(name = "DeepSeekModel::inference_with_reasoning", level = "trace", skip(self, prompt))]
+    async fn inference_with_reasoning(&self, prompt: LanguageModelPrompt) -> Result<ReasonedResponse, Error> {
+        let response = self.chat(&prompt).await?;
+
+        let message = response.content()
+            .filter(|text| !text.is_empty())
+            .map(|text| Message::Text { text: text.to_string() })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))?;
+        let reasoning = response.reasoning_content().filter(|reasoning| !reasoning.is_empty()).map(str::to_string);
+
+        Ok(ReasonedResponse::new(reasoning, message))
+    }
+}
+
+impl LogprobModel for DeepSeekModel {
+    /// Only `deepseek-chat` returns log probabilities; the reasoner fails with `Error::InvalidRequest`.
+    #[instrument(name = "DeepSeekModel::inference_with_logprobs", level = "trace", skip(self, prompt))]
+    async fn inference_with_logprobs(&self, prompt: LanguageModelPrompt) -> Result<LogprobResponse, Error> {
+        if self.is_reasoner() {
+            return Err(Error::InvalidRequest(format!("{} does not return logprobs", self.model)));
+        }
+
+        let logprobs = Some(prompt.logprobs.unwrap_or_default());
+        let response = self.chat(&LanguageModelPrompt { logprobs, ..prompt }).await?;
+
+        let message = response.content()
+            .map(|text| Message::Text { text: text.to_string() })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))?;
+
+        let parse = |value: &Value| Some(TokenLogprob::new(value.get("token")?.as_str()?, value.get("logprob")?.as_f64()?));
+        let tokens = response.choices.first()
+            .and_then(|choice| choice.logprobs.as_ref())
+            .and_then(|logprobs| logprobs.get("content"))
+            .and_then(Value::as_array)
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-logprobs")))?
+            .iter()
+            .filter_map(|token| {
+                let top = token.get("top_logprobs").and_then(Value::as_array).into_iter().flatten();
+                Some(top.filter_map(parse).fold(parse(token)?, TokenLogprob::alternative))
+            })
+            .collect::<Vec<TokenLogprob>>();
+
+        Ok(LogprobResponse::new(message, tokens))
+    }
+}
@@ -0,0 +1,223 @@
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+
+use super::{LanguageModelPrompt, Profile};
+
+/// The defaults `From` starts every prompt with; see `PromptDefaults::set_global`.
+static GLOBAL: RwLock<PromptDefaults> = RwLock::new(PromptDefaults::new());
+
+/// The settings a prompt starts with before its builders are called: `max_tokens`, `temperature`, `top_p`, the
+/// penalties and `auto_max_tokens`.
+///
+/// Prompts built with `From` start from the crate-wide defaults, `PromptDefaults::new()` (1024 tokens at
+/// temperature 0.63) unless replaced with `set_global`. On top of those, a `ModelRegistry` can apply its own
+/// defaults, optionally per model name, and `LanguageModelPrompt::defaults` chooses them for one prompt. Settings
+/// changed with the prompt's own builders are always kept.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PromptDefaults {
+    pub(crate) max_tokens: usize,
+    pub(crate) temperature: f32,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) top_p: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) frequency_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) presence_penalty: Option<f32>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) auto_max_tokens: Option<usize>,
+}
+
+impl Default for PromptDefaults {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The sampling settings of `profile`, with the default length settings.
+impl From<Profile> for PromptDefaults {
+    fn from(profile: Profile) -> Self {
+        Self {
+            temperature: profile.temperature(),
+            top_p: profile.top_p(),
+            frequency_penalty: profile.frequency_penalty(),
+            presence_penalty: profile.presence_penalty(),
+            ..Self::new()
+        }
+    }
+}
+
+impl PromptDefaults {
+    pub const fn new() -> Self {
+        Self {
+            max_tokens: 1024,
+            temperature: 0.63,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            auto_max_tokens: None,
+        }
+    }
+
+    /// The crate-wide defaults that `From` builds prompts with.
+    pub fn global() -> Self {
+        match GLOBAL.read() {
+            Ok(defaults) => *defaults,
+            Err(poisoned) => *poisoned.into_inner(),
+        }
+    }
+
+    /// Replaces the crate-wide defaults for prompts built from now on, e.g. once at startup from config.
+    pub fn set_global(defaults: Self) {
+        match GLOBAL.write() {
+            Ok(mut global) => *global = defaults,
+            Err(poisoned) => *poisoned.into_inner() = defaults,
+        }
+    }
+
+    /// Room for long answers with `Profile::Balanced` sampling: the context window's remainder, less 1024 tokens
+    /// of margin, on models with published limits and 4096 tokens elsewhere.
+    pub fn long_form() -> Self {
+        Self {
+            max_tokens: 4096,
+            auto_max_tokens: Some(1024),
+            ..Self::from(Profile::Balanced)
+        }
+    }
+
+    /// The named defaults: `default`, `long-form`, or a `Profile` such as `deterministic` or `creative`.
+    pub fn profile(name: &str) -> Option<Self> {
+        match name {
+            "default" => Some(Self::new()),
+            "long-form" | "long_form" => Some(Self::long_form()),
+            "deterministic" => Some(Profile::Deterministic.into()),
+            "balanced" => Some(Profile::Balanced.into()),
+            "creative" => Some(Profile::Creative.into()),
+            "code" => Some(Profile::Code.into()),
+            _ => None,
+        }
+    }
+
+    pub fn max_tokens(self, max_tokens: usize) -> Self {
+        Self {
+            max_tokens,
+            ..self
+        }
+    }
+
+    pub fn temperature(self, temperature: f32) -> Self {
+        Self {
+            temperature,
+            ..self
+        }
+    }
+
+    pub fn top_p(self, top_p: f32) -> Self {
+        Self {
+            top_p: Some(top_p),
+            ..self
+        }
+    }
+
+    pub fn frequency_penalty(self, frequency_penalty: f32) -> Self {
+        Self {
+            frequency_penalty: Some(frequency_penalty),
+            ..self
+        }
+    }
+
+    pub fn presence_penalty(self, presence_penalty: f32) -> Self {
+        Self {
+            presence_penalty: Some(presence_penalty),
+            ..self
+        }
+    }
+
+    /// See `LanguageModelPrompt::auto_max_tokens`.
+    pub fn auto_max_tokens(self, margin: usize) -> Self {
+        Self {
+            auto_max_tokens: Some(margin),
+            ..self
+        }
+    }
+
+    /// `prompt` with these defaults for every setting not changed with its own builders.
+    pub(crate) fn apply(self, prompt: LanguageModelPrompt) -> LanguageModelPrompt {
+        let explicit = prompt.explicit;
+
+        LanguageModelPrompt {
+            max_tokens: if explicit.max_tokens { prompt.max_tokens } else { self.max_tokens },
+            temperature: if explicit.temperature { prompt.temperature } else { self.temperature },
+            top_p: if explicit.top_p { prompt.top_p } else { self.top_p },
+            frequency_penalty: if explicit.frequency_penalty { prompt.frequency_penalty } else { self.frequency_penalty },
+            presence_penalty: if explicit.presence_penalty { prompt.presence_penalty } else { self.presence_penalty },
+            auto_max_tokens: if explicit.auto_max_tokens { prompt.auto_max_tokens } else { self.auto_max_tokens },
+            defaults: Some(self),
+            ..prompt
+        }
+    }
+}
+
+/// Which of the defaulted settings a prompt's builders have changed, so that applying defaults keeps them even
+/// when they happen to equal the previous default.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ExplicitSettings {
+    pub(crate) max_tokens: bool,
+    pub(crate) temperature: bool,
+    pub(crate) top_p: bool,
+    pub(crate) frequency_penalty: bool,
+    pub(crate) presence_penalty: bool,
+    pub(crate) auto_max_tokens: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_settings_set_explicitly() {
+        let prompt = LanguageModelPrompt::from("Hi")
+            .temperature(0.63)
+            .max_tokens(200)
+            .defaults(Profile::Creative.into());
+
+        assert_eq!(prompt.temperature, 0.63);
+        assert_eq!(prompt.max_tokens, 200);
+        assert_eq!(prompt.top_p, Some(0.95));
+        assert_eq!(prompt.frequency_penalty, Some(0.3));
+    }
+
+    #[test]
+    fn later_defaults_replace_earlier_ones() {
+        let prompt = LanguageModelPrompt::from("Hi")
+            .defaults(PromptDefaults::long_form())
+            .defaults(Profile::Deterministic.into());
+
+        assert_eq!(prompt.temperature, 0.0);
+        assert_eq!(prompt.max_tokens, 1024);
+        assert_eq!(prompt.auto_max_tokens, None);
+    }
+
+    #[test]
+    fn from_uses_the_global_defaults() {
+        PromptDefaults::set_global(PromptDefaults::new().max_tokens(777).temperature(0.11));
+        let prompt = LanguageModelPrompt::from("Hi");
+        PromptDefaults::set_global(PromptDefaults::new());
+
+        assert_eq!(prompt.max_tokens, 777);
+        assert_eq!(prompt.temperature, 0.11);
+        assert!(prompt.defaults.is_none());
+    }
+
+    #[test]
+    fn named_profiles_match_profile() {
+        assert_eq!(PromptDefaults::profile("creative"), Some(PromptDefaults::from(Profile::Creative)));
+        assert_eq!(PromptDefaults::profile("long-form"), Some(PromptDefaults::long_form()));
+        assert_eq!(PromptDefaults::profile("unknown"), None);
+    }
+}
@@ -0,0 +1,97 @@
+use tracing::{instrument, warn};
+
+use super::{Error, LanguageModel, LanguageModelPrompt, Message, ModerationModel, ModerationSource};
+
+#[derive(Clone, Debug)]
+pub struct GuardedModel<M, G> {
+    model: M,
+    guard: G,
+    screen_input: bool,
+    screen_output: bool,
+}
+
+impl<M, G> GuardedModel<M, G> {
+    pub fn new(model: M, guard: G) -> Self {
+        Self {
+            model,
+            guard,
+            screen_input: true,
+            screen_output: true,
+        }
+    }
+
+    pub fn screen_input(self, screen_input: bool) -> Self {
+        Self {
+            screen_input,
+            ..self
+        }
+    }
+
+    pub fn screen_output(self, screen_output: bool) -> Self {
+        Self {
+            screen_output,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn guard(&self) -> &G {
+        &self.guard
+    }
+}
+
+impl<M, G> GuardedModel<M, G>
+where
+    G: ModerationModel,
+{
+    async fn screen(&self, messages: &[Message], source: ModerationSource) -> Result<(), Error> {
+        let moderation = self.guard.moderate(messages, source).await?;
+
+        if moderation.flagged() {
+            warn! { ?source, categories = ?moderation.categories() };
+            Err(Error::ContentBlocked { categories: moderation.categories().to_vec() })
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<M, G> LanguageModel for GuardedModel<M, G>
+where
+    M: LanguageModel,
+    G: ModerationModel,
+{
+    #[instrument(name = "GuardedModel::inference", level = "trace", skip(self))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        if self.screen_input {
+            let mut messages = prompt.messages.clone();
+            if let Some(system) = &prompt.system {
+                messages.push(system.as_str().into());
+            }
+            self.screen(&messages, ModerationSource::Input).await?;
+        }
+
+        let response = self.model.inference(prompt).await?;
+
+        if self.screen_output {
+            self.screen(std::slice::from_ref(&response), ModerationSource::Output).await?;
+        }
+
+        Ok(response)
+    }
+
+    fn rate_limit(&self) -> Option<super::RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<super::Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> super::CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
@@ -0,0 +1,355 @@
+use anyhow::anyhow;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{error, field, instrument};
+
+use super::{
+    BatchInference,
+    BearerToken,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    TokenProvider,
+};
+
+const ROUTER_URL: &str = "https://router.huggingface.co/v1";
+
+/// Which request format the endpoint speaks.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HuggingFaceApi {
+    /// TGI's native `POST /generate` (`inputs`/`parameters` in, `generated_text` out).
+    Generate,
+
+    /// The OpenAI-compatible Messages API, `POST /v1/chat/completions`, served by TGI and the serverless router.
+    #[default]
+    Chat,
+}
+
+/// An error from TGI (`{"error": "...", "error_type": "..."}`) or the router (`{"error": {"message": "..."}}`).
+#[derive(Debug)]
+pub struct HuggingFaceErrorResponse {
+    error_type: String,
+    message: String,
+    status: u16,
+}
+
+impl HuggingFaceErrorResponse {
+    fn from_body(status: u16, body: &Value) -> Self {
+        let error = body.get("error");
+        let message = error.and_then(Value::as_str)
+            .or_else(|| error.and_then(|error| error.get("message")).and_then(Value::as_str))
+            .unwrap_or("unknown error");
+        let error_type = body.get("error_type").and_then(Value::as_str)
+            .or_else(|| error.and_then(|error| error.get("type")).and_then(Value::as_str))
+            .unwrap_or_default();
+
+        Self {
+            error_type: error_type.to_string(),
+            message: message.to_string(),
+            status,
+        }
+    }
+
+    pub fn error_type(&self) -> &str {
+        &self.error_type
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn into_error(self) -> Error {
+        match (self.status, self.error_type.as_str()) {
+            (429, _) => Error::RateLimited { retry_after: None },
+            (503, _) | (_, "overloaded") => Error::Overloaded(self.message),
+            (408, _) => Error::Timeout(self.message),
+            (401 | 403, _) => Error::AuthenticationFailed(self.message),
+            (400 | 422, _) if self.message.contains("must be <=") && self.message.contains("tokens") => Error::ContextLengthExceeded(self.message),
+            (400 | 404 | 422, _) | (_, "validation") => Error::InvalidRequest(self.message),
+            _ => Error::ModelResponse(self.message),
+        }
+    }
+}
+
+/// A Hugging Face hosted model or a self-hosted Text Generation Inference server.
+///
+/// `serverless` targets the Inference Providers router with a Hub model id; `tgi` targets any TGI base URL
+/// (a local container or a dedicated Inference Endpoint) in either of its request formats. The API key is
+/// sent as a bearer token when set.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HuggingFaceModel {
+    base_url: String,
+
+    #[serde(default)]
+    api: HuggingFaceApi,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    api_key: Option<BearerToken>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl HuggingFaceModel {
+    pub fn serverless(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            base_url: ROUTER_URL.to_string(),
+            api: HuggingFaceApi::Chat,
+            api_key: Some(api_key.into()),
+            model: Some(model.into()),
+            client: Client::new(),
+        }
+    }
+
+    pub fn tgi(base_url: impl Into<String>, api: HuggingFaceApi) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api,
+            api_key: None,
+            model: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn api_key(self, api_key: impl Into<BearerToken>) -> Self {
+        Self {
+            api_key: Some(api_key.into()),
+            ..self
+        }
+    }
+
+    /// Model id sent with chat requests; TGI serves a single model and ignores it.
+    pub fn model(self, model: impl Into<String>) -> Self {
+        Self {
+            model: Some(model.into()),
+            ..self
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn api(&self) -> HuggingFaceApi {
+        self.api
+    }
+
+    fn model_id(&self) -> &str {
+        self.model.as_deref().unwrap_or("tgi")
+    }
+
+    /// The request and its path under the base URL; the router already includes `/v1` in its base URL.
+    fn request(&self, prompt: &LanguageModelPrompt, text: String) -> (String, Value) {
+        match self.api {
+            HuggingFaceApi::Generate => {
+                let inputs = super::completion_text(prompt, &text);
+
+                let request = json!({
+                    "inputs": inputs,
+                    "parameters": {
+                        "max_new_tokens": prompt.max_tokens,
+                        "temperature": prompt.temperature,
+                        "top_p": prompt.top_p,
+                        "top_k": prompt.top_k,
+                        "seed": prompt.seed,
+                        "stop": prompt.stop_sequences,
+                        "return_full_text": false,
+                    },
+                });
+
+                (format!("{}/generate", self.base_url), request)
+            },
+            HuggingFaceApi::Chat => {
+                let messages = super::chat_messages(prompt, &text);
+
+                let mut request = json!({
+                    "model": self.model_id(),
+                    "messages": messages,
+                    "max_tokens": prompt.max_tokens,
+                    "temperature": prompt.temperature,
+                });
+                if let Some(top_p) = prompt.top_p {
+                    request["top_p"] = json!(top_p);
+                }
+                if let Some(seed) = prompt.seed {
+                    request["seed"] = json!(seed);
+                }
+                if let Some(frequency_penalty) = prompt.frequency_penalty {
+                    request["frequency_penalty"] = json!(frequency_penalty);
+                }
+                if let Some(presence_penalty) = prompt.presence_penalty {
+                    request["presence_penalty"] = json!(presence_penalty);
+                }
+                if !prompt.stop_sequences.is_empty() {
+                    request["stop"] = json!(prompt.stop_sequences);
+                }
+
+                let path = match self.base_url.ends_with("/v1") {
+                    true => format!("{}/chat/completions", self.base_url),
+                    false => format!("{}/v1/chat/completions", self.base_url),
+                };
+                (path, request)
+            },
+        }
+    }
+
+    #[instrument(name = "HuggingFaceModel::create", level = "trace", skip(self, prompt))]
+    pub async fn create(&self, prompt: &LanguageModelPrompt) -> Result<Value, HuggingFaceErrorResponse> {
+        let invalid = |kind: &str| HuggingFaceErrorResponse { error_type: "invalid_request_error".into(), message: format!("{} content is not supported", kind), status: 400 };
+
+        let text = prompt.messages.iter()
+            .map(|message| match message {
+                Message::Text { text } => Ok(text.as_str()),
+                Message::Audio(_) => Err(invalid("audio")),
+                Message::Image(_) => Err(invalid("image")),
+                Message::Unknown(_) => Err(invalid("unknown")),
+            })
+            .collect::<Result<Vec<&str>, HuggingFaceErrorResponse>>()?
+            .join("\n\n");
+
+        let (url, request) = self.request(prompt, text);
+
+        let mut request = super::prompt_request(self.client.post(url), prompt)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request);
+        if let Some(api_key) = &self.api_key {
+            let token = api_key.token().await
+                .map_err(|err| HuggingFaceErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err), status: 401 })?;
+            request = request.bearer_auth(token.expose());
+        }
+
+        match request.send().await {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<Value>().await
+                    .map_err(|err| HuggingFaceErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: 200 }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<Value>().await {
+                    Ok(body) => Err(HuggingFaceErrorResponse::from_body(status_code.as_u16(), &body)),
+                    Err(err) => Err(HuggingFaceErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: status_code.as_u16() })
+                },
+                status_code => Err(HuggingFaceErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code), status: status_code.as_u16() })
+            },
+            Err(err) if err.is_timeout() => Err(HuggingFaceErrorResponse { error_type: "timeout_error".into(), message: format!("{}", err), status: 408 }),
+            Err(err) => Err(HuggingFaceErrorResponse { error_type: "request_error".into(), message: format!("{}", err), status: 0 })
+        }
+    }
+}
+
+impl ModelCatalog for HuggingFaceModel {
+    /// Reports the configured model, or asks a TGI server which model it serves through `/info`.
+    #[instrument(name = "HuggingFaceModel::list_models", level = "trace", skip(self))]
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        if let Some(model) = &self.model {
+            return Ok(vec![ModelInfo::new(model, None)]);
+        }
+
+        let mut request = self.client.get(format!("{}/info", self.base_url));
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key.token().await?.expose());
+        }
+
+        let response = request.send().await.map_err(|err| Error::ModelResponse(format!("{}", err)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            error! { %status };
+            return Err(Error::ModelResponse(format!("{}", status)));
+        }
+
+        let info = response.json::<Value>().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        let model_id = info.get("model_id").and_then(Value::as_str).ok_or_else(|| Error::Unexpected(anyhow!("missing-model-id")))?;
+        Ok(vec![ModelInfo::new(model_id, None)])
+    }
+}
+
+impl BatchInference for HuggingFaceModel {}
+
+impl LanguageModel for HuggingFaceModel {
+    #[instrument(
+        name = "HuggingFaceModel::inference",
+        level = "trace",
+        skip(self),
+        fields(
+            gen_ai.system = "huggingface",
+            gen_ai.request.model = self.model_id(),
+            gen_ai.request.max_tokens = prompt.max_tokens,
+            gen_ai.request.temperature = prompt.temperature,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+        ),
+    )]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let prompt = super::checked(self, &prompt)?;
+
+        #[cfg(feature = "opentelemetry")]
+        let started = crate::time::Instant::now();
+
+        let response = self.create(&prompt).await;
+
+        let usage = response.as_ref().ok()
+            .and_then(|response| response.get("usage"))
+            .map(|usage| {
+                let count = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or_default() as usize;
+                (count("prompt_tokens"), count("completion_tokens"))
+            });
+
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::record_inference("huggingface", self.model_id(), started.elapsed(), usage, response.as_ref().err().map(|err| err.error_type.as_str()));
+
+        let response = response.map_err(|err| super::timeout_error(prompt.deadline, err.into_error()));
+        super::record_response(&response, None, usage);
+
+        let response = response?;
+        let text = match self.api {
+            HuggingFaceApi::Generate => response.pointer("/generated_text").or_else(|| response.pointer("/0/generated_text")),
+            HuggingFaceApi::Chat => response.pointer("/choices/0/message/content"),
+        };
+
+        text.and_then(Value::as_str)
+            .filter(|text| !text.is_empty())
+            .map(|text| Message::Text { text: text.to_string() })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.as_deref().and_then(Capabilities::lookup)
+    }
+
+    /// TGI's `/generate` has only a multiplicative `repetition_penalty`, which stands in for neither penalty,
+    /// and its Messages API has no `top_k`.
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = CompatibilityReport::default()
+            .ignore_if_any("logit_bias", &prompt.logit_bias)
+            .ignore_if("user", &prompt.user)
+            .ignore_if("logprobs", &prompt.logprobs)
+            .ignore_if_nonempty("server_tools", &prompt.server_tools)
+            .ignore_if("continuations", &prompt.continuations);
+        let report = match self.api {
+            HuggingFaceApi::Generate => report
+                .ignore_if("frequency_penalty", &prompt.frequency_penalty)
+                .ignore_if("presence_penalty", &prompt.presence_penalty),
+            HuggingFaceApi::Chat => report.ignore_if("top_k", &prompt.top_k),
+        };
+
+        match prompt.metadata.is_empty() {
+            true => report,
+            false => report.ignore("metadata"),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use async_trait::async_trait;
+use tracing::{instrument, warn};
+
+use super::{BatchInference, Capabilities, CompatibilityReport, Error, LanguageModel, LanguageModelPrompt, Message, RateLimit};
+
+/// Hooks run around every call made through a `LayeredModel`.
+#[async_trait(?Send)]
+pub trait ModelMiddleware: std::fmt::Debug {
+    /// Inspects or rewrites the prompt. Returning a message skips the model and every inner layer.
+    async fn before_request(&self, #[allow(unused)] prompt: &mut LanguageModelPrompt) -> Result<Option<Message>, Error> {
+        Ok(None)
+    }
+
+    async fn after_response(&self, #[allow(unused)] prompt: &LanguageModelPrompt, response: Message) -> Result<Message, Error> {
+        Ok(response)
+    }
+
+    /// Observes a failure from an inner layer or the model; returning `Ok` recovers from it.
+    async fn on_error(&self, #[allow(unused)] prompt: &LanguageModelPrompt, error: Error) -> Result<Message, Error> {
+        Err(error)
+    }
+}
+
+/// Rejects responses containing any of the prompt's banned phrases (case-insensitive), the portable
+/// fallback for providers that cannot suppress them during generation.
+#[derive(Clone, Debug, Default)]
+pub struct BannedPhrases;
+
+#[async_trait(?Send)]
+impl ModelMiddleware for BannedPhrases {
+    async fn after_response(&self, prompt: &LanguageModelPrompt, response: Message) -> Result<Message, Error> {
+        let text = match &response {
+            Message::Text { text } => text.to_lowercase(),
+            _ => return Ok(response),
+        };
+
+        let found = prompt.banned_phrases.iter()
+            .filter(|phrase| text.contains(&phrase.to_lowercase()))
+            .cloned()
+            .collect::<Vec<String>>();
+
+        if found.is_empty() {
+            Ok(response)
+        } else {
+            warn! { banned = ?found };
+            Err(Error::ContentBlocked { categories: found })
+        }
+    }
+}
+
+/// Composes middlewares around a model. The first layer added is the outermost: it sees the prompt first
+/// and the response last.
+#[derive(Debug)]
+pub struct LayeredModel<M> {
+    model: M,
+    layers: Vec<Box<dyn ModelMiddleware>>,
+}
+
+impl<M> LayeredModel<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            layers: Vec::new(),
+        }
+    }
+
+    pub fn layer(self, middleware: impl ModelMiddleware + 'static) -> Self {
+        let mut layers = self.layers;
+        layers.push(Box::new(middleware));
+
+        Self {
+            layers,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M> BatchInference for LayeredModel<M> where M: LanguageModel {}
+
+impl<M> LanguageModel for LayeredModel<M>
+where
+    M: LanguageModel,
+{
+    #[instrument(name = "LayeredModel::inference", level = "trace", skip(self))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let mut prompt = prompt;
+        let mut depth = self.layers.len();
+        let mut result = None;
+
+        for (index, layer) in self.layers.iter().enumerate() {
+            match layer.before_request(&mut prompt).await {
+                Ok(None) => {},
+                Ok(Some(message)) => {
+                    depth = index;
+                    result = Some(Ok(message));
+                    break;
+                },
+                Err(err) => {
+                    depth = index;
+                    result = Some(Err(err));
+                    break;
+                },
+            }
+        }
+
+        let mut result = match result {
+            Some(result) => result,
+            None => self.model.inference(prompt.clone()).await,
+        };
+
+        for layer in self.layers[..depth].iter().rev() {
+            result = match result {
+                Ok(message) => layer.after_response(&prompt, message).await,
+                Err(err) => layer.on_error(&prompt, err).await,
+            };
+        }
+
+        result
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
\ No newline at end of file
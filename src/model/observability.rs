@@ -0,0 +1,315 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::BuildHasher,
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use anyhow::anyhow;
+use chrono::{DateTime, SecondsFormat};
+use serde_json::{json, Value};
+
+use super::{Error, Transcript, TranscriptSink};
+use crate::{time, SecretString};
+
+/// `bytes` random bytes as lowercase hex, for trace, span and event ids.
+fn random_id(bytes: usize) -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let seed = COUNTER.fetch_add(1, Ordering::Relaxed) ^ time::unix_millis().rotate_left(20);
+    (0..bytes.div_ceil(8))
+        .flat_map(|index| RandomState::new().hash_one((seed, index)).to_le_bytes())
+        .take(bytes)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn iso(timestamp_ms: u64) -> String {
+    DateTime::from_timestamp_millis(timestamp_ms as i64).unwrap_or_default().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// The prompt as chat turns: the system prompt, few-shot examples and messages, non-text content as its type.
+fn chat(transcript: &Transcript) -> Vec<(&'static str, String)> {
+    let prompt = transcript.prompt();
+    let mut turns = Vec::new();
+
+    if let Some(system) = prompt["system"].as_str() {
+        turns.push(("system", system.to_string()));
+    }
+    for example in prompt["examples"].as_array().into_iter().flatten() {
+        turns.push(("user", example[0].as_str().unwrap_or_default().to_string()));
+        turns.push(("assistant", example[1].as_str().unwrap_or_default().to_string()));
+    }
+    for message in prompt["messages"].as_array().into_iter().flatten() {
+        let content = match message["text"].as_str() {
+            Some(text) => text.to_string(),
+            None => format!("[{}]", message["type"].as_str().unwrap_or("unknown")),
+        };
+        turns.push(("user", content));
+    }
+
+    turns
+}
+
+fn completion(transcript: &Transcript) -> Option<String> {
+    transcript.response().map(ToString::to_string)
+}
+
+fn error_message(transcript: &Transcript) -> Option<&str> {
+    transcript.error().and_then(|error| error["message"].as_str())
+}
+
+/// Sends transcripts to Langfuse's ingestion API as a trace with one generation, carrying the prompt, completion,
+/// usage, cost, user and session.
+#[derive(Clone, Debug)]
+pub struct LangfuseSink {
+    host: String,
+    public_key: String,
+    secret_key: SecretString,
+    name: String,
+    client: reqwest::Client,
+}
+
+impl LangfuseSink {
+    pub fn new(public_key: impl Into<String>, secret_key: impl Into<SecretString>) -> Self {
+        Self {
+            host: "https://cloud.langfuse.com".to_string(),
+            public_key: public_key.into(),
+            secret_key: secret_key.into(),
+            name: "inference".to_string(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// A self-hosted or regional deployment, e.g. `https://us.cloud.langfuse.com`.
+    pub fn host(self, host: impl Into<String>) -> Self {
+        Self {
+            host: host.into().trim_end_matches('/').to_string(),
+            ..self
+        }
+    }
+
+    /// The trace and generation name shown in Langfuse.
+    pub fn name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            ..self
+        }
+    }
+
+    pub fn client(self, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    fn batch(&self, transcript: &Transcript) -> Value {
+        let trace_id = random_id(16);
+        let start_time = iso(transcript.timestamp_ms());
+        let end_time = iso(transcript.timestamp_ms() + transcript.latency_ms());
+        let input = chat(transcript).into_iter().map(|(role, content)| json!({ "role": role, "content": content })).collect::<Vec<Value>>();
+        let output = completion(transcript);
+        let prompt = transcript.prompt();
+        let session_id = transcript.session_id();
+
+        let trace = json!({
+            "id": trace_id,
+            "timestamp": start_time,
+            "name": self.name,
+            "userId": prompt["user"],
+            "sessionId": session_id,
+            "input": input,
+            "output": output,
+            "metadata": transcript.metadata(),
+        });
+
+        let usage = transcript.usage();
+        let generation = json!({
+            "id": random_id(16),
+            "traceId": trace_id,
+            "name": self.name,
+            "startTime": start_time,
+            "endTime": end_time,
+            "model": transcript.model(),
+            "modelParameters": {
+                "max_tokens": prompt["max_tokens"],
+                "temperature": prompt["temperature"],
+                "top_p": prompt["top_p"],
+                "top_k": prompt["top_k"],
+                "seed": prompt["seed"],
+            },
+            "input": input,
+            "output": output,
+            "usageDetails": {
+                "input": usage.input_tokens(),
+                "output": usage.output_tokens(),
+                "total": usage.total_tokens(),
+            },
+            "costDetails": transcript.cost().map(|cost| json!({ "total": cost })),
+            "level": if transcript.error().is_some() { "ERROR" } else { "DEFAULT" },
+            "statusMessage": error_message(transcript),
+            "metadata": transcript.metadata(),
+        });
+
+        json!({
+            "batch": [
+                { "id": random_id(16), "timestamp": start_time, "type": "trace-create", "body": trace },
+                { "id": random_id(16), "timestamp": end_time, "type": "generation-create", "body": generation },
+            ],
+        })
+    }
+}
+
+impl TranscriptSink for LangfuseSink {
+    async fn write(&self, transcript: &Transcript) -> Result<(), Error> {
+        let response = self.client.post(format!("{}/api/public/ingestion", self.host))
+            .basic_auth(&self.public_key, Some(self.secret_key.expose()))
+            .json(&self.batch(transcript))
+            .send()
+            .await
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(Error::Unexpected(anyhow!("langfuse-ingestion-failed: {}", response.status()))),
+        }
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Option<Value> {
+    let value = match value {
+        Value::String(value) => json!({ "stringValue": value }),
+        Value::Number(value) if value.is_f64() => json!({ "doubleValue": value }),
+        Value::Number(value) => json!({ "intValue": value.to_string() }),
+        Value::Bool(value) => json!({ "boolValue": value }),
+        _ => return None,
+    };
+
+    Some(json!({ "key": key, "value": value }))
+}
+
+/// Exports transcripts as OpenTelemetry spans over OTLP/HTTP JSON, with the GenAI semantic-convention attributes
+/// and OpenLLMetry's `gen_ai.prompt.{n}` and `gen_ai.completion.{n}` content attributes, so the calls show up in
+/// dashboards built for those, including Langfuse's OTLP endpoint.
+#[derive(Clone, Debug)]
+pub struct OtlpSink {
+    endpoint: String,
+    service_name: String,
+    system: Option<String>,
+    headers: Vec<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl OtlpSink {
+    /// `endpoint` is the collector's base URL, e.g. `http://localhost:4318`; spans are posted to `/v1/traces`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into().trim_end_matches('/').to_string(),
+            service_name: env!("CARGO_PKG_NAME").to_string(),
+            system: None,
+            headers: Vec::new(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    pub fn service_name(self, service_name: impl Into<String>) -> Self {
+        Self {
+            service_name: service_name.into(),
+            ..self
+        }
+    }
+
+    /// The provider reported as `gen_ai.system`, e.g. `anthropic`.
+    pub fn system(self, system: impl Into<String>) -> Self {
+        Self {
+            system: Some(system.into()),
+            ..self
+        }
+    }
+
+    /// Adds a header to every export, e.g. `Authorization` for a hosted collector.
+    pub fn header(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut headers = self.headers;
+        headers.push((name.into(), value.into()));
+
+        Self {
+            headers,
+            ..self
+        }
+    }
+
+    pub fn client(self, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    fn traces(&self, transcript: &Transcript) -> Value {
+        let prompt = transcript.prompt();
+        let usage = transcript.usage();
+        let start = transcript.timestamp_ms() as u128 * 1_000_000;
+        let end = start + transcript.latency_ms() as u128 * 1_000_000;
+
+        let mut attributes = vec![
+            attribute("gen_ai.operation.name", json!("chat")),
+            attribute("gen_ai.system", json!(self.system)),
+            attribute("gen_ai.request.model", json!(transcript.model())),
+            attribute("gen_ai.request.max_tokens", prompt["max_tokens"].clone()),
+            attribute("gen_ai.request.temperature", prompt["temperature"].clone()),
+            attribute("gen_ai.request.top_p", prompt["top_p"].clone()),
+            attribute("gen_ai.request.top_k", prompt["top_k"].clone()),
+            attribute("gen_ai.usage.input_tokens", json!(usage.input_tokens())),
+            attribute("gen_ai.usage.output_tokens", json!(usage.output_tokens())),
+            attribute("gen_ai.usage.cost", json!(transcript.cost())),
+            attribute("session.id", json!(transcript.session_id())),
+            attribute("user.id", prompt["user"].clone()),
+            attribute("error.type", transcript.error().map(|error| error["code"].clone()).unwrap_or_default()),
+        ];
+        for (index, (role, content)) in chat(transcript).into_iter().enumerate() {
+            attributes.push(attribute(&format!("gen_ai.prompt.{}.role", index), json!(role)));
+            attributes.push(attribute(&format!("gen_ai.prompt.{}.content", index), json!(content)));
+        }
+        if let Some(completion) = completion(transcript) {
+            attributes.push(attribute("gen_ai.completion.0.role", json!("assistant")));
+            attributes.push(attribute("gen_ai.completion.0.content", json!(completion)));
+        }
+
+        let status = match error_message(transcript) {
+            Some(message) => json!({ "code": 2, "message": message }),
+            None => json!({ "code": 1 }),
+        };
+
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": [attribute("service.name", json!(self.service_name))] },
+                "scopeSpans": [{
+                    "scope": { "name": env!("CARGO_PKG_NAME"), "version": env!("CARGO_PKG_VERSION") },
+                    "spans": [{
+                        "traceId": random_id(16),
+                        "spanId": random_id(8),
+                        "name": format!("chat {}", transcript.model().unwrap_or_default()).trim_end(),
+                        "kind": 3,
+                        "startTimeUnixNano": start.to_string(),
+                        "endTimeUnixNano": end.to_string(),
+                        "attributes": attributes.into_iter().flatten().collect::<Vec<Value>>(),
+                        "status": status,
+                    }],
+                }],
+            }],
+        })
+    }
+}
+
+impl TranscriptSink for OtlpSink {
+    async fn write(&self, transcript: &Transcript) -> Result<(), Error> {
+        let request = self.client.post(format!("{}/v1/traces", self.endpoint));
+        let request = self.headers.iter().fold(request, |request, (name, value)| request.header(name, value));
+
+        let response = request.json(&self.traces(transcript)).send().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(Error::Unexpected(anyhow!("otlp-export-failed: {}", response.status()))),
+        }
+    }
+}
@@ -0,0 +1,637 @@
+use std::collections::HashMap;
+
+use reqwest::{multipart, Client, StatusCode};
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, instrument};
+
+use super::{Audio, AudioFormat, BearerToken, EmbeddingModel, Error, Image, ImageModel, ImageOptions, Message, Moderation, ModerationModel, ModerationSource, SpeechModel, TokenProvider, TranscriptionModel};
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIErrorResponse {
+    #[serde(rename = "type")]
+    error_type: String,
+
+    message: String,
+}
+
+impl OpenAIErrorResponse {
+    pub fn error_type(&self) -> &str {
+        &self.error_type
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn authentication(err: Error) -> Self {
+        Self { error_type: "authentication_error".into(), message: format!("{}", err) }
+    }
+}
+
+#[derive(Deserialize)]
+struct OpenAIErrorEnvelope {
+    error: OpenAIErrorResponse,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+enum OpenAIModerationInput {
+    #[serde(rename = "image_url")]
+    ImageUrl { image_url: OpenAIImageUrl },
+
+    #[serde(rename = "text")]
+    Text { text: String },
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct OpenAIImageUrl {
+    url: String,
+}
+
+#[derive(Serialize)]
+struct OpenAIModerationRequest {
+    model: String,
+    input: Vec<OpenAIModerationInput>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIModerationResult {
+    flagged: bool,
+    categories: HashMap<String, bool>,
+
+    #[serde(default)]
+    category_scores: HashMap<String, f64>,
+}
+
+impl OpenAIModerationResult {
+    pub fn flagged(&self) -> bool {
+        self.flagged
+    }
+
+    pub fn categories(&self) -> &HashMap<String, bool> {
+        &self.categories
+    }
+
+    pub fn category_scores(&self) -> &HashMap<String, f64> {
+        &self.category_scores
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAIImageRequest<'a> {
+    model: &'a str,
+    prompt: &'a str,
+    n: usize,
+    size: String,
+    response_format: &'a str,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIImageData {
+    b64_json: String,
+
+    #[serde(default)]
+    revised_prompt: Option<String>,
+}
+
+impl OpenAIImageData {
+    pub fn revised_prompt(&self) -> &Option<String> {
+        &self.revised_prompt
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIImageResponse {
+    created: u64,
+    data: Vec<OpenAIImageData>,
+}
+
+impl OpenAIImageResponse {
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+
+    pub fn data(&self) -> &Vec<OpenAIImageData> {
+        &self.data
+    }
+}
+
+#[derive(Serialize)]
+struct OpenAISpeechRequest<'a> {
+    model: &'a str,
+    input: &'a str,
+    voice: &'a str,
+    response_format: AudioFormat,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f32>,
+}
+
+#[derive(Serialize)]
+struct OpenAIEmbeddingRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+impl OpenAIEmbedding {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn embedding(&self) -> &[f32] {
+        &self.embedding
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIEmbeddingResponse {
+    model: String,
+    data: Vec<OpenAIEmbedding>,
+}
+
+impl OpenAIEmbeddingResponse {
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn data(&self) -> &Vec<OpenAIEmbedding> {
+        &self.data
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAITranscriptionResponse {
+    text: String,
+
+    #[serde(default)]
+    language: Option<String>,
+
+    #[serde(default)]
+    duration: Option<f64>,
+}
+
+impl OpenAITranscriptionResponse {
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn language(&self) -> &Option<String> {
+        &self.language
+    }
+
+    pub fn duration(&self) -> Option<f64> {
+        self.duration
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenAIModerationResponse {
+    id: String,
+    model: String,
+    results: Vec<OpenAIModerationResult>,
+}
+
+impl OpenAIModerationResponse {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn results(&self) -> &Vec<OpenAIModerationResult> {
+        &self.results
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenAIModerationModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl OpenAIModerationModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    #[instrument(name = "OpenAIModerationModel::create", level = "trace", skip(self))]
+    pub async fn create(&self, messages: &[Message]) -> Result<OpenAIModerationResponse, OpenAIErrorResponse> {
+        let request = OpenAIModerationRequest {
+            model: self.model.clone(),
+            input: messages.iter().filter_map(|message| match message {
+                Message::Audio(_) | Message::Unknown(_) => None,
+                Message::Image(image) => Some(OpenAIModerationInput::ImageUrl { image_url: OpenAIImageUrl { url: image.to_string() } }),
+                Message::Text { text } => Some(OpenAIModerationInput::Text { text: text.clone() }),
+            }).collect(),
+        };
+
+        let token = self.api_key.token().await.map_err(OpenAIErrorResponse::authentication)?;
+        let response = self.client
+            .post("https://api.openai.com/v1/moderations")
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<OpenAIModerationResponse>().await
+                    .map_err(|err| OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<OpenAIErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(OpenAIErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl ModerationModel for OpenAIModerationModel {
+    #[instrument(name = "OpenAIModerationModel::moderate", level = "trace", skip(self, messages))]
+    async fn moderate(&self, messages: &[Message], source: ModerationSource) -> Result<Moderation, Error> {
+        match self.create(messages).await {
+            Ok(response) => {
+                debug! { ?response };
+
+                let mut categories = response.results.iter()
+                    .flat_map(|result| result.categories.iter().filter(|(_, flagged)| **flagged).map(|(category, _)| category.clone()))
+                    .collect::<Vec<String>>();
+                categories.sort();
+                categories.dedup();
+
+                Ok(Moderation::new(response.results.iter().any(|result| result.flagged), categories))
+            },
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenAITranscriptionModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl OpenAITranscriptionModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    #[instrument(name = "OpenAITranscriptionModel::create", level = "trace", skip(self, audio))]
+    pub async fn create(&self, audio: &Audio, language: Option<&str>, prompt: Option<&str>) -> Result<OpenAITranscriptionResponse, OpenAIErrorResponse> {
+        let extension = audio.media_type().rsplit('/').next().unwrap_or("bin").trim_start_matches("x-");
+
+        let file = multipart::Part::bytes(audio.data())
+            .file_name(format!("audio.{}", if extension == "mpeg" { "mp3" } else { extension }))
+            .mime_str(audio.media_type())
+            .map_err(|err| OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })?;
+
+        let mut form = multipart::Form::new()
+            .text("model", self.model.clone())
+            .text("response_format", "json")
+            .part("file", file);
+        if let Some(language) = language {
+            form = form.text("language", language.to_string());
+        }
+        if let Some(prompt) = prompt {
+            form = form.text("prompt", prompt.to_string());
+        }
+
+        let token = self.api_key.token().await.map_err(OpenAIErrorResponse::authentication)?;
+        let response = self.client
+            .post("https://api.openai.com/v1/audio/transcriptions")
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .multipart(form)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<OpenAITranscriptionResponse>().await
+                    .map_err(|err| OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<OpenAIErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(OpenAIErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl TranscriptionModel for OpenAITranscriptionModel {
+    #[instrument(name = "OpenAITranscriptionModel::transcribe", level = "trace", skip(self, audio))]
+    async fn transcribe(&self, audio: &Audio, language: Option<&str>) -> Result<String, Error> {
+        match self.create(audio, language, None).await {
+            Ok(response) => {
+                debug! { ?response };
+                Ok(response.text)
+            },
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenAIEmbeddingModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dimensions: Option<usize>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl OpenAIEmbeddingModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            dimensions: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn dimensions(self, dimensions: usize) -> Self {
+        Self {
+            dimensions: Some(dimensions),
+            ..self
+        }
+    }
+
+    #[instrument(name = "OpenAIEmbeddingModel::create", level = "trace", skip(self, texts))]
+    pub async fn create(&self, texts: &[String]) -> Result<OpenAIEmbeddingResponse, OpenAIErrorResponse> {
+        let request = OpenAIEmbeddingRequest {
+            model: &self.model,
+            input: texts,
+            dimensions: self.dimensions,
+        };
+
+        let token = self.api_key.token().await.map_err(OpenAIErrorResponse::authentication)?;
+        let response = self.client
+            .post("https://api.openai.com/v1/embeddings")
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<OpenAIEmbeddingResponse>().await
+                    .map_err(|err| OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<OpenAIErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(OpenAIErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl EmbeddingModel for OpenAIEmbeddingModel {
+    #[instrument(name = "OpenAIEmbeddingModel::embed", level = "trace", skip(self, texts))]
+    async fn embed(&self, texts: &[String]) -> Result<Vec<Vec<f32>>, Error> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        match self.create(texts).await {
+            Ok(mut response) => {
+                response.data.sort_by_key(|embedding| embedding.index);
+                Ok(response.data.into_iter().map(|embedding| embedding.embedding).collect())
+            },
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenAISpeechModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    speed: Option<f32>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl OpenAISpeechModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            speed: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn speed(self, speed: f32) -> Self {
+        Self {
+            speed: Some(speed),
+            ..self
+        }
+    }
+
+    #[instrument(name = "OpenAISpeechModel::create", level = "trace", skip(self, text))]
+    pub async fn create(&self, text: &str, voice: &str, format: AudioFormat) -> Result<Vec<u8>, OpenAIErrorResponse> {
+        let request = OpenAISpeechRequest {
+            model: &self.model,
+            input: text,
+            voice,
+            response_format: format,
+            speed: self.speed,
+        };
+
+        let token = self.api_key.token().await.map_err(OpenAIErrorResponse::authentication)?;
+        let response = self.client
+            .post("https://api.openai.com/v1/audio/speech")
+            .bearer_auth(token.expose())
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.bytes().await
+                    .map(|bytes| bytes.to_vec())
+                    .map_err(|err| OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<OpenAIErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(OpenAIErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl SpeechModel for OpenAISpeechModel {
+    #[instrument(name = "OpenAISpeechModel::synthesize", level = "trace", skip(self, text))]
+    async fn synthesize(&self, text: &str, voice: &str, format: AudioFormat) -> Result<Audio, Error> {
+        match self.create(text, voice, format).await {
+            Ok(data) => Ok(Audio::new(format.media_type(), data)),
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct OpenAIImageModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl OpenAIImageModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    #[instrument(name = "OpenAIImageModel::create", level = "trace", skip(self))]
+    pub async fn create(&self, prompt: &str, options: &ImageOptions) -> Result<OpenAIImageResponse, OpenAIErrorResponse> {
+        if options.negative_prompt.is_some() || options.seed.is_some() {
+            debug!("negative prompt and seed are not supported by the images API and are ignored");
+        }
+
+        let request = OpenAIImageRequest {
+            model: &self.model,
+            prompt,
+            n: options.count,
+            size: format!("{}x{}", options.width, options.height),
+            response_format: "b64_json",
+        };
+
+        let token = self.api_key.token().await.map_err(OpenAIErrorResponse::authentication)?;
+        let response = self.client
+            .post("https://api.openai.com/v1/images/generations")
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<OpenAIImageResponse>().await
+                    .map_err(|err| OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<OpenAIErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(OpenAIErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(OpenAIErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(OpenAIErrorResponse { error_type: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl ImageModel for OpenAIImageModel {
+    #[instrument(name = "OpenAIImageModel::generate", level = "trace", skip(self))]
+    async fn generate(&self, prompt: &str, options: ImageOptions) -> Result<Vec<Image>, Error> {
+        match self.create(prompt, &options).await {
+            Ok(response) => response.data.into_iter()
+                .map(|data| Ok(Image::new("image/png", BASE64_STANDARD.decode(data.b64_json)?)))
+                .collect(),
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
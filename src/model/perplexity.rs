@@ -0,0 +1,428 @@
+use anyhow::anyhow;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use tracing::{field, instrument};
+
+use super::{
+    BatchInference,
+    BearerToken,
+    Capabilities,
+    Citation,
+    CitedResponse,
+    CitingModel,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    ReasonedResponse,
+    ReasoningModel,
+    TokenProvider,
+};
+
+const API_URL: &str = "https://api.perplexity.ai/chat/completions";
+
+/// Splits the `<think>` block the Sonar reasoning models put before their answer.
+fn split_reasoning(content: &str) -> (Option<String>, String) {
+    match content.trim_start().strip_prefix("<think>").and_then(|rest| rest.split_once("</think>")) {
+        Some((reasoning, answer)) => (Some(reasoning.trim().to_string()), answer.trim().to_string()),
+        None => (None, content.to_string()),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PerplexityErrorResponse {
+    #[serde(rename = "type", default)]
+    error_type: String,
+
+    message: String,
+
+    #[serde(skip)]
+    status: u16,
+}
+
+impl PerplexityErrorResponse {
+    pub fn error_type(&self) -> &str {
+        &self.error_type
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn into_error(self) -> Error {
+        match self.status {
+            429 => Error::RateLimited { retry_after: None },
+            503 => Error::Overloaded(self.message),
+            408 => Error::Timeout(self.message),
+            401 | 403 => Error::AuthenticationFailed(self.message),
+            400 | 422 if self.message.contains("context length") => Error::ContextLengthExceeded(self.message),
+            400 | 404 | 422 => Error::InvalidRequest(self.message),
+            _ => Error::ModelResponse(self.message),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct PerplexityErrorEnvelope {
+    error: PerplexityErrorResponse,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PerplexityUsage {
+    prompt_tokens: usize,
+    completion_tokens: usize,
+}
+
+impl PerplexityUsage {
+    pub fn input_tokens(&self) -> usize {
+        self.prompt_tokens
+    }
+
+    pub fn output_tokens(&self) -> usize {
+        self.completion_tokens
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PerplexitySearchResult {
+    url: String,
+
+    #[serde(default)]
+    title: Option<String>,
+
+    #[serde(default)]
+    date: Option<String>,
+
+    #[serde(default)]
+    snippet: Option<String>,
+}
+
+impl PerplexitySearchResult {
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    pub fn date(&self) -> Option<&str> {
+        self.date.as_deref()
+    }
+
+    pub fn snippet(&self) -> Option<&str> {
+        self.snippet.as_deref()
+    }
+}
+
+impl From<&PerplexitySearchResult> for Citation {
+    fn from(result: &PerplexitySearchResult) -> Self {
+        let citation = Citation::new(&result.url);
+        let citation = match &result.title {
+            Some(title) => citation.titled(title),
+            None => citation,
+        };
+        let citation = match &result.date {
+            Some(date) => citation.dated(date),
+            None => citation,
+        };
+
+        match &result.snippet {
+            Some(snippet) => citation.quoting(snippet),
+            None => citation,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PerplexityChoice {
+    message: PerplexityResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PerplexityChatResponse {
+    id: String,
+    model: String,
+    choices: Vec<PerplexityChoice>,
+    usage: PerplexityUsage,
+
+    #[serde(default)]
+    citations: Vec<String>,
+
+    #[serde(default)]
+    search_results: Vec<PerplexitySearchResult>,
+}
+
+impl PerplexityChatResponse {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    pub fn usage(&self) -> &PerplexityUsage {
+        &self.usage
+    }
+
+    pub fn content(&self) -> Option<&str> {
+        self.choices.first().and_then(|choice| choice.message.content.as_deref())
+    }
+
+    /// Source URLs, in the order the `[n]` markers in the content refer to them (1-based).
+    pub fn citation_urls(&self) -> &[String] {
+        &self.citations
+    }
+
+    pub fn search_results(&self) -> &[PerplexitySearchResult] {
+        &self.search_results
+    }
+
+    /// One citation per source URL, filled in with the matching search result's title, date and snippet.
+    pub fn citations(&self) -> Vec<Citation> {
+        match self.citations.is_empty() {
+            true => self.search_results.iter().map(Citation::from).collect(),
+            false => self.citations.iter()
+                .map(|url| match self.search_results.iter().find(|result| result.url == *url) {
+                    Some(result) => Citation::from(result),
+                    None => Citation::new(url),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Perplexity's Sonar models, which answer from a live web search and cite their sources.
+///
+/// `inference` returns the answer text with its `[n]` citation markers; `CitingModel::inference_with_citations`
+/// also returns the sources those markers refer to. For the reasoning models the `<think>` block is split off
+/// the answer and available through `ReasoningModel::inference_with_reasoning`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PerplexityModel {
+    api_key: BearerToken,
+    model: String,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    search_domain_filter: Vec<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    search_recency_filter: Option<String>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl PerplexityModel {
+    pub fn new(api_key: impl Into<BearerToken>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            search_domain_filter: Vec::new(),
+            search_recency_filter: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    /// Restricts (or, with a leading `-`, excludes) the domains searched.
+    pub fn search_domain_filter<I, S>(self, domains: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            search_domain_filter: domains.into_iter().map(Into::into).collect(),
+            ..self
+        }
+    }
+
+    /// Limits search results to the last `hour`, `day`, `week` or `month`.
+    pub fn search_recency_filter(self, recency: impl Into<String>) -> Self {
+        Self {
+            search_recency_filter: Some(recency.into()),
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(name = "PerplexityModel::create", level = "trace", skip(self, prompt))]
+    pub async fn create(&self, prompt: &LanguageModelPrompt) -> Result<PerplexityChatResponse, PerplexityErrorResponse> {
+        let invalid = |message: String| PerplexityErrorResponse { error_type: "invalid_request_error".into(), message, status: 400 };
+
+        let content = prompt.messages.iter()
+            .map(|message| match message {
+                Message::Text { text } => Ok(text.as_str()),
+                Message::Audio(_) => Err(invalid("audio content is not supported".to_string())),
+                Message::Image(_) => Err(invalid("image content is not supported".to_string())),
+                Message::Unknown(_) => Err(invalid("unknown content is not supported".to_string())),
+            })
+            .collect::<Result<Vec<&str>, PerplexityErrorResponse>>()?
+            .join("\n\n");
+
+        let messages = super::chat_messages(prompt, &content);
+
+        let mut request = json!({
+            "model": self.model,
+            "messages": messages,
+            "max_tokens": prompt.max_tokens,
+            "temperature": prompt.temperature,
+        });
+        if let Some(top_p) = prompt.top_p {
+            request["top_p"] = json!(top_p);
+        }
+        if let Some(top_k) = prompt.top_k {
+            request["top_k"] = json!(top_k);
+        }
+        if let Some(frequency_penalty) = prompt.frequency_penalty {
+            request["frequency_penalty"] = json!(frequency_penalty);
+        }
+        if let Some(presence_penalty) = prompt.presence_penalty {
+            request["presence_penalty"] = json!(presence_penalty);
+        }
+        if !self.search_domain_filter.is_empty() {
+            request["search_domain_filter"] = json!(self.search_domain_filter);
+        }
+        if let Some(recency) = &self.search_recency_filter {
+            request["search_recency_filter"] = json!(recency);
+        }
+
+        let token = self.api_key.token().await
+            .map_err(|err| PerplexityErrorResponse { error_type: "authentication_error".into(), message: format!("{}", err), status: 401 })?;
+        let response = super::prompt_request(self.client.post(API_URL), prompt)
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<PerplexityChatResponse>().await
+                    .map_err(|err| PerplexityErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: 200 }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<PerplexityErrorEnvelope>().await {
+                    Ok(envelope) => Err(PerplexityErrorResponse { status: status_code.as_u16(), ..envelope.error }),
+                    Err(err) => Err(PerplexityErrorResponse { error_type: "invalid_response_error".into(), message: format!("{}", err), status: status_code.as_u16() })
+                },
+                status_code => Err(PerplexityErrorResponse { error_type: "invalid_status_error".into(), message: format!("{}", status_code), status: status_code.as_u16() })
+            },
+            Err(err) if err.is_timeout() => Err(PerplexityErrorResponse { error_type: "timeout_error".into(), message: format!("{}", err), status: 408 }),
+            Err(err) => Err(PerplexityErrorResponse { error_type: "request_error".into(), message: format!("{}", err), status: 0 })
+        }
+    }
+
+    /// One Sonar chat completion for `prompt`, after checking it against the model. The response carries the
+    /// citations and search results the answer draws on.
+    async fn chat(&self, prompt: &LanguageModelPrompt) -> Result<PerplexityChatResponse, Error> {
+        let prompt = &*super::checked(self, prompt)?;
+
+        #[cfg(feature = "opentelemetry")]
+        let started = crate::time::Instant::now();
+
+        let response = self.create(prompt).await;
+        let usage = response.as_ref().ok().map(|response| (response.usage.prompt_tokens, response.usage.completion_tokens));
+
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::record_inference("perplexity", &self.model, started.elapsed(), usage, response.as_ref().err().map(|err| err.error_type.as_str()));
+
+        let response = response.map_err(|err| super::timeout_error(prompt.deadline, err.into_error()));
+        super::record_response(&response, response.as_ref().ok().map(|response| response.id.as_str()), usage);
+        response
+    }
+
+    async fn answer(&self, prompt: &LanguageModelPrompt) -> Result<(Option<String>, Message, PerplexityChatResponse), Error> {
+        let response = self.chat(prompt).await?;
+
+        let (reasoning, answer) = response.content()
+            .map(split_reasoning)
+            .filter(|(_, answer)| !answer.is_empty())
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))?;
+
+        Ok((reasoning, Message::Text { text: answer }, response))
+    }
+}
+
+impl ModelCatalog for PerplexityModel {
+    /// Perplexity has no model listing endpoint, so only the configured model is reported.
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        Ok(vec![ModelInfo::new(&self.model, None)])
+    }
+}
+
+impl BatchInference for PerplexityModel {}
+
+impl LanguageModel for PerplexityModel {
+    #[instrument(
+        name = "PerplexityModel::inference",
+        level = "trace",
+        skip(self),
+        fields(
+            gen_ai.system = "perplexity",
+            gen_ai.request.model = self.model,
+            gen_ai.request.max_tokens = prompt.max_tokens,
+            gen_ai.request.temperature = prompt.temperature,
+            gen_ai.response.id = field::Empty,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+        ),
+    )]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        self.answer(&prompt).await.map(|(_, message, _)| message)
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        Capabilities::lookup(&self.model)
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = CompatibilityReport::default()
+            .ignore_if("seed", &prompt.seed)
+            .ignore_if_any("logit_bias", &prompt.logit_bias)
+            .ignore_if("user", &prompt.user)
+            .ignore_if("logprobs", &prompt.logprobs)
+            .ignore_if_nonempty("stop_sequences", &prompt.stop_sequences)
+            .ignore_if_nonempty("server_tools", &prompt.server_tools)
+            .ignore_if("continuations", &prompt.continuations);
+
+        match prompt.metadata.is_empty() {
+            true => report,
+            false => report.ignore("metadata"),
+        }
+    }
+}
+
+impl CitingModel for PerplexityModel {
+    #[instrument(name = "PerplexityModel::inference_with_citations", level = "trace", skip(self, prompt))]
+    async fn inference_with_citations(&self, prompt: LanguageModelPrompt) -> Result<CitedResponse, Error> {
+        let (_, message, response) = self.answer(&prompt).await?;
+        Ok(CitedResponse::new(message, response.citations()))
+    }
+}
+
+impl ReasoningModel for PerplexityModel {
+    #[instrument(name = "PerplexityModel::inference_with_reasoning", level = "trace", skip(self, prompt))]
+    async fn inference_with_reasoning(&self, prompt: LanguageModelPrompt) -> Result<ReasonedResponse, Error> {
+        let (reasoning, message, _) = self.answer(&prompt).await?;
+        Ok(ReasonedResponse::new(reasoning, message))
+    }
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use super::{
+    BatchInference,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    RateLimit,
+};
+
+/// A language model from another crate, built from configs by its provider name like the built-in providers.
+///
+/// Put `#[async_trait]` and `#[typetag::serde(name = "acme")]` on the impl; a config with `provider = "acme"`
+/// then deserializes into `LanguageModel::Plugin` through the rest of the config, without a variant of its own.
+#[async_trait]
+#[typetag::serde(tag = "provider")]
+pub trait PluginModel: std::fmt::Debug + Send + Sync {
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error>;
+
+    /// Called by `LanguageModel::from_config` once the model is deserialized, e.g. to warm up a client.
+    async fn initialize(&self) {}
+
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        Ok(Vec::new())
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        None
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        None
+    }
+
+    fn compatibility(&self, #[allow(unused)] prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        CompatibilityReport::default()
+    }
+}
+
+/// A shared `PluginModel`, cloneable like the built-in providers.
+#[derive(Clone, Debug)]
+pub struct Plugin(Arc<dyn PluginModel>);
+
+impl Plugin {
+    pub fn new(model: impl PluginModel + 'static) -> Self {
+        Self(Arc::new(model))
+    }
+
+    pub fn model(&self) -> &dyn PluginModel {
+        self.0.as_ref()
+    }
+
+    pub async fn initialize(&self) {
+        self.0.initialize().await
+    }
+}
+
+impl Serialize for Plugin {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Plugin {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Box::<dyn PluginModel>::deserialize(deserializer).map(|model| Self(Arc::from(model)))
+    }
+}
+
+impl BatchInference for Plugin {}
+
+impl ModelCatalog for Plugin {
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        self.0.list_models().await
+    }
+}
+
+impl LanguageModel for Plugin {
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        self.0.inference(prompt).await
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.0.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.0.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.0.compatibility(prompt)
+    }
+}
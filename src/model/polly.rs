@@ -0,0 +1,68 @@
+use anyhow::anyhow;
+use aws_sdk_polly::{
+    types::{Engine, OutputFormat, VoiceId},
+    Client,
+};
+use serde::Serialize;
+use tracing::{error, instrument};
+
+use super::{aws::sdk_config, Audio, AudioFormat, AwsConfig, Error, SpeechModel};
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PollySpeechModel {
+    engine: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aws_config: Option<AwsConfig>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl PollySpeechModel {
+    pub async fn new(engine: impl Into<String>, aws_config: Option<AwsConfig>) -> Self {
+        let client = Client::new(&sdk_config(&aws_config).await);
+
+        Self {
+            engine: engine.into(),
+            aws_config,
+            client,
+        }
+    }
+
+    pub fn engine(&self) -> &str {
+        &self.engine
+    }
+}
+
+impl SpeechModel for PollySpeechModel {
+    #[instrument(name = "PollySpeechModel::synthesize", level = "trace", skip(self, text))]
+    async fn synthesize(&self, text: &str, voice: &str, format: AudioFormat) -> Result<Audio, Error> {
+        let output_format = match format {
+            AudioFormat::Mp3 => OutputFormat::Mp3,
+            AudioFormat::Pcm => OutputFormat::Pcm,
+            format => return Err(Error::Unexpected(anyhow!("unsupported-audio-format: {:?}", format))),
+        };
+
+        let response = self.client.synthesize_speech()
+            .engine(Engine::from(self.engine.as_str()))
+            .voice_id(VoiceId::from(voice))
+            .output_format(output_format)
+            .text(text)
+            .send()
+            .await
+            .map_err(|err| {
+                error! { ?err };
+                Error::ModelResponse(format!("{}", err))
+            })?;
+
+        let data = response.audio_stream
+            .collect()
+            .await
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?
+            .into_bytes()
+            .to_vec();
+
+        Ok(Audio::new(format.media_type(), data))
+    }
+}
@@ -0,0 +1,300 @@
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use tokio::time::{sleep_until, Instant};
+use tracing::{debug, instrument};
+
+use super::{
+    estimate_tokens,
+    BatchInference,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    Message,
+    RateLimit,
+};
+
+/// A token bucket refilled continuously at `per_minute`, holding at most `burst`.
+#[derive(Debug)]
+struct Bucket {
+    per_minute: f64,
+    burst: f64,
+    available: f64,
+    updated: Instant,
+}
+
+impl Bucket {
+    fn new(per_minute: u32, burst: Option<u32>) -> Self {
+        let burst = burst.unwrap_or(per_minute) as f64;
+
+        Self {
+            per_minute: per_minute as f64,
+            burst,
+            available: burst,
+            updated: Instant::now(),
+        }
+    }
+
+    fn set_burst(&mut self, burst: u32) {
+        self.burst = burst as f64;
+        self.available = self.available.min(self.burst);
+    }
+
+    /// Fails when the bucket can never hold `amount`, including every amount when it never refills.
+    fn check(&self, amount: f64, unit: &str) -> Result<(), Error> {
+        match (self.per_minute, self.burst) {
+            (per_minute, _) if per_minute <= 0.0 => {
+                Err(Error::InvalidRequest(format!("a rate limit of 0 {} per minute admits no requests", unit)))
+            },
+            (_, burst) if amount > burst => {
+                Err(Error::InvalidRequest(format!("~{} {} exceeds the rate limit burst of {} {}", amount, unit, burst, unit)))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.available = (self.available + elapsed * self.per_minute / 60.0).min(self.burst);
+        self.updated = now;
+    }
+
+    /// Time until `amount` is available, zero when it already is.
+    fn wait(&self, amount: f64) -> Duration {
+        match amount <= self.available {
+            true => Duration::ZERO,
+            false => Duration::from_secs_f64((amount - self.available) * 60.0 / self.per_minute),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Buckets {
+    requests: Option<Bucket>,
+    tokens: Option<Bucket>,
+    request_burst: Option<u32>,
+    token_burst: Option<u32>,
+    paused_until: Option<Instant>,
+}
+
+/// Requests-per-minute and tokens-per-minute budgets enforced on the client side.
+///
+/// Clones share the same buckets, so give every model that draws on one API key a clone of the same limiter.
+/// Without limits set, every request passes immediately. A limit or burst of 0 fails every request.
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn configure(self, configure: impl FnOnce(&mut Buckets)) -> Self {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            configure(&mut buckets);
+        }
+        self
+    }
+
+    /// Starts with a full minute's worth of requests, which is also the largest burst unless `request_burst` is set.
+    pub fn requests_per_minute(self, requests_per_minute: u32) -> Self {
+        self.configure(|buckets| buckets.requests = Some(Bucket::new(requests_per_minute, buckets.request_burst)))
+    }
+
+    pub fn tokens_per_minute(self, tokens_per_minute: u32) -> Self {
+        self.configure(|buckets| buckets.tokens = Some(Bucket::new(tokens_per_minute, buckets.token_burst)))
+    }
+
+    /// Applies whether it is set before or after `requests_per_minute`.
+    pub fn request_burst(self, burst: u32) -> Self {
+        self.configure(|buckets| {
+            buckets.request_burst = Some(burst);
+            if let Some(bucket) = &mut buckets.requests {
+                bucket.set_burst(burst);
+            }
+        })
+    }
+
+    /// Applies whether it is set before or after `tokens_per_minute`.
+    pub fn token_burst(self, burst: u32) -> Self {
+        self.configure(|buckets| {
+            buckets.token_burst = Some(burst);
+            if let Some(bucket) = &mut buckets.tokens {
+                bucket.set_burst(burst);
+            }
+        })
+    }
+
+    /// Waits until one request and `tokens` tokens are available, then takes them. Fails when either bucket can
+    /// never hold that much.
+    pub async fn acquire(&self, tokens: usize) -> Result<(), Error> {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+                let now = Instant::now();
+
+                if let Some(bucket) = &buckets.requests {
+                    bucket.check(1.0, "requests")?;
+                }
+                if let Some(bucket) = &buckets.tokens {
+                    bucket.check(tokens as f64, "tokens")?;
+                }
+
+                let paused = buckets.paused_until.map(|until| until.saturating_duration_since(now)).unwrap_or_default();
+                let Buckets { requests, tokens: token_bucket, .. } = &mut *buckets;
+                let wait = [(requests, 1.0), (token_bucket, tokens as f64)].into_iter()
+                    .filter_map(|(bucket, amount)| bucket.as_mut().map(|bucket| {
+                        bucket.refill(now);
+                        bucket.wait(amount)
+                    }))
+                    .fold(paused, Duration::max);
+
+                if wait.is_zero() {
+                    if let Some(bucket) = &mut buckets.requests {
+                        bucket.available -= 1.0;
+                    }
+                    if let Some(bucket) = &mut buckets.tokens {
+                        bucket.available -= tokens as f64;
+                    }
+                    return Ok(());
+                }
+                wait
+            };
+
+            debug! { ?wait, tokens, "waiting for rate limit" };
+            sleep_until(Instant::now() + wait).await;
+        }
+    }
+
+    /// Returns tokens taken by `acquire` that the request did not use.
+    pub fn release(&self, tokens: usize) {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            if let Some(bucket) = &mut buckets.tokens {
+                bucket.available = (bucket.available + tokens as f64).min(bucket.burst);
+            }
+        }
+    }
+
+    /// Holds every request back for `duration`, e.g. after the provider answered with a rate-limit error.
+    pub fn pause(&self, duration: Duration) {
+        if let Ok(mut buckets) = self.buckets.lock() {
+            let until = Instant::now() + duration;
+            buckets.paused_until = Some(buckets.paused_until.map_or(until, |paused_until| paused_until.max(until)));
+        }
+    }
+}
+
+/// The estimated tokens of a text response. Images, audio and unknown blocks are not charged, since their
+/// serialized form says nothing about the tokens they cost.
+fn output_tokens(message: &Message) -> usize {
+    match message {
+        Message::Text { text } => estimate_tokens(text),
+        _ => 0,
+    }
+}
+
+/// Waits for `limiter` before every request. Each request is charged the estimated prompt tokens plus
+/// `max_tokens`, and the unused part of `max_tokens` is returned once the response arrives. A rate-limit error
+/// with a `retry_after` pauses every model sharing the limiter.
+#[derive(Clone, Debug)]
+pub struct RateLimitedModel<M> {
+    model: M,
+    limiter: RateLimiter,
+}
+
+impl<M> RateLimitedModel<M> {
+    pub fn new(model: M, limiter: RateLimiter) -> Self {
+        Self {
+            model,
+            limiter,
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn limiter(&self) -> &RateLimiter {
+        &self.limiter
+    }
+}
+
+impl<M> BatchInference for RateLimitedModel<M>
+where
+    M: LanguageModel,
+{}
+
+impl<M> LanguageModel for RateLimitedModel<M>
+where
+    M: LanguageModel,
+{
+    #[instrument(name = "RateLimitedModel::inference", level = "trace", skip(self, prompt))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let prompt = prompt.fit_max_tokens(self.model.capabilities()).into_owned();
+        let input_tokens = prompt.input_tokens();
+        let max_tokens = prompt.max_tokens;
+
+        self.limiter.acquire(input_tokens + max_tokens).await?;
+
+        let response = self.model.inference(prompt).await;
+        match &response {
+            Ok(message) => self.limiter.release(max_tokens.saturating_sub(output_tokens(message))),
+            Err(Error::RateLimited { retry_after: Some(retry_after) }) => self.limiter.pause(*retry_after),
+            Err(_) => self.limiter.release(max_tokens),
+        }
+
+        response
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn burst_applies_in_either_order() {
+        let before = RateLimiter::new().token_burst(5).tokens_per_minute(100);
+        let after = RateLimiter::new().tokens_per_minute(100).token_burst(5);
+
+        for limiter in [before, after] {
+            assert!(limiter.acquire(5).await.is_ok());
+            assert!(matches!(limiter.acquire(6).await, Err(Error::InvalidRequest(_))));
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_zero_limits() {
+        let requests = RateLimiter::new().requests_per_minute(0);
+        let tokens = RateLimiter::new().tokens_per_minute(0);
+
+        assert!(matches!(requests.acquire(0).await, Err(Error::InvalidRequest(_))));
+        assert!(matches!(tokens.acquire(0).await, Err(Error::InvalidRequest(_))));
+    }
+
+    #[test]
+    fn charges_only_text_output() {
+        let image = Message::Image(crate::Image::new("image/png", vec![0; 4096]));
+
+        assert_eq!(output_tokens(&image), 0);
+        assert!(output_tokens(&Message::Text { text: "Hello there, how are you?".to_string() }) > 0);
+    }
+}
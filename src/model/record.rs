@@ -0,0 +1,188 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{debug, instrument};
+
+use super::{BatchInference, Capabilities, CompatibilityReport, Error, LanguageModel, LanguageModelPrompt, Message};
+
+/// The parts of a prompt that affect the response, in a stable JSON form.
+fn request(prompt: &LanguageModelPrompt) -> Value {
+    let mut logit_bias = prompt.logit_bias.iter().collect::<Vec<(&u32, &f32)>>();
+    logit_bias.sort_by_key(|(token, _)| **token);
+
+    let mut request = json!({
+        "model": prompt.model,
+        "system": prompt.system,
+        "messages": prompt.messages,
+        "max_tokens": prompt.max_tokens,
+        "temperature": prompt.temperature,
+        "top_p": prompt.top_p,
+        "frequency_penalty": prompt.frequency_penalty,
+        "presence_penalty": prompt.presence_penalty,
+        "logit_bias": logit_bias,
+        "banned_phrases": prompt.banned_phrases,
+        "stop_sequences": prompt.stop_sequences,
+    });
+
+    // Added after fixtures were first recorded, so only present when set to keep existing keys stable.
+    if let Some(top_k) = prompt.top_k {
+        request["top_k"] = json!(top_k);
+    }
+    if let Some(seed) = prompt.seed {
+        request["seed"] = json!(seed);
+    }
+    if !prompt.server_tools.is_empty() {
+        request["server_tools"] = json!(prompt.server_tools);
+    }
+    if let Some(continuations) = prompt.continuations {
+        request["continuations"] = json!(continuations);
+    }
+    if !prompt.examples.is_empty() {
+        request["examples"] = json!(prompt.examples);
+    }
+
+    request
+}
+
+fn fingerprint(request: &Value) -> String {
+    Sha256::digest(request.to_string().as_bytes()).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// One request/response pair in a fixture file, stored one per line.
+#[derive(Deserialize, Serialize)]
+struct Interaction {
+    key: String,
+    request: Value,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    response: Option<Value>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Passes every call through to `model` and appends the request and its response (or error) to a JSON-lines
+/// fixture that `ReplayModel` can serve back.
+#[derive(Debug)]
+pub struct RecordingModel<M> {
+    model: M,
+    path: PathBuf,
+}
+
+impl<M> RecordingModel<M> {
+    pub fn new(model: M, path: impl Into<PathBuf>) -> Self {
+        Self { model, path: path.into() }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    async fn append(&self, interaction: &Interaction) -> Result<(), Error> {
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        }
+
+        let mut line = serde_json::to_vec(interaction).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        line.push(b'\n');
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.write_all(&line).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.flush().await.map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+}
+
+impl<M> BatchInference for RecordingModel<M> where M: LanguageModel {}
+
+impl<M> LanguageModel for RecordingModel<M>
+where
+    M: LanguageModel,
+{
+    #[instrument(name = "RecordingModel::inference", level = "trace", skip(self, prompt), fields(path = %self.path.display()))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let request = request(&prompt);
+        let response = self.model.inference(prompt).await;
+
+        self.append(&Interaction {
+            key: fingerprint(&request),
+            request,
+            response: response.as_ref().ok().map(|message| serde_json::to_value(message).unwrap_or_default()),
+            error: response.as_ref().err().map(|err| err.to_string()),
+        }).await?;
+
+        response
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
+
+/// Serves responses from a fixture written by `RecordingModel`, without calling any provider.
+///
+/// Prompts are matched on every setting that affects the response. A prompt recorded several times is answered
+/// with its recorded responses in order; recorded errors are returned as `Error::ModelResponse`.
+#[derive(Debug, Default)]
+pub struct ReplayModel {
+    interactions: Mutex<HashMap<String, VecDeque<Result<Value, String>>>>,
+}
+
+impl ReplayModel {
+    #[instrument(name = "ReplayModel::load", level = "trace", skip(path), fields(path = %path.as_ref().display()))]
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        let mut interactions = HashMap::<String, VecDeque<Result<Value, String>>>::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let interaction = serde_json::from_str::<Interaction>(line).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+            let response = match (interaction.response, interaction.error) {
+                (Some(response), _) => Ok(response),
+                (None, error) => Err(error.unwrap_or_default()),
+            };
+            interactions.entry(interaction.key).or_default().push_back(response);
+        }
+        debug! { prompts = interactions.len() };
+
+        Ok(Self { interactions: Mutex::new(interactions) })
+    }
+}
+
+impl BatchInference for ReplayModel {}
+
+impl LanguageModel for ReplayModel {
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let key = fingerprint(&request(&prompt));
+
+        let response = {
+            let mut interactions = self.interactions.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+            interactions.get_mut(&key).and_then(VecDeque::pop_front)
+        };
+
+        match response {
+            Some(Ok(response)) => serde_json::from_value(response).map_err(|err| Error::Unexpected(anyhow!(err))),
+            Some(Err(err)) => Err(Error::ModelResponse(err)),
+            None => Err(Error::Unexpected(anyhow!("no-recorded-response: {}", key))),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use anyhow::anyhow;
+use aws_sdk_sagemakerruntime::{primitives::Blob, Client};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::{debug, error, instrument, warn};
+
+use super::{
+    aws::sdk_config,
+    AwsConfig,
+    BatchInference,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    LogprobModel,
+    LogprobResponse,
+    Message,
+    TokenLogprob,
+};
+
+/// Joins the text messages of a prompt, rejecting content the endpoint cannot accept.
+fn prompt_text(prompt: &LanguageModelPrompt) -> Result<String, Error> {
+    prompt.messages.iter()
+        .map(|message| match message {
+            Message::Text { text } => Ok(text.as_str()),
+            Message::Audio(_) => Err(Error::UnsupportedContent { kind: "audio".to_string() }),
+            Message::Image(_) => Err(Error::UnsupportedContent { kind: "image".to_string() }),
+            Message::Unknown(_) => Err(Error::UnsupportedContent { kind: "unknown".to_string() }),
+        })
+        .collect::<Result<Vec<&str>, Error>>()
+        .map(|texts| texts.join("\n\n"))
+}
+
+/// How prompts are encoded into endpoint requests and completions read back from responses.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "format", rename_all = "snake_case")]
+pub enum SageMakerMapping {
+    /// Hugging Face Text Generation Inference containers (`inputs`/`parameters` in, `generated_text` out).
+    Tgi,
+
+    /// OpenAI-compatible chat containers such as vLLM or LMI (`messages` in, `choices[0].message.content` out).
+    Messages,
+
+    /// Arbitrary JSON. String values `{{prompt}}`, `{{system}}`, `{{max_tokens}}`, `{{temperature}}`, `{{top_p}}`,
+    /// `{{top_k}}`, `{{seed}}`, `{{user}}`, `{{stop_sequences}}` and `{{examples}}` (a list of `{"input", "output"}`
+    /// objects) in `request` are substituted, and the completion is read at the JSON pointer `response`.
+    Template { request: Value, response: String },
+}
+
+impl SageMakerMapping {
+    fn request(&self, prompt: &LanguageModelPrompt) -> Result<Value, Error> {
+        let text = prompt_text(prompt)?;
+
+        Ok(match self {
+            Self::Tgi => {
+                let inputs = super::completion_text(prompt, &text);
+
+                json!({
+                    "inputs": inputs,
+                    "parameters": {
+                        "max_new_tokens": prompt.max_tokens,
+                        "temperature": prompt.temperature,
+                        "top_p": prompt.top_p,
+                        "top_k": prompt.top_k,
+                        "seed": prompt.seed,
+                        "stop": prompt.stop_sequences,
+                        "return_full_text": false,
+                        "details": prompt.logprobs.is_some(),
+                        "top_n_tokens": prompt.logprobs.filter(|top_n| *top_n > 0),
+                    },
+                })
+            },
+            Self::Messages => {
+                let messages = super::chat_messages(prompt, &text);
+
+                json!({
+                    "messages": messages,
+                    "max_tokens": prompt.max_tokens,
+                    "temperature": prompt.temperature,
+                    "top_p": prompt.top_p,
+                    "frequency_penalty": prompt.frequency_penalty,
+                    "presence_penalty": prompt.presence_penalty,
+                    "logit_bias": prompt.logit_bias,
+                    "seed": prompt.seed,
+                    "user": prompt.user,
+                    "stop": prompt.stop_sequences,
+                    "logprobs": prompt.logprobs.is_some(),
+                    "top_logprobs": prompt.logprobs.filter(|top_n| *top_n > 0),
+                })
+            },
+            Self::Template { request, .. } => Self::substitute(request, prompt, &text),
+        })
+    }
+
+    fn substitute(template: &Value, prompt: &LanguageModelPrompt, text: &str) -> Value {
+        match template {
+            Value::String(value) => match value.as_str() {
+                "{{prompt}}" => json!(text),
+                "{{system}}" => json!(prompt.system),
+                "{{max_tokens}}" => json!(prompt.max_tokens),
+                "{{temperature}}" => json!(prompt.temperature),
+                "{{top_p}}" => json!(prompt.top_p),
+                "{{top_k}}" => json!(prompt.top_k),
+                "{{seed}}" => json!(prompt.seed),
+                "{{user}}" => json!(prompt.user),
+                "{{stop_sequences}}" => json!(prompt.stop_sequences),
+                "{{examples}}" => prompt.examples.iter().map(|(input, output)| json!({ "input": input, "output": output })).collect(),
+                value => json!(value.replace("{{prompt}}", text).replace("{{system}}", prompt.system.as_deref().unwrap_or_default())),
+            },
+            Value::Array(values) => Value::Array(values.iter().map(|value| Self::substitute(value, prompt, text)).collect()),
+            Value::Object(map) => Value::Object(map.iter().map(|(key, value)| (key.clone(), Self::substitute(value, prompt, text))).collect()),
+            value => value.clone(),
+        }
+    }
+
+    /// TGI has only a multiplicative `repetition_penalty`, which is not a faithful stand-in for either penalty.
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = match self {
+            Self::Tgi => CompatibilityReport::default()
+                .ignore_if("frequency_penalty", &prompt.frequency_penalty)
+                .ignore_if("presence_penalty", &prompt.presence_penalty)
+                .ignore_if_any("logit_bias", &prompt.logit_bias)
+                .ignore_if("user", &prompt.user),
+            Self::Messages => CompatibilityReport::default().ignore_if("top_k", &prompt.top_k),
+            Self::Template { .. } => CompatibilityReport::default().ignore_if("logprobs", &prompt.logprobs),
+        };
+
+        report
+            .ignore_if_nonempty("server_tools", &prompt.server_tools)
+            .ignore_if("continuations", &prompt.continuations)
+            .ignore_if_nonempty("headers", &prompt.headers)
+            .ignore_if_nonempty("query", &prompt.query)
+    }
+
+    /// Token log probabilities from a TGI `details` block or an OpenAI-style `logprobs.content` list.
+    fn logprobs(&self, response: &Value) -> Option<Vec<TokenLogprob>> {
+        let parse = |value: &Value, key: &str| Some(TokenLogprob::new(value.get(key)?.as_str()?, value.get("logprob")?.as_f64()?));
+
+        match self {
+            Self::Tgi => {
+                let details = response.pointer("/0/details").or_else(|| response.pointer("/details"))?;
+                let top_tokens = details.get("top_tokens").and_then(Value::as_array).cloned().unwrap_or_default();
+
+                details.get("tokens")?.as_array()?.iter()
+                    .enumerate()
+                    .map(|(index, token)| {
+                        let top = top_tokens.get(index).and_then(Value::as_array).into_iter().flatten();
+                        Some(top.filter_map(|value| parse(value, "text")).fold(parse(token, "text")?, TokenLogprob::alternative))
+                    })
+                    .collect()
+            },
+            Self::Messages => response.pointer("/choices/0/logprobs/content")?.as_array()?.iter()
+                .map(|token| {
+                    let top = token.get("top_logprobs").and_then(Value::as_array).into_iter().flatten();
+                    Some(top.filter_map(|value| parse(value, "token")).fold(parse(token, "token")?, TokenLogprob::alternative))
+                })
+                .collect(),
+            Self::Template { .. } => None,
+        }
+    }
+
+    fn completion(&self, response: &Value) -> Option<String> {
+        match self {
+            Self::Tgi => response.pointer("/0/generated_text").or_else(|| response.pointer("/generated_text")),
+            Self::Messages => response.pointer("/choices/0/message/content"),
+            Self::Template { response: pointer, .. } => response.pointer(pointer),
+        }.and_then(Value::as_str).map(str::to_string)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SageMakerModel {
+    endpoint_name: String,
+    mapping: SageMakerMapping,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    aws_config: Option<AwsConfig>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl SageMakerModel {
+    pub async fn new(endpoint_name: impl Into<String>, mapping: SageMakerMapping, aws_config: Option<AwsConfig>) -> Self {
+        let client = Client::new(&sdk_config(&aws_config).await);
+
+        Self {
+            endpoint_name: endpoint_name.into(),
+            mapping,
+            aws_config,
+            client,
+        }
+    }
+
+    pub fn endpoint_name(&self) -> &str {
+        &self.endpoint_name
+    }
+
+    pub fn mapping(&self) -> &SageMakerMapping {
+        &self.mapping
+    }
+}
+
+impl BatchInference for SageMakerModel {}
+
+impl SageMakerModel {
+    async fn invoke(&self, prompt: &LanguageModelPrompt) -> Result<Value, Error> {
+        prompt.validate()?;
+        let compatibility = self.mapping.compatibility(prompt);
+        if !compatibility.is_supported() {
+            warn! { ignored = ?compatibility.ignored() };
+        }
+
+        let request = self.mapping.request(prompt)?;
+
+        let invocation = self.client.invoke_endpoint()
+            .endpoint_name(&self.endpoint_name)
+            .content_type("application/json")
+            .accept("application/json")
+            .body(Blob::new(serde_json::to_vec(&request).map_err(|err| Error::Unexpected(anyhow!(err)))?))
+            .send();
+        let response = super::within_deadline(prompt, invocation).await
+            .ok_or(Error::DeadlineExceeded)?
+            .map_err(|err| {
+                error! { ?err };
+                Error::ModelResponse(format!("{}", err))
+            })?;
+
+        let body = response.body().map(|body| body.as_ref()).unwrap_or_default();
+        let response = serde_json::from_slice::<Value>(body).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        debug! { ?response };
+
+        Ok(response)
+    }
+}
+
+impl LanguageModel for SageMakerModel {
+    #[instrument(name = "SageMakerModel::inference", level = "trace", skip(self))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let response = self.invoke(&prompt).await?;
+
+        self.mapping.completion(&response)
+            .map(|text| Message::Text { text })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.mapping.compatibility(prompt)
+    }
+}
+
+impl LogprobModel for SageMakerModel {
+    #[instrument(name = "SageMakerModel::inference_with_logprobs", level = "trace", skip(self))]
+    async fn inference_with_logprobs(&self, prompt: LanguageModelPrompt) -> Result<LogprobResponse, Error> {
+        if matches!(self.mapping, SageMakerMapping::Template { .. }) {
+            return Err(Error::InvalidRequest("template mappings do not return logprobs".to_string()));
+        }
+
+        let logprobs = Some(prompt.logprobs.unwrap_or_default());
+        let response = self.invoke(&LanguageModelPrompt { logprobs, ..prompt }).await?;
+
+        let message = self.mapping.completion(&response)
+            .map(|text| Message::Text { text })
+            .ok_or_else(|| Error::Unexpected(anyhow!("no-content")))?;
+        let tokens = self.mapping.logprobs(&response).ok_or_else(|| Error::Unexpected(anyhow!("no-logprobs")))?;
+
+        Ok(LogprobResponse::new(message, tokens))
+    }
+}
@@ -0,0 +1,171 @@
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use tracing::{error, instrument, warn};
+
+use super::{BearerToken, Error, Image, ImageModel, ImageOptions, TokenProvider};
+
+#[derive(Debug, Deserialize)]
+pub struct StabilityErrorResponse {
+    name: String,
+    message: String,
+}
+
+impl StabilityErrorResponse {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[derive(Serialize)]
+struct StabilityTextPrompt<'a> {
+    text: &'a str,
+    weight: f32,
+}
+
+#[derive(Serialize)]
+struct StabilityRequest<'a> {
+    text_prompts: Vec<StabilityTextPrompt<'a>>,
+    width: u32,
+    height: u32,
+    samples: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u64>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cfg_scale: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StabilityArtifact {
+    base64: String,
+    seed: u64,
+    finish_reason: String,
+}
+
+impl StabilityArtifact {
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn finish_reason(&self) -> &str {
+        &self.finish_reason
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StabilityResponse {
+    artifacts: Vec<StabilityArtifact>,
+}
+
+impl StabilityResponse {
+    pub fn artifacts(&self) -> &Vec<StabilityArtifact> {
+        &self.artifacts
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct StabilityModel {
+    api_key: BearerToken,
+    engine: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cfg_scale: Option<f32>,
+
+    #[serde(skip)]
+    client: Client,
+}
+
+impl StabilityModel {
+    pub fn new(api_key: impl Into<BearerToken>, engine: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            engine: engine.into(),
+            cfg_scale: None,
+            client: Client::new(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn cfg_scale(self, cfg_scale: f32) -> Self {
+        Self {
+            cfg_scale: Some(cfg_scale),
+            ..self
+        }
+    }
+
+    #[instrument(name = "StabilityModel::create", level = "trace", skip(self))]
+    pub async fn create(&self, prompt: &str, options: &ImageOptions) -> Result<StabilityResponse, StabilityErrorResponse> {
+        let mut text_prompts = vec![StabilityTextPrompt { text: prompt, weight: 1.0 }];
+        if let Some(negative_prompt) = &options.negative_prompt {
+            text_prompts.push(StabilityTextPrompt { text: negative_prompt, weight: -1.0 });
+        }
+
+        let request = StabilityRequest {
+            text_prompts,
+            width: options.width,
+            height: options.height,
+            samples: options.count,
+            seed: options.seed,
+            cfg_scale: self.cfg_scale,
+        };
+
+        let token = self.api_key.token().await
+            .map_err(|err| StabilityErrorResponse { name: "authentication_error".into(), message: format!("{}", err) })?;
+        let response = self.client
+            .post(format!("https://api.stability.ai/v1/generation/{}/text-to-image", self.engine))
+            .bearer_auth(token.expose())
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<StabilityResponse>().await
+                    .map_err(|err| StabilityErrorResponse { name: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<StabilityErrorResponse>().await {
+                    Ok(error) => Err(error),
+                    Err(err) => Err(StabilityErrorResponse { name: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(StabilityErrorResponse { name: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) => Err(StabilityErrorResponse { name: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+}
+
+impl ImageModel for StabilityModel {
+    #[instrument(name = "StabilityModel::generate", level = "trace", skip(self))]
+    async fn generate(&self, prompt: &str, options: ImageOptions) -> Result<Vec<Image>, Error> {
+        match self.create(prompt, &options).await {
+            Ok(response) => response.artifacts.into_iter()
+                .filter(|artifact| {
+                    let success = artifact.finish_reason == "SUCCESS";
+                    if !success {
+                        warn! { finish_reason = artifact.finish_reason, seed = artifact.seed };
+                    }
+                    success
+                })
+                .map(|artifact| Ok(Image::new("image/png", BASE64_STANDARD.decode(artifact.base64)?)))
+                .collect(),
+            Err(err) => {
+                error! { ?err };
+                Err(Error::ModelResponse(err.message))
+            }
+        }
+    }
+}
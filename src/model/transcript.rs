@@ -0,0 +1,323 @@
+use std::{collections::BTreeMap, future::Future};
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
+
+use anyhow::anyhow;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::{instrument, warn};
+
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::{fs, io::AsyncWriteExt, sync::Mutex};
+
+use super::{estimate_tokens, BatchInference, Capabilities, CompatibilityReport, Error, LanguageModel, LanguageModelPrompt, Message, RateLimit};
+use crate::{time, Usage};
+
+/// The metadata key read as the session a call belongs to, e.g. set with `LanguageModelPrompt::metadata`.
+pub const SESSION_ID: &str = "session_id";
+
+/// One inference call as written to a `TranscriptSink`.
+///
+/// Providers report token usage only on their own responses, so `usage` is estimated with `estimate_tokens`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Transcript {
+    timestamp_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<String>,
+
+    prompt: Value,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    response: Option<Message>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<Value>,
+
+    usage: Usage,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cost: Option<f64>,
+
+    latency_ms: u64,
+
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    metadata: BTreeMap<String, String>,
+}
+
+impl Transcript {
+    pub fn timestamp_ms(&self) -> u64 {
+        self.timestamp_ms
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+
+    pub fn prompt(&self) -> &Value {
+        &self.prompt
+    }
+
+    pub fn response(&self) -> Option<&Message> {
+        self.response.as_ref()
+    }
+
+    /// The failure as `{"code", "message"}`, with the code from `Error::code`.
+    pub fn error(&self) -> Option<&Value> {
+        self.error.as_ref()
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// The estimated usage priced with `TranscriptModel::prices`.
+    pub fn cost(&self) -> Option<f64> {
+        self.cost
+    }
+
+    pub fn latency_ms(&self) -> u64 {
+        self.latency_ms
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+
+    pub fn session_id(&self) -> Option<&str> {
+        self.metadata.get(SESSION_ID).map(String::as_str)
+    }
+}
+
+/// Where a `TranscriptModel` writes its transcripts, e.g. for audit logs or offline analysis.
+pub trait TranscriptSink {
+    fn write(&self, transcript: &Transcript) -> impl Future<Output = Result<(), Error>>;
+}
+
+/// Appends transcripts to a JSON-lines file, creating it and its directory if needed.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub struct JsonlSink {
+    path: PathBuf,
+    lock: Mutex<()>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl JsonlSink {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into(), lock: Mutex::new(()) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl TranscriptSink for JsonlSink {
+    async fn write(&self, transcript: &Transcript) -> Result<(), Error> {
+        let mut line = serde_json::to_vec(transcript).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        line.push(b'\n');
+
+        // Concurrent calls must not interleave their lines.
+        let _guard = self.lock.lock().await;
+
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        }
+
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.write_all(&line).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        file.flush().await.map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+}
+
+/// Posts each transcript as a JSON body to a webhook URL.
+#[derive(Clone, Debug)]
+pub struct WebhookSink {
+    url: String,
+    headers: Vec<(String, String)>,
+    client: reqwest::Client,
+}
+
+impl WebhookSink {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            url: url.into(),
+            headers: Vec::new(),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Adds a header to every post, e.g. the collector's auth header.
+    pub fn header(self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        let mut headers = self.headers;
+        headers.push((name.into(), value.into()));
+
+        Self {
+            headers,
+            ..self
+        }
+    }
+
+    pub fn client(self, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+}
+
+impl TranscriptSink for WebhookSink {
+    async fn write(&self, transcript: &Transcript) -> Result<(), Error> {
+        let request = self.headers.iter().fold(self.client.post(&self.url), |request, (name, value)| request.header(name, value));
+
+        let response = request.json(transcript).send().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        match response.status().is_success() {
+            true => Ok(()),
+            false => Err(Error::Unexpected(anyhow!("transcript-webhook-failed: {}", response.status()))),
+        }
+    }
+}
+
+/// The parts of a prompt worth keeping in a transcript.
+fn prompt_json(prompt: &LanguageModelPrompt) -> Value {
+    json!({
+        "system": prompt.system,
+        "examples": prompt.examples,
+        "messages": prompt.messages,
+        "max_tokens": prompt.max_tokens,
+        "temperature": prompt.temperature,
+        "top_p": prompt.top_p,
+        "top_k": prompt.top_k,
+        "seed": prompt.seed,
+        "stop_sequences": prompt.stop_sequences,
+        "user": prompt.user,
+    })
+}
+
+/// Passes every call through to `model` and writes a `Transcript` of it to `sink`: the prompt, the response or
+/// error, estimated usage and latency.
+///
+/// A failed write is logged and the call's result returned regardless, unless the sink is `required`, in which
+/// case the call fails with the sink's error.
+#[derive(Debug)]
+pub struct TranscriptModel<M, S> {
+    model: M,
+    sink: S,
+    name: Option<String>,
+    prices: Option<(f64, f64)>,
+    required: bool,
+}
+
+impl<M, S> TranscriptModel<M, S> {
+    pub fn new(model: M, sink: S) -> Self {
+        Self {
+            model,
+            sink,
+            name: None,
+            prices: None,
+            required: false,
+        }
+    }
+
+    /// The model name recorded when the prompt does not route to one by name.
+    pub fn name(self, name: impl Into<String>) -> Self {
+        Self {
+            name: Some(name.into()),
+            ..self
+        }
+    }
+
+    /// Prices per million input and output tokens, in any currency, for the transcripts' `cost`.
+    pub fn prices(self, input_per_million: f64, output_per_million: f64) -> Self {
+        Self {
+            prices: Some((input_per_million, output_per_million)),
+            ..self
+        }
+    }
+
+    pub fn required(self, required: bool) -> Self {
+        Self {
+            required,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+
+    pub fn sink(&self) -> &S {
+        &self.sink
+    }
+}
+
+impl<M, S> BatchInference for TranscriptModel<M, S>
+where
+    M: LanguageModel,
+    S: TranscriptSink,
+{}
+
+impl<M, S> LanguageModel for TranscriptModel<M, S>
+where
+    M: LanguageModel,
+    S: TranscriptSink,
+{
+    #[instrument(name = "TranscriptModel::inference", level = "trace", skip(self, prompt))]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let timestamp_ms = time::unix_millis();
+        let input_tokens = prompt.input_tokens();
+        let model = prompt.model.clone().or_else(|| self.name.clone());
+        let metadata = prompt.metadata.clone();
+        let request = prompt_json(&prompt);
+
+        let started = time::Instant::now();
+        let result = self.model.inference(prompt).await;
+        let latency_ms = started.elapsed().as_millis() as u64;
+
+        let output_tokens = match &result {
+            Ok(Message::Text { text }) => estimate_tokens(text),
+            _ => 0,
+        };
+        let usage = Usage::new(input_tokens as u64, output_tokens as u64);
+        let transcript = Transcript {
+            timestamp_ms,
+            model,
+            prompt: request,
+            response: result.as_ref().ok().cloned(),
+            error: result.as_ref().err().map(|err| json!({ "code": err.code(), "message": err.to_string() })),
+            usage,
+            cost: self.prices.map(|(input, output)| (usage.input_tokens() as f64 * input + usage.output_tokens() as f64 * output) / 1_000_000.0),
+            latency_ms,
+            metadata,
+        };
+
+        if let Err(err) = self.sink.write(&transcript).await {
+            if self.required {
+                return Err(err);
+            }
+            warn! { %err, "transcript could not be written" };
+        }
+
+        result
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
@@ -0,0 +1,213 @@
+use std::{
+    collections::{BTreeMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use super::{Error, Transcript, TranscriptSink, SESSION_ID};
+use crate::{time, Usage};
+
+/// How `UsageTracker::summary` groups calls.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UsageGroup {
+    /// By the transcript's model name, `unknown` when it has none.
+    Model,
+
+    /// By the `SESSION_ID` metadata; calls without one are left out.
+    Session,
+
+    /// By each other metadata entry as a `key:value` tag, so a call counts once for every tag it has.
+    Tag,
+}
+
+/// Calls and usage for one model, session or tag.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct UsageSummary {
+    key: String,
+    calls: u64,
+    errors: u64,
+    usage: Usage,
+    cost: f64,
+}
+
+impl UsageSummary {
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors
+    }
+
+    pub fn usage(&self) -> Usage {
+        self.usage
+    }
+
+    /// The sum of the calls' estimated costs; calls without prices add nothing.
+    pub fn cost(&self) -> f64 {
+        self.cost
+    }
+
+    fn add(&mut self, entry: &Entry) {
+        self.calls += 1;
+        self.errors += entry.failed as u64;
+        self.usage = self.usage + entry.usage;
+        self.cost += entry.cost.unwrap_or_default();
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Entry {
+    timestamp_ms: u64,
+    model: Option<String>,
+    session_id: Option<String>,
+    tags: Vec<String>,
+    usage: Usage,
+    cost: Option<f64>,
+    failed: bool,
+}
+
+impl Entry {
+    fn keys(&self, group: UsageGroup) -> Vec<String> {
+        match group {
+            UsageGroup::Model => vec![self.model.clone().unwrap_or_else(|| "unknown".to_string())],
+            UsageGroup::Session => self.session_id.iter().cloned().collect(),
+            UsageGroup::Tag => self.tags.clone(),
+        }
+    }
+}
+
+/// Aggregates token usage and estimated cost per model, session and tag at runtime.
+///
+/// Use it as the sink of a `TranscriptModel`, or `record` transcripts from another sink. Clones share their
+/// records, so keep one to query while the model owns the other. Records older than the retention period, a day
+/// by default, are discarded.
+#[derive(Clone, Debug)]
+pub struct UsageTracker {
+    entries: Arc<Mutex<VecDeque<Entry>>>,
+    retention: Duration,
+}
+
+impl Default for UsageTracker {
+    fn default() -> Self {
+        Self {
+            entries: Arc::default(),
+            retention: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn retention(self, retention: Duration) -> Self {
+        Self {
+            retention,
+            ..self
+        }
+    }
+
+    pub fn record(&self, transcript: &Transcript) {
+        let entry = Entry {
+            timestamp_ms: transcript.timestamp_ms(),
+            model: transcript.model().map(str::to_string),
+            session_id: transcript.session_id().map(str::to_string),
+            tags: transcript.metadata().iter()
+                .filter(|(key, _)| key.as_str() != SESSION_ID)
+                .map(|(key, value)| format!("{}:{}", key, value))
+                .collect(),
+            usage: transcript.usage(),
+            cost: transcript.cost(),
+            failed: transcript.error().is_some(),
+        };
+
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        let cutoff = time::unix_millis().saturating_sub(self.retention.as_millis() as u64);
+        while entries.front().is_some_and(|entry| entry.timestamp_ms < cutoff) {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+
+    /// Calls within the last `window`, or everything retained without one.
+    fn entries(&self, window: Option<Duration>) -> Vec<Entry> {
+        let cutoff = window.map(|window| time::unix_millis().saturating_sub(window.as_millis() as u64)).unwrap_or_default();
+        let Ok(entries) = self.entries.lock() else {
+            return Vec::new();
+        };
+
+        entries.iter().filter(|entry| entry.timestamp_ms >= cutoff).cloned().collect()
+    }
+
+    /// Usage per model, session or tag over the last `window`, most tokens first.
+    pub fn summary(&self, group: UsageGroup, window: Option<Duration>) -> Vec<UsageSummary> {
+        let mut summaries = BTreeMap::<String, UsageSummary>::new();
+        for entry in self.entries(window) {
+            for key in entry.keys(group) {
+                summaries.entry(key.clone()).or_insert_with(|| UsageSummary { key, ..UsageSummary::default() }).add(&entry);
+            }
+        }
+
+        let mut summaries = summaries.into_values().collect::<Vec<UsageSummary>>();
+        summaries.sort_by_key(|summary| std::cmp::Reverse(summary.usage.total_tokens()));
+        summaries
+    }
+
+    /// Usage of every call over the last `window`, keyed `total`.
+    pub fn total(&self, window: Option<Duration>) -> UsageSummary {
+        let mut total = UsageSummary { key: "total".to_string(), ..UsageSummary::default() };
+        self.entries(window).iter().for_each(|entry| total.add(entry));
+        total
+    }
+
+    pub fn to_json(&self, group: UsageGroup, window: Option<Duration>) -> Value {
+        serde_json::to_value(self.summary(group, window)).unwrap_or_default()
+    }
+
+    /// One row per key under a `key,calls,errors,input_tokens,output_tokens,total_tokens,cost` header.
+    pub fn to_csv(&self, group: UsageGroup, window: Option<Duration>) -> String {
+        let mut csv = "key,calls,errors,input_tokens,output_tokens,total_tokens,cost\n".to_string();
+        for summary in self.summary(group, window) {
+            let key = match summary.key.contains([',', '"', '\n']) {
+                true => format!("\"{}\"", summary.key.replace('"', "\"\"")),
+                false => summary.key.clone(),
+            };
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                key,
+                summary.calls,
+                summary.errors,
+                summary.usage.input_tokens(),
+                summary.usage.output_tokens(),
+                summary.usage.total_tokens(),
+                summary.cost,
+            ));
+        }
+
+        csv
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+}
+
+impl TranscriptSink for UsageTracker {
+    async fn write(&self, transcript: &Transcript) -> Result<(), Error> {
+        self.record(transcript);
+        Ok(())
+    }
+}
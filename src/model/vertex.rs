@@ -0,0 +1,328 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::anyhow;
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use gcp_auth::TokenProvider;
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+use tracing::{field, instrument};
+
+use super::{
+    BatchInference,
+    Capabilities,
+    CompatibilityReport,
+    Error,
+    LanguageModel,
+    LanguageModelPrompt,
+    LogprobModel,
+    LogprobResponse,
+    Message,
+    ModelCatalog,
+    ModelInfo,
+    TokenLogprob,
+};
+
+const SCOPES: &[&str] = &["https://www.googleapis.com/auth/cloud-platform"];
+
+/// Application Default Credentials, resolved lazily on first use and shared between clones.
+#[derive(Clone, Default)]
+pub struct VertexAuth {
+    provider: Arc<OnceCell<Arc<dyn TokenProvider>>>,
+}
+
+impl fmt::Debug for VertexAuth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VertexAuth").field("initialized", &self.provider.initialized()).finish()
+    }
+}
+
+impl VertexAuth {
+    pub async fn token(&self) -> Result<String, Error> {
+        let provider = self.provider
+            .get_or_try_init(gcp_auth::provider)
+            .await
+            .map_err(|err| Error::AuthenticationFailed(format!("{}", err)))?;
+
+        let token = provider.token(SCOPES).await.map_err(|err| Error::AuthenticationFailed(format!("{}", err)))?;
+        Ok(token.as_str().to_string())
+    }
+}
+
+/// Lets gateways in front of Vertex AI, or any other Google-authenticated endpoint, reuse the ADC tokens.
+impl super::TokenProvider for VertexAuth {
+    async fn token(&self) -> Result<crate::SecretString, Error> {
+        VertexAuth::token(self).await.map(crate::SecretString::from)
+    }
+}
+
+pub fn endpoint(project_id: &str, region: &str, publisher: &str, model: &str, method: &str) -> String {
+    let host = if region == "global" { "aiplatform.googleapis.com".to_string() } else { format!("{}-aiplatform.googleapis.com", region) };
+
+    format!("https://{}/v1/projects/{}/locations/{}/publishers/{}/models/{}:{}", host, project_id, region, publisher, model, method)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeminiErrorResponse {
+    #[serde(default)]
+    status: String,
+
+    message: String,
+}
+
+impl GeminiErrorResponse {
+    pub fn status(&self) -> &str {
+        &self.status
+    }
+
+    /// Maps the `google.rpc.Code` status name onto the crate's error variants.
+    fn into_error(self) -> Error {
+        match self.status.as_str() {
+            "RESOURCE_EXHAUSTED" => Error::RateLimited { retry_after: None },
+            "UNAVAILABLE" => Error::Overloaded(self.message),
+            "DEADLINE_EXCEEDED" => Error::Timeout(self.message),
+            "UNAUTHENTICATED" | "PERMISSION_DENIED" => Error::AuthenticationFailed(self.message),
+            "INVALID_ARGUMENT" if self.message.contains("token count") => Error::ContextLengthExceeded(self.message),
+            "INVALID_ARGUMENT" | "NOT_FOUND" | "FAILED_PRECONDITION" => Error::InvalidRequest(self.message),
+            _ => Error::ModelResponse(self.message),
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+#[derive(Deserialize)]
+struct GeminiErrorEnvelope {
+    error: GeminiErrorResponse,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct GeminiModel {
+    project_id: String,
+    region: String,
+    model: String,
+
+    #[serde(skip)]
+    client: Client,
+
+    #[serde(skip)]
+    auth: VertexAuth,
+}
+
+impl GeminiModel {
+    pub fn new(project_id: impl Into<String>, region: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            project_id: project_id.into(),
+            region: region.into(),
+            model: model.into(),
+            client: Client::new(),
+            auth: VertexAuth::default(),
+        }
+    }
+
+    pub fn http_client(self, client: Client) -> Self {
+        Self {
+            client,
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &str {
+        &self.model
+    }
+
+    #[instrument(name = "GeminiModel::create", level = "trace", skip(self, prompt))]
+    pub async fn create(&self, prompt: &LanguageModelPrompt) -> Result<Value, GeminiErrorResponse> {
+        let parts = prompt.messages.iter()
+            .map(|message| match message {
+                Message::Audio(audio) => json!({ "inlineData": { "mimeType": audio.media_type(), "data": BASE64_STANDARD.encode(audio.data()) } }),
+                Message::Image(image) => json!({ "inlineData": { "mimeType": image.media_type(), "data": BASE64_STANDARD.encode(image.data_ref()) } }),
+                Message::Text { text } => json!({ "text": text }),
+                Message::Unknown(value) => value.clone(),
+            })
+            .collect::<Vec<Value>>();
+
+        let mut contents = prompt.examples.iter()
+            .flat_map(|(user, assistant)| [json!({ "role": "user", "parts": [{ "text": user }] }), json!({ "role": "model", "parts": [{ "text": assistant }] })])
+            .collect::<Vec<Value>>();
+        contents.push(json!({ "role": "user", "parts": parts }));
+
+        let mut request = json!({
+            "contents": contents,
+            "generationConfig": {
+                "maxOutputTokens": prompt.max_tokens,
+                "temperature": prompt.temperature,
+            },
+        });
+        if let Some(top_p) = prompt.top_p {
+            request["generationConfig"]["topP"] = json!(top_p);
+        }
+        if prompt.candidates > 1 {
+            request["generationConfig"]["candidateCount"] = json!(prompt.candidates);
+        }
+        if let Some(top_n) = prompt.logprobs {
+            request["generationConfig"]["responseLogprobs"] = json!(true);
+            if top_n > 0 {
+                request["generationConfig"]["logprobs"] = json!(top_n);
+            }
+        }
+        if let Some(top_k) = prompt.top_k {
+            request["generationConfig"]["topK"] = json!(top_k);
+        }
+        if let Some(seed) = prompt.seed {
+            request["generationConfig"]["seed"] = json!(seed);
+        }
+        if let Some(frequency_penalty) = prompt.frequency_penalty {
+            request["generationConfig"]["frequencyPenalty"] = json!(frequency_penalty);
+        }
+        if let Some(presence_penalty) = prompt.presence_penalty {
+            request["generationConfig"]["presencePenalty"] = json!(presence_penalty);
+        }
+        if !prompt.stop_sequences.is_empty() {
+            request["generationConfig"]["stopSequences"] = json!(prompt.stop_sequences);
+        }
+        if let Some(system) = &prompt.system {
+            request["systemInstruction"] = json!({ "parts": [{ "text": system }] });
+        }
+
+        let token = self.auth.token().await.map_err(|err| GeminiErrorResponse { status: "authentication_error".into(), message: format!("{}", err) })?;
+
+        let response = super::prompt_request(self.client.post(endpoint(&self.project_id, &self.region, "google", &self.model, "generateContent")), prompt)
+            .bearer_auth(token)
+            .header("Accept", "application/json")
+            .header("Content-Type", "application/json")
+            .json(&request)
+            .send()
+            .await;
+
+        match response {
+            Ok(response) => match response.status() {
+                StatusCode::OK => response.json::<Value>().await
+                    .map_err(|err| GeminiErrorResponse { status: "invalid_response_error".into(), message: format!("{}", err) }),
+                status_code if status_code.is_client_error() || status_code.is_server_error() => match response.json::<GeminiErrorEnvelope>().await {
+                    Ok(envelope) => Err(envelope.error),
+                    Err(err) => Err(GeminiErrorResponse { status: "invalid_response_error".into(), message: format!("{}", err) })
+                },
+                status_code => Err(GeminiErrorResponse { status: "invalid_status_error".into(), message: format!("{}", status_code) })
+            },
+            Err(err) if err.is_timeout() => Err(GeminiErrorResponse { status: "DEADLINE_EXCEEDED".into(), message: format!("{}", err) }),
+            Err(err) => Err(GeminiErrorResponse { status: "request_error".into(), message: format!("{}", err) })
+        }
+    }
+
+    /// The raw `generateContent` response for `prompt`, after checking it against the model. Usage is recorded
+    /// from its `usageMetadata` when present.
+    async fn generate(&self, prompt: &LanguageModelPrompt) -> Result<Value, Error> {
+        let prompt = &*super::checked(self, prompt)?;
+
+        #[cfg(feature = "opentelemetry")]
+        let started = crate::time::Instant::now();
+
+        let response = self.create(prompt).await;
+        let usage = response.as_ref().ok()
+            .and_then(|response| response.get("usageMetadata"))
+            .map(|usage| {
+                let count = |key: &str| usage.get(key).and_then(Value::as_u64).unwrap_or_default() as usize;
+                (count("promptTokenCount"), count("candidatesTokenCount"))
+            });
+
+        #[cfg(feature = "opentelemetry")]
+        crate::telemetry::record_inference("gcp.vertex_ai", &self.model, started.elapsed(), usage, response.as_ref().err().map(|err| err.status.as_str()));
+
+        let response = response.map_err(|err| super::timeout_error(prompt.deadline, err.into_error()));
+        super::record_response(&response, None, usage);
+        response
+    }
+}
+
+impl ModelCatalog for GeminiModel {
+    /// Vertex AI has no per-project listing of publisher models, so only the configured model is reported.
+    async fn list_models(&self) -> Result<Vec<ModelInfo>, Error> {
+        Ok(vec![ModelInfo::new(&self.model, None)])
+    }
+}
+
+impl BatchInference for GeminiModel {}
+
+impl LanguageModel for GeminiModel {
+    fn capabilities(&self) -> Option<Capabilities> {
+        Capabilities::lookup(&self.model)
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        let report = CompatibilityReport::default()
+            .ignore_if_any("logit_bias", &prompt.logit_bias)
+            .ignore_if("user", &prompt.user)
+            .ignore_if_nonempty("server_tools", &prompt.server_tools)
+            .ignore_if("continuations", &prompt.continuations);
+
+        match prompt.metadata.is_empty() {
+            true => report,
+            false => report.ignore("metadata"),
+        }
+    }
+
+    #[instrument(
+        name = "GeminiModel::inference",
+        level = "trace",
+        skip(self),
+        fields(
+            gen_ai.system = "gcp.vertex_ai",
+            gen_ai.request.model = self.model,
+            gen_ai.request.max_tokens = prompt.max_tokens,
+            gen_ai.request.temperature = prompt.temperature,
+            gen_ai.usage.input_tokens = field::Empty,
+            gen_ai.usage.output_tokens = field::Empty,
+        ),
+    )]
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        let response = self.generate(&LanguageModelPrompt { candidates: 1, ..prompt }).await?;
+        candidate(&response, 0).ok_or_else(|| Error::Unexpected(anyhow!("no-content")))
+    }
+
+    #[instrument(name = "GeminiModel::inference_multi", level = "trace", skip(self, prompt), fields(candidates = prompt.candidates))]
+    async fn inference_multi(&self, prompt: LanguageModelPrompt) -> Result<Vec<Message>, Error> {
+        let response = self.generate(&prompt).await?;
+
+        let candidates = (0..prompt.candidates.max(1)).map_while(|index| candidate(&response, index)).collect::<Vec<Message>>();
+        match candidates.is_empty() {
+            true => Err(Error::Unexpected(anyhow!("no-content"))),
+            false => Ok(candidates),
+        }
+    }
+}
+
+impl LogprobModel for GeminiModel {
+    #[instrument(name = "GeminiModel::inference_with_logprobs", level = "trace", skip(self, prompt))]
+    async fn inference_with_logprobs(&self, prompt: LanguageModelPrompt) -> Result<LogprobResponse, Error> {
+        let logprobs = Some(prompt.logprobs.unwrap_or_default());
+        let response = self.generate(&LanguageModelPrompt { candidates: 1, logprobs, ..prompt }).await?;
+
+        let message = candidate(&response, 0).ok_or_else(|| Error::Unexpected(anyhow!("no-content")))?;
+        let result = response.pointer("/candidates/0/logprobsResult").ok_or_else(|| Error::Unexpected(anyhow!("no-logprobs")))?;
+
+        let parse = |value: &Value| Some(TokenLogprob::new(value.get("token")?.as_str()?, value.get("logProbability")?.as_f64()?));
+        let alternatives = result.get("topCandidates").and_then(Value::as_array).cloned().unwrap_or_default();
+        let tokens = result.get("chosenCandidates").and_then(Value::as_array).into_iter().flatten()
+            .enumerate()
+            .filter_map(|(index, chosen)| {
+                let top = alternatives.get(index).and_then(|top| top.get("candidates")).and_then(Value::as_array).into_iter().flatten();
+                Some(top.filter_map(parse).fold(parse(chosen)?, TokenLogprob::alternative))
+            })
+            .collect::<Vec<TokenLogprob>>();
+
+        Ok(LogprobResponse::new(message, tokens))
+    }
+}
+
+/// The text of the `index`th candidate, `None` when there is no such candidate or it has no text.
+fn candidate(response: &Value, index: usize) -> Option<Message> {
+    response.pointer(&format!("/candidates/{}/content/parts", index))
+        .and_then(Value::as_array)
+        .map(|parts| parts.iter().filter_map(|part| part.get("text").and_then(Value::as_str)).collect::<String>())
+        .filter(|text| !text.is_empty())
+        .map(|text| Message::Text { text })
+}
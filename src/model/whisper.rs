@@ -0,0 +1,104 @@
+use std::{io::Cursor, path::PathBuf, sync::Arc};
+
+use anyhow::anyhow;
+use tracing::{debug, instrument};
+use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+
+use super::{Audio, Error, TranscriptionModel};
+
+const SAMPLE_RATE: u32 = 16_000;
+
+#[derive(Clone, Debug)]
+pub struct WhisperCppModel {
+    model_path: PathBuf,
+    threads: i32,
+    context: Arc<WhisperContext>,
+}
+
+impl WhisperCppModel {
+    pub fn new(model_path: impl Into<PathBuf>) -> Result<Self, Error> {
+        let model_path = model_path.into();
+        let context = WhisperContext::new_with_params(&model_path, WhisperContextParameters::default())
+            .map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+
+        Ok(Self {
+            model_path,
+            threads: 4,
+            context: Arc::new(context),
+        })
+    }
+
+    pub fn threads(self, threads: i32) -> Self {
+        Self {
+            threads,
+            ..self
+        }
+    }
+
+    pub fn model_path(&self) -> &PathBuf {
+        &self.model_path
+    }
+
+    /// Decodes a 16 kHz WAV payload into mono `f32` samples as expected by whisper.cpp.
+    fn samples(audio: &Audio) -> Result<Vec<f32>, Error> {
+        match audio.media_type() {
+            "audio/wav" | "audio/x-wav" | "audio/wave" => {},
+            media_type => return Err(Error::Unexpected(anyhow!("unsupported-media-type: {}", media_type))),
+        }
+
+        let mut reader = hound::WavReader::new(Cursor::new(audio.data()))
+            .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        let spec = reader.spec();
+        if spec.sample_rate != SAMPLE_RATE {
+            return Err(Error::Unexpected(anyhow!("unsupported-sample-rate: {}", spec.sample_rate)));
+        }
+
+        let samples = match spec.sample_format {
+            hound::SampleFormat::Float => reader.samples::<f32>().collect::<Result<Vec<f32>, _>>(),
+            hound::SampleFormat::Int => {
+                // Integer PCM of any width (8 to 32 bits) reads as `i32`, scaled by its own full-scale value.
+                let scale = (1u64 << (spec.bits_per_sample.clamp(1, 32) - 1)) as f32;
+                reader.samples::<i32>()
+                    .map(|sample| sample.map(|sample| sample as f32 / scale))
+                    .collect::<Result<Vec<f32>, _>>()
+            },
+        }.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+        let channels = spec.channels as usize;
+        Ok(if channels > 1 {
+            samples.chunks(channels).map(|frame| frame.iter().sum::<f32>() / channels as f32).collect()
+        } else {
+            samples
+        })
+    }
+}
+
+impl TranscriptionModel for WhisperCppModel {
+    #[instrument(name = "WhisperCppModel::transcribe", level = "trace", skip(self, audio))]
+    async fn transcribe(&self, audio: &Audio, language: Option<&str>) -> Result<String, Error> {
+        let samples = Self::samples(audio)?;
+        let context = self.context.clone();
+        let threads = self.threads;
+        let language = language.map(str::to_string);
+
+        tokio::task::spawn_blocking(move || {
+            let mut state = context.create_state().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+
+            let mut params = FullParams::new(SamplingStrategy::Greedy { best_of: 1 });
+            params.set_n_threads(threads);
+            params.set_language(language.as_deref());
+            params.set_print_progress(false);
+            params.set_print_realtime(false);
+
+            state.full(params, &samples).map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+
+            let mut text = String::new();
+            for segment in state.as_iter() {
+                text.push_str(&segment.to_str_lossy().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?);
+            }
+            debug! { segments = state.full_n_segments() };
+
+            Ok(text.trim().to_string())
+        }).await.map_err(|err| Error::Unexpected(anyhow!(err)))?
+    }
+}
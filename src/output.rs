@@ -0,0 +1,273 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use tracing::{instrument, warn};
+
+use super::{
+    model::{LanguageModel, LanguageModelPrompt},
+    Error,
+    Message,
+};
+
+/// Turns a model's text response into a value. Failures are `Error::ModelResponse` with a message that tells the
+/// model what was wrong, so `parse_with_retries` can send it back.
+pub trait OutputParser {
+    type Output;
+
+    fn parse(&self, text: &str) -> Result<Self::Output, Error>;
+
+    /// Parses the text this parser extracts with `next`, e.g. `CodeBlock::new("json").then(Json::<T>::new())`.
+    fn then<P>(self, next: P) -> Then<Self, P>
+    where
+        Self: OutputParser<Output = String> + Sized,
+        P: OutputParser,
+    {
+        Then { first: self, next }
+    }
+
+    /// Falls back to `other` when this parser fails; the error reported is `other`'s.
+    fn or<P>(self, other: P) -> Or<Self, P>
+    where
+        Self: Sized,
+        P: OutputParser<Output = Self::Output>,
+    {
+        Or { first: self, other }
+    }
+
+    fn map<F, T>(self, f: F) -> Map<Self, F>
+    where
+        Self: Sized,
+        F: Fn(Self::Output) -> T,
+    {
+        Map { parser: self, f }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Then<A, B> {
+    first: A,
+    next: B,
+}
+
+impl<A, B> OutputParser for Then<A, B>
+where
+    A: OutputParser<Output = String>,
+    B: OutputParser,
+{
+    type Output = B::Output;
+
+    fn parse(&self, text: &str) -> Result<Self::Output, Error> {
+        self.next.parse(&self.first.parse(text)?)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Or<A, B> {
+    first: A,
+    other: B,
+}
+
+impl<A, B> OutputParser for Or<A, B>
+where
+    A: OutputParser,
+    B: OutputParser<Output = A::Output>,
+{
+    type Output = A::Output;
+
+    fn parse(&self, text: &str) -> Result<Self::Output, Error> {
+        self.first.parse(text).or_else(|_| self.other.parse(text))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Map<P, F> {
+    parser: P,
+    f: F,
+}
+
+impl<P, F, T> OutputParser for Map<P, F>
+where
+    P: OutputParser,
+    F: Fn(P::Output) -> T,
+{
+    type Output = T;
+
+    fn parse(&self, text: &str) -> Result<T, Error> {
+        self.parser.parse(text).map(&self.f)
+    }
+}
+
+/// The body of the first fenced code block (```` ``` ```` or `~~~`), optionally only one tagged with `language`.
+/// A block left open by a truncated response runs to the end of the text.
+#[derive(Clone, Debug, Default)]
+pub struct CodeBlock {
+    language: Option<String>,
+}
+
+impl CodeBlock {
+    pub fn new(language: impl Into<String>) -> Self {
+        Self { language: Some(language.into()) }
+    }
+
+    pub fn any() -> Self {
+        Self::default()
+    }
+}
+
+/// Every fenced code block in `text` as its language tag, empty when untagged, and body.
+pub fn code_blocks(text: &str) -> Vec<(String, String)> {
+    let mut blocks = Vec::new();
+    let mut open: Option<(&str, String, Vec<&str>)> = None;
+
+    for line in text.lines() {
+        let trimmed = line.trim_start();
+        match open.take() {
+            Some((fence, language, body)) if trimmed.trim_end() == fence => blocks.push((language, body.join("\n"))),
+            Some((fence, language, mut body)) => {
+                body.push(line);
+                open = Some((fence, language, body));
+            },
+            None => {
+                let fence = ["```", "~~~"].into_iter().find(|fence| trimmed.starts_with(fence));
+                if let Some(fence) = fence {
+                    let language = trimmed[fence.len()..].split_whitespace().next().unwrap_or_default();
+                    open = Some((fence, language.to_string(), Vec::new()));
+                }
+            },
+        }
+    }
+    if let Some((_, language, body)) = open {
+        blocks.push((language, body.join("\n")));
+    }
+
+    blocks
+}
+
+impl OutputParser for CodeBlock {
+    type Output = String;
+
+    fn parse(&self, text: &str) -> Result<String, Error> {
+        let block = code_blocks(text).into_iter().find(|(language, _)| match &self.language {
+            Some(expected) => language.eq_ignore_ascii_case(expected),
+            None => true,
+        });
+
+        match (block, &self.language) {
+            (Some((_, body)), _) => Ok(body),
+            (None, Some(language)) => Err(Error::ModelResponse(format!("the response has no ```{} code block", language))),
+            (None, None) => Err(Error::ModelResponse("the response has no fenced code block".to_string())),
+        }
+    }
+}
+
+/// The first JSON object in the text that deserializes into `T`, wherever it sits among prose or fences.
+#[derive(Clone, Debug)]
+pub struct Json<T> {
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Json<T> {
+    pub fn new() -> Self {
+        Self { marker: PhantomData }
+    }
+}
+
+impl<T> Default for Json<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> OutputParser for Json<T>
+where
+    T: DeserializeOwned,
+{
+    type Output = T;
+
+    fn parse(&self, text: &str) -> Result<T, Error> {
+        let mut mismatch = None;
+
+        for (start, _) in text.match_indices('{') {
+            let Some(Ok(value)) = serde_json::Deserializer::from_str(&text[start..]).into_iter::<Value>().next() else {
+                continue;
+            };
+
+            match serde_json::from_value::<T>(value) {
+                Ok(output) => return Ok(output),
+                Err(err) => {
+                    mismatch.get_or_insert(err);
+                },
+            }
+        }
+
+        match mismatch {
+            Some(err) => Err(Error::ModelResponse(format!("the JSON object in the response does not have the expected shape: {}", err))),
+            None => Err(Error::ModelResponse("the response has no valid JSON object".to_string())),
+        }
+    }
+}
+
+/// The trimmed content of the first `<tag>…</tag>` element, e.g. `<answer>` in a response that reasons first.
+#[derive(Clone, Debug)]
+pub struct XmlTag {
+    tag: String,
+}
+
+impl XmlTag {
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self { tag: tag.into() }
+    }
+}
+
+impl OutputParser for XmlTag {
+    type Output = String;
+
+    fn parse(&self, text: &str) -> Result<String, Error> {
+        let open = format!("<{}", self.tag);
+        let start = text.match_indices(&open)
+            .map(|(index, _)| index + open.len())
+            .find(|&index| text[index..].starts_with('>') || text[index..].starts_with(char::is_whitespace))
+            .and_then(|index| text[index..].find('>').map(|end| index + end + 1))
+            .ok_or_else(|| Error::ModelResponse(format!("the response has no <{}> tag", self.tag)))?;
+
+        let close = format!("</{}>", self.tag);
+        let end = text[start..].find(&close)
+            .ok_or_else(|| Error::ModelResponse(format!("the <{}> tag in the response is never closed", self.tag)))?;
+
+        Ok(text[start..start + end].trim().to_string())
+    }
+}
+
+const RETRY_INSTRUCTION: &str = "Your previous response could not be parsed. Reply again to the request above, \
+fixing the problem described in <parse_error> and keeping the required format exactly.";
+
+/// Runs `prompt` and parses the response with `parser`. A parse failure re-prompts the model up to `max_retries`
+/// times with its previous response and the parse error appended to the prompt.
+#[instrument(name = "output::parse_with_retries", level = "trace", skip(model, prompt, parser))]
+pub async fn parse_with_retries<M, P>(model: &M, prompt: LanguageModelPrompt, parser: &P, max_retries: usize) -> Result<P::Output, Error>
+where
+    M: LanguageModel,
+    P: OutputParser,
+{
+    let mut attempt = prompt.clone();
+    let mut retries = 0;
+
+    loop {
+        let response = model.inference(attempt).await?.to_string();
+
+        let err = match parser.parse(&response) {
+            Ok(output) => return Ok(output),
+            Err(err) if retries == max_retries => return Err(err),
+            Err(err) => err,
+        };
+        retries += 1;
+        warn! { retries, %err, "response could not be parsed" };
+
+        let mut messages = prompt.messages.clone();
+        messages.push(Message::Text {
+            text: format!("<previous_response>\n{}\n</previous_response>\n\n<parse_error>\n{}\n</parse_error>\n\n{}", response, err, RETRY_INSTRUCTION),
+        });
+        attempt = LanguageModelPrompt { messages, ..prompt.clone() };
+    }
+}
@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tracing::{debug, info, instrument, warn};
+
+use super::{
+    model::{EmbeddingModel, LanguageModel, LanguageModelPrompt},
+    CheckpointStore,
+    Error,
+};
+
+/// One step of a [`Pipeline`]. Each input item may produce any number of output items.
+#[async_trait(?Send)]
+pub trait PipelineStage: std::fmt::Debug {
+    fn name(&self) -> &str;
+
+    async fn process(&self, item: Value) -> Result<Vec<Value>, Error>;
+}
+
+fn item_text(item: &Value) -> Option<&str> {
+    match item {
+        Value::String(text) => Some(text.as_str()),
+        Value::Object(map) => map.get("text").and_then(Value::as_str),
+        _ => None,
+    }
+}
+
+/// Splits text items into overlapping chunks of at most `size` characters.
+#[derive(Debug)]
+pub struct ChunkStage {
+    size: usize,
+    overlap: usize,
+}
+
+impl ChunkStage {
+    pub fn new(size: usize, overlap: usize) -> Self {
+        let size = size.max(1);
+        Self { size, overlap: overlap.min(size - 1) }
+    }
+}
+
+#[async_trait(?Send)]
+impl PipelineStage for ChunkStage {
+    fn name(&self) -> &str {
+        "chunk"
+    }
+
+    async fn process(&self, item: Value) -> Result<Vec<Value>, Error> {
+        let chars = item_text(&item).unwrap_or_default().chars().collect::<Vec<char>>();
+
+        let mut chunks = Vec::new();
+        let mut start = 0;
+        while start < chars.len() {
+            let end = (start + self.size).min(chars.len());
+            chunks.push(Value::String(chars[start..end].iter().collect()));
+            if end == chars.len() {
+                break;
+            }
+            start = end - self.overlap;
+        }
+
+        Ok(chunks)
+    }
+}
+
+/// Attaches an `embedding` to each text item.
+#[derive(Debug)]
+pub struct EmbedStage<E> {
+    model: E,
+}
+
+impl<E> EmbedStage<E> {
+    pub fn new(model: E) -> Self {
+        Self { model }
+    }
+}
+
+#[async_trait(?Send)]
+impl<E> PipelineStage for EmbedStage<E>
+where
+    E: EmbeddingModel + std::fmt::Debug,
+{
+    fn name(&self) -> &str {
+        "embed"
+    }
+
+    async fn process(&self, item: Value) -> Result<Vec<Value>, Error> {
+        let text = item_text(&item).unwrap_or_default().to_string();
+        let embedding = self.model.embed(std::slice::from_ref(&text)).await?.pop().unwrap_or_default();
+
+        Ok(vec![json!({ "text": text, "embedding": embedding })])
+    }
+}
+
+/// Runs a language model over each item, using `prompt` to build the request from the item.
+pub struct GenerateStage<M> {
+    model: M,
+    prompt: Box<dyn Fn(&Value) -> LanguageModelPrompt>,
+}
+
+impl<M> std::fmt::Debug for GenerateStage<M>
+where
+    M: std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("GenerateStage").field("model", &self.model).finish_non_exhaustive()
+    }
+}
+
+impl<M> GenerateStage<M> {
+    pub fn new(model: M, prompt: impl Fn(&Value) -> LanguageModelPrompt + 'static) -> Self {
+        Self { model, prompt: Box::new(prompt) }
+    }
+}
+
+#[async_trait(?Send)]
+impl<M> PipelineStage for GenerateStage<M>
+where
+    M: LanguageModel + std::fmt::Debug,
+{
+    fn name(&self) -> &str {
+        "generate"
+    }
+
+    async fn process(&self, item: Value) -> Result<Vec<Value>, Error> {
+        let output = self.model.inference((self.prompt)(&item)).await?;
+
+        Ok(vec![json!({ "input": item, "output": output.to_string() })])
+    }
+}
+
+type Validator = Box<dyn Fn(&Value) -> Result<(), String>>;
+
+/// Drops items rejected by `validator`, logging the reason.
+pub struct ValidateStage {
+    validator: Validator,
+}
+
+impl std::fmt::Debug for ValidateStage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ValidateStage").finish_non_exhaustive()
+    }
+}
+
+impl ValidateStage {
+    pub fn new(validator: impl Fn(&Value) -> Result<(), String> + 'static) -> Self {
+        Self { validator: Box::new(validator) }
+    }
+}
+
+#[async_trait(?Send)]
+impl PipelineStage for ValidateStage {
+    fn name(&self) -> &str {
+        "validate"
+    }
+
+    async fn process(&self, item: Value) -> Result<Vec<Value>, Error> {
+        match (self.validator)(&item) {
+            Ok(()) => Ok(vec![item]),
+            Err(reason) => {
+                warn! { reason, "dropping invalid item" };
+                Ok(Vec::new())
+            }
+        }
+    }
+}
+
+/// Sequence of stages whose per-item outputs are checkpointed, so an interrupted run resumes without
+/// repeating completed work.
+///
+/// Outputs of stage `n` are stored under the namespace `{pipeline}.{n}.{stage}`, keyed by the input item key.
+/// Items fanned out by a stage are keyed `{key}/{index}`.
+#[derive(Debug)]
+pub struct Pipeline<S> {
+    name: String,
+    store: S,
+    stages: Vec<Box<dyn PipelineStage>>,
+}
+
+impl<S> Pipeline<S>
+where
+    S: CheckpointStore,
+{
+    pub fn new(name: impl Into<String>, store: S) -> Self {
+        Self {
+            name: name.into(),
+            store,
+            stages: Vec::new(),
+        }
+    }
+
+    pub fn stage(self, stage: impl PipelineStage + 'static) -> Self {
+        let mut stages = self.stages;
+        stages.push(Box::new(stage));
+
+        Self {
+            stages,
+            ..self
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    #[instrument(name = "Pipeline::run", level = "trace", skip(self, inputs), fields(pipeline = %self.name))]
+    pub async fn run(&self, inputs: impl IntoIterator<Item = (String, Value)>) -> Result<Vec<(String, Value)>, Error> {
+        let mut items = inputs.into_iter().collect::<Vec<(String, Value)>>();
+
+        for (index, stage) in self.stages.iter().enumerate() {
+            let namespace = format!("{}.{}.{}", self.name, index, stage.name());
+            let completed = self.store.entries(&namespace).await?;
+
+            let mut outputs = Vec::new();
+            let mut resumed = 0;
+            for (key, item) in items {
+                let produced = match completed.get(&key).and_then(Value::as_array) {
+                    Some(produced) => {
+                        resumed += 1;
+                        produced.clone()
+                    },
+                    None => {
+                        let produced = stage.process(item).await?;
+                        self.store.put(&namespace, &key, Value::Array(produced.clone())).await?;
+                        produced
+                    },
+                };
+
+                if produced.len() == 1 {
+                    outputs.extend(produced.into_iter().map(|value| (key.clone(), value)));
+                } else {
+                    outputs.extend(produced.into_iter().enumerate().map(|(n, value)| (format!("{}/{}", key, n), value)));
+                }
+            }
+
+            debug! { stage = stage.name(), resumed };
+            info! { stage = stage.name(), items = outputs.len() };
+            items = outputs;
+        }
+
+        Ok(items)
+    }
+}
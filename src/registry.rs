@@ -0,0 +1,287 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    pin::pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures_util::future::{self, Either};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, instrument, warn};
+
+use super::{model, time::Instant, Error, LanguageModel, Message};
+
+/// Latencies kept per model for `HedgePolicy` percentiles.
+const LATENCY_WINDOW: usize = 100;
+
+/// Sends a second request to `fallback` when a model has not answered within the `percentile` of its recent
+/// latencies, and takes whichever response arrives first. Until `min_samples` latencies are known,
+/// `initial_delay` is used instead.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct HedgePolicy {
+    fallback: String,
+
+    #[serde(default = "HedgePolicy::default_percentile")]
+    percentile: f64,
+
+    #[serde(default = "HedgePolicy::default_initial_delay")]
+    initial_delay: Duration,
+
+    #[serde(default = "HedgePolicy::default_min_samples")]
+    min_samples: usize,
+}
+
+impl HedgePolicy {
+    fn default_percentile() -> f64 {
+        0.95
+    }
+
+    fn default_initial_delay() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_min_samples() -> usize {
+        20
+    }
+
+    pub fn new(fallback: impl Into<String>) -> Self {
+        Self {
+            fallback: fallback.into(),
+            percentile: Self::default_percentile(),
+            initial_delay: Self::default_initial_delay(),
+            min_samples: Self::default_min_samples(),
+        }
+    }
+
+    /// Latency percentile, between 0 and 1, after which the hedge is sent.
+    pub fn percentile(self, percentile: f64) -> Self {
+        Self {
+            percentile: percentile.clamp(0.0, 1.0),
+            ..self
+        }
+    }
+
+    pub fn initial_delay(self, initial_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            ..self
+        }
+    }
+
+    pub fn min_samples(self, min_samples: usize) -> Self {
+        Self {
+            min_samples,
+            ..self
+        }
+    }
+
+    pub fn fallback(&self) -> &str {
+        &self.fallback
+    }
+}
+
+/// Named language models, with a default used unless a prompt asks for another one by name.
+///
+/// Prompts that did not choose their own `PromptDefaults` get the registry's defaults for the model name they
+/// are routed to, or else its `prompt_defaults`, for every setting their builders left alone.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ModelRegistry {
+    default: String,
+    models: HashMap<String, LanguageModel>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    hedges: HashMap<String, HedgePolicy>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    prompt_defaults: Option<model::PromptDefaults>,
+
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    model_prompt_defaults: HashMap<String, model::PromptDefaults>,
+
+    #[serde(skip)]
+    latencies: Arc<Mutex<HashMap<String, VecDeque<Duration>>>>,
+}
+
+impl ModelRegistry {
+    pub fn new(default: impl Into<String>, model: LanguageModel) -> Self {
+        let default = default.into();
+
+        Self {
+            models: HashMap::from([(default.clone(), model)]),
+            default,
+            hedges: HashMap::new(),
+            prompt_defaults: None,
+            model_prompt_defaults: HashMap::new(),
+            latencies: Arc::default(),
+        }
+    }
+
+    pub fn register(self, name: impl Into<String>, model: LanguageModel) -> Self {
+        let mut models = self.models;
+        models.insert(name.into(), model);
+
+        Self {
+            models,
+            ..self
+        }
+    }
+
+    pub fn default_model(self, default: impl Into<String>) -> Self {
+        Self {
+            default: default.into(),
+            ..self
+        }
+    }
+
+    /// Hedges requests to the model registered as `name` according to `policy`.
+    pub fn hedge(self, name: impl Into<String>, policy: HedgePolicy) -> Self {
+        let mut hedges = self.hedges;
+        hedges.insert(name.into(), policy);
+
+        Self {
+            hedges,
+            ..self
+        }
+    }
+
+    /// Defaults for prompts routed to any model without defaults of its own.
+    pub fn prompt_defaults(self, defaults: model::PromptDefaults) -> Self {
+        Self {
+            prompt_defaults: Some(defaults),
+            ..self
+        }
+    }
+
+    /// Defaults for prompts routed to the model registered as `name`.
+    pub fn model_prompt_defaults(self, name: impl Into<String>, defaults: model::PromptDefaults) -> Self {
+        let mut model_prompt_defaults = self.model_prompt_defaults;
+        model_prompt_defaults.insert(name.into(), defaults);
+
+        Self {
+            model_prompt_defaults,
+            ..self
+        }
+    }
+
+    /// `prompt` with the defaults for `name`, unless it chose its own.
+    fn with_defaults(&self, name: &str, prompt: model::LanguageModelPrompt) -> model::LanguageModelPrompt {
+        match (prompt.defaults, self.model_prompt_defaults.get(name).or(self.prompt_defaults.as_ref())) {
+            (None, Some(defaults)) => defaults.apply(prompt),
+            _ => prompt,
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LanguageModel> {
+        self.models.get(name)
+    }
+
+    pub fn resolve(&self, name: Option<&str>) -> Result<&LanguageModel, Error> {
+        let name = name.unwrap_or(&self.default);
+        self.get(name).ok_or_else(|| Error::InvalidRequest(format!("unknown model `{}`", name)))
+    }
+
+    /// The `percentile` latency of the model registered as `name`, `None` until it has answered a request.
+    pub fn latency(&self, name: &str, percentile: f64) -> Option<Duration> {
+        let latencies = self.latencies.lock().ok()?;
+        let mut latencies = latencies.get(name)?.iter().copied().collect::<Vec<Duration>>();
+        latencies.sort();
+
+        let index = ((latencies.len().saturating_sub(1)) as f64 * percentile.clamp(0.0, 1.0)).round() as usize;
+        latencies.get(index).copied()
+    }
+
+    fn samples(&self, name: &str) -> usize {
+        self.latencies.lock().ok().and_then(|latencies| latencies.get(name).map(VecDeque::len)).unwrap_or_default()
+    }
+
+    fn record_latency(&self, name: &str, latency: Duration) {
+        if let Ok(mut latencies) = self.latencies.lock() {
+            let latencies = latencies.entry(name.to_string()).or_default();
+            if latencies.len() == LATENCY_WINDOW {
+                latencies.pop_front();
+            }
+            latencies.push_back(latency);
+        }
+    }
+
+    /// Runs `name` and records its latency when it succeeds.
+    async fn timed(&self, name: &str, prompt: model::LanguageModelPrompt) -> Result<Message, Error> {
+        let started = Instant::now();
+        let response = model::LanguageModel::inference(self.resolve(Some(name))?, prompt).await;
+
+        if response.is_ok() {
+            self.record_latency(name, started.elapsed());
+        }
+
+        response
+    }
+
+    /// Starts `name`, and the policy's fallback if `name` is still running after the hedge delay. The first
+    /// success wins and the other request is dropped, which cancels it; an error waits for the other request.
+    ///
+    /// A primary that loses to the fallback has its time so far recorded as its latency, so slow requests still
+    /// count towards the percentile instead of only the fast ones that finish.
+    async fn hedged(&self, name: &str, policy: &HedgePolicy, prompt: model::LanguageModelPrompt) -> Result<Message, Error> {
+        let delay = match self.samples(name) >= policy.min_samples {
+            true => self.latency(name, policy.percentile).unwrap_or(policy.initial_delay),
+            false => policy.initial_delay,
+        };
+
+        let started = Instant::now();
+        let mut primary = pin!(self.timed(name, prompt.clone()));
+        let primary = match future::select(primary.as_mut(), pin!(tokio::time::sleep(delay))).await {
+            Either::Left((response, _)) => return response,
+            Either::Right((_, primary)) => primary,
+        };
+
+        debug! { model = name, fallback = policy.fallback, ?delay, "hedging" };
+        let fallback = pin!(self.timed(&policy.fallback, prompt));
+
+        match future::select(primary, fallback).await {
+            Either::Left((Ok(message), _)) => Ok(message),
+            Either::Right((Ok(message), _)) => {
+                self.record_latency(name, started.elapsed());
+                Ok(message)
+            },
+            Either::Left((Err(err), other)) | Either::Right((Err(err), other)) => {
+                warn! { ?err, "hedged request failed" };
+                other.await
+            },
+        }
+    }
+}
+
+impl model::BatchInference for ModelRegistry {}
+
+impl model::LanguageModel for ModelRegistry {
+    #[instrument(name = "ModelRegistry::inference", level = "trace", skip(self))]
+    async fn inference(&self, prompt: model::LanguageModelPrompt) -> Result<Message, Error> {
+        let name = prompt.model.clone().unwrap_or_else(|| self.default.clone());
+        debug! { model = name };
+        let prompt = self.with_defaults(&name, prompt);
+
+        match self.hedges.get(&name) {
+            Some(policy) => self.hedged(&name, policy, prompt).await,
+            None => self.timed(&name, prompt).await,
+        }
+    }
+
+    async fn inference_multi(&self, prompt: model::LanguageModelPrompt) -> Result<Vec<Message>, Error> {
+        let name = prompt.model.clone().unwrap_or_else(|| self.default.clone());
+        let prompt = self.with_defaults(&name, prompt);
+
+        self.resolve(Some(&name))?.inference_multi(prompt).await
+    }
+
+    fn rate_limit(&self) -> Option<model::RateLimit> {
+        self.resolve(None).ok().and_then(|model| model.rate_limit())
+    }
+
+    fn capabilities(&self) -> Option<model::Capabilities> {
+        self.resolve(None).ok().and_then(|model| model.capabilities())
+    }
+
+    fn compatibility(&self, prompt: &model::LanguageModelPrompt) -> model::CompatibilityReport {
+        self.resolve(prompt.model.as_deref()).map(|model| model.compatibility(prompt)).unwrap_or_default()
+    }
+}
\ No newline at end of file
@@ -0,0 +1,251 @@
+use std::{cell::Cell, env, fmt};
+
+use anyhow::anyhow;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use tracing::{debug, instrument};
+
+use super::Error;
+
+const REDACTED: &str = "[REDACTED]";
+
+thread_local! {
+    /// Set while an `Exporting` value is being serialized on this thread.
+    static EXPORTING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Restores the previous export state when dropped, including when serialization panics.
+struct ExportGuard(bool);
+
+impl ExportGuard {
+    fn start() -> Self {
+        Self(EXPORTING.with(|exporting| exporting.replace(true)))
+    }
+}
+
+impl Drop for ExportGuard {
+    fn drop(&mut self) {
+        EXPORTING.with(|exporting| exporting.set(self.0));
+    }
+}
+
+/// Serializes the wrapped value with its secrets in plain text; see `SecretString::exporting`.
+#[derive(Debug)]
+pub struct Exporting<'a, T: ?Sized>(&'a T);
+
+impl<T> Serialize for Exporting<'_, T>
+where
+    T: Serialize + ?Sized,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let _guard = ExportGuard::start();
+        self.0.serialize(serializer)
+    }
+}
+
+/// A credential that prints and serializes as `[REDACTED]`, so API keys cannot leak through `Debug` logs or
+/// saved configs. Use `expose` to read the value and `SecretString::exporting` to serialize it verbatim.
+///
+/// Deserializing `[REDACTED]` fails, so a config saved without `exporting` cannot be loaded with a placeholder
+/// for its key.
+#[derive(Clone, Default, Eq, PartialEq)]
+pub struct SecretString(String);
+
+impl SecretString {
+    pub fn new(secret: impl Into<String>) -> Self {
+        Self(secret.into())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+
+    /// `value` serializing with its secrets in plain text, e.g. to write out a config that must be loadable
+    /// again. Only that serialization exports them; anything serialized meanwhile, on this thread or another,
+    /// stays redacted.
+    pub fn exporting<T: ?Sized>(value: &T) -> Exporting<'_, T> {
+        Exporting(value)
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(REDACTED)
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(value: String) -> Self {
+        Self(value)
+    }
+}
+
+impl From<&str> for SecretString {
+    fn from(value: &str) -> Self {
+        Self(value.to_string())
+    }
+}
+
+impl Serialize for SecretString {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match EXPORTING.with(Cell::get) {
+            true => serializer.serialize_str(&self.0),
+            false => serializer.serialize_str(REDACTED),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SecretString {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        match String::deserialize(deserializer)? {
+            secret if secret == REDACTED => Err(de::Error::custom("secret is `[REDACTED]`: it was saved without `SecretString::exporting`")),
+            secret => Ok(Self(secret)),
+        }
+    }
+}
+
+/// Where a secret is looked up, so that config files name it instead of holding it. The `from` field selects the
+/// backend, e.g. `{ from = "env", var = "ANTHROPIC_API_KEY" }`; see `config::resolve_secrets`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "from", rename_all = "snake_case")]
+pub enum SecretSource {
+    Env { var: String },
+
+    /// A generic password in the macOS keychain, or in the Secret Service through `secret-tool` elsewhere.
+    #[cfg(feature = "keyring")]
+    Keyring { service: String, account: String },
+
+    /// The secret string of `secret_id`, or its `key` field when the secret holds a JSON object.
+    #[cfg(feature = "aws-secrets-manager")]
+    AwsSecretsManager {
+        secret_id: String,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        aws_config: Option<crate::model::AwsConfig>,
+    },
+}
+
+impl SecretSource {
+    /// Reads the secret, `None` when this backend does not hold it.
+    #[instrument(name = "SecretSource::lookup", level = "trace")]
+    pub async fn lookup(&self) -> Result<Option<SecretString>, Error> {
+        match self {
+            Self::Env { var } => match env::var(var) {
+                Ok(value) => Ok(Some(SecretString::from(value))),
+                Err(env::VarError::NotPresent) => Ok(None),
+                Err(err) => Err(Error::Unexpected(anyhow!("invalid-env-var: {}: {}", var, err))),
+            },
+
+            #[cfg(feature = "keyring")]
+            Self::Keyring { service, account } => keyring(service, account),
+
+            #[cfg(feature = "aws-secrets-manager")]
+            Self::AwsSecretsManager { secret_id, key, aws_config } => {
+                let Some(secret) = crate::model::secret_value(secret_id, aws_config).await? else {
+                    return Ok(None);
+                };
+
+                match key {
+                    Some(key) => {
+                        let fields = serde_json::from_str::<serde_json::Value>(&secret).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+                        Ok(fields.get(key).and_then(serde_json::Value::as_str).map(SecretString::from))
+                    },
+                    None => Ok(Some(SecretString::from(secret))),
+                }
+            },
+        }
+    }
+
+    /// Tries `sources` in order and returns the first secret found.
+    pub async fn resolve(sources: &[SecretSource]) -> Result<SecretString, Error> {
+        for source in sources {
+            if let Some(secret) = source.lookup().await? {
+                return Ok(secret);
+            }
+            debug! { ?source, "secret not found" };
+        }
+
+        Err(Error::Unexpected(anyhow!("missing-secret: {:?}", sources)))
+    }
+}
+
+/// Runs the platform's keyring CLI, which blocks briefly; secrets are resolved once, while loading configs. A
+/// missing CLI counts as the secret not being there, so the next source is tried.
+#[cfg(feature = "keyring")]
+fn keyring(service: &str, account: &str) -> Result<Option<SecretString>, Error> {
+    #[cfg(target_os = "macos")]
+    let output = std::process::Command::new("security").args(["find-generic-password", "-s", service, "-a", account, "-w"]).output();
+
+    #[cfg(not(target_os = "macos"))]
+    let output = std::process::Command::new("secret-tool").args(["lookup", "service", service, "account", account]).output();
+
+    match output {
+        Ok(output) if output.status.success() => Ok(Some(SecretString::from(String::from_utf8_lossy(&output.stdout).trim_end_matches(['\r', '\n']).to_string()))),
+        Ok(_) => Ok(None),
+        Err(err) => {
+            debug! { ?err, "keyring unavailable" };
+            Ok(None)
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Barrier;
+
+    use super::*;
+
+    /// Holds its serialization open between two barrier waits, so another thread can serialize meanwhile.
+    struct Paused<'a> {
+        secret: &'a SecretString,
+        barrier: &'a Barrier,
+    }
+
+    impl Serialize for Paused<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            self.barrier.wait();
+            self.barrier.wait();
+            self.secret.serialize(serializer)
+        }
+    }
+
+    #[test]
+    fn redacted_placeholder_is_rejected() {
+        assert!(serde_json::from_str::<SecretString>(r#""[REDACTED]""#).is_err());
+        assert_eq!(serde_json::from_str::<SecretString>(r#""sk-test""#).unwrap().expose(), "sk-test");
+    }
+
+    #[test]
+    fn exporting_is_scoped_to_its_own_serialization() {
+        let secret = SecretString::from("sk-test");
+        let barrier = Barrier::new(2);
+
+        let (exported, concurrent) = std::thread::scope(|scope| {
+            let exporter = scope.spawn(|| serde_json::to_string(&SecretString::exporting(&Paused { secret: &secret, barrier: &barrier })).unwrap());
+
+            barrier.wait();
+            let concurrent = serde_json::to_string(&secret).unwrap();
+            barrier.wait();
+
+            (exporter.join().unwrap(), concurrent)
+        });
+
+        assert_eq!(exported, r#""sk-test""#);
+        assert_eq!(concurrent, r#""[REDACTED]""#);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), r#""[REDACTED]""#);
+    }
+}
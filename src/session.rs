@@ -0,0 +1,312 @@
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+        Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use tokio::sync::{self, watch};
+use tracing::{debug, instrument};
+
+use super::{time::Instant, Assistant, AssistantResponse, Context, Conversation, Error, Role, SessionStore, Usage};
+
+/// Per-session limits; a session over either gets `Error::RateLimited` until it is ended.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SessionBudget {
+    max_requests: Option<u64>,
+    max_tokens: Option<u64>,
+}
+
+impl SessionBudget {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_requests(self, max_requests: u64) -> Self {
+        Self {
+            max_requests: Some(max_requests),
+            ..self
+        }
+    }
+
+    /// Limit on the tokens reported through `SessionManager::record_usage`.
+    pub fn max_tokens(self, max_tokens: u64) -> Self {
+        Self {
+            max_tokens: Some(max_tokens),
+            ..self
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Session {
+    usage: Mutex<Usage>,
+    requests: AtomicU64,
+
+    /// Identical requests being solved, each with the channel its result will be published on.
+    inflight: Mutex<HashMap<String, watch::Receiver<Option<AssistantResponse>>>>,
+
+    /// Held while a request is solved, so a session's turns are recorded in the order they were answered.
+    serial: sync::Mutex<()>,
+    last_active: Mutex<Instant>,
+}
+
+impl Session {
+    fn new() -> Self {
+        Self {
+            usage: Mutex::new(Usage::default()),
+            requests: AtomicU64::new(0),
+            inflight: Mutex::new(HashMap::new()),
+            serial: sync::Mutex::new(()),
+            last_active: Mutex::new(Instant::now()),
+        }
+    }
+
+    fn touch(&self) {
+        if let Ok(mut last_active) = self.last_active.lock() {
+            *last_active = Instant::now();
+        }
+    }
+
+    fn idle(&self) -> Duration {
+        self.last_active.lock().map(|last_active| last_active.elapsed()).unwrap_or_default()
+    }
+}
+
+/// Removes a leader's in-flight entry even if its solve is cancelled, so followers stop waiting on it.
+struct InflightGuard<'a> {
+    session: &'a Session,
+    key: String,
+}
+
+impl Drop for InflightGuard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut inflight) = self.session.inflight.lock() {
+            inflight.remove(&self.key);
+        }
+    }
+}
+
+/// Lets one `Assistant` serve many users at once by keeping each `session_id`'s state apart.
+///
+/// Every session's conversation lives in the `SessionStore` and is handed to `Assistant::solve_stateless` with
+/// each request, so the assistant must support stateless mode; `SessionStore::snapshot` and `restore` roll a
+/// session back. Each session also has its own request and token counters and in-flight table: a request
+/// identical to one still being solved for the same session (a double-submitted form, a client retry) waits
+/// for and shares that result instead of calling the assistant again. Requests for one session are solved one
+/// at a time; different sessions run concurrently.
+#[derive(Debug)]
+pub struct SessionManager<S> {
+    sessions: Mutex<HashMap<String, Arc<Session>>>,
+    store: S,
+    budget: SessionBudget,
+}
+
+impl<S> SessionManager<S>
+where
+    S: SessionStore,
+{
+    pub fn new(store: S) -> Self {
+        Self {
+            sessions: Mutex::new(HashMap::new()),
+            store,
+            budget: SessionBudget::default(),
+        }
+    }
+
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    pub fn budget(self, budget: SessionBudget) -> Self {
+        Self {
+            budget,
+            ..self
+        }
+    }
+
+    fn session(&self, session_id: &str) -> Result<Arc<Session>, Error> {
+        let mut sessions = self.sessions.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        Ok(sessions.entry(session_id.to_string()).or_insert_with(|| Arc::new(Session::new())).clone())
+    }
+
+    fn existing(&self, session_id: &str) -> Option<Arc<Session>> {
+        self.sessions.lock().ok().and_then(|sessions| sessions.get(session_id).cloned())
+    }
+
+    fn check_budget(&self, session: &Session) -> Result<(), Error> {
+        let requests = session.requests.load(Ordering::Relaxed);
+        let tokens = session.usage.lock().map(|usage| usage.total_tokens()).unwrap_or_default();
+
+        if self.budget.max_requests.is_some_and(|max_requests| requests >= max_requests)
+            || self.budget.max_tokens.is_some_and(|max_tokens| tokens >= max_tokens) {
+            return Err(Error::RateLimited { retry_after: None });
+        }
+
+        Ok(())
+    }
+
+    /// Solves `query` for `session_id` with the session's conversation so far, recording the exchange in it.
+    #[instrument(name = "SessionManager::solve", level = "trace", skip(self, assistant, query, context))]
+    pub async fn solve<A>(&self, assistant: &A, query: &str, context: Option<Context>, session_id: &str) -> AssistantResponse
+    where
+        A: Assistant + ?Sized,
+    {
+        let session = match self.session(session_id) {
+            Ok(session) => session,
+            Err(err) => return AssistantResponse::error(&err),
+        };
+        session.touch();
+
+        let key = format!("{}\u{0}{}", query, context.as_ref().map(|context| context.to_value().to_string()).unwrap_or_default());
+        loop {
+            let (sender, receiver) = {
+                let mut inflight = match session.inflight.lock() {
+                    Ok(inflight) => inflight,
+                    Err(err) => return AssistantResponse::error(&Error::Unexpected(anyhow!("{}", err))),
+                };
+                match inflight.get(&key) {
+                    Some(receiver) => (None, receiver.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        inflight.insert(key.clone(), receiver.clone());
+                        (Some(sender), receiver)
+                    },
+                }
+            };
+
+            let Some(sender) = sender else {
+                // Follower: share the leader's result, or take over if the leader was cancelled.
+                let mut receiver = receiver;
+                if let Ok(response) = receiver.wait_for(Option::is_some).await {
+                    debug! { session_id, "shared in-flight response" };
+                    return response.clone().unwrap_or_else(|| AssistantResponse::error(&Error::Unexpected(anyhow!("missing-inflight-response"))));
+                }
+                continue;
+            };
+
+            let _guard = InflightGuard { session: &session, key: key.clone() };
+            let response = self.solve_serially(assistant, &session, query, context, session_id).await;
+            let _ = sender.send(Some(response.clone()));
+            return response;
+        }
+    }
+
+    async fn solve_serially<A>(&self, assistant: &A, session: &Session, query: &str, context: Option<Context>, session_id: &str) -> AssistantResponse
+    where
+        A: Assistant + ?Sized,
+    {
+        let _serial = session.serial.lock().await;
+
+        if let Err(err) = self.check_budget(session) {
+            return AssistantResponse::error(&err);
+        }
+        session.requests.fetch_add(1, Ordering::Relaxed);
+
+        let mut conversation = match self.store.load(session_id).await {
+            Ok(conversation) => conversation.unwrap_or_default(),
+            Err(err) => return AssistantResponse::error(&err),
+        };
+
+        let response = assistant.solve_stateless(query, context, &conversation).await;
+        session.touch();
+
+        if let AssistantResponse::Final { response, .. } = &response {
+            conversation.push(Role::User, query);
+            conversation.push(Role::Assistant, response.clone());
+            if let Err(err) = self.store.save(session_id, &conversation).await {
+                return AssistantResponse::error(&err);
+            }
+        }
+
+        response
+    }
+
+    /// Adds `usage` to the session's token counter, checked against `SessionBudget::max_tokens`.
+    pub fn record_usage(&self, session_id: &str, usage: Usage) -> Result<(), Error> {
+        let session = self.session(session_id)?;
+        let mut total = session.usage.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        *total = *total + usage;
+        Ok(())
+    }
+
+    pub async fn conversation(&self, session_id: &str) -> Result<Option<Conversation>, Error> {
+        self.store.load(session_id).await
+    }
+
+    pub fn usage(&self, session_id: &str) -> Option<Usage> {
+        self.existing(session_id).and_then(|session| session.usage.lock().ok().map(|usage| *usage))
+    }
+
+    pub fn requests(&self, session_id: &str) -> Option<u64> {
+        self.existing(session_id).map(|session| session.requests.load(Ordering::Relaxed))
+    }
+
+    pub fn sessions(&self) -> usize {
+        self.sessions.lock().map(|sessions| sessions.len()).unwrap_or_default()
+    }
+
+    /// Forgets the session's counters and in-flight requests; its conversation stays in the store. Requests still
+    /// running finish against the old state. Returns whether the session was active.
+    pub fn end(&self, session_id: &str) -> bool {
+        self.sessions.lock().is_ok_and(|mut sessions| sessions.remove(session_id).is_some())
+    }
+
+    /// Ends every session idle for at least `idle`, returning how many were removed.
+    pub fn evict_idle(&self, idle: Duration) -> usize {
+        let Ok(mut sessions) = self.sessions.lock() else { return 0 };
+        let before = sessions.len();
+        sessions.retain(|_, session| session.idle() < idle);
+        before - sessions.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+    use crate::MemorySessionStore;
+
+    /// Answers with the number of turns in the history it was given.
+    #[derive(Debug, Deserialize, Serialize)]
+    struct TurnCounter;
+
+    #[async_trait]
+    #[typetag::serde]
+    impl Assistant for TurnCounter {
+        async fn solve(&self, _query: &str, _context: Option<Context>, _session_id: &str) -> AssistantResponse {
+            AssistantResponse::error(&Error::InvalidRequest("stateless only".to_string()))
+        }
+
+        async fn solve_stateless(&self, _query: &str, _context: Option<Context>, history: &Conversation) -> AssistantResponse {
+            AssistantResponse::Final { response: history.turns().len().to_string().into(), context: None }
+        }
+    }
+
+    fn text(response: AssistantResponse) -> String {
+        match response {
+            AssistantResponse::Final { response, .. } => response.to_string(),
+            response => panic!("expected a final response, got {:?}", response),
+        }
+    }
+
+    #[tokio::test]
+    async fn history_is_passed_to_the_assistant_and_restorable() {
+        let manager = SessionManager::new(MemorySessionStore::new());
+
+        assert_eq!(text(manager.solve(&TurnCounter, "one", None, "s").await), "0");
+        manager.store().snapshot("s", "after-one").await.unwrap();
+        assert_eq!(text(manager.solve(&TurnCounter, "two", None, "s").await), "2");
+        assert_eq!(text(manager.solve(&TurnCounter, "other", None, "t").await), "0");
+
+        manager.store().restore("s", "after-one").await.unwrap();
+        assert_eq!(text(manager.solve(&TurnCounter, "three", None, "s").await), "2");
+        assert_eq!(manager.conversation("s").await.unwrap().map(|conversation| conversation.turns().len()), Some(4));
+    }
+}
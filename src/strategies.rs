@@ -0,0 +1,204 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::anyhow;
+use tracing::{debug, instrument};
+
+use super::{
+    model::{BatchInference, Capabilities, CompatibilityReport, LanguageModel, LanguageModelPrompt, RateLimit},
+    Error,
+    Message,
+};
+
+type AnswerParser = Arc<dyn Fn(&str) -> Option<String> + Send + Sync>;
+
+/// The text after the last `Answer:` marker, or else the last non-empty line, trimmed.
+fn parse_answer(text: &str) -> Option<String> {
+    let answer = match text.to_ascii_lowercase().rfind("answer:") {
+        Some(index) => text.get(index + "answer:".len()..).and_then(|rest| rest.lines().next()),
+        None => text.lines().rev().find(|line| !line.trim().is_empty()),
+    };
+
+    answer.map(str::trim).filter(|answer| !answer.is_empty()).map(str::to_string)
+}
+
+/// The outcome of a vote: the winning answer and how every answer fared.
+#[derive(Clone, Debug)]
+pub struct Majority {
+    answer: String,
+    response: Message,
+    counts: Vec<(String, usize)>,
+    samples: usize,
+}
+
+impl Majority {
+    pub fn answer(&self) -> &str {
+        &self.answer
+    }
+
+    /// The first full response whose parsed answer won.
+    pub fn response(&self) -> &Message {
+        &self.response
+    }
+
+    pub fn votes(&self) -> usize {
+        self.counts.first().map(|(_, votes)| *votes).unwrap_or_default()
+    }
+
+    /// Votes per distinct answer, most votes first; ties keep the order answers were first seen.
+    pub fn counts(&self) -> &[(String, usize)] {
+        &self.counts
+    }
+
+    /// Completions sampled, including those no answer could be parsed from.
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Share of the samples that agreed on the winning answer.
+    pub fn agreement(&self) -> f32 {
+        match self.samples {
+            0 => 0.0,
+            samples => self.votes() as f32 / samples as f32,
+        }
+    }
+}
+
+/// Self-consistency decoding: samples several completions for the same prompt and keeps the answer most of
+/// them agree on.
+///
+/// Answers are pulled out of each completion by the parser (by default the text after the last `Answer:`, or
+/// the last line), so completions that reason differently but reach the same answer vote together. Used as a
+/// `LanguageModel` it returns the first completion that reached the majority answer.
+pub struct SelfConsistency<M> {
+    model: M,
+    samples: usize,
+    temperature: Option<f32>,
+    parser: AnswerParser,
+}
+
+impl<M> fmt::Debug for SelfConsistency<M>
+where
+    M: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SelfConsistency")
+            .field("model", &self.model)
+            .field("samples", &self.samples)
+            .field("temperature", &self.temperature)
+            .finish()
+    }
+}
+
+impl<M> SelfConsistency<M> {
+    pub fn new(model: M) -> Self {
+        Self {
+            model,
+            samples: 5,
+            temperature: None,
+            parser: Arc::new(parse_answer),
+        }
+    }
+
+    pub fn samples(self, samples: usize) -> Self {
+        Self {
+            samples,
+            ..self
+        }
+    }
+
+    /// Sampling temperature for every completion, overriding the prompt's; votes are meaningless at 0.
+    pub fn temperature(self, temperature: f32) -> Self {
+        Self {
+            temperature: Some(temperature),
+            ..self
+        }
+    }
+
+    /// Extracts the answer to vote on from a completion; completions it returns `None` for cast no vote.
+    pub fn parser<F>(self, parser: F) -> Self
+    where
+        F: Fn(&str) -> Option<String> + Send + Sync + 'static,
+    {
+        Self {
+            parser: Arc::new(parser),
+            ..self
+        }
+    }
+
+    pub fn model(&self) -> &M {
+        &self.model
+    }
+}
+
+impl<M> SelfConsistency<M>
+where
+    M: LanguageModel,
+{
+    /// Samples the completions and tallies their answers.
+    #[instrument(name = "SelfConsistency::vote", level = "trace", skip(self, prompt), fields(samples = self.samples))]
+    pub async fn vote(&self, prompt: LanguageModelPrompt) -> Result<Majority, Error> {
+        if self.samples == 0 {
+            return Err(Error::InvalidRequest("samples must be greater than 0".to_string()));
+        }
+
+        let prompt = match self.temperature {
+            Some(temperature) => prompt.temperature(temperature),
+            None => prompt,
+        };
+        let responses = self.model.inference_multi(prompt.candidates(self.samples)).await?;
+        let samples = responses.len();
+
+        let mut counts = Vec::<(String, usize)>::new();
+        let mut winners = Vec::<Message>::new();
+        for response in responses {
+            let Some(answer) = (self.parser)(&response.to_string()) else { continue };
+
+            match counts.iter_mut().position(|(seen, _)| *seen == answer) {
+                Some(index) => counts[index].1 += 1,
+                None => {
+                    counts.push((answer, 1));
+                    winners.push(response);
+                },
+            }
+        }
+
+        let mut ranked = counts.into_iter().zip(winners).collect::<Vec<((String, usize), Message)>>();
+        ranked.sort_by(|((_, a), _), ((_, b), _)| b.cmp(a));
+        debug! { samples, answers = ranked.len() };
+
+        let ((answer, _), response) = ranked.first().cloned().ok_or_else(|| Error::Unexpected(anyhow!("no-parseable-answers")))?;
+
+        Ok(Majority {
+            answer,
+            response,
+            counts: ranked.into_iter().map(|(count, _)| count).collect(),
+            samples,
+        })
+    }
+}
+
+impl<M> BatchInference for SelfConsistency<M>
+where
+    M: LanguageModel,
+{}
+
+impl<M> LanguageModel for SelfConsistency<M>
+where
+    M: LanguageModel,
+{
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        self.vote(prompt).await.map(|majority| majority.response)
+    }
+
+    fn rate_limit(&self) -> Option<RateLimit> {
+        self.model.rate_limit()
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.model.capabilities()
+    }
+
+    fn compatibility(&self, prompt: &LanguageModelPrompt) -> CompatibilityReport {
+        self.model.compatibility(prompt)
+    }
+}
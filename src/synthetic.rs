@@ -0,0 +1,240 @@
+use std::path::Path;
+
+use anyhow::anyhow;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::{fs, io::AsyncWriteExt};
+use tracing::{debug, info, instrument, warn};
+
+use super::{
+    model::{EmbeddingModel, LanguageModel, LanguageModelPrompt},
+    Error,
+};
+
+type Validator = Box<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+/// Describes the examples to generate.
+pub struct SynthesisSpec {
+    description: String,
+    schema: Option<Value>,
+    seeds: Vec<Value>,
+    count: usize,
+    batch_size: usize,
+    max_rounds: usize,
+    similarity_threshold: f32,
+    validator: Option<Validator>,
+}
+
+impl std::fmt::Debug for SynthesisSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SynthesisSpec")
+            .field("description", &self.description)
+            .field("schema", &self.schema)
+            .field("seeds", &self.seeds)
+            .field("count", &self.count)
+            .field("batch_size", &self.batch_size)
+            .field("max_rounds", &self.max_rounds)
+            .field("similarity_threshold", &self.similarity_threshold)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SynthesisSpec {
+    pub fn new(description: impl Into<String>, count: usize) -> Self {
+        Self {
+            description: description.into(),
+            schema: None,
+            seeds: Vec::new(),
+            count,
+            batch_size: 10,
+            max_rounds: count.max(1) * 2,
+            similarity_threshold: 0.92,
+            validator: None,
+        }
+    }
+
+    /// JSON schema every example must follow; included verbatim in the generation prompt.
+    pub fn schema(self, schema: Value) -> Self {
+        Self {
+            schema: Some(schema),
+            ..self
+        }
+    }
+
+    pub fn seed(self, seed: Value) -> Self {
+        let mut seeds = self.seeds;
+        seeds.push(seed);
+
+        Self {
+            seeds,
+            ..self
+        }
+    }
+
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    pub fn max_rounds(self, max_rounds: usize) -> Self {
+        Self {
+            max_rounds,
+            ..self
+        }
+    }
+
+    /// Candidates whose cosine similarity to an accepted example reaches this value are discarded.
+    pub fn similarity_threshold(self, similarity_threshold: f32) -> Self {
+        Self {
+            similarity_threshold,
+            ..self
+        }
+    }
+
+    pub fn validator(self, validator: impl Fn(&Value) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        Self {
+            validator: Some(Box::new(validator)),
+            ..self
+        }
+    }
+
+    fn prompt(&self, accepted: &[Value], remaining: usize) -> LanguageModelPrompt {
+        let mut request = format!("<task>\n{}\n</task>", self.description);
+        if let Some(schema) = &self.schema {
+            request.push_str(&format!("\n\n<schema>\n{}\n</schema>", schema));
+        }
+
+        let shown = self.seeds.iter().chain(accepted.iter().rev().take(self.batch_size)).collect::<Vec<&Value>>();
+        if !shown.is_empty() {
+            request.push_str("\n\n<existing_examples>");
+            for example in shown {
+                request.push_str(&format!("\n{}", example));
+            }
+            request.push_str("\n</existing_examples>");
+        }
+
+        request.push_str(&format!(
+            "\n\nGenerate {} new examples that differ substantially from the existing ones and from each other. \
+Reply with only a JSON array of the examples.",
+            remaining.min(self.batch_size)
+        ));
+
+        LanguageModelPrompt::from(request)
+            .max_tokens(4096)
+            .temperature(1.0)
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct SynthesisReport {
+    generated: usize,
+    duplicates: usize,
+    invalid: usize,
+    rounds: usize,
+}
+
+impl SynthesisReport {
+    pub fn generated(&self) -> usize {
+        self.generated
+    }
+
+    /// Candidates dropped for being too similar to an accepted example.
+    pub fn duplicates(&self) -> usize {
+        self.duplicates
+    }
+
+    pub fn invalid(&self) -> usize {
+        self.invalid
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot = a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>();
+    let norm = a.iter().map(|x| x * x).sum::<f32>().sqrt() * b.iter().map(|y| y * y).sum::<f32>().sqrt();
+
+    if norm == 0.0 { 0.0 } else { dot / norm }
+}
+
+fn parse_array(text: &str) -> Result<Vec<Value>, Error> {
+    let start = text.find('[').ok_or_else(|| Error::Unexpected(anyhow!("response-not-json-array")))?;
+    let end = text.rfind(']').ok_or_else(|| Error::Unexpected(anyhow!("response-not-json-array")))?;
+
+    serde_json::from_str(&text[start..=end]).map_err(|err| Error::Unexpected(anyhow!(err)))
+}
+
+/// Generates `spec.count` distinct, validated examples with `model` and appends them to the JSONL file at `path`.
+///
+/// Each candidate is embedded with `embedder` and rejected when it is a near-duplicate of anything already
+/// accepted, so the output stays diverse even across many generation rounds.
+#[instrument(name = "synthetic::synthesize", level = "trace", skip(model, embedder, path))]
+pub async fn synthesize<M, E>(spec: &SynthesisSpec, model: &M, embedder: &E, path: impl AsRef<Path>) -> Result<SynthesisReport, Error>
+where
+    M: LanguageModel,
+    E: EmbeddingModel,
+{
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .await
+        .map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+    let mut report = SynthesisReport::default();
+    let mut accepted = Vec::new();
+    let mut embeddings = embedder.embed(&spec.seeds.iter().map(Value::to_string).collect::<Vec<String>>()).await?;
+
+    while report.generated < spec.count && report.rounds < spec.max_rounds {
+        report.rounds += 1;
+
+        let response = model.inference(spec.prompt(&accepted, spec.count - report.generated)).await?;
+        let candidates = match parse_array(&response.to_string()) {
+            Ok(candidates) => candidates,
+            Err(err) => {
+                warn! { round = report.rounds, ?err };
+                continue;
+            }
+        };
+
+        let candidates = candidates.into_iter()
+            .filter(|candidate| match spec.validator.as_ref().map(|validator| validator(candidate)) {
+                Some(Err(reason)) => {
+                    debug! { reason, "rejecting invalid example" };
+                    report.invalid += 1;
+                    false
+                },
+                _ => true,
+            })
+            .collect::<Vec<Value>>();
+
+        let texts = candidates.iter().map(Value::to_string).collect::<Vec<String>>();
+        for (candidate, embedding) in candidates.into_iter().zip(embedder.embed(&texts).await?) {
+            if report.generated >= spec.count {
+                break;
+            }
+
+            if embeddings.iter().any(|existing| cosine_similarity(existing, &embedding) >= spec.similarity_threshold) {
+                report.duplicates += 1;
+                continue;
+            }
+
+            let mut line = serde_json::to_vec(&candidate).map_err(|err| Error::Unexpected(anyhow!(err)))?;
+            line.push(b'\n');
+            file.write_all(&line).await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+
+            embeddings.push(embedding);
+            accepted.push(candidate);
+            report.generated += 1;
+        }
+    }
+
+    file.flush().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+    info! { generated = report.generated, duplicates = report.duplicates, invalid = report.invalid, rounds = report.rounds };
+
+    Ok(report)
+}
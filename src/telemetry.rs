@@ -0,0 +1,63 @@
+use std::{sync::OnceLock, time::Duration};
+
+use opentelemetry::{
+    global,
+    metrics::{Counter, Histogram},
+    KeyValue,
+};
+
+/// Instruments named after the OpenTelemetry gen_ai semantic conventions, created on first use from the
+/// global meter provider so applications only need to install one.
+struct Instruments {
+    duration: Histogram<f64>,
+    tokens: Histogram<u64>,
+    errors: Counter<u64>,
+}
+
+fn instruments() -> &'static Instruments {
+    static INSTRUMENTS: OnceLock<Instruments> = OnceLock::new();
+
+    INSTRUMENTS.get_or_init(|| {
+        let meter = global::meter(env!("CARGO_PKG_NAME"));
+
+        Instruments {
+            duration: meter.f64_histogram("gen_ai.client.operation.duration")
+                .with_unit("s")
+                .with_description("Duration of language model inference calls")
+                .init(),
+            tokens: meter.u64_histogram("gen_ai.client.token.usage")
+                .with_unit("{token}")
+                .with_description("Tokens used per inference call, by token type")
+                .init(),
+            errors: meter.u64_counter("gen_ai.client.errors")
+                .with_description("Failed inference calls, by error type")
+                .init(),
+        }
+    })
+}
+
+/// Records one inference call; `usage` is `(input_tokens, output_tokens)` when the provider reports it.
+pub(crate) fn record_inference(system: &'static str, model: &str, elapsed: Duration, usage: Option<(usize, usize)>, error: Option<&str>) {
+    let instruments = instruments();
+
+    let mut attributes = vec![
+        KeyValue::new("gen_ai.operation.name", "chat"),
+        KeyValue::new("gen_ai.system", system),
+        KeyValue::new("gen_ai.request.model", model.to_string()),
+    ];
+    if let Some(error) = error {
+        attributes.push(KeyValue::new("error.type", error.to_string()));
+        instruments.errors.add(1, &attributes);
+    }
+    instruments.duration.record(elapsed.as_secs_f64(), &attributes);
+
+    if let Some((input_tokens, output_tokens)) = usage {
+        let mut input = attributes.clone();
+        input.push(KeyValue::new("gen_ai.token.type", "input"));
+        instruments.tokens.record(input_tokens as u64, &input);
+
+        let mut output = attributes;
+        output.push(KeyValue::new("gen_ai.token.type", "output"));
+        instruments.tokens.record(output_tokens as u64, &output);
+    }
+}
\ No newline at end of file
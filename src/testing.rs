@@ -0,0 +1,315 @@
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use futures_util::stream::{self, StreamExt};
+
+use super::{
+    model::{BatchInference, Capabilities, LanguageModel, LanguageModelPrompt, StreamingModel, TextStream},
+    Error,
+    Message,
+};
+
+#[cfg(feature = "test-util")]
+mod conformance;
+
+#[cfg(feature = "test-util")]
+pub use conformance::{Check, Conformance, ConformanceReport, Outcome};
+
+/// The parts of a prompt a `MockModel` was called with, for assertions.
+#[derive(Clone, Debug)]
+pub struct CapturedPrompt {
+    system: Option<String>,
+    messages: Vec<Message>,
+    max_tokens: usize,
+    temperature: f32,
+    model: Option<String>,
+}
+
+impl CapturedPrompt {
+    pub fn system(&self) -> Option<&str> {
+        self.system.as_deref()
+    }
+
+    pub fn messages(&self) -> &[Message] {
+        &self.messages
+    }
+
+    /// The text of every message, joined by newlines.
+    pub fn text(&self) -> String {
+        self.messages.iter().map(Message::to_string).collect::<Vec<String>>().join("\n")
+    }
+
+    pub fn max_tokens(&self) -> usize {
+        self.max_tokens
+    }
+
+    pub fn temperature(&self) -> f32 {
+        self.temperature
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.model.as_deref()
+    }
+}
+
+impl From<&LanguageModelPrompt> for CapturedPrompt {
+    fn from(prompt: &LanguageModelPrompt) -> Self {
+        Self {
+            system: prompt.system.clone(),
+            messages: prompt.messages.clone(),
+            max_tokens: prompt.max_tokens,
+            temperature: prompt.temperature,
+            model: prompt.model.clone(),
+        }
+    }
+}
+
+/// A scripted streamed response: the deltas a `MockModel` emits, how fast, and whether the stream breaks.
+#[derive(Debug)]
+pub struct MockStream {
+    deltas: Vec<String>,
+    latency: Duration,
+    pace: Duration,
+    failure: Option<(usize, Error)>,
+}
+
+impl MockStream {
+    pub fn new<I, S>(deltas: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            deltas: deltas.into_iter().map(Into::into).collect(),
+            latency: Duration::ZERO,
+            pace: Duration::ZERO,
+            failure: None,
+        }
+    }
+
+    /// Splits `text` into deltas of whole words, `words` at a time, keeping the whitespace so the deltas
+    /// concatenate back to `text`.
+    pub fn words(text: &str, words: usize) -> Self {
+        let mut deltas = Vec::new();
+        let mut delta = String::new();
+        let mut count = 0;
+
+        for (index, word) in text.split_inclusive(char::is_whitespace).enumerate() {
+            if index > 0 && count == words.max(1) && !word.trim().is_empty() {
+                deltas.push(std::mem::take(&mut delta));
+                count = 0;
+            }
+            if !word.trim().is_empty() {
+                count += 1;
+            }
+            delta.push_str(word);
+        }
+        if !delta.is_empty() {
+            deltas.push(delta);
+        }
+
+        Self::new(deltas)
+    }
+
+    /// Delay before the first delta, like a provider's time to first token.
+    pub fn latency(self, latency: Duration) -> Self {
+        Self {
+            latency,
+            ..self
+        }
+    }
+
+    /// Delay between deltas.
+    pub fn pace(self, pace: Duration) -> Self {
+        Self {
+            pace,
+            ..self
+        }
+    }
+
+    /// Ends the stream with `err` after the first `after` deltas, as when a connection drops mid-response.
+    pub fn fail_after(self, after: usize, err: Error) -> Self {
+        Self {
+            failure: Some((after, err)),
+            ..self
+        }
+    }
+
+    fn into_text_stream(self) -> TextStream {
+        let mut items = self.deltas.into_iter().map(Ok).collect::<Vec<Result<String, Error>>>();
+        if let Some((after, err)) = self.failure {
+            items.truncate(after);
+            items.push(Err(err));
+        }
+
+        let (latency, pace) = (self.latency, self.pace);
+        Box::pin(stream::iter(items.into_iter().enumerate()).then(move |(index, item)| async move {
+            tokio::time::sleep(if index == 0 { latency } else { pace }).await;
+            item
+        }))
+    }
+
+    /// The whole response, as `inference` returns it for a streamed script entry.
+    fn into_message(self) -> Result<Message, Error> {
+        match self.failure {
+            Some((_, err)) => Err(err),
+            None => Ok(Message::from(self.deltas.concat())),
+        }
+    }
+}
+
+#[derive(Debug)]
+enum Scripted {
+    Response(Result<Message, Error>),
+    Stream(MockStream),
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    script: VecDeque<Scripted>,
+    prompts: Vec<CapturedPrompt>,
+}
+
+/// A `LanguageModel` that answers from a script and records every prompt it receives.
+///
+/// Scripted responses and errors are returned once each, in order; after the script runs out every call gets
+/// the fallback response, or fails if there is none. `stream` and `inference` share the script: a scripted
+/// stream answers `inference` with its deltas joined, and a plain response streams as a single delta. Clones share the same script and captured prompts, so a
+/// clone can be handed to the code under test and the original kept for assertions.
+#[derive(Clone, Debug, Default)]
+pub struct MockModel {
+    state: Arc<Mutex<MockState>>,
+    fallback: Option<Message>,
+    capabilities: Option<Capabilities>,
+}
+
+impl MockModel {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers every call with `response`.
+    pub fn always(response: impl Into<Message>) -> Self {
+        Self::new().fallback(response)
+    }
+
+    /// Queues `response` for the next unanswered call.
+    pub fn respond(self, response: impl Into<Message>) -> Self {
+        self.push(Scripted::Response(Ok(response.into())));
+        self
+    }
+
+    /// Queues `err` for the next unanswered call.
+    pub fn fail(self, err: Error) -> Self {
+        self.push(Scripted::Response(Err(err)));
+        self
+    }
+
+    /// Queues a streamed response for the next unanswered call.
+    pub fn stream_response(self, stream: MockStream) -> Self {
+        self.push(Scripted::Stream(stream));
+        self
+    }
+
+    pub fn fallback(self, fallback: impl Into<Message>) -> Self {
+        Self {
+            fallback: Some(fallback.into()),
+            ..self
+        }
+    }
+
+    /// Reports `capabilities` so `Capabilities::check` runs on every call, as it does for real providers.
+    pub fn capabilities(self, capabilities: Capabilities) -> Self {
+        Self {
+            capabilities: Some(capabilities),
+            ..self
+        }
+    }
+
+    fn push(&self, response: Scripted) {
+        if let Ok(mut state) = self.state.lock() {
+            state.script.push_back(response);
+        }
+    }
+
+    pub fn calls(&self) -> usize {
+        self.state.lock().map(|state| state.prompts.len()).unwrap_or_default()
+    }
+
+    pub fn prompts(&self) -> Vec<CapturedPrompt> {
+        self.state.lock().map(|state| state.prompts.clone()).unwrap_or_default()
+    }
+
+    pub fn last_prompt(&self) -> Option<CapturedPrompt> {
+        self.state.lock().ok().and_then(|state| state.prompts.last().cloned())
+    }
+
+    /// Scripted responses not yet consumed.
+    pub fn remaining(&self) -> usize {
+        self.state.lock().map(|state| state.script.len()).unwrap_or_default()
+    }
+
+    #[track_caller]
+    pub fn assert_calls(&self, expected: usize) {
+        assert_eq!(self.calls(), expected, "MockModel was called {} times, expected {}", self.calls(), expected);
+    }
+
+    /// Asserts that some captured prompt's text or system prompt contains `needle`.
+    #[track_caller]
+    pub fn assert_prompted_with(&self, needle: &str) {
+        let prompts = self.prompts();
+        assert!(
+            prompts.iter().any(|prompt| prompt.text().contains(needle) || prompt.system().is_some_and(|system| system.contains(needle))),
+            "no prompt sent to MockModel contains {:?}; prompts were {:#?}",
+            needle,
+            prompts.iter().map(CapturedPrompt::text).collect::<Vec<String>>(),
+        );
+    }
+}
+
+impl BatchInference for MockModel {}
+
+impl MockModel {
+    /// Records the prompt and takes the next scripted entry, or the fallback once the script is exhausted.
+    fn next(&self, prompt: &LanguageModelPrompt) -> Result<Scripted, Error> {
+        let prompt = &*prompt.fit_max_tokens(self.capabilities);
+        let mut state = self.state.lock().map_err(|err| Error::Unexpected(anyhow!("{}", err)))?;
+        state.prompts.push(CapturedPrompt::from(prompt));
+
+        if let Some(capabilities) = self.capabilities {
+            capabilities.check(prompt)?;
+        }
+
+        match (state.script.pop_front(), &self.fallback) {
+            (Some(scripted), _) => Ok(scripted),
+            (None, Some(fallback)) => Ok(Scripted::Response(Ok(fallback.clone()))),
+            (None, None) => Err(Error::Unexpected(anyhow!("mock-script-exhausted"))),
+        }
+    }
+}
+
+impl LanguageModel for MockModel {
+    async fn inference(&self, prompt: LanguageModelPrompt) -> Result<Message, Error> {
+        match self.next(&prompt)? {
+            Scripted::Response(response) => response,
+            Scripted::Stream(stream) => stream.into_message(),
+        }
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+}
+
+impl StreamingModel for MockModel {
+    async fn stream(&self, prompt: LanguageModelPrompt) -> Result<TextStream, Error> {
+        match self.next(&prompt)? {
+            Scripted::Response(response) => Ok(MockStream::new([response?.to_string()]).into_text_stream()),
+            Scripted::Stream(stream) => Ok(stream.into_text_stream()),
+        }
+    }
+}
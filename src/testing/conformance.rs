@@ -0,0 +1,277 @@
+use std::fmt;
+
+use base64::prelude::{BASE64_STANDARD, Engine as _};
+use tracing::{debug, instrument};
+
+use crate::{
+    model::{estimate_tokens, LanguageModel, LanguageModelPrompt},
+    Error,
+    Image,
+    Message,
+};
+
+/// A 16x16 solid red PNG.
+const RED_SQUARE: &str = "iVBORw0KGgoAAAANSUhEUgAAABAAAAAQCAIAAACQkWg2AAAAFklEQVR42mP4z8BAEmIY1TCqYfhqAACQ+f8B8u7oVwAAAABJRU5ErkJggg==";
+
+/// A model id no provider serves, used to provoke a request error.
+const UNKNOWN_MODEL: &str = "april-conformance-nonexistent-model";
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Check {
+    /// A plain text prompt gets a non-empty text response.
+    BasicText,
+
+    /// An empty prompt either succeeds or fails with `InvalidRequest`/`UnsupportedContent`, never `Unexpected`.
+    EmptyPrompt,
+
+    /// Output stops before a stop sequence, unless the provider reports the setting as ignored.
+    StopSequences,
+
+    /// Images are answered by vision models and rejected with `UnsupportedContent` by the rest.
+    Images,
+
+    /// A long answer succeeds and stays within `max_tokens`.
+    LongOutput,
+
+    /// A request for an unknown model fails with a typed error rather than `Unexpected`.
+    ErrorMapping,
+}
+
+impl Check {
+    pub const ALL: [Check; 6] = [Check::BasicText, Check::EmptyPrompt, Check::StopSequences, Check::Images, Check::LongOutput, Check::ErrorMapping];
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Check::BasicText => "basic-text",
+            Check::EmptyPrompt => "empty-prompt",
+            Check::StopSequences => "stop-sequences",
+            Check::Images => "images",
+            Check::LongOutput => "long-output",
+            Check::ErrorMapping => "error-mapping",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Outcome {
+    Passed,
+    Failed(String),
+    Skipped(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct ConformanceReport {
+    results: Vec<(Check, Outcome)>,
+}
+
+impl ConformanceReport {
+    pub fn results(&self) -> &[(Check, Outcome)] {
+        &self.results
+    }
+
+    pub fn outcome(&self, check: Check) -> Option<&Outcome> {
+        self.results.iter().find(|(ran, _)| *ran == check).map(|(_, outcome)| outcome)
+    }
+
+    pub fn failures(&self) -> impl Iterator<Item = (Check, &str)> {
+        self.results.iter().filter_map(|(check, outcome)| match outcome {
+            Outcome::Failed(reason) => Some((*check, reason.as_str())),
+            _ => None,
+        })
+    }
+
+    pub fn is_conformant(&self) -> bool {
+        self.failures().next().is_none()
+    }
+
+    #[track_caller]
+    pub fn assert_conformant(&self) {
+        let failures = self.failures().map(|(check, reason)| format!("{}: {}", check, reason)).collect::<Vec<String>>();
+        assert!(failures.is_empty(), "model failed {} conformance check(s):\n{}", failures.len(), failures.join("\n"));
+    }
+}
+
+/// Runs the same behavioral checks against any `LanguageModel`, so third-party providers can verify they
+/// behave like the built-in ones.
+///
+/// The checks call the model for real, so a provider run costs a handful of small requests. Provider-specific
+/// errors are expected to be mapped onto the typed `Error` variants; `Error::Unexpected` fails a check.
+#[derive(Clone, Debug)]
+pub struct Conformance {
+    checks: Vec<Check>,
+    image: Option<Image>,
+    long_output_tokens: usize,
+}
+
+impl Default for Conformance {
+    fn default() -> Self {
+        Self {
+            checks: Check::ALL.to_vec(),
+            image: None,
+            long_output_tokens: 256,
+        }
+    }
+}
+
+impl Conformance {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn skip(self, check: Check) -> Self {
+        Self {
+            checks: self.checks.into_iter().filter(|included| *included != check).collect(),
+            ..self
+        }
+    }
+
+    /// Image for the `Images` check, in place of the built-in red square.
+    pub fn image(self, image: Image) -> Self {
+        Self {
+            image: Some(image),
+            ..self
+        }
+    }
+
+    /// `max_tokens` for the `LongOutput` check.
+    pub fn long_output_tokens(self, long_output_tokens: usize) -> Self {
+        Self {
+            long_output_tokens,
+            ..self
+        }
+    }
+
+    #[instrument(name = "Conformance::run", level = "trace", skip(self, model))]
+    pub async fn run<M>(&self, model: &M) -> ConformanceReport
+    where
+        M: LanguageModel,
+    {
+        let mut results = Vec::with_capacity(self.checks.len());
+        for check in &self.checks {
+            let outcome = match check {
+                Check::BasicText => self.basic_text(model).await,
+                Check::EmptyPrompt => self.empty_prompt(model).await,
+                Check::StopSequences => self.stop_sequences(model).await,
+                Check::Images => self.images(model).await,
+                Check::LongOutput => self.long_output(model).await,
+                Check::ErrorMapping => self.error_mapping(model).await,
+            };
+            debug! { %check, ?outcome };
+            results.push((*check, outcome));
+        }
+
+        ConformanceReport { results }
+    }
+
+    async fn basic_text<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        let prompt = LanguageModelPrompt::from("Reply with the single word: pong").max_tokens(16).temperature(0.0);
+        match model.inference(prompt).await {
+            Ok(Message::Text { text }) if !text.trim().is_empty() => Outcome::Passed,
+            Ok(Message::Text { .. }) => Outcome::Failed("empty text response".to_string()),
+            Ok(message) => Outcome::Failed(format!("expected a text response, got {}", kind(&message))),
+            Err(err) => Outcome::Failed(format!("request failed: {}", err)),
+        }
+    }
+
+    async fn empty_prompt<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        match model.inference(LanguageModelPrompt::from("").max_tokens(16)).await {
+            Ok(_) | Err(Error::InvalidRequest(_)) | Err(Error::UnsupportedContent { .. }) => Outcome::Passed,
+            Err(err) => Outcome::Failed(format!("expected success, InvalidRequest or UnsupportedContent, got {}", variant(&err))),
+        }
+    }
+
+    async fn stop_sequences<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        let prompt = LanguageModelPrompt::from("Count from 1 to 10 in digits, separated by single spaces, and nothing else.")
+            .max_tokens(64)
+            .temperature(0.0)
+            .stop_sequence("6");
+        if model.compatibility(&prompt).ignored().contains(&"stop_sequences") {
+            return Outcome::Skipped("provider ignores stop_sequences".to_string());
+        }
+
+        match model.inference(prompt).await {
+            Ok(message) if message.to_string().contains(['7', '8', '9']) => Outcome::Failed(format!("output continued past the stop sequence: {:?}", message.to_string())),
+            Ok(_) => Outcome::Passed,
+            Err(err) => Outcome::Failed(format!("request failed: {}", err)),
+        }
+    }
+
+    async fn images<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        let image = match &self.image {
+            Some(image) => image.clone(),
+            None => match BASE64_STANDARD.decode(RED_SQUARE) {
+                Ok(data) => Image::new("image/png", data),
+                Err(err) => return Outcome::Failed(format!("invalid built-in image: {}", err)),
+            },
+        };
+        let prompt = LanguageModelPrompt::from(image).add_message("What color is this image? Answer in one word.").max_tokens(16).temperature(0.0);
+        let vision = model.capabilities().map(|capabilities| capabilities.vision());
+
+        match (vision, model.inference(prompt).await) {
+            (Some(false), Ok(_)) => Outcome::Failed("model reports no vision support but accepted an image".to_string()),
+            (Some(false) | None, Err(Error::UnsupportedContent { .. })) => Outcome::Passed,
+            (_, Ok(Message::Text { text })) if !text.trim().is_empty() => Outcome::Passed,
+            (_, Ok(message)) => Outcome::Failed(format!("expected a text answer about the image, got {:?}", message.to_string())),
+            (Some(false), Err(err)) => Outcome::Failed(format!("expected UnsupportedContent, got {}", variant(&err))),
+            (_, Err(err)) => Outcome::Failed(format!("request failed: {}", err)),
+        }
+    }
+
+    async fn long_output<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        let prompt = LanguageModelPrompt::from("Write a detailed, multi-paragraph essay on the history of the printing press.")
+            .max_tokens(self.long_output_tokens);
+
+        match model.inference(prompt).await {
+            Ok(message) => {
+                // `estimate_tokens` is rough, so allow generous slack before calling `max_tokens` ignored.
+                let tokens = estimate_tokens(&message.to_string());
+                match tokens <= self.long_output_tokens * 2 + 32 {
+                    true => Outcome::Passed,
+                    false => Outcome::Failed(format!("~{} tokens returned for max_tokens {}", tokens, self.long_output_tokens)),
+                }
+            },
+            Err(err) => Outcome::Failed(format!("request failed: {}", err)),
+        }
+    }
+
+    async fn error_mapping<M>(&self, model: &M) -> Outcome
+    where
+        M: LanguageModel,
+    {
+        match model.inference(LanguageModelPrompt::from("ping").model(UNKNOWN_MODEL).max_tokens(16)).await {
+            Ok(_) => Outcome::Skipped("provider ignores the model override".to_string()),
+            Err(err @ Error::Unexpected(_)) => Outcome::Failed(format!("unknown model mapped to Unexpected: {}", err)),
+            Err(_) => Outcome::Passed,
+        }
+    }
+}
+
+fn kind(message: &Message) -> &'static str {
+    match message {
+        Message::Audio(_) => "audio",
+        Message::Image(_) => "image",
+        Message::Text { .. } => "text",
+        Message::Unknown(_) => "unknown content",
+    }
+}
+
+fn variant(err: &Error) -> String {
+    format!("{} ({})", err.code(), err)
+}
@@ -0,0 +1,52 @@
+//! `std::time::Instant` panics on `wasm32-unknown-unknown`, so code reachable from a plain model call measures
+//! time through this `Instant` instead, which reads the JavaScript clock there. Elsewhere it is
+//! `std::time::Instant` itself, e.g. for `LanguageModelPrompt::deadline`.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+pub use wasm::Instant;
+
+/// Wall-clock time in milliseconds since the Unix epoch, for timestamps.
+pub(crate) fn unix_millis() -> u64 {
+    #[cfg(not(target_arch = "wasm32"))]
+    let millis = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_millis() as u64;
+
+    #[cfg(target_arch = "wasm32")]
+    let millis = js_sys::Date::now() as u64;
+
+    millis
+}
+
+#[cfg(target_arch = "wasm32")]
+mod wasm {
+    use std::{ops::Add, time::Duration};
+
+    /// Time since the Unix epoch from `Date.now()`, at millisecond resolution. Unlike a monotonic clock it can go
+    /// backwards when the system clock is adjusted, so differences saturate at zero.
+    #[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+    pub struct Instant(Duration);
+
+    impl Instant {
+        pub fn now() -> Self {
+            Self(Duration::from_secs_f64(js_sys::Date::now() / 1000.0))
+        }
+
+        pub fn saturating_duration_since(&self, earlier: Instant) -> Duration {
+            self.0.saturating_sub(earlier.0)
+        }
+
+        pub fn elapsed(&self) -> Duration {
+            Self::now().saturating_duration_since(*self)
+        }
+    }
+
+    impl Add<Duration> for Instant {
+        type Output = Instant;
+
+        fn add(self, duration: Duration) -> Instant {
+            Self(self.0 + duration)
+        }
+    }
+}
@@ -0,0 +1,152 @@
+use std::fmt;
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tracing::{debug, info, instrument, warn};
+
+use super::Error;
+
+#[cfg(feature = "builtin-tools")]
+mod builtin;
+
+#[cfg(feature = "builtin-tools")]
+pub use builtin::{CalculatorTool, DateTimeTool, RegexExtractTool, UnitConversionTool};
+
+#[cfg(feature = "fs-tool")]
+mod fs;
+
+#[cfg(feature = "fs-tool")]
+pub use fs::{ListDirectoryTool, ReadFileTool, Sandbox, WriteFileTool};
+
+#[cfg(feature = "http-tool")]
+mod http;
+
+#[cfg(feature = "http-tool")]
+pub use http::HttpRequestTool;
+
+mod scratchpad;
+
+pub use scratchpad::ScratchpadTool;
+
+#[cfg(feature = "shell-tool")]
+mod shell;
+
+#[cfg(feature = "shell-tool")]
+pub use shell::{ShellApproval, ShellTool};
+
+/// A function the model can call, described by a JSON schema for its input.
+#[async_trait]
+pub trait Tool: fmt::Debug + Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    fn input_schema(&self) -> Value;
+
+    async fn call(&self, input: Value) -> Result<Value, Error>;
+}
+
+/// Hooks consulted by `ToolRegistry` around every tool call, for access control and auditing.
+#[async_trait]
+pub trait ToolPolicy: fmt::Debug + Send + Sync {
+    /// Returning an error prevents the call; the error is returned to the caller in place of the output.
+    async fn authorize(&self, #[allow(unused)] tool: &str, #[allow(unused)] input: &Value) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn record(&self, #[allow(unused)] tool: &str, #[allow(unused)] input: &Value, #[allow(unused)] output: &Result<Value, Error>) {}
+}
+
+/// Logs every tool call and its outcome through `tracing`.
+#[derive(Clone, Debug, Default)]
+pub struct AuditLog;
+
+#[async_trait]
+impl ToolPolicy for AuditLog {
+    async fn record(&self, tool: &str, input: &Value, output: &Result<Value, Error>) {
+        match output {
+            Ok(_) => info! { tool, %input, "tool call succeeded" },
+            Err(err) => warn! { tool, %input, ?err, "tool call failed" },
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Box<dyn Tool>>,
+    policies: Vec<Box<dyn ToolPolicy>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the calculator, date/time, unit conversion and regex extraction tools.
+    #[cfg(feature = "builtin-tools")]
+    pub fn with_builtins() -> Self {
+        Self::new()
+            .register(CalculatorTool)
+            .register(DateTimeTool)
+            .register(UnitConversionTool)
+            .register(RegexExtractTool::default())
+    }
+
+    /// Adds `tool`, replacing any registered tool with the same name.
+    pub fn register(self, tool: impl Tool + 'static) -> Self {
+        let mut tools = self.tools.into_iter()
+            .filter(|registered| registered.name() != tool.name())
+            .collect::<Vec<Box<dyn Tool>>>();
+        tools.push(Box::new(tool));
+
+        Self {
+            tools,
+            ..self
+        }
+    }
+
+    /// Adds a policy; policies are consulted in the order they were added.
+    pub fn policy(self, policy: impl ToolPolicy + 'static) -> Self {
+        let mut policies = self.policies;
+        policies.push(Box::new(policy));
+
+        Self {
+            policies,
+            ..self
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Tool> {
+        self.tools.iter().find(|tool| tool.name() == name).map(|tool| tool.as_ref())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.tools.iter().map(|tool| tool.name())
+    }
+
+    /// Tool definitions in the `{ name, description, input_schema }` shape providers expect.
+    pub fn definitions(&self) -> Vec<Value> {
+        self.tools.iter()
+            .map(|tool| json!({ "name": tool.name(), "description": tool.description(), "input_schema": tool.input_schema() }))
+            .collect()
+    }
+
+    #[instrument(name = "ToolRegistry::call", level = "trace", skip(self))]
+    pub async fn call(&self, name: &str, input: Value) -> Result<Value, Error> {
+        let tool = self.get(name).ok_or_else(|| Error::Unexpected(anyhow!("unknown-tool: {}", name)))?;
+
+        for policy in self.policies.iter() {
+            policy.authorize(name, &input).await?;
+        }
+
+        let output = tool.call(input.clone()).await;
+        debug! { ?output };
+
+        for policy in self.policies.iter() {
+            policy.record(name, &input, &output).await;
+        }
+
+        output
+    }
+}
\ No newline at end of file
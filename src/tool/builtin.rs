@@ -0,0 +1,417 @@
+use std::{iter::Peekable, str::Chars};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use chrono::{FixedOffset, Utc};
+use regex::RegexBuilder;
+use serde_json::{json, Map, Value};
+
+use super::Tool;
+use crate::Error;
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::Unexpected(anyhow!("invalid-tool-input: {}", message))
+}
+
+fn input_str<'a>(input: &'a Value, field: &str) -> Result<&'a str, Error> {
+    input.get(field).and_then(Value::as_str).ok_or_else(|| invalid(format!("missing string `{}`", field)))
+}
+
+/// Recursive-descent evaluator for arithmetic expressions; it never executes code or touches the environment.
+struct Expression<'a> {
+    chars: Peekable<Chars<'a>>,
+    depth: usize,
+}
+
+impl Expression<'_> {
+    const MAX_LENGTH: usize = 1024;
+    const MAX_DEPTH: usize = 64;
+
+    fn evaluate(source: &str) -> Result<f64, Error> {
+        if source.len() > Self::MAX_LENGTH {
+            return Err(invalid("expression too long"));
+        }
+
+        let mut expression = Expression { chars: source.chars().peekable(), depth: 0 };
+        let value = expression.sum()?;
+
+        expression.skip_whitespace();
+        match expression.chars.next() {
+            Some(c) => Err(invalid(format!("unexpected `{}`", c))),
+            None => Ok(value),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn next_if_eq(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        self.chars.next_if_eq(&expected).is_some()
+    }
+
+    fn sum(&mut self) -> Result<f64, Error> {
+        let mut value = self.product()?;
+        loop {
+            if self.next_if_eq('+') {
+                value += self.product()?;
+            } else if self.next_if_eq('-') {
+                value -= self.product()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn product(&mut self) -> Result<f64, Error> {
+        let mut value = self.power()?;
+        loop {
+            if self.next_if_eq('*') {
+                value *= self.power()?;
+            } else if self.next_if_eq('/') {
+                value /= self.power()?;
+            } else if self.next_if_eq('%') {
+                value %= self.power()?;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn power(&mut self) -> Result<f64, Error> {
+        let base = self.unary()?;
+        match self.next_if_eq('^') {
+            true => Ok(base.powf(self.power()?)),
+            false => Ok(base),
+        }
+    }
+
+    fn unary(&mut self) -> Result<f64, Error> {
+        self.depth += 1;
+        if self.depth > Self::MAX_DEPTH {
+            return Err(invalid("expression nested too deeply"));
+        }
+
+        let value = if self.next_if_eq('-') {
+            -self.unary()?
+        } else if self.next_if_eq('+') {
+            self.unary()?
+        } else {
+            self.primary()?
+        };
+
+        self.depth -= 1;
+        Ok(value)
+    }
+
+    fn primary(&mut self) -> Result<f64, Error> {
+        if self.next_if_eq('(') {
+            let value = self.sum()?;
+            return match self.next_if_eq(')') {
+                true => Ok(value),
+                false => Err(invalid("expected `)`")),
+            };
+        }
+
+        match self.chars.peek() {
+            Some(c) if c.is_ascii_digit() || *c == '.' => {
+                let mut number = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E') {
+                    number.push(c);
+                    if c == 'e' || c == 'E' {
+                        if let Some(sign) = self.chars.next_if(|c| *c == '-' || *c == '+') {
+                            number.push(sign);
+                        }
+                    }
+                }
+                number.parse::<f64>().map_err(|_| invalid(format!("invalid number `{}`", number)))
+            },
+            Some(c) if c.is_ascii_alphabetic() => {
+                let mut name = String::new();
+                while let Some(c) = self.chars.next_if(|c| c.is_ascii_alphanumeric()) {
+                    name.push(c);
+                }
+                self.identifier(&name)
+            },
+            Some(c) => Err(invalid(format!("unexpected `{}`", c))),
+            None => Err(invalid("unexpected end of expression")),
+        }
+    }
+
+    fn identifier(&mut self, name: &str) -> Result<f64, Error> {
+        match name {
+            "pi" => return Ok(std::f64::consts::PI),
+            "e" => return Ok(std::f64::consts::E),
+            _ => {},
+        }
+
+        if !self.next_if_eq('(') {
+            return Err(invalid(format!("unknown constant `{}`", name)));
+        }
+        let mut arguments = vec![self.sum()?];
+        while self.next_if_eq(',') {
+            arguments.push(self.sum()?);
+        }
+        if !self.next_if_eq(')') {
+            return Err(invalid("expected `)`"));
+        }
+
+        match (name, arguments.as_slice()) {
+            ("abs", [x]) => Ok(x.abs()),
+            ("sqrt", [x]) => Ok(x.sqrt()),
+            ("ln", [x]) => Ok(x.ln()),
+            ("log10", [x]) => Ok(x.log10()),
+            ("exp", [x]) => Ok(x.exp()),
+            ("sin", [x]) => Ok(x.sin()),
+            ("cos", [x]) => Ok(x.cos()),
+            ("tan", [x]) => Ok(x.tan()),
+            ("floor", [x]) => Ok(x.floor()),
+            ("ceil", [x]) => Ok(x.ceil()),
+            ("round", [x]) => Ok(x.round()),
+            ("min", [x, y]) => Ok(x.min(*y)),
+            ("max", [x, y]) => Ok(x.max(*y)),
+            ("pow", [x, y]) => Ok(x.powf(*y)),
+            _ => Err(invalid(format!("unknown function `{}` with {} argument(s)", name, arguments.len()))),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct CalculatorTool;
+
+#[async_trait]
+impl Tool for CalculatorTool {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn description(&self) -> &str {
+        "Evaluates an arithmetic expression with + - * / % ^, parentheses, the constants pi and e, and the functions abs, sqrt, ln, log10, exp, sin, cos, tan, floor, ceil, round, min, max and pow."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "expression": { "type": "string" } },
+            "required": ["expression"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let result = Expression::evaluate(input_str(&input, "expression")?)?;
+
+        match result.is_finite() {
+            true => Ok(json!({ "result": result })),
+            false => Err(invalid("result is not a finite number")),
+        }
+    }
+}
+
+/// Parses `Z`, `UTC`, `+HH`, `+HHMM` or `+HH:MM` into a fixed offset.
+fn parse_offset(offset: &str) -> Option<FixedOffset> {
+    if offset.eq_ignore_ascii_case("z") || offset.eq_ignore_ascii_case("utc") {
+        return FixedOffset::east_opt(0);
+    }
+
+    let (sign, digits) = match offset.split_at_checked(1)? {
+        ("+", digits) => (1, digits),
+        ("-", digits) => (-1, digits),
+        _ => return None,
+    };
+    let digits = digits.replace(':', "");
+    let (hours, minutes) = match digits.len() {
+        2 => (digits.parse::<i32>().ok()?, 0),
+        4 => (digits[..2].parse::<i32>().ok()?, digits[2..].parse::<i32>().ok()?),
+        _ => return None,
+    };
+
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DateTimeTool;
+
+#[async_trait]
+impl Tool for DateTimeTool {
+    fn name(&self) -> &str {
+        "current_datetime"
+    }
+
+    fn description(&self) -> &str {
+        "Returns the current date and time, in UTC or at the given UTC offset such as +05:30."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "utc_offset": { "type": "string", "description": "Z, UTC, +HH, +HHMM or +HH:MM" } },
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let offset = input.get("utc_offset").and_then(Value::as_str).unwrap_or("UTC");
+        let offset = parse_offset(offset).ok_or_else(|| invalid(format!("invalid utc_offset `{}`", offset)))?;
+
+        let now = Utc::now().with_timezone(&offset);
+        Ok(json!({
+            "datetime": now.to_rfc3339(),
+            "date": now.format("%Y-%m-%d").to_string(),
+            "time": now.format("%H:%M:%S").to_string(),
+            "weekday": now.format("%A").to_string(),
+            "unix_timestamp": now.timestamp(),
+        }))
+    }
+}
+
+/// Linear units as (name, dimension, size in the dimension's base unit).
+const UNITS: &[(&str, &str, f64)] = &[
+    ("mm", "length", 0.001), ("cm", "length", 0.01), ("m", "length", 1.0), ("km", "length", 1000.0),
+    ("in", "length", 0.0254), ("ft", "length", 0.3048), ("yd", "length", 0.9144), ("mi", "length", 1609.344),
+    ("mg", "mass", 0.000001), ("g", "mass", 0.001), ("kg", "mass", 1.0), ("t", "mass", 1000.0),
+    ("oz", "mass", 0.028349523125), ("lb", "mass", 0.45359237),
+    ("ml", "volume", 0.001), ("l", "volume", 1.0), ("m3", "volume", 1000.0),
+    ("floz", "volume", 0.0295735295625), ("cup", "volume", 0.2365882365), ("gal", "volume", 3.785411784),
+    ("ms", "time", 0.001), ("s", "time", 1.0), ("min", "time", 60.0), ("h", "time", 3600.0), ("day", "time", 86400.0), ("week", "time", 604800.0),
+    ("b", "data", 1.0), ("kb", "data", 1e3), ("mb", "data", 1e6), ("gb", "data", 1e9), ("tb", "data", 1e12),
+    ("kib", "data", 1024.0), ("mib", "data", 1048576.0), ("gib", "data", 1073741824.0),
+    ("m/s", "speed", 1.0), ("km/h", "speed", 1.0 / 3.6), ("mph", "speed", 0.44704), ("kn", "speed", 0.514444),
+];
+
+fn to_kelvin(value: f64, unit: &str) -> Option<f64> {
+    match unit {
+        "c" => Some(value + 273.15),
+        "f" => Some((value - 32.0) * 5.0 / 9.0 + 273.15),
+        "k" => Some(value),
+        _ => None,
+    }
+}
+
+fn from_kelvin(value: f64, unit: &str) -> Option<f64> {
+    match unit {
+        "c" => Some(value - 273.15),
+        "f" => Some((value - 273.15) * 9.0 / 5.0 + 32.0),
+        "k" => Some(value),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct UnitConversionTool;
+
+impl UnitConversionTool {
+    pub fn convert(value: f64, from: &str, to: &str) -> Result<f64, Error> {
+        let (from, to) = (from.to_lowercase(), to.to_lowercase());
+
+        if let (Some(kelvin), true) = (to_kelvin(value, &from), to_kelvin(0.0, &to).is_some()) {
+            return from_kelvin(kelvin, &to).ok_or_else(|| invalid(format!("unknown unit `{}`", to)));
+        }
+
+        let lookup = |unit: &str| UNITS.iter().find(|(name, ..)| *name == unit).ok_or_else(|| invalid(format!("unknown unit `{}`", unit)));
+        let (_, from_dimension, from_size) = lookup(&from)?;
+        let (_, to_dimension, to_size) = lookup(&to)?;
+
+        match from_dimension == to_dimension {
+            true => Ok(value * from_size / to_size),
+            false => Err(invalid(format!("cannot convert {} to {}", from_dimension, to_dimension))),
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for UnitConversionTool {
+    fn name(&self) -> &str {
+        "convert_units"
+    }
+
+    fn description(&self) -> &str {
+        "Converts a value between units of length, mass, volume, time, data size, speed or temperature (c, f, k)."
+    }
+
+    fn input_schema(&self) -> Value {
+        let units = UNITS.iter().map(|(name, ..)| *name).chain(["c", "f", "k"]).collect::<Vec<&str>>();
+
+        json!({
+            "type": "object",
+            "properties": {
+                "value": { "type": "number" },
+                "from": { "type": "string", "enum": units },
+                "to": { "type": "string", "enum": units },
+            },
+            "required": ["value", "from", "to"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let value = input.get("value").and_then(Value::as_f64).ok_or_else(|| invalid("missing number `value`"))?;
+        let result = Self::convert(value, input_str(&input, "from")?, input_str(&input, "to")?)?;
+
+        Ok(json!({ "result": result }))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RegexExtractTool {
+    max_matches: usize,
+}
+
+impl Default for RegexExtractTool {
+    fn default() -> Self {
+        Self {
+            max_matches: 100,
+        }
+    }
+}
+
+impl RegexExtractTool {
+    pub fn max_matches(self, max_matches: usize) -> Self {
+        Self {
+            max_matches,
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for RegexExtractTool {
+    fn name(&self) -> &str {
+        "regex_extract"
+    }
+
+    fn description(&self) -> &str {
+        "Finds every match of a regular expression in a text, returning each match with its numbered and named capture groups."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "pattern": { "type": "string" },
+                "text": { "type": "string" },
+            },
+            "required": ["pattern", "text"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        // The regex engine runs in linear time; the size limit bounds compilation of pathological patterns.
+        let regex = RegexBuilder::new(input_str(&input, "pattern")?)
+            .size_limit(1 << 20)
+            .build()
+            .map_err(invalid)?;
+
+        let matches = regex.captures_iter(input_str(&input, "text")?)
+            .take(self.max_matches)
+            .map(|captures| {
+                let groups = captures.iter().skip(1).map(|group| group.map(|group| group.as_str())).collect::<Vec<Option<&str>>>();
+                let named = regex.capture_names()
+                    .flatten()
+                    .filter_map(|name| captures.name(name).map(|group| (name.to_string(), json!(group.as_str()))))
+                    .collect::<Map<String, Value>>();
+
+                json!({ "match": &captures[0], "groups": groups, "named": named })
+            })
+            .collect::<Vec<Value>>();
+
+        Ok(json!({ "matches": matches }))
+    }
+}
\ No newline at end of file
@@ -0,0 +1,329 @@
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+use tokio::fs;
+
+use super::Tool;
+use crate::Error;
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::Unexpected(anyhow!("invalid-tool-input: {}", message))
+}
+
+fn io(err: std::io::Error) -> Error {
+    Error::Unexpected(anyhow!(err))
+}
+
+/// The directory the filesystem tools are confined to, with limits on what they may read or write.
+#[derive(Clone, Debug)]
+pub struct Sandbox {
+    root: PathBuf,
+    max_file_bytes: u64,
+    extensions: Option<Vec<String>>,
+}
+
+impl Sandbox {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            max_file_bytes: 1024 * 1024,
+            extensions: None,
+        }
+    }
+
+    pub fn max_file_bytes(self, max_file_bytes: u64) -> Self {
+        Self {
+            max_file_bytes,
+            ..self
+        }
+    }
+
+    /// Restricts reads and writes to files with one of `extensions` (without the leading dot).
+    pub fn extensions(self, extensions: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            extensions: Some(extensions.into_iter().map(|extension| extension.into().trim_start_matches('.').to_ascii_lowercase()).collect()),
+            ..self
+        }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Maps a relative tool path into the sandbox.
+    ///
+    /// Absolute paths and `..` are rejected outright, and the deepest existing ancestor is canonicalized so
+    /// that symlinks inside the sandbox cannot point outside it.
+    pub(super) async fn resolve(&self, path: &str) -> Result<PathBuf, Error> {
+        let relative = Path::new(path);
+        if relative.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return Err(invalid(format!("path must be relative and stay inside the sandbox: {}", path)));
+        }
+
+        let root = fs::canonicalize(&self.root).await.map_err(io)?;
+        let target = root.join(relative);
+
+        let mut existing = target.as_path();
+        while fs::symlink_metadata(existing).await.is_err() {
+            existing = existing.parent().unwrap_or(&root);
+        }
+        if !fs::canonicalize(existing).await.map_err(io)?.starts_with(&root) {
+            return Err(invalid(format!("path escapes the sandbox: {}", path)));
+        }
+
+        Ok(target)
+    }
+
+    fn check_extension(&self, path: &Path) -> Result<(), Error> {
+        let Some(extensions) = &self.extensions else { return Ok(()) };
+
+        let extension = path.extension().and_then(|extension| extension.to_str()).unwrap_or_default().to_ascii_lowercase();
+        match extensions.contains(&extension) {
+            true => Ok(()),
+            false => Err(invalid(format!("extension not allowed: {}", path.display()))),
+        }
+    }
+}
+
+fn path_schema() -> Value {
+    json!({ "type": "string", "description": "Path relative to the sandbox root" })
+}
+
+#[derive(Clone, Debug)]
+pub struct ReadFileTool {
+    sandbox: Sandbox,
+}
+
+impl ReadFileTool {
+    pub fn new(sandbox: Sandbox) -> Self {
+        Self { sandbox }
+    }
+}
+
+#[async_trait]
+impl Tool for ReadFileTool {
+    fn name(&self) -> &str {
+        "read_file"
+    }
+
+    fn description(&self) -> &str {
+        "Reads a UTF-8 text file from the sandbox."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": { "path": path_schema() }, "required": ["path"] })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let path = input.get("path").and_then(Value::as_str).ok_or_else(|| invalid("missing string `path`"))?;
+        let target = self.sandbox.resolve(path).await?;
+        self.sandbox.check_extension(&target)?;
+
+        let size = fs::metadata(&target).await.map_err(io)?.len();
+        if size > self.sandbox.max_file_bytes {
+            return Err(invalid(format!("file exceeds {} bytes", self.sandbox.max_file_bytes)));
+        }
+
+        let content = fs::read_to_string(&target).await.map_err(io)?;
+        Ok(json!({ "path": path, "content": content }))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct WriteFileTool {
+    sandbox: Sandbox,
+}
+
+impl WriteFileTool {
+    pub fn new(sandbox: Sandbox) -> Self {
+        Self { sandbox }
+    }
+}
+
+#[async_trait]
+impl Tool for WriteFileTool {
+    fn name(&self) -> &str {
+        "write_file"
+    }
+
+    fn description(&self) -> &str {
+        "Writes a UTF-8 text file in the sandbox, creating parent directories and replacing any existing file."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": { "path": path_schema(), "content": { "type": "string" } },
+            "required": ["path", "content"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let path = input.get("path").and_then(Value::as_str).ok_or_else(|| invalid("missing string `path`"))?;
+        let content = input.get("content").and_then(Value::as_str).ok_or_else(|| invalid("missing string `content`"))?;
+
+        let target = self.sandbox.resolve(path).await?;
+        self.sandbox.check_extension(&target)?;
+        if content.len() as u64 > self.sandbox.max_file_bytes {
+            return Err(invalid(format!("content exceeds {} bytes", self.sandbox.max_file_bytes)));
+        }
+
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).await.map_err(io)?;
+        }
+        fs::write(&target, content).await.map_err(io)?;
+
+        Ok(json!({ "path": path, "bytes": content.len() }))
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ListDirectoryTool {
+    sandbox: Sandbox,
+    max_entries: usize,
+}
+
+impl ListDirectoryTool {
+    pub fn new(sandbox: Sandbox) -> Self {
+        Self { sandbox, max_entries: 500 }
+    }
+
+    /// Lists the first `max_entries` entries by name; the output's `truncated` is set when there are more.
+    pub fn max_entries(self, max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            ..self
+        }
+    }
+}
+
+#[async_trait]
+impl Tool for ListDirectoryTool {
+    fn name(&self) -> &str {
+        "list_directory"
+    }
+
+    fn description(&self) -> &str {
+        "Lists the files and directories directly inside a sandbox directory."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({ "type": "object", "properties": { "path": path_schema() } })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let path = input.get("path").and_then(Value::as_str).unwrap_or(".");
+        let target = self.sandbox.resolve(path).await?;
+
+        let mut found = Vec::new();
+        let mut directory = fs::read_dir(&target).await.map_err(io)?;
+        while let Some(entry) = directory.next_entry().await.map_err(io)? {
+            found.push(entry);
+        }
+        found.sort_by_key(|entry| entry.file_name());
+
+        let truncated = found.len() > self.max_entries;
+        found.truncate(self.max_entries);
+
+        let mut entries = Vec::with_capacity(found.len());
+        for entry in found {
+            let metadata = entry.metadata().await.map_err(io)?;
+            entries.push(json!({
+                "name": entry.file_name().to_string_lossy(),
+                "type": if metadata.is_dir() { "directory" } else { "file" },
+                "bytes": metadata.len(),
+            }));
+        }
+
+        Ok(json!({ "path": path, "entries": entries, "truncated": truncated }))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::os::unix::fs::symlink;
+
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn sandbox() -> (TempDir, Sandbox) {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("docs")).unwrap();
+        std::fs::write(root.path().join("docs/readme.txt"), "hello").unwrap();
+
+        let sandbox = Sandbox::new(root.path());
+        (root, sandbox)
+    }
+
+    #[tokio::test]
+    async fn resolves_paths_inside_the_root() {
+        let (root, sandbox) = sandbox();
+
+        let target = sandbox.resolve("./docs/readme.txt").await.unwrap();
+        assert_eq!(target, root.path().canonicalize().unwrap().join("docs/readme.txt"));
+    }
+
+    #[tokio::test]
+    async fn rejects_parent_components() {
+        let (_root, sandbox) = sandbox();
+
+        assert!(sandbox.resolve("..").await.is_err());
+        assert!(sandbox.resolve("docs/../../etc/passwd").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_absolute_paths() {
+        let (root, sandbox) = sandbox();
+
+        assert!(sandbox.resolve("/etc/passwd").await.is_err());
+        assert!(sandbox.resolve(&root.path().join("docs/readme.txt").to_string_lossy()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_symlinks_escaping_the_root() {
+        let (root, sandbox) = sandbox();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+        symlink(outside.path(), root.path().join("link")).unwrap();
+
+        assert!(sandbox.resolve("link/secret.txt").await.is_err());
+        assert!(sandbox.resolve("link/new.txt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn rejects_dangling_symlinks_pointing_outside() {
+        let (root, sandbox) = sandbox();
+        let outside = tempfile::tempdir().unwrap();
+        symlink(outside.path().join("missing.txt"), root.path().join("dangling.txt")).unwrap();
+
+        assert!(sandbox.resolve("dangling.txt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn writes_under_a_new_directory() {
+        let (root, sandbox) = sandbox();
+        let tool = WriteFileTool::new(sandbox);
+
+        tool.call(json!({ "path": "notes/2024/today.txt", "content": "done" })).await.unwrap();
+        assert_eq!(std::fs::read_to_string(root.path().join("notes/2024/today.txt")).unwrap(), "done");
+    }
+
+    #[tokio::test]
+    async fn lists_the_first_entries_by_name() {
+        let (root, sandbox) = sandbox();
+        for name in ["c.txt", "a.txt", "b.txt"] {
+            std::fs::write(root.path().join(name), name).unwrap();
+        }
+
+        let listing = ListDirectoryTool::new(sandbox.clone()).max_entries(2).call(json!({})).await.unwrap();
+        assert_eq!(listing["entries"].as_array().unwrap().iter().map(|entry| entry["name"].as_str().unwrap()).collect::<Vec<&str>>(), ["a.txt", "b.txt"]);
+        assert_eq!(listing["truncated"], true);
+
+        let listing = ListDirectoryTool::new(sandbox).max_entries(4).call(json!({})).await.unwrap();
+        assert_eq!(listing["entries"].as_array().unwrap().len(), 4);
+        assert_eq!(listing["truncated"], false);
+    }
+}
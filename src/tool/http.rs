@@ -0,0 +1,186 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use reqwest::{redirect, Client, Method, Url};
+use serde_json::{json, Value};
+use tracing::debug;
+
+use super::Tool;
+use crate::Error;
+
+/// Headers the model may not set: `Host`, which would pick a virtual host other than the allowed domain, and the
+/// hop-by-hop and framing headers the client manages itself.
+const FORBIDDEN_HEADERS: &[&str] = &[
+    "connection",
+    "content-length",
+    "host",
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::Unexpected(anyhow!("invalid-tool-input: {}", message))
+}
+
+/// Whether `url` is http(s) on one of `domains` or a subdomain of one.
+fn allowed(domains: &[String], url: &Url) -> bool {
+    matches!(url.scheme(), "http" | "https") && url.host_str().is_some_and(|host| {
+        let host = host.to_ascii_lowercase();
+        domains.iter().any(|domain| host == *domain || host.ends_with(&format!(".{}", domain)))
+    })
+}
+
+/// GET and POST requests restricted to an allow-list of domains, with bounded request and response sizes.
+///
+/// Redirects are followed only while they stay on allowed domains, model-supplied headers cannot include `Host`
+/// or hop-by-hop headers, and response bodies beyond `max_response_bytes` are truncated rather than buffered.
+#[derive(Clone, Debug)]
+pub struct HttpRequestTool {
+    allowed_domains: Arc<Vec<String>>,
+    max_request_bytes: usize,
+    max_response_bytes: usize,
+    timeout: Duration,
+    client: Client,
+}
+
+impl HttpRequestTool {
+    /// Fails when the HTTP client cannot be built, rather than falling back to one whose redirects are not
+    /// checked against the allow-list.
+    pub fn new(allowed_domains: impl IntoIterator<Item = impl Into<String>>) -> Result<Self, Error> {
+        let allowed_domains = Arc::new(allowed_domains.into_iter()
+            .map(|domain| domain.into().trim_start_matches('.').to_ascii_lowercase())
+            .collect::<Vec<String>>());
+
+        Ok(Self {
+            client: Self::client(&allowed_domains)?,
+            allowed_domains,
+            max_request_bytes: 64 * 1024,
+            max_response_bytes: 64 * 1024,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    fn client(allowed_domains: &Arc<Vec<String>>) -> Result<Client, Error> {
+        let domains = allowed_domains.clone();
+
+        Client::builder()
+            .redirect(redirect::Policy::custom(move |attempt| {
+                if attempt.previous().len() >= 5 || !allowed(&domains, attempt.url()) {
+                    attempt.stop()
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .build()
+            .map_err(|err| Error::Unexpected(anyhow!(err)))
+    }
+
+    pub fn max_request_bytes(self, max_request_bytes: usize) -> Self {
+        Self {
+            max_request_bytes,
+            ..self
+        }
+    }
+
+    pub fn max_response_bytes(self, max_response_bytes: usize) -> Self {
+        Self {
+            max_response_bytes,
+            ..self
+        }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..self
+        }
+    }
+
+    pub fn allowed_domains(&self) -> &[String] {
+        &self.allowed_domains
+    }
+}
+
+#[async_trait]
+impl Tool for HttpRequestTool {
+    fn name(&self) -> &str {
+        "http_request"
+    }
+
+    fn description(&self) -> &str {
+        "Sends an HTTP GET or POST request to an allowed domain and returns the status and (possibly truncated) response body."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "method": { "type": "string", "enum": ["GET", "POST"] },
+                "url": { "type": "string" },
+                "headers": { "type": "object", "additionalProperties": { "type": "string" } },
+                "body": { "type": "string" },
+            },
+            "required": ["url"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let method = match input.get("method").and_then(Value::as_str).unwrap_or("GET").to_ascii_uppercase().as_str() {
+            "GET" => Method::GET,
+            "POST" => Method::POST,
+            method => return Err(invalid(format!("unsupported method `{}`", method))),
+        };
+
+        let url = input.get("url").and_then(Value::as_str).ok_or_else(|| invalid("missing string `url`"))?;
+        let url = Url::parse(url).map_err(invalid)?;
+        if !allowed(&self.allowed_domains, &url) {
+            return Err(invalid(format!("domain not allowed: {}", url.host_str().unwrap_or_default())));
+        }
+
+        let mut request = self.client.request(method, url).timeout(self.timeout);
+        if let Some(headers) = input.get("headers").and_then(Value::as_object) {
+            for (name, value) in headers {
+                if FORBIDDEN_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+                    return Err(invalid(format!("header `{}` is not allowed", name)));
+                }
+                request = request.header(name, value.as_str().ok_or_else(|| invalid(format!("header `{}` is not a string", name)))?);
+            }
+        }
+        if let Some(body) = input.get("body").and_then(Value::as_str) {
+            if body.len() > self.max_request_bytes {
+                return Err(invalid(format!("request body exceeds {} bytes", self.max_request_bytes)));
+            }
+            request = request.body(body.to_string());
+        }
+
+        let mut response = request.send().await.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        let status = response.status();
+        let content_type = response.headers().get("content-type").and_then(|value| value.to_str().ok()).map(str::to_string);
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response.chunk().await.map_err(|err| Error::Unexpected(anyhow!(err)))? {
+            let remaining = self.max_response_bytes - body.len();
+            if chunk.len() > remaining {
+                body.extend_from_slice(&chunk[..remaining]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+        debug! { %status, bytes = body.len(), truncated };
+
+        Ok(json!({
+            "status": status.as_u16(),
+            "content_type": content_type,
+            "body": String::from_utf8_lossy(&body),
+            "truncated": truncated,
+        }))
+    }
+}
\ No newline at end of file
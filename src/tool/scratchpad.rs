@@ -0,0 +1,115 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use serde_json::{json, Value};
+
+use super::Tool;
+use crate::{Error, SessionStore};
+
+fn invalid(message: impl fmt::Display) -> Error {
+    Error::Unexpected(anyhow!("invalid-tool-input: {}", message))
+}
+
+/// Named notes the model can write and read back on later turns, stored with the session's conversation.
+///
+/// The tool is bound to a single session; create one per session when registering tools.
+pub struct ScratchpadTool<S> {
+    store: Arc<S>,
+    session_id: String,
+    max_note_bytes: usize,
+}
+
+impl<S> ScratchpadTool<S>
+where
+    S: SessionStore,
+{
+    pub fn new(store: Arc<S>, session_id: impl Into<String>) -> Self {
+        Self {
+            store,
+            session_id: session_id.into(),
+            max_note_bytes: 16 * 1024,
+        }
+    }
+
+    pub fn max_note_bytes(self, max_note_bytes: usize) -> Self {
+        Self {
+            max_note_bytes,
+            ..self
+        }
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+}
+
+impl<S> fmt::Debug for ScratchpadTool<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ScratchpadTool")
+            .field("session_id", &self.session_id)
+            .field("max_note_bytes", &self.max_note_bytes)
+            .finish()
+    }
+}
+
+#[async_trait]
+impl<S> Tool for ScratchpadTool<S>
+where
+    S: SessionStore,
+{
+    fn name(&self) -> &str {
+        "scratchpad"
+    }
+
+    fn description(&self) -> &str {
+        "Writes, reads, lists or deletes named notes that persist across turns of this conversation."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "action": { "type": "string", "enum": ["write", "read", "list", "delete"] },
+                "name": { "type": "string" },
+                "content": { "type": "string" },
+            },
+            "required": ["action"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let action = input.get("action").and_then(Value::as_str).ok_or_else(|| invalid("missing string `action`"))?;
+        let name = || input.get("name").and_then(Value::as_str).ok_or_else(|| invalid("missing string `name`"));
+
+        let mut conversation = self.store.load(&self.session_id).await?.unwrap_or_default();
+
+        match action {
+            "write" => {
+                let name = name()?;
+                let content = input.get("content").and_then(Value::as_str).ok_or_else(|| invalid("missing string `content`"))?;
+                if content.len() > self.max_note_bytes {
+                    return Err(invalid(format!("note exceeds {} bytes", self.max_note_bytes)));
+                }
+
+                conversation.set_note(name, content);
+                self.store.save(&self.session_id, &conversation).await?;
+                Ok(json!({ "name": name, "bytes": content.len() }))
+            },
+            "read" => {
+                let name = name()?;
+                Ok(json!({ "name": name, "content": conversation.note(name) }))
+            },
+            "list" => Ok(json!({ "names": conversation.notes().map(|(name, _)| name).collect::<Vec<&str>>() })),
+            "delete" => {
+                let name = name()?;
+                let deleted = conversation.remove_note(name);
+                if deleted {
+                    self.store.save(&self.session_id, &conversation).await?;
+                }
+                Ok(json!({ "name": name, "deleted": deleted }))
+            },
+            action => Err(invalid(format!("unsupported action `{}`", action))),
+        }
+    }
+}
@@ -0,0 +1,227 @@
+use std::{
+    env,
+    fmt,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::anyhow;
+use async_trait::async_trait;
+use futures_util::future;
+use serde_json::{json, Value};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt},
+    process::Command,
+    time,
+};
+use tracing::{debug, warn};
+
+use super::{Sandbox, Tool};
+use crate::Error;
+
+fn invalid(message: impl std::fmt::Display) -> Error {
+    Error::Unexpected(anyhow!("invalid-tool-input: {}", message))
+}
+
+/// Reads at most `limit` bytes, reporting whether the stream had more.
+async fn read_capped(reader: Option<impl AsyncRead + Unpin>, limit: usize) -> (Vec<u8>, bool) {
+    let Some(reader) = reader else { return (Vec::new(), false) };
+
+    let mut buffer = Vec::new();
+    if let Err(err) = reader.take(limit as u64 + 1).read_to_end(&mut buffer).await {
+        warn! { ?err };
+    }
+
+    let truncated = buffer.len() > limit;
+    buffer.truncate(limit);
+    (buffer, truncated)
+}
+
+/// Decides whether a command the model asked `ShellTool` to run may execute.
+#[async_trait]
+pub trait ShellApproval: fmt::Debug + Send + Sync {
+    /// Called before every command, which runs only if this returns `Ok`; the error is returned to the model
+    /// instead. Deliberately has no default, so approving everything has to be written out.
+    async fn approve(&self, command: &str, working_directory: &Path) -> Result<(), Error>;
+}
+
+/// Kills the command's whole process group when dropped, so processes started by `sh` do not outlive a
+/// finished, timed-out or cancelled call.
+struct ProcessGroup(Option<u32>);
+
+impl Drop for ProcessGroup {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Some(id) = self.0.and_then(|id| i32::try_from(id).ok()) {
+            // SAFETY: `kill` has no memory-safety preconditions; a group that already exited yields ESRCH.
+            unsafe { libc::kill(-id, libc::SIGKILL) };
+        }
+    }
+}
+
+/// Runs shell commands after explicit approval.
+///
+/// Every command is passed to the `ShellApproval` before anything executes; there is no way to construct the
+/// tool without one. Commands run with `sh -c` in their own process group, with a cleared environment (only
+/// `PATH` is kept, so provider credentials are not inherited), capped output and a timeout after which the
+/// whole group is killed. On Unix, CPU time and address space can also be limited per process.
+///
+/// The root and `working_directory` only choose where a command starts: the command itself is not confined
+/// and can reach anything the current user can, e.g. with `cd ..` or an absolute path.
+#[derive(Clone, Debug)]
+pub struct ShellTool {
+    root: PathBuf,
+    approval: Arc<dyn ShellApproval>,
+    timeout: Duration,
+    max_output_bytes: usize,
+    max_cpu_time: Option<Duration>,
+    max_memory_bytes: Option<u64>,
+}
+
+impl ShellTool {
+    pub fn new(root: impl Into<PathBuf>, approval: impl ShellApproval + 'static) -> Self {
+        Self {
+            root: root.into(),
+            approval: Arc::new(approval),
+            timeout: Duration::from_secs(30),
+            max_output_bytes: 16 * 1024,
+            max_cpu_time: None,
+            max_memory_bytes: None,
+        }
+    }
+
+    pub fn timeout(self, timeout: Duration) -> Self {
+        Self {
+            timeout,
+            ..self
+        }
+    }
+
+    pub fn max_output_bytes(self, max_output_bytes: usize) -> Self {
+        Self {
+            max_output_bytes,
+            ..self
+        }
+    }
+
+    /// `RLIMIT_CPU` for the shell and each process it starts. Unix only.
+    pub fn max_cpu_time(self, max_cpu_time: Duration) -> Self {
+        Self {
+            max_cpu_time: Some(max_cpu_time),
+            ..self
+        }
+    }
+
+    /// `RLIMIT_AS` for the shell and each process it starts. Unix only.
+    pub fn max_memory_bytes(self, max_memory_bytes: u64) -> Self {
+        Self {
+            max_memory_bytes: Some(max_memory_bytes),
+            ..self
+        }
+    }
+
+    /// The starting directory, resolved like a `Sandbox` path so that it cannot lie outside the root.
+    async fn working_directory(&self, directory: Option<&str>) -> Result<PathBuf, Error> {
+        Sandbox::new(&self.root).resolve(directory.unwrap_or(".")).await
+    }
+
+    #[cfg(unix)]
+    fn limit_resources(&self, command: &mut Command) {
+        let limits = [
+            (libc::RLIMIT_CPU, self.max_cpu_time.map(|cpu_time| cpu_time.as_secs().max(1))),
+            (libc::RLIMIT_AS, self.max_memory_bytes),
+        ];
+        if limits.iter().all(|(_, limit)| limit.is_none()) {
+            return;
+        }
+
+        // SAFETY: the closure only calls `setrlimit`, which is async-signal-safe, and allocates nothing.
+        unsafe {
+            command.pre_exec(move || {
+                for (resource, limit) in limits {
+                    if let Some(limit) = limit {
+                        let limit = libc::rlimit { rlim_cur: limit as libc::rlim_t, rlim_max: limit as libc::rlim_t };
+                        if libc::setrlimit(resource, &limit) != 0 {
+                            return Err(std::io::Error::last_os_error());
+                        }
+                    }
+                }
+                Ok(())
+            });
+        }
+    }
+
+    #[cfg(not(unix))]
+    fn limit_resources(&self, _command: &mut Command) {}
+}
+
+#[async_trait]
+impl Tool for ShellTool {
+    fn name(&self) -> &str {
+        "shell"
+    }
+
+    fn description(&self) -> &str {
+        "Runs a shell command (after approval) and returns its exit code, stdout and stderr."
+    }
+
+    fn input_schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "command": { "type": "string" },
+                "working_directory": { "type": "string", "description": "Directory relative to the tool's root" },
+            },
+            "required": ["command"],
+        })
+    }
+
+    async fn call(&self, input: Value) -> Result<Value, Error> {
+        let command = input.get("command").and_then(Value::as_str).ok_or_else(|| invalid("missing string `command`"))?;
+        let directory = self.working_directory(input.get("working_directory").and_then(Value::as_str)).await?;
+
+        self.approval.approve(command, &directory).await?;
+
+        let mut shell = Command::new("sh");
+        shell.arg("-c")
+            .arg(command)
+            .current_dir(directory)
+            .env_clear()
+            .env("PATH", env::var_os("PATH").unwrap_or_default())
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true);
+        #[cfg(unix)]
+        shell.process_group(0);
+        self.limit_resources(&mut shell);
+
+        let mut child = shell.spawn().map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        let _group = ProcessGroup(child.id());
+
+        let (stdout, stderr) = (child.stdout.take(), child.stderr.take());
+        let run = async {
+            let ((stdout, stdout_truncated), (stderr, stderr_truncated)) = future::join(
+                read_capped(stdout, self.max_output_bytes),
+                read_capped(stderr, self.max_output_bytes),
+            ).await;
+            let status = child.wait().await;
+            (status, stdout, stdout_truncated, stderr, stderr_truncated)
+        };
+
+        let (status, stdout, stdout_truncated, stderr, stderr_truncated) = time::timeout(self.timeout, run)
+            .await
+            .map_err(|_| Error::Unexpected(anyhow!("shell-timeout: {:?}", self.timeout)))?;
+        let status = status.map_err(|err| Error::Unexpected(anyhow!(err)))?;
+        debug! { ?status, stdout = stdout.len(), stderr = stderr.len() };
+
+        Ok(json!({
+            "exit_code": status.code(),
+            "stdout": String::from_utf8_lossy(&stdout),
+            "stderr": String::from_utf8_lossy(&stderr),
+            "truncated": stdout_truncated || stderr_truncated,
+        }))
+    }
+}
\ No newline at end of file